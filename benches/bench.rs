@@ -2,7 +2,7 @@
 
 mod wire {
     use smoltcp::phy::ChecksumCapabilities;
-    use smoltcp::wire::{IpAddress, IpProtocol};
+    use smoltcp::wire::{IpAddress, IpEcn, IpProtocol};
     #[cfg(feature = "proto-ipv4")]
     use smoltcp::wire::{Ipv4Address, Ipv4Packet, Ipv4Repr};
     #[cfg(feature = "proto-ipv6")]
@@ -30,6 +30,8 @@ mod wire {
             src_port: 48896,
             dst_port: 80,
             control: TcpControl::Syn,
+            ece: false,
+            cwr: false,
             seq_number: TcpSeqNumber(0x01234567),
             ack_number: None,
             window_len: 0x0123,
@@ -39,6 +41,8 @@ mod wire {
             sack_ranges: [None, None, None],
             payload: &PAYLOAD_BYTES,
             timestamp: None,
+            fast_open: None,
+            auth: None,
         };
         let mut bytes = vec![0xa5; repr.buffer_len()];
 
@@ -85,6 +89,7 @@ mod wire {
             next_header: IpProtocol::Tcp,
             payload_len: 100,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         };
         let mut bytes = vec![0xa5; repr.buffer_len()];
 
@@ -103,6 +108,7 @@ mod wire {
             next_header: IpProtocol::Tcp,
             payload_len: 100,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         };
         let mut bytes = vec![0xa5; repr.buffer_len()];
 
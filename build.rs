@@ -11,8 +11,14 @@ static CONFIGS: &[(&str, usize)] = &[
     ("IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT", 4),
     ("IFACE_NEIGHBOR_CACHE_COUNT", 8),
     ("IFACE_MAX_ROUTE_COUNT", 2),
+    ("IFACE_MAX_ROUTE_RULE_COUNT", 2),
+    ("IFACE_PATH_MTU_CACHE_COUNT", 4),
     ("FRAGMENTATION_BUFFER_SIZE", 1500),
     ("ASSEMBLER_MAX_SEGMENT_COUNT", 4),
+    ("TCP_SACK_SCOREBOARD_COUNT", 8),
+    ("TCP_RACK_SEGMENT_COUNT", 8),
+    ("TCP_RATE_SEGMENT_COUNT", 8),
+    ("TCP_FAST_OPEN_CACHE_COUNT", 4),
     ("REASSEMBLY_BUFFER_SIZE", 1500),
     ("REASSEMBLY_BUFFER_COUNT", 1),
     ("IPV6_HBH_MAX_OPTIONS", 4),
@@ -31,9 +31,15 @@ feature("iface_max_addr_count", default=2, min=1, max=8)
 feature("iface_max_multicast_group_count", default=4, min=1, max=1024, pow2=8)
 feature("iface_max_sixlowpan_address_context_count", default=4, min=1, max=1024, pow2=8)
 feature("iface_neighbor_cache_count", default=8, min=1, max=1024, pow2=8)
-feature("iface_max_route_count", default=2, min=0, max=1024, pow2=8)
+feature("iface_max_route_count", default=2, min=0, max=4096, pow2=8)
+feature("iface_max_route_rule_count", default=2, min=0, max=256, pow2=8)
+feature("iface_path_mtu_cache_count", default=4, min=1, max=1024, pow2=8)
 feature("fragmentation_buffer_size", default=1500, min=256, max=65536, pow2=True)
 feature("assembler_max_segment_count", default=4, min=1, max=32, pow2=4)
+feature("tcp_sack_scoreboard_count", default=8, min=1, max=32, pow2=4)
+feature("tcp_rack_segment_count", default=8, min=1, max=64, pow2=4)
+feature("tcp_rate_segment_count", default=8, min=1, max=64, pow2=4)
+feature("tcp_fast_open_cache_count", default=4, min=1, max=1024, pow2=8)
 feature("reassembly_buffer_size", default=1500, min=256, max=65536, pow2=True)
 feature("reassembly_buffer_count", default=1, min=1, max=32, pow2=4)
 feature("ipv6_hbh_max_options", default=4, min=1, max=32, pow2=4)
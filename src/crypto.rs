@@ -0,0 +1,517 @@
+// Message digests and MACs needed for TCP segment authentication: MD5 for
+// TCP-MD5 (RFC 2385), and HMAC-SHA-1 and AES-128-CMAC for TCP-AO (RFC 5926).
+//
+// These are small, portable implementations meant for authenticating segments,
+// not general purpose cryptography. In particular they make no attempt to be
+// constant time with respect to their keys, beyond what the algorithms give.
+
+/// A hash function or MAC that can be fed incrementally.
+pub(crate) trait Digest {
+    fn update(&mut self, data: &[u8]);
+}
+
+/// Buffers input into 64-octet blocks for the Merkle–Damgård hashes below.
+#[derive(Debug, Clone)]
+struct Blocks {
+    buffer: [u8; 64],
+    buffer_len: usize,
+    total_len: u64,
+}
+
+impl Blocks {
+    const fn new() -> Self {
+        Blocks {
+            buffer: [0; 64],
+            buffer_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8], mut compress: impl FnMut(&[u8; 64])) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let len = data.len().min(64 - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + len].copy_from_slice(&data[..len]);
+            self.buffer_len += len;
+            data = &data[len..];
+            if self.buffer_len == 64 {
+                compress(&self.buffer);
+                self.buffer_len = 0;
+            }
+        }
+    }
+
+    /// Pad the message with its length in bits, encoded by `encode_len`.
+    fn finish(&mut self, encode_len: fn(u64) -> [u8; 8], mut compress: impl FnMut(&[u8; 64])) {
+        let bit_len = encode_len(self.total_len.wrapping_mul(8));
+        self.buffer[self.buffer_len] = 0x80;
+        self.buffer[self.buffer_len + 1..].fill(0);
+        if self.buffer_len >= 56 {
+            compress(&self.buffer);
+            self.buffer.fill(0);
+        }
+        self.buffer[56..].copy_from_slice(&bit_len);
+        compress(&self.buffer);
+    }
+}
+
+/// MD5, as described in RFC 1321.
+#[derive(Debug, Clone)]
+pub(crate) struct Md5 {
+    state: [u32; 4],
+    blocks: Blocks,
+}
+
+impl Md5 {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    const K: [u32; 64] = [
+        0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613,
+        0xfd469501, 0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193,
+        0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d,
+        0x02441453, 0xd8a1e681, 0xe7d3fbc8, 0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+        0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122,
+        0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa,
+        0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665, 0xf4292244,
+        0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+        0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb,
+        0xeb86d391,
+    ];
+
+    pub(crate) const fn new() -> Self {
+        Md5 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            blocks: Blocks::new(),
+        }
+    }
+
+    fn compress(state: &mut [u32; 4], block: &[u8; 64]) {
+        let mut m = [0u32; 16];
+        for (word, chunk) in m.iter_mut().zip(block.chunks_exact(4)) {
+            // NOTE(unwrap): chunks_exact yields 4 octets.
+            *word = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+
+        let [mut a, mut b, mut c, mut d] = *state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(Self::K[i])
+                .wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(Self::SHIFTS[i / 16 * 4 + i % 4]));
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 16] {
+        let state = &mut self.state;
+        self.blocks
+            .finish(u64::to_le_bytes, |block| Self::compress(state, block));
+        let mut digest = [0; 16];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+}
+
+impl Digest for Md5 {
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.blocks
+            .update(data, |block| Self::compress(state, block));
+    }
+}
+
+/// SHA-1, as described in RFC 3174.
+#[derive(Debug, Clone)]
+pub(crate) struct Sha1 {
+    state: [u32; 5],
+    blocks: Blocks,
+}
+
+impl Sha1 {
+    pub(crate) const fn new() -> Self {
+        Sha1 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0],
+            blocks: Blocks::new(),
+        }
+    }
+
+    fn compress(state: &mut [u32; 5], block: &[u8; 64]) {
+        let mut w = [0u32; 80];
+        for (word, chunk) in w.iter_mut().zip(block.chunks_exact(4)) {
+            // NOTE(unwrap): chunks_exact yields 4 octets.
+            *word = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = *state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5a827999),
+                1 => (b ^ c ^ d, 0x6ed9eba1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 20] {
+        let state = &mut self.state;
+        self.blocks
+            .finish(u64::to_be_bytes, |block| Self::compress(state, block));
+        let mut digest = [0; 20];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+impl Digest for Sha1 {
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.blocks
+            .update(data, |block| Self::compress(state, block));
+    }
+}
+
+/// HMAC-SHA-1, as described in RFC 2104.
+#[derive(Debug, Clone)]
+pub(crate) struct HmacSha1 {
+    inner: Sha1,
+    outer: Sha1,
+}
+
+impl HmacSha1 {
+    pub(crate) fn new(key: &[u8]) -> Self {
+        let mut block = [0u8; 64];
+        if key.len() > block.len() {
+            let mut hash = Sha1::new();
+            hash.update(key);
+            block[..20].copy_from_slice(&hash.finish());
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha1::new();
+        let mut outer = Sha1::new();
+        for octet in block.iter_mut() {
+            *octet ^= 0x36;
+        }
+        inner.update(&block);
+        for octet in block.iter_mut() {
+            *octet ^= 0x36 ^ 0x5c;
+        }
+        outer.update(&block);
+        HmacSha1 { inner, outer }
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 20] {
+        self.outer.update(&self.inner.finish());
+        self.outer.finish()
+    }
+}
+
+impl Digest for HmacSha1 {
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data)
+    }
+}
+
+/// The AES S-box, computed from the multiplicative inverse in GF(2^8) followed by
+/// the affine transformation.
+const SBOX: [u8; 256] = {
+    const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+        let mut product = 0;
+        while b != 0 {
+            if b & 1 != 0 {
+                product ^= a;
+            }
+            a = (a << 1) ^ if a & 0x80 != 0 { 0x1b } else { 0 };
+            b >>= 1;
+        }
+        product
+    }
+
+    let mut sbox = [0; 256];
+    let mut x = 0;
+    while x < 256 {
+        // x^254 is the inverse of x, and maps 0 to 0.
+        let mut inverse = 1;
+        let mut i = 0;
+        while i < 254 {
+            inverse = gf_mul(inverse, x as u8);
+            i += 1;
+        }
+        sbox[x] = inverse
+            ^ inverse.rotate_left(1)
+            ^ inverse.rotate_left(2)
+            ^ inverse.rotate_left(3)
+            ^ inverse.rotate_left(4)
+            ^ 0x63;
+        x += 1;
+    }
+    sbox
+};
+
+/// The AES-128 block cipher, as described in FIPS 197. Only encryption is needed.
+#[derive(Debug, Clone)]
+pub(crate) struct Aes128 {
+    round_keys: [[u8; 16]; 11],
+}
+
+impl Aes128 {
+    pub(crate) fn new(key: &[u8; 16]) -> Self {
+        let mut round_keys = [[0; 16]; 11];
+        round_keys[0] = *key;
+        let mut rcon = 1u8;
+        for round in 1..11 {
+            let prev = round_keys[round - 1];
+            let mut word = [prev[13], prev[14], prev[15], prev[12]];
+            for octet in word.iter_mut() {
+                *octet = SBOX[*octet as usize];
+            }
+            word[0] ^= rcon;
+            rcon = (rcon << 1) ^ if rcon & 0x80 != 0 { 0x1b } else { 0 };
+
+            let next = &mut round_keys[round];
+            for i in 0..16 {
+                next[i] = prev[i] ^ if i < 4 { word[i] } else { next[i - 4] };
+            }
+        }
+        Aes128 { round_keys }
+    }
+
+    pub(crate) fn encrypt(&self, block: &mut [u8; 16]) {
+        fn xtime(x: u8) -> u8 {
+            (x << 1) ^ if x & 0x80 != 0 { 0x1b } else { 0 }
+        }
+
+        let add_round_key = |block: &mut [u8; 16], round: usize| {
+            for (octet, key) in block.iter_mut().zip(self.round_keys[round]) {
+                *octet ^= key;
+            }
+        };
+
+        add_round_key(block, 0);
+        for round in 1..11 {
+            // SubBytes and ShiftRows. The state is stored column by column.
+            let state = *block;
+            for column in 0..4 {
+                for row in 0..4 {
+                    block[column * 4 + row] = SBOX[state[(column + row) % 4 * 4 + row] as usize];
+                }
+            }
+            if round != 10 {
+                // MixColumns.
+                for column in block.chunks_exact_mut(4) {
+                    let [a, b, c, d] = [column[0], column[1], column[2], column[3]];
+                    let all = a ^ b ^ c ^ d;
+                    column[0] ^= all ^ xtime(a ^ b);
+                    column[1] ^= all ^ xtime(b ^ c);
+                    column[2] ^= all ^ xtime(c ^ d);
+                    column[3] ^= all ^ xtime(d ^ a);
+                }
+            }
+            add_round_key(block, round);
+        }
+    }
+}
+
+/// AES-CMAC with a 128-bit key, as described in RFC 4493.
+#[derive(Debug, Clone)]
+pub(crate) struct AesCmac {
+    cipher: Aes128,
+    state: [u8; 16],
+    /// The last block seen, which is only processed once more data arrives, as
+    /// the final block is treated differently.
+    buffer: [u8; 16],
+    buffer_len: usize,
+}
+
+impl AesCmac {
+    pub(crate) fn new(key: &[u8; 16]) -> Self {
+        AesCmac {
+            cipher: Aes128::new(key),
+            state: [0; 16],
+            buffer: [0; 16],
+            buffer_len: 0,
+        }
+    }
+
+    /// Multiply by x in GF(2^128), for subkey generation.
+    fn double(block: [u8; 16]) -> [u8; 16] {
+        let value = u128::from_be_bytes(block);
+        let carry = if value >> 127 != 0 { 0x87 } else { 0 };
+        ((value << 1) ^ carry).to_be_bytes()
+    }
+
+    fn process_buffer(&mut self) {
+        for (s, b) in self.state.iter_mut().zip(self.buffer) {
+            *s ^= b;
+        }
+        self.cipher.encrypt(&mut self.state);
+        self.buffer_len = 0;
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 16] {
+        let mut subkey = [0; 16];
+        self.cipher.encrypt(&mut subkey);
+        subkey = Self::double(subkey);
+        if self.buffer_len < 16 {
+            subkey = Self::double(subkey);
+            self.buffer[self.buffer_len] = 0x80;
+            self.buffer[self.buffer_len + 1..].fill(0);
+        }
+        for (b, k) in self.buffer.iter_mut().zip(subkey) {
+            *b ^= k;
+        }
+        self.process_buffer();
+        self.state
+    }
+}
+
+impl Digest for AesCmac {
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.buffer_len == 16 {
+                self.process_buffer();
+            }
+            let len = data.len().min(16 - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + len].copy_from_slice(&data[..len]);
+            self.buffer_len += len;
+            data = &data[len..];
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn md5(data: &[u8]) -> [u8; 16] {
+        let mut md5 = Md5::new();
+        md5.update(data);
+        md5.finish()
+    }
+
+    fn sha1(data: &[u8]) -> [u8; 20] {
+        let mut sha1 = Sha1::new();
+        sha1.update(data);
+        sha1.finish()
+    }
+
+    fn hex<const N: usize>(s: &str) -> [u8; N] {
+        core::array::from_fn(|i| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap())
+    }
+
+    #[test]
+    fn test_md5() {
+        // Test vectors from RFC 1321, appendix A.5.
+        assert_eq!(md5(b""), hex("d41d8cd98f00b204e9800998ecf8427e"));
+        assert_eq!(md5(b"abc"), hex("900150983cd24fb0d6963f7d28e17f72"));
+        assert_eq!(
+            md5(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            ),
+            hex("57edf4a22be3c955ac49da2e2107b67a")
+        );
+
+        // Feeding the input in pieces doesn't change the digest.
+        let mut pieces = Md5::new();
+        pieces.update(b"1234567890123456789012345678901234567890");
+        pieces.update(b"1234567890123456789012345678901234567890");
+        assert_eq!(pieces.finish(), hex("57edf4a22be3c955ac49da2e2107b67a"));
+    }
+
+    #[test]
+    fn test_sha1() {
+        // Test vectors from RFC 3174, section 7.3.
+        assert_eq!(
+            sha1(b"abc"),
+            hex("a9993e364706816aba3e25717850c26c9cd0d89d")
+        );
+        assert_eq!(
+            sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            hex("84983e441c3bd26ebaae4aa1f95129e5e54670f1")
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha1() {
+        // Test cases 1 and 6 from RFC 2202.
+        let mut hmac = HmacSha1::new(&[0x0b; 20]);
+        hmac.update(b"Hi There");
+        assert_eq!(
+            hmac.finish(),
+            hex("b617318655057264e28bc0b6fb378c8ef146be00")
+        );
+
+        let mut hmac = HmacSha1::new(&[0xaa; 80]);
+        hmac.update(b"Test Using Larger Than Block-Size Key - Hash Key First");
+        assert_eq!(
+            hmac.finish(),
+            hex("aa4ae5e15272d00e95705637ce8a3b55ed402112")
+        );
+    }
+
+    #[test]
+    fn test_aes128() {
+        // Example from FIPS 197, appendix C.1.
+        let cipher = Aes128::new(&hex("000102030405060708090a0b0c0d0e0f"));
+        let mut block = hex("00112233445566778899aabbccddeeff");
+        cipher.encrypt(&mut block);
+        assert_eq!(block, hex("69c4e0d86a7b0430d8cdb78070b4c55a"));
+    }
+
+    #[test]
+    fn test_aes_cmac() {
+        // Examples from RFC 4493, section 4.
+        let key = hex("2b7e151628aed2a6abf7158809cf4f3c");
+        let message: [u8; 40] =
+            hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e5130c81c46a35ce411");
+
+        let cmac = AesCmac::new(&key);
+        assert_eq!(cmac.finish(), hex("bb1d6929e95937287fa37d129b756746"));
+
+        let mut cmac = AesCmac::new(&key);
+        cmac.update(&message[..16]);
+        assert_eq!(cmac.finish(), hex("070a16b46b4d4144f79bdd9dd04a287c"));
+
+        let mut cmac = AesCmac::new(&key);
+        cmac.update(&message[..20]);
+        cmac.update(&message[20..]);
+        assert_eq!(cmac.finish(), hex("dfa66747de9ae63030ca32611497c827"));
+    }
+}
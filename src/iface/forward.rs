@@ -0,0 +1,501 @@
+// Forwarding of IP packets between interfaces.
+//
+// An interface with forwarding queues (see `Config::forward_queue_capacity`) sets
+// aside the unicast packets it receives for an address that is not its own, instead
+// of dropping them. A `Forwarder` joins several such interfaces: it picks the one
+// with the most specific route to the destination of each packet, decrements its
+// TTL or hop limit, and queues it for transmission there, sending ICMP errors back
+// through the interface the packet came from when it cannot do so. The interfaces
+// send what is queued for them on their next egress poll, and fragment the IPv4
+// packets larger than their MTU with the fragmenter they use for their own packets.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use super::Interface;
+#[cfg(feature = "proto-ipv6")]
+use super::packet::icmp_reply_payload_len;
+#[cfg(feature = "proto-ipv4")]
+use crate::phy::ChecksumCapabilities;
+use crate::storage::{PacketBuffer, PacketMetadata};
+use crate::wire::*;
+
+/// A fixed-size queue of raw IP packets.
+#[derive(Debug)]
+pub(crate) struct ForwardQueue {
+    packets: PacketBuffer<'static, ()>,
+    dropped: usize,
+}
+
+impl ForwardQueue {
+    /// Create a queue of at most `capacity` packets of at most `mtu` octets each.
+    /// A queue of no packet disables forwarding.
+    pub(crate) fn new(capacity: usize, mtu: usize) -> Self {
+        Self {
+            packets: PacketBuffer::new(
+                vec![PacketMetadata::EMPTY; capacity],
+                vec![0; capacity * mtu],
+            ),
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.packets.packet_capacity() > 0
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Queue a copy of `packet`. Return `false` if the queue is full.
+    pub(crate) fn push(&mut self, packet: &[u8]) -> bool {
+        self.push_with(packet.len(), |buffer| buffer.copy_from_slice(packet))
+    }
+
+    /// Queue a packet of `len` octets, written by `f` into a zeroed buffer. Return
+    /// `false` if the queue is full.
+    fn push_with(&mut self, len: usize, f: impl FnOnce(&mut [u8])) -> bool {
+        match self.packets.enqueue(len, ()) {
+            Ok(buffer) => {
+                buffer.fill(0);
+                f(buffer);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub(crate) fn peek(&mut self) -> Option<&[u8]> {
+        self.packets.peek().ok().map(|((), packet)| packet)
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<&mut [u8]> {
+        self.packets.dequeue().ok().map(|((), packet)| packet)
+    }
+
+    /// Count a packet dropped on its way through the queue.
+    pub(crate) fn count_dropped(&mut self) {
+        self.dropped += 1;
+    }
+
+    fn take_dropped(&mut self) -> usize {
+        mem::take(&mut self.dropped)
+    }
+}
+
+/// Statistics of the decisions taken on forwarded packets.
+///
+/// Every packet set aside for forwarding by an interface is counted exactly once:
+/// either as forwarded, or under the reason it was dropped. The ICMP errors sent
+/// back in place of dropped packets are counted on their own.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct ForwardCounters {
+    /// Packets queued for transmission on the interface with a route to their destination.
+    pub forwarded: usize,
+    /// Forwarded packets larger than the MTU of the outgoing interface, sent as fragments.
+    pub fragmented: usize,
+    /// Packets dropped because their TTL or hop limit ran out.
+    pub ttl_exceeded: usize,
+    /// Packets dropped because no interface has a route to their destination.
+    pub no_route: usize,
+    /// Packets dropped because they are larger than the MTU of the outgoing interface,
+    /// and their sender does not allow them to be fragmented.
+    pub too_big: usize,
+    /// Packets dropped because they are malformed, or larger than the MTU of the
+    /// outgoing interface and cannot be fragmented here.
+    pub discarded: usize,
+    /// Packets dropped because the queue of the incoming or outgoing interface was full.
+    pub queue_full: usize,
+    /// Forwarded packets dropped by the outgoing interface, because it could not
+    /// find the link-layer address of the next hop.
+    pub unresolved: usize,
+    /// ICMP errors queued back to the sender of a dropped packet.
+    pub icmp_errors: usize,
+}
+
+/// Forwarding of IP packets between interfaces.
+///
+/// Interfaces take part in forwarding when created with a non-zero
+/// [`Config::forward_queue_capacity`](super::Config::forward_queue_capacity).
+/// Each call to [`forward`](Forwarder::forward) hands the packets set aside by the
+/// interfaces since the last call to the interface with the most specific route to
+/// their destination, that is, an address prefix assigned to the interface, or a
+/// route in its [`Routes`](super::Routes) table. The interfaces send them on their
+/// next egress poll.
+#[derive(Debug, Default)]
+pub struct Forwarder {
+    counters: ForwardCounters,
+    buffer: Vec<u8>,
+}
+
+impl Forwarder {
+    /// Create a forwarder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the statistics of the decisions taken so far.
+    pub fn counters(&self) -> ForwardCounters {
+        self.counters
+    }
+
+    /// Forward the packets set aside by `interfaces`.
+    ///
+    /// Returns `true` if any packet, forwarded or an ICMP error, was queued for
+    /// transmission, in which case the interfaces should be polled for egress.
+    pub fn forward(&mut self, interfaces: &mut [&mut Interface]) -> bool {
+        for iface in interfaces.iter_mut() {
+            self.counters.queue_full += iface.inner.forward_rx.take_dropped();
+            self.counters.unresolved += iface.forward_tx.take_dropped();
+        }
+
+        let mut buffer = mem::take(&mut self.buffer);
+        let mut queued = false;
+        for ingress in 0..interfaces.len() {
+            while let Some(packet) = interfaces[ingress].inner.forward_rx.pop() {
+                buffer.clear();
+                buffer.extend_from_slice(packet);
+                queued |= self.forward_packet(interfaces, ingress, &mut buffer);
+            }
+        }
+        self.buffer = buffer;
+        queued
+    }
+
+    fn forward_packet(
+        &mut self,
+        interfaces: &mut [&mut Interface],
+        ingress: usize,
+        packet: &mut [u8],
+    ) -> bool {
+        match IpVersion::of_packet(packet) {
+            #[cfg(feature = "proto-ipv4")]
+            Ok(IpVersion::Ipv4) => self.forward_ipv4(interfaces, ingress, packet),
+            #[cfg(feature = "proto-ipv6")]
+            Ok(IpVersion::Ipv6) => self.forward_ipv6(interfaces, ingress, packet),
+            Err(_) => {
+                self.counters.discarded += 1;
+                false
+            }
+        }
+    }
+
+    #[cfg(feature = "proto-ipv4")]
+    fn forward_ipv4(
+        &mut self,
+        interfaces: &mut [&mut Interface],
+        ingress: usize,
+        packet: &mut [u8],
+    ) -> bool {
+        let Ok(ipv4_packet) = Ipv4Packet::new_checked(&*packet) else {
+            self.counters.discarded += 1;
+            return false;
+        };
+
+        let dst_addr = IpAddress::Ipv4(ipv4_packet.dst_addr());
+        if is_local(interfaces, &dst_addr) {
+            self.counters.discarded += 1;
+            return false;
+        }
+
+        let Some(egress) = route(interfaces, &dst_addr) else {
+            net_debug!("forward: no route to {}", dst_addr);
+            self.counters.no_route += 1;
+            return self.icmpv4_error(
+                interfaces[ingress],
+                packet,
+                Icmpv4Message::DstUnreachable,
+                Icmpv4DstUnreachable::NetUnreachable.into(),
+                0,
+            );
+        };
+
+        if ipv4_packet.hop_limit() <= 1 {
+            self.counters.ttl_exceeded += 1;
+            return self.icmpv4_error(
+                interfaces[ingress],
+                packet,
+                Icmpv4Message::TimeExceeded,
+                Icmpv4TimeExceeded::TtlExpired.into(),
+                0,
+            );
+        }
+
+        let mtu = interfaces[egress].inner.ip_mtu();
+        let len = packet.len();
+        if len > mtu {
+            if ipv4_packet.dont_frag() {
+                self.counters.too_big += 1;
+                return self.icmpv4_error(
+                    interfaces[ingress],
+                    packet,
+                    Icmpv4Message::DstUnreachable,
+                    Icmpv4DstUnreachable::FragRequired.into(),
+                    mtu.min(u16::MAX as usize) as u16,
+                );
+            }
+
+            // Only whole packets without options fit the fragmenter, which copies
+            // the header of the first fragment into the others.
+            #[cfg(feature = "proto-ipv4-fragmentation")]
+            let fragmentable = len <= crate::config::FRAGMENTATION_BUFFER_SIZE
+                && ipv4_packet.header_len() as usize == IPV4_HEADER_LEN
+                && !ipv4_packet.more_frags()
+                && ipv4_packet.frag_offset() == 0;
+            #[cfg(not(feature = "proto-ipv4-fragmentation"))]
+            let fragmentable = false;
+
+            if !fragmentable {
+                net_debug!("forward: cannot fragment packet to {}", dst_addr);
+                self.counters.discarded += 1;
+                return false;
+            }
+        }
+
+        let mut ipv4_packet = Ipv4Packet::new_unchecked(&mut *packet);
+        ipv4_packet.set_hop_limit(ipv4_packet.hop_limit() - 1);
+        ipv4_packet.fill_checksum();
+        self.queue(interfaces[egress], packet, len > mtu)
+    }
+
+    #[cfg(feature = "proto-ipv6")]
+    fn forward_ipv6(
+        &mut self,
+        interfaces: &mut [&mut Interface],
+        ingress: usize,
+        packet: &mut [u8],
+    ) -> bool {
+        let Ok(ipv6_packet) = Ipv6Packet::new_checked(&*packet) else {
+            self.counters.discarded += 1;
+            return false;
+        };
+
+        let dst_addr = IpAddress::Ipv6(ipv6_packet.dst_addr());
+        if is_local(interfaces, &dst_addr) {
+            self.counters.discarded += 1;
+            return false;
+        }
+
+        let Some(egress) = route(interfaces, &dst_addr) else {
+            net_debug!("forward: no route to {}", dst_addr);
+            self.counters.no_route += 1;
+            return self.icmpv6_error(
+                interfaces[ingress],
+                packet,
+                Icmpv6Message::DstUnreachable,
+                Icmpv6DstUnreachable::NoRoute.into(),
+                0,
+            );
+        };
+
+        if ipv6_packet.hop_limit() <= 1 {
+            self.counters.ttl_exceeded += 1;
+            return self.icmpv6_error(
+                interfaces[ingress],
+                packet,
+                Icmpv6Message::TimeExceeded,
+                Icmpv6TimeExceeded::HopLimitExceeded.into(),
+                0,
+            );
+        }
+
+        // Routers never fragment IPv6 packets, RFC 8200 § 5.
+        let mtu = interfaces[egress].inner.ip_mtu();
+        if packet.len() > mtu {
+            self.counters.too_big += 1;
+            return self.icmpv6_error(
+                interfaces[ingress],
+                packet,
+                Icmpv6Message::PktTooBig,
+                0,
+                mtu as u32,
+            );
+        }
+
+        let mut ipv6_packet = Ipv6Packet::new_unchecked(&mut *packet);
+        ipv6_packet.set_hop_limit(ipv6_packet.hop_limit() - 1);
+        self.queue(interfaces[egress], packet, false)
+    }
+
+    /// Queue `packet` for transmission on `iface`.
+    fn queue(&mut self, iface: &mut Interface, packet: &[u8], fragmented: bool) -> bool {
+        if !iface.forward_tx.push(packet) {
+            net_debug!("forward: queue full");
+            self.counters.queue_full += 1;
+            return false;
+        }
+
+        self.counters.forwarded += 1;
+        if fragmented {
+            self.counters.fragmented += 1;
+        }
+        true
+    }
+
+    /// Queue an ICMP error of `msg_type` and `msg_code` about `packet` back to its
+    /// sender through `iface`, the interface it came from. `info` fills the second
+    /// half of the ICMP header, for the next-hop MTU of a fragmentation required
+    /// error.
+    ///
+    /// No error is sent about an ICMP error or a fragment other than the first,
+    /// RFC 1812 § 4.3.2.7.
+    #[cfg(feature = "proto-ipv4")]
+    fn icmpv4_error(
+        &mut self,
+        iface: &mut Interface,
+        packet: &[u8],
+        msg_type: Icmpv4Message,
+        msg_code: u8,
+        info: u16,
+    ) -> bool {
+        let ipv4_packet = Ipv4Packet::new_unchecked(packet);
+        let Ok(header) = Ipv4Repr::parse(&ipv4_packet, &ChecksumCapabilities::ignored()) else {
+            return false;
+        };
+        let payload = ipv4_packet.payload();
+
+        if ipv4_packet.frag_offset() != 0 {
+            return false;
+        }
+        if header.next_header == IpProtocol::Icmp
+            && let Ok(icmp_packet) = Icmpv4Packet::new_checked(payload)
+            && !matches!(
+                icmp_packet.msg_type(),
+                Icmpv4Message::EchoRequest | Icmpv4Message::EchoReply
+            )
+        {
+            return false;
+        }
+
+        let Some(src_addr) = iface.inner.get_source_address_ipv4(&header.src_addr) else {
+            return false;
+        };
+
+        // The quote keeps the options of the original header, and as much of its
+        // payload as fits in the minimum MTU, RFC 1812 § 4.3.2.3.
+        let quote_header_len = ipv4_packet.header_len() as usize;
+        let data_len = payload
+            .len()
+            .min(IPV4_MIN_MTU - header.buffer_len() - quote_header_len - 8);
+        let data = &payload[..data_len];
+        let icmp_len = 8 + quote_header_len + data.len();
+        let ip_repr = Ipv4Repr {
+            src_addr,
+            dst_addr: header.src_addr,
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_len,
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        };
+
+        let checksum = iface.inner.checksum_caps();
+        let queued = iface
+            .forward_tx
+            .push_with(ip_repr.buffer_len() + icmp_len, |buffer| {
+                ip_repr.emit(&mut Ipv4Packet::new_unchecked(&mut *buffer), &checksum);
+
+                let mut icmp_packet =
+                    Icmpv4Packet::new_unchecked(&mut buffer[ip_repr.buffer_len()..]);
+                icmp_packet.set_msg_type(msg_type);
+                icmp_packet.set_msg_code(msg_code);
+                icmp_packet.set_next_hop_mtu(info);
+                let quote = icmp_packet.data_mut();
+                quote[..quote_header_len].copy_from_slice(&packet[..quote_header_len]);
+                quote[quote_header_len..].copy_from_slice(data);
+                if checksum.icmpv4.tx() {
+                    icmp_packet.fill_checksum();
+                }
+            });
+        if queued {
+            self.counters.icmp_errors += 1;
+        }
+        queued
+    }
+
+    /// Queue an ICMPv6 error of `msg_type` and `msg_code` about `packet` back to
+    /// its sender through `iface`, the interface it came from. `info` fills the
+    /// second half of the ICMPv6 header, for the MTU of a packet too big error.
+    ///
+    /// No error is sent about an ICMPv6 error, RFC 4443 § 2.4.
+    #[cfg(feature = "proto-ipv6")]
+    fn icmpv6_error(
+        &mut self,
+        iface: &mut Interface,
+        packet: &[u8],
+        msg_type: Icmpv6Message,
+        msg_code: u8,
+        info: u32,
+    ) -> bool {
+        let ipv6_packet = Ipv6Packet::new_unchecked(packet);
+        let src_addr = ipv6_packet.src_addr();
+        let payload = ipv6_packet.payload();
+
+        if ipv6_packet.next_header() == IpProtocol::Icmpv6
+            && let Ok(icmp_packet) = Icmpv6Packet::new_checked(payload)
+            && icmp_packet.msg_type().is_error()
+        {
+            return false;
+        }
+
+        let reply_src_addr = iface.inner.get_source_address_ipv6(&src_addr);
+        let header_len = IPV6_HEADER_LEN;
+        let data = &payload[..icmp_reply_payload_len(payload.len(), IPV6_MIN_MTU, header_len)];
+        let icmp_len = 8 + header_len + data.len();
+        let ip_repr = Ipv6Repr {
+            src_addr: reply_src_addr,
+            dst_addr: src_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_len,
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        };
+
+        let checksum = iface.inner.checksum_caps();
+        let queued = iface
+            .forward_tx
+            .push_with(ip_repr.buffer_len() + icmp_len, |buffer| {
+                ip_repr.emit(&mut Ipv6Packet::new_unchecked(&mut *buffer));
+
+                let mut icmp_packet =
+                    Icmpv6Packet::new_unchecked(&mut buffer[ip_repr.buffer_len()..]);
+                icmp_packet.set_msg_type(msg_type);
+                icmp_packet.set_msg_code(msg_code);
+                icmp_packet.set_pkt_too_big_mtu(info);
+                let quote = icmp_packet.payload_mut();
+                quote[..header_len].copy_from_slice(&packet[..header_len]);
+                quote[header_len..].copy_from_slice(data);
+                if checksum.icmpv6.tx() {
+                    icmp_packet.fill_checksum(&reply_src_addr, &src_addr);
+                }
+            });
+        if queued {
+            self.counters.icmp_errors += 1;
+        }
+        queued
+    }
+}
+
+/// Query whether `addr` belongs to one of `interfaces`. Packets received by an
+/// interface for an address of another are dropped, since the interfaces do
+/// not share their sockets.
+fn is_local(interfaces: &[&mut Interface], addr: &IpAddress) -> bool {
+    interfaces.iter().any(|iface| iface.has_ip_addr(*addr))
+}
+
+/// Return the index of the interface with the most specific route to `addr`.
+fn route(interfaces: &[&mut Interface], addr: &IpAddress) -> Option<usize> {
+    let mut best: Option<(usize, u8)> = None;
+    for (index, iface) in interfaces.iter().enumerate() {
+        let Some(prefix_len) = iface.inner.forward_prefix_len(addr) else {
+            continue;
+        };
+        if best.is_none_or(|(_, best_len)| prefix_len > best_len) {
+            best = Some((index, prefix_len));
+        }
+    }
+    best.map(|(index, _)| index)
+}
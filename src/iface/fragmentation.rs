@@ -334,6 +334,7 @@ impl Fragmenter {
                     next_header: IpProtocol::Unknown(0),
                     payload_len: 0,
                     hop_limit: 0,
+                    ecn: IpEcn::NotEct,
                 },
                 #[cfg(feature = "medium-ethernet")]
                 dst_hardware_addr: EthernetAddress::default(),
@@ -378,6 +379,7 @@ impl Fragmenter {
                 next_header: IpProtocol::Unknown(0),
                 payload_len: 0,
                 hop_limit: 0,
+                ecn: IpEcn::NotEct,
             };
             #[cfg(feature = "medium-ethernet")]
             {
@@ -0,0 +1,247 @@
+use super::*;
+
+impl Interface {
+    /// Send the packets queued for this interface by a [`Forwarder`](crate::iface::Forwarder).
+    pub(super) fn forward_egress(&mut self, device: &mut (impl Device + ?Sized)) {
+        while let Some(packet) = self.forward_tx.peek() {
+            // A packet to fragment waits for the fragments of the previous one.
+            #[cfg(feature = "proto-ipv4-fragmentation")]
+            if packet.len() > self.inner.ip_mtu() && !self.fragmenter.is_empty() {
+                break;
+            }
+
+            let Some(tx_token) = device.transmit(self.inner.now) else {
+                break;
+            };
+
+            let result = self
+                .inner
+                .dispatch_forwarded(tx_token, packet, &mut self.fragmenter);
+            match result {
+                Ok(()) => (),
+                // Hold the packet until the next hop is resolved, if there's room.
+                #[cfg(feature = "medium-ethernet")]
+                Err(DispatchError::NeighborPending)
+                    if dst_addr(packet).is_some_and(|dst_addr| {
+                        let flow = flow(packet);
+                        self.inner
+                            .hold_packet(&dst_addr, &flow, packet.len(), |buffer| {
+                                buffer.copy_from_slice(packet)
+                            })
+                    }) => {}
+                Err(e) => {
+                    net_debug!("Failed to forward packet: {:?}", e);
+                    self.forward_tx.count_dropped();
+                }
+            }
+            self.forward_tx.pop();
+        }
+    }
+}
+
+impl InterfaceInner {
+    /// Set `packet`, received from `src_addr` for `dst_addr`, aside for a
+    /// [`Forwarder`](crate::iface::Forwarder), if the interface forwards packets
+    /// and this one is not for us.
+    ///
+    /// Returns `true` if the packet was taken, even if the queue was full.
+    pub(super) fn forward_ingress(
+        &mut self,
+        src_addr: IpAddress,
+        dst_addr: IpAddress,
+        packet: &[u8],
+    ) -> bool {
+        if !self.forward_rx.is_enabled()
+            || !src_addr.is_unicast()
+            || !dst_addr.is_unicast()
+            || is_link_scoped(&src_addr)
+            || is_link_scoped(&dst_addr)
+            || self.has_ip_addr(dst_addr)
+            || self.is_broadcast(&dst_addr)
+        {
+            return false;
+        }
+
+        if self.any_ip
+            && self
+                .routes
+                .lookup(&dst_addr, self.now)
+                .is_some_and(|router_addr| self.has_ip_addr(router_addr))
+        {
+            return false;
+        }
+
+        if !self.forward_rx.push(packet) {
+            net_debug!("Forwarding queue full; dropping packet to {}", dst_addr);
+            self.forward_rx.count_dropped();
+        }
+        true
+    }
+
+    /// Return the prefix length of the most specific route to `addr` through
+    /// this interface, whether an address prefix assigned to it, or a route.
+    pub(crate) fn forward_prefix_len(&self, addr: &IpAddress) -> Option<u8> {
+        let on_link = self
+            .ip_addrs
+            .iter()
+            .filter(|cidr| cidr.contains_addr(addr))
+            .map(|cidr| cidr.prefix_len())
+            .max();
+        let routed = self
+            .routes
+            .lookup_route(addr, &Flow::default(), self.now)
+            .map(|route| route.cidr.prefix_len());
+        on_link.max(routed)
+    }
+
+    /// Send `packet`, a whole IP packet forwarded from another interface or held
+    /// until its next hop was resolved, to that next hop, starting to fragment it
+    /// if it is larger than the MTU.
+    pub(super) fn dispatch_forwarded<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        packet: &[u8],
+        #[allow(unused_variables)] frag: &mut Fragmenter,
+    ) -> Result<(), DispatchError> {
+        #[allow(unused_variables)]
+        let version = match IpVersion::of_packet(packet) {
+            Ok(version) => version,
+            Err(_) => return Err(DispatchError::NoRoute),
+        };
+        #[allow(unused_variables)]
+        let dst_addr = dst_addr(packet).ok_or(DispatchError::NoRoute)?;
+
+        #[cfg(feature = "medium-ieee802154")]
+        if matches!(self.caps.medium, Medium::Ieee802154) {
+            net_debug!("Forwarding over IEEE 802.15.4 is unsupported");
+            return Err(DispatchError::NoRoute);
+        }
+
+        #[allow(unused_mut)]
+        let mut link_len = 0;
+
+        #[cfg(feature = "medium-ethernet")]
+        #[allow(unreachable_patterns)]
+        let (dst_hardware_addr, tx_token) = match self.caps.medium {
+            Medium::Ethernet => {
+                link_len = EthernetFrame::<&[u8]>::header_len();
+                match self.lookup_hardware_addr(tx_token, &dst_addr, &flow(packet), frag)? {
+                    (HardwareAddress::Ethernet(addr), tx_token) => (addr, tx_token),
+                    (_, _) => unreachable!(),
+                }
+            }
+            _ => (EthernetAddress([0; 6]), tx_token),
+        };
+
+        // Emit function for the Ethernet header.
+        #[cfg(feature = "medium-ethernet")]
+        let src_hardware_addr = self.hardware_addr;
+        let emit_link = |tx_buffer: &mut [u8]| {
+            #[cfg(feature = "medium-ethernet")]
+            if link_len != 0 {
+                let mut frame = EthernetFrame::new_unchecked(tx_buffer);
+                frame.set_src_addr(src_hardware_addr.ethernet_or_panic());
+                frame.set_dst_addr(dst_hardware_addr);
+                match version {
+                    #[cfg(feature = "proto-ipv4")]
+                    IpVersion::Ipv4 => frame.set_ethertype(EthernetProtocol::Ipv4),
+                    #[cfg(feature = "proto-ipv6")]
+                    IpVersion::Ipv6 => frame.set_ethertype(EthernetProtocol::Ipv6),
+                }
+            }
+            #[cfg(not(feature = "medium-ethernet"))]
+            let _ = tx_buffer;
+        };
+
+        let ip_mtu = self.caps.ip_mtu();
+        if packet.len() <= ip_mtu {
+            return tx_token.consume(link_len + packet.len(), |tx_buffer| {
+                emit_link(tx_buffer);
+                tx_buffer[link_len..].copy_from_slice(packet);
+                Ok(())
+            });
+        }
+
+        // Forwarding only lets through IPv4 packets the fragmenter can take, see
+        // `Forwarder::forward_ipv4`.
+        #[cfg(feature = "proto-ipv4-fragmentation")]
+        if version == IpVersion::Ipv4 && packet.len() <= frag.buffer.len() {
+            let ipv4_packet = Ipv4Packet::new_unchecked(packet);
+            let Ok(repr) = Ipv4Repr::parse(&ipv4_packet, &ChecksumCapabilities::ignored()) else {
+                net_debug!("Malformed forwarded packet. Dropping.");
+                return Ok(());
+            };
+            let first_frag_ip_len = ip_mtu;
+
+            // Save the whole packet, and the IP header for the other fragments.
+            frag.buffer[..packet.len()].copy_from_slice(packet);
+            frag.packet_len = packet.len();
+            frag.sent_bytes = first_frag_ip_len;
+            frag.ipv4.repr = repr;
+            frag.ipv4.ident = ipv4_packet.ident();
+            frag.ipv4.frag_offset = (first_frag_ip_len - repr.buffer_len()) as u16;
+            #[cfg(feature = "medium-ethernet")]
+            {
+                frag.ipv4.dst_hardware_addr = dst_hardware_addr;
+            }
+
+            let mut first_frag = Ipv4Packet::new_unchecked(&mut frag.buffer[..first_frag_ip_len]);
+            first_frag.set_total_len(first_frag_ip_len as u16);
+            first_frag.set_more_frags(true);
+            if self.caps.checksum.ipv4.tx() {
+                first_frag.fill_checksum();
+            }
+
+            return tx_token.consume(link_len + first_frag_ip_len, |tx_buffer| {
+                emit_link(tx_buffer);
+                tx_buffer[link_len..].copy_from_slice(&frag.buffer[..first_frag_ip_len]);
+                Ok(())
+            });
+        }
+
+        net_debug!("Forwarded packet larger than the MTU. Dropping.");
+        Ok(())
+    }
+}
+
+/// Return the destination address of `packet`, a whole IP packet.
+fn dst_addr(packet: &[u8]) -> Option<IpAddress> {
+    match IpVersion::of_packet(packet).ok()? {
+        #[cfg(feature = "proto-ipv4")]
+        IpVersion::Ipv4 => Some(IpAddress::Ipv4(Ipv4Packet::new_unchecked(packet).dst_addr())),
+        #[cfg(feature = "proto-ipv6")]
+        IpVersion::Ipv6 => Some(IpAddress::Ipv6(Ipv6Packet::new_unchecked(packet).dst_addr())),
+    }
+}
+
+/// Return the flow of `packet`, a whole IP packet, for route rules.
+fn flow(packet: &[u8]) -> Flow {
+    let (src_addr, protocol) = match IpVersion::of_packet(packet) {
+        #[cfg(feature = "proto-ipv4")]
+        Ok(IpVersion::Ipv4) => {
+            let packet = Ipv4Packet::new_unchecked(packet);
+            (IpAddress::Ipv4(packet.src_addr()), packet.next_header())
+        }
+        #[cfg(feature = "proto-ipv6")]
+        Ok(IpVersion::Ipv6) => {
+            let packet = Ipv6Packet::new_unchecked(packet);
+            (IpAddress::Ipv6(packet.src_addr()), packet.next_header())
+        }
+        Err(_) => return Flow::default(),
+    };
+    Flow {
+        src_addr: Some(src_addr),
+        protocol: Some(protocol),
+        mark: 0,
+    }
+}
+
+/// Query whether `addr` must not leave its link.
+fn is_link_scoped(addr: &IpAddress) -> bool {
+    match addr {
+        #[cfg(feature = "proto-ipv4")]
+        IpAddress::Ipv4(addr) => addr.is_link_local() || addr.is_loopback(),
+        #[cfg(feature = "proto-ipv6")]
+        IpAddress::Ipv6(addr) => addr.is_link_local() || addr.is_loopback(),
+    }
+}
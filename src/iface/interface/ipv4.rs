@@ -43,7 +43,26 @@ impl InterfaceInner {
     /// address is found, the first IPv4 address from the interface is returned.
     #[allow(unused)]
     pub(crate) fn get_source_address_ipv4(&self, dst_addr: &Ipv4Address) -> Option<Ipv4Address> {
+        self.get_source_address_ipv4_flow(dst_addr, &Flow::default())
+    }
+
+    /// Get an IPv4 source address based on a destination address, for the
+    /// packets of `flow`, whose source address is not known yet.
+    ///
+    /// The router of the table selected by the route rules for `flow` is used.
+    #[allow(unused)]
+    pub(crate) fn get_source_address_ipv4_flow(
+        &self,
+        dst_addr: &Ipv4Address,
+        flow: &Flow,
+    ) -> Option<Ipv4Address> {
+        let router = match IpAddress::Ipv4(*dst_addr) {
+            addr if addr.is_unicast() => self.routes.lookup_flow(&addr, flow, self.now),
+            _ => None,
+        };
+
         let mut first_ipv4 = None;
+        let mut router_ipv4 = None;
         for cidr in self.ip_addrs.iter() {
             #[allow(irrefutable_let_patterns)] // if only ipv4 is enabled
             if let IpCidr::Ipv4(cidr) = cidr {
@@ -52,13 +71,23 @@ impl InterfaceInner {
                     return Some(cidr.address());
                 }
 
+                // Remember the first IPv4 address in the subnet of the router the
+                // packets would go through, so that they leave with a source it
+                // expects.
+                if router_ipv4.is_none()
+                    && let Some(IpAddress::Ipv4(router)) = router
+                    && cidr.contains_addr(&router)
+                {
+                    router_ipv4 = Some(cidr.address());
+                }
+
                 // Remember the first IPv4 address as fallback
                 if first_ipv4.is_none() {
                     first_ipv4 = Some(cidr.address());
                 }
             }
         }
-        first_ipv4
+        router_ipv4.or(first_ipv4)
     }
 
     /// Checks if an address is broadcast, taking into account ipv4 subnet-local
@@ -154,6 +183,15 @@ impl InterfaceInner {
             return None;
         }
 
+        #[cfg(feature = "alloc")]
+        if self.forward_ingress(
+            IpAddress::Ipv4(ipv4_repr.src_addr),
+            IpAddress::Ipv4(ipv4_repr.dst_addr),
+            &ipv4_packet.as_ref()[..ipv4_packet.total_len() as usize],
+        ) {
+            return None;
+        }
+
         #[cfg(feature = "proto-ipv4-fragmentation")]
         let ip_payload = {
             if ipv4_packet.more_frags() || ipv4_packet.frag_offset() != 0 {
@@ -374,12 +412,21 @@ impl InterfaceInner {
                 // Fill the ARP cache from any ARP packet aimed at us (both request or response).
                 // We fill from requests too because if someone is requesting our address they
                 // are probably going to talk to us, so we avoid having to request their address
-                // when we later reply to them.
-                self.neighbor_cache.fill(
-                    source_protocol_addr.into(),
-                    source_hardware_addr.into(),
-                    timestamp,
-                );
+                // when we later reply to them. Only a response confirms that the neighbor
+                // receives what we send, like a solicited advertisement in RFC 4861.
+                if operation == ArpOperation::Reply {
+                    self.neighbor_cache.fill(
+                        source_protocol_addr.into(),
+                        source_hardware_addr.into(),
+                        timestamp,
+                    );
+                } else {
+                    self.neighbor_cache.fill_stale(
+                        source_protocol_addr.into(),
+                        source_hardware_addr.into(),
+                        timestamp,
+                    );
+                }
                 on_neighbor_ready(source_protocol_addr.into());
 
                 if operation == ArpOperation::Request {
@@ -441,6 +488,19 @@ impl InterfaceInner {
             // Ignore any echo replies.
             Icmpv4Repr::EchoReply { .. } => None,
 
+            Icmpv4Repr::DstUnreachable {
+                reason: Icmpv4DstUnreachable::FragRequired,
+                header,
+                ..
+            } => {
+                self.process_packet_too_big(
+                    header.src_addr.into(),
+                    header.dst_addr.into(),
+                    icmp_packet.next_hop_mtu() as usize,
+                );
+                None
+            }
+
             // Don't report an error if a packet with unknown type
             // has been handled by an ICMP socket
             #[cfg(feature = "socket-icmp")]
@@ -467,6 +527,7 @@ impl InterfaceInner {
                 next_header: IpProtocol::Icmp,
                 payload_len: icmp_repr.buffer_len(),
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             };
             Some(Packet::new_ipv4(
                 ipv4_reply_repr,
@@ -483,6 +544,7 @@ impl InterfaceInner {
                             next_header: IpProtocol::Icmp,
                             payload_len: icmp_repr.buffer_len(),
                             hop_limit: 64,
+                            ecn: IpEcn::NotEct,
                         };
                         Some(Packet::new_ipv4(
                             ipv4_reply_repr,
@@ -212,6 +212,15 @@ impl InterfaceInner {
             return None;
         }
 
+        #[cfg(feature = "alloc")]
+        if self.forward_ingress(
+            IpAddress::Ipv6(ipv6_repr.src_addr),
+            IpAddress::Ipv6(ipv6_repr.dst_addr),
+            &ipv6_packet.as_ref()[..ipv6_repr.buffer_len() + ipv6_repr.payload_len],
+        ) {
+            return None;
+        }
+
         let (next_header, ip_payload) = if ipv6_repr.next_header == IpProtocol::HopByHop {
             match self.process_hopbyhop(ipv6_repr, ipv6_packet.payload()) {
                 HopByHopResponse::Discard(e) => return e,
@@ -457,6 +466,15 @@ impl InterfaceInner {
             // Ignore any echo replies.
             Icmpv6Repr::EchoReply { .. } => None,
 
+            Icmpv6Repr::PktTooBig { mtu, header, .. } => {
+                self.process_packet_too_big(
+                    header.src_addr.into(),
+                    header.dst_addr.into(),
+                    mtu as usize,
+                );
+                None
+            }
+
             // Forward any NDISC packets to the ndisc packet handler
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
             Icmpv6Repr::Ndisc(repr) if ip_repr.hop_limit == 0xff => match self.caps.medium {
@@ -501,15 +519,32 @@ impl InterfaceInner {
                 flags,
             } => {
                 let ip_addr = ip_repr.src_addr.into();
-                if let Some(lladdr) = lladdr {
-                    let lladdr = check!(lladdr.parse(self.caps.medium));
-                    if !lladdr.is_unicast() || !target_addr.x_is_unicast() {
-                        return None;
+                let solicited = flags.contains(NdiscNeighborFlags::SOLICITED);
+                let Some(lladdr) = lladdr else {
+                    // A solicited advertisement confirms the address we already know.
+                    if solicited {
+                        self.neighbor_cache.confirm_reachable(ip_addr, self.now);
                     }
-                    if flags.contains(NdiscNeighborFlags::OVERRIDE)
-                        || !self.neighbor_cache.lookup(&ip_addr, self.now).found()
-                    {
+                    return None;
+                };
+                let lladdr = check!(lladdr.parse(self.caps.medium));
+                if !lladdr.is_unicast() || !target_addr.x_is_unicast() {
+                    return None;
+                }
+                // See RFC 4861 § 7.2.5 for which advertisements may change
+                // the address and reachability of a neighbor.
+                let known = match self.neighbor_cache.lookup(&ip_addr, self.now) {
+                    NeighborAnswer::Found(known) => Some(known),
+                    _ => None,
+                };
+                if known.is_none()
+                    || known == Some(lladdr)
+                    || flags.contains(NdiscNeighborFlags::OVERRIDE)
+                {
+                    if solicited {
                         self.neighbor_cache.fill(ip_addr, lladdr, self.now);
+                    } else {
+                        self.neighbor_cache.fill_stale(ip_addr, lladdr, self.now);
                     }
                 }
                 None
@@ -525,10 +560,14 @@ impl InterfaceInner {
                         return None;
                     }
                     self.neighbor_cache
-                        .fill(ip_repr.src_addr.into(), lladdr, self.now);
+                        .fill_stale(ip_repr.src_addr.into(), lladdr, self.now);
                 }
 
-                if self.has_solicited_node(ip_repr.dst_addr) && self.has_ip_addr(target_addr) {
+                // Solicitations are multicast to resolve an address, and unicast
+                // to probe reachability.
+                if (self.has_solicited_node(ip_repr.dst_addr) || self.has_ip_addr(ip_repr.dst_addr))
+                    && self.has_ip_addr(target_addr)
+                {
                     let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
                         flags: NdiscNeighborFlags::SOLICITED,
                         target_addr,
@@ -541,6 +580,7 @@ impl InterfaceInner {
                         next_header: IpProtocol::Icmpv6,
                         hop_limit: 0xff,
                         payload_len: advert.buffer_len(),
+                        ecn: IpEcn::NotEct,
                     };
                     Some(Packet::new_ipv6(ip_repr, IpPayload::Icmpv6(advert)))
                 } else {
@@ -571,6 +611,7 @@ impl InterfaceInner {
             next_header: IpProtocol::Icmpv6,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         };
         Some(Packet::new_ipv6(
             ipv6_reply_repr,
@@ -622,6 +663,7 @@ impl InterfaceInner {
                     + mld_repr.buffer_len()
                     + records_len,
                 hop_limit: 1,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::HopByHopIcmpv6(hbh_repr, Icmpv6Repr::Mld(mld_repr)),
         ))
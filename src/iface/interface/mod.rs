@@ -7,6 +7,8 @@ mod tests;
 
 #[cfg(feature = "medium-ethernet")]
 mod ethernet;
+#[cfg(feature = "alloc")]
+mod forward;
 #[cfg(feature = "medium-ieee802154")]
 mod ieee802154;
 
@@ -19,6 +21,8 @@ mod sixlowpan;
 
 #[cfg(feature = "multicast")]
 pub(crate) mod multicast;
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+mod neighbor;
 #[cfg(feature = "socket-tcp")]
 mod tcp;
 #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
@@ -31,6 +35,8 @@ use core::fmt;
 use core::result::Result;
 use heapless::Vec;
 
+#[cfg(feature = "alloc")]
+use super::forward::ForwardQueue;
 #[cfg(feature = "_proto-fragmentation")]
 use super::fragmentation::FragKey;
 #[cfg(feature = "proto-ipv4-fragmentation")]
@@ -40,14 +46,22 @@ use super::fragmentation::{Fragmenter, FragmentsBuffer};
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 use super::neighbor::{
     Answer as NeighborAnswer, Cache as NeighborCache, GatewayNeighborConfigError,
-    GatewayNeighborUpdate,
+    GatewayNeighborUpdate, Probe as NeighborProbe,
 };
+#[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+use super::neighbor_hold::HoldQueue;
+use super::path_mtu::Cache as PathMtuCache;
+use super::route::Flow;
 use super::socket_set::{SocketHandle, SocketSet};
 #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
 use super::tcp_flow_cache::{TcpFlowCache, TcpFlowCacheError, TcpFlowKey, TcpListenerCache};
+#[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+use super::tcp_time_wait::TcpTimeWait;
 #[cfg(feature = "proto-sixlowpan")]
 use crate::config::IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT;
 use crate::config::IFACE_MAX_ADDR_COUNT;
+#[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+use crate::config::IFACE_NEIGHBOR_CACHE_COUNT;
 use crate::iface::Routes;
 use crate::phy::PacketMeta;
 use crate::phy::{ChecksumCapabilities, Device, DeviceCapabilities, Medium, RxToken, TxToken};
@@ -255,6 +269,8 @@ pub struct Interface {
     pub(crate) inner: InterfaceInner,
     fragments: FragmentsBuffer,
     fragmenter: Fragmenter,
+    #[cfg(feature = "alloc")]
+    pub(crate) forward_tx: ForwardQueue,
 }
 
 /// The device independent part of an Ethernet network interface.
@@ -271,6 +287,7 @@ pub struct InterfaceInner {
 
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
     neighbor_cache: NeighborCache,
+    path_mtu_cache: PathMtuCache,
     hardware_addr: HardwareAddress,
     #[cfg(feature = "medium-ieee802154")]
     sequence_no: u8,
@@ -286,14 +303,24 @@ pub struct InterfaceInner {
     ip_addrs: Vec<IpCidr, IFACE_MAX_ADDR_COUNT>,
     any_ip: bool,
     routes: Routes,
+    #[cfg(feature = "alloc")]
+    pub(crate) forward_rx: ForwardQueue,
+    #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+    neighbor_hold: HoldQueue,
     #[cfg(feature = "multicast")]
     multicast: multicast::State,
     /// Round-robin index for socket egress to ensure fair scheduling
     egress_start_index: usize,
+    #[cfg(feature = "socket-tcp")]
+    tcp_fast_open: crate::socket::tcp::FastOpen,
+    #[cfg(feature = "socket-tcp")]
+    tcp_syn_cookies: crate::socket::tcp::SynCookies,
     #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
     tcp_flow_cache: TcpFlowCache,
     #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
     tcp_listener_cache: TcpListenerCache,
+    #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+    tcp_time_wait: TcpTimeWait,
     #[cfg(all(any(feature = "latency-probe", feature = "market-trace"), feature = "alloc", feature = "socket-tcp"))]
     tcp_probe_cache_hits: usize,
     #[cfg(all(any(feature = "latency-probe", feature = "market-trace"), feature = "alloc", feature = "socket-tcp"))]
@@ -334,6 +361,49 @@ pub struct Config {
     #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
     pub tcp_flow_cache_capacity: usize,
 
+    /// Maximum number of TCP connections in the TIME-WAIT state kept by the interface.
+    ///
+    /// A TCP socket whose connection reaches the TIME-WAIT state, and has nothing
+    /// left to deliver, hands the connection over to the interface and moves to the
+    /// `CLOSED` state, so that it can be used again right away. The interface then
+    /// acknowledges retransmitted FINs and rejects old duplicate segments in its place.
+    /// When the table is full, sockets keep their connection in the TIME-WAIT state.
+    ///
+    /// Storage is allocated once by [`Interface::new`]. Defaults to zero, which
+    /// leaves the TIME-WAIT state to the sockets.
+    #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+    pub tcp_time_wait_capacity: usize,
+
+    /// Maximum number of packets kept by the interface for forwarding, in each direction.
+    ///
+    /// An interface with room for packets to forward sets aside the unicast packets
+    /// it receives for an address that is not its own, for a [`Forwarder`] to pass
+    /// them on to another interface, and sends the packets a [`Forwarder`] passes
+    /// on to it on its next egress poll. Each queue holds at most this many packets
+    /// of the MTU of the interface.
+    ///
+    /// Storage is allocated once by [`Interface::new`]. Defaults to zero, which
+    /// disables forwarding on the interface.
+    ///
+    /// [`Forwarder`]: crate::iface::Forwarder
+    #[cfg(feature = "alloc")]
+    pub forward_queue_capacity: usize,
+
+    /// Maximum number of packets held by the interface for each neighbor whose
+    /// link-layer address is being resolved.
+    ///
+    /// Packets for such a neighbor are otherwise dropped, and the sockets that sent
+    /// them retry once the neighbor is resolved or after a while. Held packets are
+    /// sent on the next egress poll after the neighbor is resolved. Those held for
+    /// a neighbor that could not be resolved are handed back by
+    /// [`Interface::take_unresolved`]. Only Ethernet interfaces hold packets, of at
+    /// most the MTU of the interface.
+    ///
+    /// Storage is allocated once by [`Interface::new`], for as many neighbors as the
+    /// neighbor cache holds. Defaults to zero, which holds no packet.
+    #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+    pub neighbor_hold_capacity: usize,
+
     /// Set the IEEE802.15.4 PAN ID the interface will use.
     ///
     /// **NOTE**: we use the same PAN ID for destination and source.
@@ -348,6 +418,12 @@ impl Config {
             hardware_addr,
             #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
             tcp_flow_cache_capacity: 16_384,
+            #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+            tcp_time_wait_capacity: 0,
+            #[cfg(feature = "alloc")]
+            forward_queue_capacity: 0,
+            #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+            neighbor_hold_capacity: 0,
             #[cfg(feature = "medium-ieee802154")]
             pan_id: None,
         }
@@ -402,6 +478,19 @@ impl Interface {
             }
         }
 
+        #[cfg(feature = "alloc")]
+        let forward_mtu = caps.ip_mtu();
+        #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+        let neighbor_hold = match caps.medium {
+            Medium::Ethernet => HoldQueue::new(
+                config.neighbor_hold_capacity,
+                IFACE_NEIGHBOR_CACHE_COUNT,
+                caps.ip_mtu(),
+            ),
+            #[allow(unreachable_patterns)]
+            _ => HoldQueue::new(0, 0, 0),
+        };
+
         Interface {
             fragments: FragmentsBuffer {
                 #[cfg(feature = "proto-sixlowpan")]
@@ -413,6 +502,8 @@ impl Interface {
                 reassembly_timeout: Duration::from_secs(60),
             },
             fragmenter: Fragmenter::new(),
+            #[cfg(feature = "alloc")]
+            forward_tx: ForwardQueue::new(config.forward_queue_capacity, forward_mtu),
             inner: InterfaceInner {
                 now,
                 caps,
@@ -420,8 +511,13 @@ impl Interface {
                 ip_addrs: Vec::new(),
                 any_ip: false,
                 routes: Routes::new(),
+                #[cfg(feature = "alloc")]
+                forward_rx: ForwardQueue::new(config.forward_queue_capacity, forward_mtu),
+                #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+                neighbor_hold,
                 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
                 neighbor_cache: NeighborCache::new(),
+                path_mtu_cache: PathMtuCache::new(),
                 #[cfg(feature = "multicast")]
                 multicast: multicast::State::new(),
                 #[cfg(feature = "medium-ieee802154")]
@@ -436,10 +532,16 @@ impl Interface {
                 sixlowpan_address_context: Vec::new(),
                 rand,
                 egress_start_index: 0,
+                #[cfg(feature = "socket-tcp")]
+                tcp_fast_open: crate::socket::tcp::FastOpen::new(),
+                #[cfg(feature = "socket-tcp")]
+                tcp_syn_cookies: crate::socket::tcp::SynCookies::new(),
                 #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
                 tcp_flow_cache: TcpFlowCache::new(config.tcp_flow_cache_capacity),
                 #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
                 tcp_listener_cache: TcpListenerCache::new(config.tcp_flow_cache_capacity),
+                #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+                tcp_time_wait: TcpTimeWait::new(config.tcp_time_wait_capacity),
                 #[cfg(all(any(feature = "latency-probe", feature = "market-trace"), feature = "alloc", feature = "socket-tcp"))]
                 tcp_probe_cache_hits: 0,
                 #[cfg(all(any(feature = "latency-probe", feature = "market-trace"), feature = "alloc", feature = "socket-tcp"))]
@@ -474,6 +576,28 @@ impl Interface {
         self.inner.unregister_tcp_flow(handle)
     }
 
+    /// Register the 4-tuple of a connected TCP socket, such as one restored with
+    /// [`tcp::Socket::restore`](crate::socket::tcp::Socket::restore), before its
+    /// first inbound packet.
+    ///
+    /// A socket that is not connected has no 4-tuple, and is left unregistered.
+    #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+    pub fn register_tcp_socket<'s, B>(
+        &mut self,
+        sockets: &SocketSet<'s, B>,
+        handle: SocketHandle,
+    ) -> Result<(), TcpFlowCacheError>
+    where
+        B: SocketBufferT<'s>,
+        crate::socket::tcp::Socket<'s, B>: AnySocket<'s, B>,
+    {
+        let socket = sockets.get::<crate::socket::tcp::Socket<'s, B>>(handle);
+        match (socket.local_endpoint(), socket.remote_endpoint()) {
+            (Some(local), Some(remote)) => self.inner.register_tcp_flow(handle, local, remote),
+            _ => Ok(()),
+        }
+    }
+
     /// Register a passive TCP socket in the fixed listener endpoint index.
     #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
     pub fn register_tcp_listener(
@@ -490,6 +614,25 @@ impl Interface {
         self.inner.tcp_listener_cache.remove_handle(handle)
     }
 
+    /// Return the number of TCP connections in the TIME-WAIT state taken over from
+    /// sockets, including those that expired and were not dropped yet.
+    ///
+    /// See [`Config::tcp_time_wait_capacity`].
+    #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+    pub fn tcp_time_wait_len(&self) -> usize {
+        self.inner.tcp_time_wait.len()
+    }
+
+    /// Pass a packet that could not be sent, because its next hop could not be
+    /// resolved while it was held, to `f` with the address of that next hop.
+    ///
+    /// Returns `None` if there is no such packet. Those not taken are dropped
+    /// when their room is needed. See [`Config::neighbor_hold_capacity`].
+    #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+    pub fn take_unresolved<R>(&mut self, f: impl FnOnce(IpAddress, &[u8]) -> R) -> Option<R> {
+        self.inner.neighbor_hold.pop_unresolved(f)
+    }
+
     /// Configure the single gateway whose last-known-good link address may be
     /// used after normal neighbor-cache expiry.
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
@@ -734,6 +877,19 @@ impl Interface {
         self.inner.any_ip
     }
 
+    /// Return the path MTU towards the given destination.
+    ///
+    /// This is the IP MTU of the device, unless an ICMP "fragmentation needed" or
+    /// "packet too big" message reported a lower one recently.
+    pub fn path_mtu(&self, addr: &IpAddress) -> usize {
+        self.inner.path_mtu(addr)
+    }
+
+    /// Forget the path MTUs learnt from ICMP messages.
+    pub fn flush_path_mtu_cache(&mut self) {
+        self.inner.path_mtu_cache.flush()
+    }
+
     /// Get the packet reassembly timeout.
     #[cfg(feature = "_proto-fragmentation")]
     pub fn reassembly_timeout(&self) -> Duration {
@@ -832,6 +988,12 @@ impl Interface {
         #[cfg(feature = "multicast")]
         self.multicast_egress(device);
 
+        #[cfg(feature = "alloc")]
+        self.forward_egress(device);
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        self.neighbor_egress(device);
+
         self.socket_egress(device, sockets)
     }
 
@@ -861,6 +1023,12 @@ impl Interface {
         #[cfg(feature = "multicast")]
         self.multicast_egress(device);
 
+        #[cfg(feature = "alloc")]
+        self.forward_egress(device);
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        self.neighbor_egress(device);
+
         match self.socket_egress_index(device, sockets, handle.index()) {
             SocketEgressOutcome::Changed => PollEgressHandleResult::SocketStateChanged,
             SocketEgressOutcome::None => PollEgressHandleResult::None,
@@ -961,21 +1129,36 @@ impl Interface {
             return Some(Instant::from_millis(0));
         }
 
+        #[cfg(feature = "alloc")]
+        if !self.forward_tx.is_empty() {
+            return Some(Instant::from_millis(0));
+        }
+
+        #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+        if self.inner.neighbor_hold_ready().is_some() {
+            return Some(Instant::from_millis(0));
+        }
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        let neighbor_poll_at = self.inner.neighbor_cache.poll_at();
+        #[cfg(not(any(feature = "medium-ethernet", feature = "medium-ieee802154")))]
+        let neighbor_poll_at = None;
+
         let inner = &mut self.inner;
 
         sockets
             .items()
             .filter_map(move |item| {
                 let socket_poll_at = item.socket.poll_at(inner);
-                match item
-                    .meta
-                    .poll_at(socket_poll_at, |ip_addr| inner.has_neighbor(&ip_addr))
-                {
+                match item.meta.poll_at(socket_poll_at, |ip_addr, flow| {
+                    inner.has_neighbor(&ip_addr, flow)
+                }) {
                     PollAt::Ingress => None,
                     PollAt::Time(instant) => Some(instant),
                     PollAt::Now => Some(Instant::from_millis(0)),
                 }
             })
+            .chain(neighbor_poll_at)
             .min()
     }
 
@@ -998,10 +1181,9 @@ impl Interface {
         let inner = &mut self.inner;
         let item = sockets.item_at(index)?;
         let socket_poll_at = item.socket.poll_at(inner);
-        match item
-            .meta
-            .poll_at(socket_poll_at, |ip_addr| inner.has_neighbor(&ip_addr))
-        {
+        match item.meta.poll_at(socket_poll_at, |ip_addr, flow| {
+            inner.has_neighbor(&ip_addr, flow)
+        }) {
             PollAt::Ingress => None,
             PollAt::Time(instant) => Some(instant),
             PollAt::Now => Some(Instant::from_millis(0)),
@@ -1173,17 +1355,17 @@ impl Interface {
             return SocketEgressOutcome::None;
         };
 
-        if !item
-            .meta
-            .egress_permitted(self.inner.now, |ip_addr| self.inner.has_neighbor(&ip_addr))
-        {
+        if !item.meta.egress_permitted(self.inner.now, |ip_addr, flow| {
+            self.inner.has_neighbor(&ip_addr, flow)
+        }) {
             return SocketEgressOutcome::None;
         }
 
         let mut result = SocketEgressOutcome::None;
         let mut neighbor_addr = None;
         let mut respond = |inner: &mut InterfaceInner, meta: PacketMeta, response: Packet| {
-            neighbor_addr = Some(response.ip_repr().dst_addr());
+            let ip_repr = response.ip_repr();
+            neighbor_addr = Some((ip_repr.dst_addr(), Flow::new(&ip_repr, &meta)));
             let t = device.transmit(inner.now).ok_or_else(|| {
                 net_debug!("failed to transmit IP: device exhausted");
                 EgressError::Exhausted
@@ -1233,13 +1415,17 @@ impl Interface {
                 })
             }
             #[cfg(feature = "socket-tcp")]
-            Socket::Tcp(socket) => socket.dispatch(&mut self.inner, |inner, (ip, tcp)| {
-                respond(
-                    inner,
-                    PacketMeta::default(),
-                    Packet::new(ip, IpPayload::Tcp(tcp)),
-                )
-            }),
+            Socket::Tcp(socket) => {
+                #[allow(unused_mut)]
+                let mut meta = PacketMeta::default();
+                #[cfg(feature = "packetmeta-mark")]
+                {
+                    meta.mark = socket.mark();
+                }
+                socket.dispatch(&mut self.inner, |inner, (ip, tcp)| {
+                    respond(inner, meta, Packet::new(ip, IpPayload::Tcp(tcp)))
+                })
+            }
             #[cfg(feature = "socket-dhcpv4")]
             Socket::Dhcpv4(socket) => {
                 socket.dispatch(&mut self.inner, |inner, (ip, udp, dhcp)| {
@@ -1263,13 +1449,26 @@ impl Interface {
         match dispatch_result {
             Err(EgressError::Exhausted) => return SocketEgressOutcome::Exhausted,
             Err(EgressError::Dispatch) => {
-                item.meta.neighbor_missing(
-                    self.inner.now,
-                    neighbor_addr.expect("non-IP response packet"),
-                );
+                let (neighbor_addr, flow) = neighbor_addr.expect("non-IP response packet");
+                item.meta
+                    .neighbor_missing(self.inner.now, neighbor_addr, flow);
             }
             Ok(()) => {}
         }
+
+        // Free the socket of a connection in the TIME-WAIT state, if the interface
+        // can take the connection over.
+        #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+        if let Socket::Tcp(socket) = &mut item.socket
+            && socket.state() == crate::socket::tcp::State::TimeWait
+            && self.inner.tcp_time_wait.has_room(self.inner.now)
+            && let Some(time_wait) = socket.take_time_wait()
+        {
+            self.inner.tcp_time_wait.insert(time_wait);
+            self.inner.tcp_flow_cache.remove_handle(item.meta.handle);
+            result = SocketEgressOutcome::Changed;
+        }
+
         result
     }
 }
@@ -1383,6 +1582,15 @@ impl InterfaceInner {
         self.caps.ip_mtu()
     }
 
+    /// Return the path MTU towards `addr`.
+    #[allow(unused)] // unused depending on which sockets are enabled
+    pub(crate) fn path_mtu(&self, addr: &IpAddress) -> usize {
+        let mtu = self.caps.ip_mtu();
+        self.path_mtu_cache
+            .lookup(addr, self.now)
+            .map_or(mtu, |cached| cached.min(mtu))
+    }
+
     #[allow(unused)] // unused depending on which sockets are enabled, and in tests
     pub(crate) fn rand(&mut self) -> &mut Rand {
         &mut self.rand
@@ -1390,9 +1598,22 @@ impl InterfaceInner {
 
     #[allow(unused)] // unused depending on which sockets are enabled
     pub(crate) fn get_source_address(&self, dst_addr: &IpAddress) -> Option<IpAddress> {
+        self.get_source_address_flow(dst_addr, &Flow::default())
+    }
+
+    /// Get a source address for `dst_addr`, for the packets of `flow`, whose
+    /// source address is not known yet.
+    #[allow(unused)] // unused depending on which sockets are enabled
+    pub(crate) fn get_source_address_flow(
+        &self,
+        dst_addr: &IpAddress,
+        #[allow(unused_variables)] flow: &Flow,
+    ) -> Option<IpAddress> {
         match dst_addr {
             #[cfg(feature = "proto-ipv4")]
-            IpAddress::Ipv4(addr) => self.get_source_address_ipv4(addr).map(|a| a.into()),
+            IpAddress::Ipv4(addr) => self
+                .get_source_address_ipv4_flow(addr, flow)
+                .map(|a| a.into()),
             #[cfg(feature = "proto-ipv6")]
             IpAddress::Ipv6(addr) => Some(self.get_source_address_ipv6(addr).into()),
         }
@@ -1570,6 +1791,11 @@ impl InterfaceInner {
     }
 
     fn route(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
+        self.route_flow(addr, &Flow::default(), timestamp)
+    }
+
+    /// Return the next hop to `addr` for the packets of `flow`.
+    fn route_flow(&self, addr: &IpAddress, flow: &Flow, timestamp: Instant) -> Option<IpAddress> {
         // Send directly.
         // note: no need to use `self.is_broadcast()` to check for subnet-local broadcast addrs
         //       here because `in_same_network` will already return true.
@@ -1578,11 +1804,12 @@ impl InterfaceInner {
         }
 
         // Route via a router.
-        self.routes.lookup(addr, timestamp)
+        self.routes.lookup_flow(addr, flow, timestamp)
     }
 
-    fn has_neighbor(&self, addr: &IpAddress) -> bool {
-        match self.route(addr, self.now) {
+    /// Query whether the next hop to `addr` for the packets of `flow` is known.
+    fn has_neighbor(&self, addr: &IpAddress, flow: &Flow) -> bool {
+        match self.route_flow(addr, flow, self.now) {
             Some(_routed_addr) => match self.caps.medium {
                 #[cfg(feature = "medium-ethernet")]
                 Medium::Ethernet => self.neighbor_cache.lookup(&_routed_addr, self.now).found(),
@@ -1600,6 +1827,7 @@ impl InterfaceInner {
         &mut self,
         tx_token: Tx,
         dst_addr: &IpAddress,
+        flow: &Flow,
         fragmenter: &mut Fragmenter,
     ) -> Result<(HardwareAddress, Tx), DispatchError>
     where
@@ -1662,85 +1890,77 @@ impl InterfaceInner {
         }
 
         let dst_addr = self
-            .route(dst_addr, self.now)
+            .route_flow(dst_addr, flow, self.now)
             .ok_or(DispatchError::NoRoute)?;
 
         match self.neighbor_cache.lookup(&dst_addr, self.now) {
-            NeighborAnswer::Found(hardware_addr) => return Ok((hardware_addr, tx_token)),
+            NeighborAnswer::Found(hardware_addr) => {
+                self.neighbor_cache.mark_used(dst_addr, self.now);
+                return Ok((hardware_addr, tx_token));
+            }
             NeighborAnswer::RateLimited => return Err(DispatchError::NeighborPending),
             _ => (), // XXX
         }
 
-        match dst_addr {
-            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
-            IpAddress::Ipv4(dst_addr) if matches!(self.caps.medium, Medium::Ethernet) => {
-                net_debug!(
-                    "address {} not in neighbor cache, sending ARP request",
-                    dst_addr
-                );
-                let src_hardware_addr = self.hardware_addr.ethernet_or_panic();
-
-                let arp_repr = ArpRepr::EthernetIpv4 {
-                    operation: ArpOperation::Request,
-                    source_hardware_addr: src_hardware_addr,
-                    source_protocol_addr: self
-                        .get_source_address_ipv4(&dst_addr)
-                        .ok_or(DispatchError::NoRoute)?,
-                    target_hardware_addr: EthernetAddress::BROADCAST,
-                    target_protocol_addr: dst_addr,
-                };
+        net_debug!(
+            "address {} not in neighbor cache, sending solicitation",
+            dst_addr
+        );
+        let probe = NeighborProbe {
+            protocol_addr: dst_addr,
+            hardware_addr: None,
+        };
+        if let Err(e) = self.solicit_neighbor(tx_token, probe, fragmenter) {
+            net_debug!("Failed to dispatch neighbor solicitation: {:?}", e);
+            return Err(DispatchError::NeighborPending);
+        }
 
-                if let Err(e) =
-                    self.dispatch_ethernet(tx_token, arp_repr.buffer_len(), |mut frame| {
-                        frame.set_dst_addr(EthernetAddress::BROADCAST);
-                        frame.set_ethertype(EthernetProtocol::Arp);
+        // The request got dispatched, limit the rate of solicitations.
+        self.neighbor_cache.mark_solicited(dst_addr, self.now);
+        Err(DispatchError::NeighborPending)
+    }
 
-                        arp_repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()))
-                    })
-                {
-                    net_debug!("Failed to dispatch ARP request: {:?}", e);
-                    return Err(DispatchError::NeighborPending);
-                }
-            }
+    /// Process an ICMP error reporting that a packet from `src_addr` to `dst_addr`
+    /// didn't fit the MTU of the next hop, as described in RFC 1191 and RFC 8201.
+    pub(crate) fn process_packet_too_big(
+        &mut self,
+        src_addr: IpAddress,
+        dst_addr: IpAddress,
+        mtu: usize,
+    ) {
+        // Only believe errors about packets we could have sent.
+        if !self.has_ip_addr(src_addr) {
+            net_debug!("ignoring packet too big for {}, not sent by us", src_addr);
+            return;
+        }
+        // Routers predating RFC 1191 report no MTU.
+        if mtu == 0 {
+            return;
+        }
 
+        let min_mtu = match dst_addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(_) => IPV4_MIN_MTU,
             #[cfg(feature = "proto-ipv6")]
-            IpAddress::Ipv6(dst_addr) => {
-                net_debug!(
-                    "address {} not in neighbor cache, sending Neighbor Solicitation",
-                    dst_addr
-                );
-
-                let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
-                    target_addr: dst_addr,
-                    lladdr: Some(self.hardware_addr.into()),
-                });
-
-                let packet = Packet::new_ipv6(
-                    Ipv6Repr {
-                        src_addr: self.get_source_address_ipv6(&dst_addr),
-                        dst_addr: dst_addr.solicited_node(),
-                        next_header: IpProtocol::Icmpv6,
-                        payload_len: solicit.buffer_len(),
-                        hop_limit: 0xff,
-                    },
-                    IpPayload::Icmpv6(solicit),
-                );
-
-                if let Err(e) =
-                    self.dispatch_ip(tx_token, PacketMeta::default(), packet, fragmenter)
-                {
-                    net_debug!("Failed to dispatch NDISC solicit: {:?}", e);
-                    return Err(DispatchError::NeighborPending);
-                }
-            }
-
-            #[allow(unreachable_patterns)]
-            _ => (),
+            IpAddress::Ipv6(_) => IPV6_MIN_MTU,
+        };
+        let mtu = mtu.max(min_mtu);
+        if mtu < self.caps.ip_mtu() {
+            self.path_mtu_cache.fill(dst_addr, mtu, self.now);
         }
+    }
 
-        // The request got dispatched, limit the rate on the cache.
-        self.neighbor_cache.limit_rate(self.now);
-        Err(DispatchError::NeighborPending)
+    /// Note that an upper layer protocol made forward progress with `addr`, which
+    /// confirms that the neighbor the packets of `flow` reach it through is
+    /// reachable, see RFC 4861 § 7.3.1.
+    pub(crate) fn confirm_neighbor_reachable(&mut self, addr: &IpAddress, flow: &Flow) {
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        if let Some(neighbor_addr) = self.route_flow(addr, flow, self.now) {
+            self.neighbor_cache
+                .confirm_reachable(neighbor_addr, self.now);
+        }
+        #[cfg(not(any(feature = "medium-ethernet", feature = "medium-ieee802154")))]
+        let _ = (addr, flow);
     }
 
     fn flush_neighbor_cache(&mut self) {
@@ -1759,13 +1979,15 @@ impl InterfaceInner {
     ) -> Result<(), DispatchError> {
         let mut ip_repr = packet.ip_repr();
         assert!(!ip_repr.dst_addr().is_unspecified());
+        #[allow(unused_variables)]
+        let flow = Flow::new(&ip_repr, &meta);
 
         // Dispatch IEEE802.15.4:
 
         #[cfg(feature = "medium-ieee802154")]
         if matches!(self.caps.medium, Medium::Ieee802154) {
             let (addr, tx_token) =
-                self.lookup_hardware_addr(tx_token, &ip_repr.dst_addr(), frag)?;
+                self.lookup_hardware_addr(tx_token, &ip_repr.dst_addr(), &flow, frag)?;
             let addr = addr.ieee802154_or_panic();
 
             self.dispatch_ieee802154(addr, tx_token, meta, packet, frag);
@@ -1794,9 +2016,16 @@ impl InterfaceInner {
         let (dst_hardware_addr, mut tx_token) = match self.caps.medium {
             Medium::Ethernet => {
                 #[allow(unreachable_patterns)]
-                match self.lookup_hardware_addr(tx_token, &ip_repr.dst_addr(), frag)? {
-                    (HardwareAddress::Ethernet(addr), tx_token) => (addr, tx_token),
-                    (_, _) => unreachable!(),
+                match self.lookup_hardware_addr(tx_token, &ip_repr.dst_addr(), &flow, frag) {
+                    Ok((HardwareAddress::Ethernet(addr), tx_token)) => (addr, tx_token),
+                    #[cfg(feature = "alloc")]
+                    Err(DispatchError::NeighborPending)
+                        if self.hold_ip(&ip_repr, &flow, &packet) =>
+                    {
+                        return Ok(());
+                    }
+                    Err(e) => return Err(e),
+                    Ok((_, _)) => unreachable!(),
                 }
             }
             _ => (EthernetAddress([0; 6]), tx_token),
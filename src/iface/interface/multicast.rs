@@ -482,6 +482,7 @@ impl InterfaceInner {
                 payload_len: igmp_repr.buffer_len(),
                 hop_limit: 1,
                 // [#183](https://github.com/m-labs/smoltcp/issues/183).
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Igmp(igmp_repr),
         );
@@ -499,6 +500,7 @@ impl InterfaceInner {
                     next_header: IpProtocol::Igmp,
                     payload_len: igmp_repr.buffer_len(),
                     hop_limit: 1,
+                    ecn: IpEcn::NotEct,
                 },
                 IpPayload::Igmp(igmp_repr),
             )
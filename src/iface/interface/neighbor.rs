@@ -0,0 +1,185 @@
+use super::*;
+
+impl Interface {
+    /// Send the solicitations due to resolve neighbors or to probe their
+    /// reachability, and forget the neighbors that could not be reached.
+    pub(super) fn neighbor_egress(&mut self, device: &mut (impl Device + ?Sized)) {
+        let now = self.inner.now;
+        self.inner.neighbor_cache.expire(now);
+
+        while let Some(probe) = self.inner.neighbor_cache.probe_due(now) {
+            let Some(tx_token) = device.transmit(now) else {
+                break;
+            };
+
+            if let Err(e) = self
+                .inner
+                .solicit_neighbor(tx_token, probe, &mut self.fragmenter)
+            {
+                net_debug!("Failed to solicit {}: {:?}", probe.protocol_addr, e);
+            }
+            // Count the solicitation even if it failed, so that a neighbor we
+            // cannot solicit is eventually given up on.
+            self.inner
+                .neighbor_cache
+                .mark_solicited(probe.protocol_addr, now);
+        }
+
+        #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+        self.neighbor_hold_egress(device);
+    }
+
+    /// Send the packets held for the neighbors that were resolved, and set aside
+    /// those held for the neighbors that could not be.
+    #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+    fn neighbor_hold_egress(&mut self, device: &mut (impl Device + ?Sized)) {
+        let now = self.inner.now;
+
+        while let Some(index) = self.inner.neighbor_hold_ready() {
+            let neighbor = self.inner.neighbor_hold.neighbor(index);
+            if !self.inner.neighbor_cache.lookup(&neighbor, now).found() {
+                net_debug!("could not resolve {}; giving up on held packet", neighbor);
+                self.inner.neighbor_hold.mark_unresolved(index);
+                continue;
+            }
+
+            let Some(tx_token) = device.transmit(now) else {
+                break;
+            };
+
+            let (packet, len) = self.inner.neighbor_hold.take(index);
+            let result =
+                self.inner
+                    .dispatch_forwarded(tx_token, &packet[..len], &mut self.fragmenter);
+            if let Err(e) = result {
+                net_debug!("Failed to send held packet: {:?}", e);
+            }
+            self.inner.neighbor_hold.free(index, packet);
+        }
+    }
+}
+
+impl InterfaceInner {
+    /// Hold `packet` of `flow`, sent with `ip_repr`, until its next hop is resolved.
+    /// Return `false` if there is no room for it.
+    #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+    pub(super) fn hold_ip(&mut self, ip_repr: &IpRepr, flow: &Flow, packet: &Packet) -> bool {
+        let len = ip_repr.buffer_len();
+        if len > self.caps.ip_mtu() {
+            return false;
+        }
+
+        let caps = self.caps.clone();
+        self.hold_packet(&ip_repr.dst_addr(), flow, len, |buffer| {
+            ip_repr.emit(&mut *buffer, &caps.checksum);
+            packet.emit_payload(ip_repr, &mut buffer[ip_repr.header_len()..], &caps);
+        })
+    }
+
+    /// Hold a packet of `flow` and `len` octets for `dst_addr`, written by `f`, until
+    /// its next hop is resolved. Return `false` if there is no room for it.
+    #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+    pub(super) fn hold_packet(
+        &mut self,
+        dst_addr: &IpAddress,
+        flow: &Flow,
+        len: usize,
+        f: impl FnOnce(&mut [u8]),
+    ) -> bool {
+        if !self.neighbor_hold.is_enabled() {
+            return false;
+        }
+        let Some(neighbor) = self.route_flow(dst_addr, flow, self.now) else {
+            return false;
+        };
+        let held = self.neighbor_hold.push_with(neighbor, len, f);
+        if held {
+            net_trace!(
+                "holding packet to {} until {} is resolved",
+                dst_addr,
+                neighbor
+            );
+        }
+        held
+    }
+
+    /// Return the index of the oldest packet held for a neighbor no longer being
+    /// resolved.
+    #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+    pub(super) fn neighbor_hold_ready(&self) -> Option<usize> {
+        let now = self.now;
+        let neighbor_cache = &self.neighbor_cache;
+        self.neighbor_hold
+            .oldest_ready(|neighbor| !neighbor_cache.is_resolving(neighbor, now))
+    }
+
+    /// Send an ARP request or a Neighbor Solicitation for `probe.protocol_addr`,
+    /// by broadcast or multicast to resolve its link-layer address, or unicast
+    /// to check that it is still reachable.
+    pub(super) fn solicit_neighbor<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        probe: NeighborProbe,
+        #[allow(unused_variables)] fragmenter: &mut Fragmenter,
+    ) -> Result<(), DispatchError> {
+        match probe.protocol_addr {
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+            IpAddress::Ipv4(dst_addr) if matches!(self.caps.medium, Medium::Ethernet) => {
+                #[allow(unreachable_patterns)]
+                let target_hardware_addr = match probe.hardware_addr {
+                    Some(HardwareAddress::Ethernet(addr)) => addr,
+                    _ => EthernetAddress::BROADCAST,
+                };
+
+                let arp_repr = ArpRepr::EthernetIpv4 {
+                    operation: ArpOperation::Request,
+                    source_hardware_addr: self.hardware_addr.ethernet_or_panic(),
+                    source_protocol_addr: self
+                        .get_source_address_ipv4(&dst_addr)
+                        .ok_or(DispatchError::NoRoute)?,
+                    target_hardware_addr,
+                    target_protocol_addr: dst_addr,
+                };
+
+                self.dispatch_ethernet(tx_token, arp_repr.buffer_len(), |mut frame| {
+                    frame.set_dst_addr(target_hardware_addr);
+                    frame.set_ethertype(EthernetProtocol::Arp);
+
+                    arp_repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()))
+                })
+            }
+
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(dst_addr) => {
+                let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+                    target_addr: dst_addr,
+                    lladdr: Some(self.hardware_addr.into()),
+                });
+
+                // Probes of a known neighbor go to the neighbor itself, see
+                // RFC 4861 § 7.2.2.
+                let ip_dst_addr = match probe.hardware_addr {
+                    Some(_) => dst_addr,
+                    None => dst_addr.solicited_node(),
+                };
+
+                let packet = Packet::new_ipv6(
+                    Ipv6Repr {
+                        src_addr: self.get_source_address_ipv6(&dst_addr),
+                        dst_addr: ip_dst_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: solicit.buffer_len(),
+                        hop_limit: 0xff,
+                        ecn: IpEcn::NotEct,
+                    },
+                    IpPayload::Icmpv6(solicit),
+                );
+
+                self.dispatch_ip(tx_token, PacketMeta::default(), packet, fragmenter)
+            }
+
+            #[allow(unreachable_patterns)]
+            _ => Ok(()),
+        }
+    }
+}
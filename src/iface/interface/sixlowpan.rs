@@ -284,6 +284,9 @@ impl InterfaceInner {
             next_header: decompress_next_header(iphc_repr.next_header, iphc.payload())?,
             payload_len: total_len.unwrap_or(payload_len) - 40,
             hop_limit: iphc_repr.hop_limit,
+            ecn: iphc_repr
+                .ecn
+                .map_or(IpEcn::NotEct, |ecn| IpEcn::from(ecn >> 6)),
         };
         ipv6_repr.emit(&mut ipv6_header);
 
@@ -442,6 +445,7 @@ impl InterfaceInner {
             next_header
         };
 
+        let (ecn, dscp) = iphc_traffic_class(packet.header.ecn);
         let iphc_repr = SixlowpanIphcRepr {
             src_addr: packet.header.src_addr,
             ll_src_addr: ieee_repr.src_addr,
@@ -449,8 +453,8 @@ impl InterfaceInner {
             ll_dst_addr: ieee_repr.dst_addr,
             next_header,
             hop_limit: packet.header.hop_limit,
-            ecn: None,
-            dscp: None,
+            ecn,
+            dscp,
             flow_label: None,
         };
 
@@ -577,6 +581,7 @@ impl InterfaceInner {
             next_header
         };
 
+        let (ecn, dscp) = iphc_traffic_class(packet.header.ecn);
         let iphc = SixlowpanIphcRepr {
             src_addr: packet.header.src_addr,
             ll_src_addr: ieee_repr.src_addr,
@@ -584,8 +589,8 @@ impl InterfaceInner {
             ll_dst_addr: ieee_repr.dst_addr,
             next_header,
             hop_limit: packet.header.hop_limit,
-            ecn: None,
-            dscp: None,
+            ecn,
+            dscp,
             flow_label: None,
         };
 
@@ -689,6 +694,15 @@ impl InterfaceInner {
     }
 }
 
+/// Return the ECN and DSCP fields to carry `ecn` inline in an IPHC header. The
+/// traffic class is elided if the packet isn't ECN-capable.
+fn iphc_traffic_class(ecn: IpEcn) -> (Option<u8>, Option<u8>) {
+    match ecn {
+        IpEcn::NotEct => (None, None),
+        ecn => (Some(u8::from(ecn) << 6), Some(0)),
+    }
+}
+
 /// Convert a 6LoWPAN next header to an IPv6 next header.
 #[inline]
 fn decompress_next_header(next_header: SixlowpanNextHeader, payload: &[u8]) -> Result<IpProtocol> {
@@ -849,6 +863,7 @@ mod tests {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 66,
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             },
             #[cfg(feature = "proto-ipv6-hbh")]
             hop_by_hop: None,
@@ -928,6 +943,7 @@ mod tests {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 66,
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             },
             #[cfg(feature = "proto-ipv6-hbh")]
             hop_by_hop: Some(Ipv6HopByHopRepr {
@@ -72,6 +72,10 @@ impl InterfaceInner {
                 self.record_tcp_probe_cache_miss();
             }
 
+            if let Some(reply) = self.tcp_time_wait.process(self.now, &ip_repr, &tcp_repr) {
+                return reply.map(|(ip, tcp)| Packet::new(ip, IpPayload::Tcp(tcp)));
+            }
+
             while let Some(handle) = self
                 .tcp_listener_cache
                 .get(ip_repr.dst_addr(), tcp_repr.dst_port)
@@ -79,9 +83,10 @@ impl InterfaceInner {
                 let mut stale = true;
                 let handled = sockets.item_mut_at(handle.index()).and_then(|item| {
                     if let crate::socket::Socket::Tcp(ref mut tcp_socket) = item.socket {
-                        if tcp_socket.is_listening() && tcp_socket.accepts(self, &ip_repr, &tcp_repr)
-                        {
-                            stale = false;
+                        // A listening socket may turn down a segment, such as an ACK
+                        // without a valid SYN cookie, and still be listening.
+                        stale = !tcp_socket.is_listening();
+                        if !stale && tcp_socket.accepts(self, &ip_repr, &tcp_repr) {
                             on_touched(item.meta.handle);
                             let packet = tcp_socket
                                 .process(self, &ip_repr, &tcp_repr)
@@ -127,6 +132,11 @@ impl InterfaceInner {
             }
         }
 
+        if let Some(packet) = self.process_tcp_syn_cookie(sockets, &ip_repr, &tcp_repr, on_touched)
+        {
+            return packet;
+        }
+
         if tcp_repr.control == TcpControl::Rst
             || ip_repr.dst_addr().is_unspecified()
             || ip_repr.src_addr().is_unspecified()
@@ -142,4 +152,105 @@ impl InterfaceInner {
             Some(Packet::new(ip, IpPayload::Tcp(tcp)))
         }
     }
+
+    /// Handle a segment that no socket took with SYN cookies: answer a SYN with a
+    /// cookie on behalf of a socket using them on its endpoint, or open a connection
+    /// on a socket listening there from an ACK carrying a valid cookie back.
+    ///
+    /// Return `None` if SYN cookies do not apply to the segment.
+    fn process_tcp_syn_cookie<'frame, 's, B: SocketBufferT<'s>>(
+        &mut self,
+        sockets: &mut SocketSet<'s, B>,
+        ip_repr: &IpRepr,
+        tcp_repr: &TcpRepr,
+        on_touched: &mut impl FnMut(SocketHandle),
+    ) -> Option<Option<Packet<'frame>>> {
+        // Sockets in SYN-RECEIVED are not indexed by their endpoint, so they are
+        // looked for among every socket.
+        let mut candidates = sockets.items_mut().filter_map(|item| match item.socket {
+            crate::socket::Socket::Tcp(ref mut tcp_socket)
+                if tcp_socket.uses_syn_cookies(ip_repr, tcp_repr) =>
+            {
+                Some((item.meta.handle, tcp_socket))
+            }
+            _ => None,
+        });
+
+        match (tcp_repr.control, tcp_repr.ack_number) {
+            (TcpControl::Syn, None) => {
+                let (_, tcp_socket) = candidates.next()?;
+                let reply = tcp_socket.syn_cookie_reply(self, ip_repr, tcp_repr);
+                Some(reply.map(|(ip, tcp)| Packet::new(ip, IpPayload::Tcp(tcp))))
+            }
+            (TcpControl::Syn | TcpControl::Rst, _) | (_, None) => None,
+            (_, Some(ack_number)) => {
+                let local = IpEndpoint::new(ip_repr.dst_addr(), tcp_repr.dst_port);
+                let remote = IpEndpoint::new(ip_repr.src_addr(), tcp_repr.src_port);
+                let cookie = self.check_tcp_syn_cookie(
+                    local,
+                    remote,
+                    tcp_repr.seq_number - 1,
+                    ack_number - 1,
+                )?;
+                let (handle, tcp_socket) = candidates.find(|(_, tcp_socket)| {
+                    tcp_socket.state() == crate::socket::tcp::State::Listen
+                })?;
+                on_touched(handle);
+                let packet = tcp_socket
+                    .process_syn_cookie(self, ip_repr, tcp_repr, cookie)
+                    .map(|(ip, tcp)| Packet::new(ip, IpPayload::Tcp(tcp)));
+                #[cfg(feature = "alloc")]
+                if !tcp_socket.is_listening() {
+                    self.tcp_listener_cache.remove_handle(handle);
+                    let key = TcpFlowKey::from_incoming(ip_repr, tcp_repr);
+                    if let Err(error) = self.tcp_flow_cache.insert(key, handle) {
+                        net_debug!("TCP flow cache registration failed: {:?}", error);
+                    }
+                }
+                Some(packet)
+            }
+        }
+    }
+
+    /// Return the TCP Fast Open cookie received from the server at `addr`, if any.
+    pub(crate) fn tcp_fast_open_cookie(&self, addr: &IpAddress) -> Option<TcpFastOpenCookie> {
+        self.tcp_fast_open.cookie(addr)
+    }
+
+    /// Remember the TCP Fast Open cookie received from the server at `addr`.
+    pub(crate) fn set_tcp_fast_open_cookie(&mut self, addr: IpAddress, cookie: TcpFastOpenCookie) {
+        self.tcp_fast_open.set_cookie(addr, cookie)
+    }
+
+    /// Return the TCP Fast Open cookie to hand out to the client at `addr`.
+    pub(crate) fn generate_tcp_fast_open_cookie(&mut self, addr: &IpAddress) -> TcpFastOpenCookie {
+        self.tcp_fast_open.generate_cookie(&mut self.rand, addr)
+    }
+
+    /// Return the initial sequence number encoding the SYN cookie `cookie`, for a SYN
+    /// with the initial sequence number `remote_isn` sent from `remote` to `local`.
+    pub(crate) fn generate_tcp_syn_cookie(
+        &mut self,
+        local: IpEndpoint,
+        remote: IpEndpoint,
+        remote_isn: TcpSeqNumber,
+        cookie: crate::socket::tcp::SynCookie,
+    ) -> TcpSeqNumber {
+        self.tcp_syn_cookies
+            .generate(&mut self.rand, self.now, local, remote, remote_isn, cookie)
+    }
+
+    /// Return the SYN cookie encoded in `isn`, if it is one handed out recently for
+    /// the SYN with the initial sequence number `remote_isn` sent from `remote` to
+    /// `local`.
+    pub(crate) fn check_tcp_syn_cookie(
+        &self,
+        local: IpEndpoint,
+        remote: IpEndpoint,
+        remote_isn: TcpSeqNumber,
+        isn: TcpSeqNumber,
+    ) -> Option<crate::socket::tcp::SynCookie> {
+        self.tcp_syn_cookies
+            .check(self.now, local, remote, remote_isn, isn)
+    }
 }
@@ -40,6 +40,7 @@ fn gateway_observation_ipv4_frame(
         next_header: IpProtocol::Unknown(253),
         payload_len: 0,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     };
     let mut bytes = vec![0; ethernet_repr.buffer_len() + ipv4_repr.buffer_len()];
     let mut frame = EthernetFrame::new_unchecked(&mut bytes);
@@ -516,7 +517,7 @@ fn arp_reply_immediately_reports_gateway_waiters() {
         .item_mut_at(handle.index())
         .unwrap()
         .meta
-        .neighbor_missing(Instant::ZERO, gateway);
+        .neighbor_missing(Instant::ZERO, gateway, Default::default());
 
     let remote_hardware = EthernetAddress::from_bytes(&[0x02, 0x02, 0x02, 0x02, 0x02, 0x03]);
     let local_hardware = EthernetAddress::from_bytes(&[0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
@@ -579,6 +580,7 @@ fn test_no_icmp_no_unicast(#[case] medium: Medium) {
         next_header: IpProtocol::Unknown(0x0c),
         payload_len: 0,
         hop_limit: 0x40,
+        ecn: IpEcn::NotEct,
     });
 
     let mut bytes = vec![0u8; 54];
@@ -617,6 +619,7 @@ fn test_icmp_error_no_payload(#[case] medium: Medium) {
         next_header: IpProtocol::Unknown(0x0c),
         payload_len: 0,
         hop_limit: 0x40,
+        ecn: IpEcn::NotEct,
     });
 
     let mut bytes = vec![0u8; 34];
@@ -633,6 +636,7 @@ fn test_icmp_error_no_payload(#[case] medium: Medium) {
             next_header: IpProtocol::Unknown(12),
             payload_len: 0,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         },
         data: &NO_BYTES,
     };
@@ -644,6 +648,7 @@ fn test_icmp_error_no_payload(#[case] medium: Medium) {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv4(icmp_repr),
     );
@@ -797,6 +802,7 @@ fn test_icmp_error_port_unreachable(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     });
 
     // Emit the representations to a packet
@@ -821,6 +827,7 @@ fn test_icmp_error_port_unreachable(#[case] medium: Medium) {
             next_header: IpProtocol::Udp,
             payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         },
         data,
     };
@@ -831,6 +838,7 @@ fn test_icmp_error_port_unreachable(#[case] medium: Medium) {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv4(icmp_repr),
     );
@@ -850,6 +858,7 @@ fn test_icmp_error_port_unreachable(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     });
 
     // Emit the representations to a packet
@@ -905,6 +914,7 @@ fn test_handle_ipv4_broadcast(#[case] medium: Medium) {
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
         payload_len: icmpv4_repr.buffer_len(),
+        ecn: IpEcn::NotEct,
     };
 
     // Emit to ip frame
@@ -933,6 +943,7 @@ fn test_handle_ipv4_broadcast(#[case] medium: Medium) {
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
         payload_len: expected_icmpv4_repr.buffer_len(),
+        ecn: IpEcn::NotEct,
     };
     let expected_packet =
         Packet::new_ipv4(expected_ipv4_repr, IpPayload::Icmpv4(expected_icmpv4_repr));
@@ -999,12 +1010,365 @@ fn test_handle_valid_arp_request(#[case] medium: Medium) {
         iface.inner.lookup_hardware_addr(
             MockTxToken,
             &IpAddress::Ipv4(remote_ip_addr),
+            &Flow::default(),
             &mut iface.fragmenter,
         ),
         Ok((HardwareAddress::Ethernet(remote_hw_addr), MockTxToken))
     );
 }
 
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_arp_unicast_probe(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    let local_ip_addr = Ipv4Address::new(0x7f, 0x00, 0x00, 0x01);
+    let remote_ip_addr = Ipv4Address::new(0x7f, 0x00, 0x00, 0x02);
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    let arp_frame = |operation| {
+        let repr = ArpRepr::EthernetIpv4 {
+            operation,
+            source_hardware_addr: remote_hw_addr,
+            source_protocol_addr: remote_ip_addr,
+            target_hardware_addr: local_hw_addr,
+            target_protocol_addr: local_ip_addr,
+        };
+        let mut eth_bytes = vec![0u8; 42];
+        let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+        frame.set_dst_addr(local_hw_addr);
+        frame.set_src_addr(remote_hw_addr);
+        frame.set_ethertype(EthernetProtocol::Arp);
+        repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+        eth_bytes
+    };
+
+    // A request tells us the address of the neighbor, but not that it is reachable.
+    iface.inner.process_ethernet(
+        &mut sockets,
+        PacketMeta::default(),
+        &arp_frame(ArpOperation::Request),
+        &mut iface.fragments,
+    );
+    assert_eq!(
+        iface.inner.lookup_hardware_addr(
+            MockTxToken,
+            &IpAddress::Ipv4(remote_ip_addr),
+            &Flow::default(),
+            &mut iface.fragmenter,
+        ),
+        Ok((HardwareAddress::Ethernet(remote_hw_addr), MockTxToken))
+    );
+
+    let probe_at = Instant::ZERO + NeighborCache::DELAY_FIRST_PROBE_TIME;
+    assert_eq!(iface.poll_at(Instant::ZERO, &sockets), Some(probe_at));
+    iface.poll_egress(probe_at, &mut device, &mut sockets);
+
+    let arp_sent = |device: &mut crate::tests::TestingDevice| -> Vec<Vec<u8>> {
+        device
+            .tx_queue
+            .drain(..)
+            .filter(|tx| {
+                EthernetFrame::new_checked(&tx[..]).unwrap().ethertype() == EthernetProtocol::Arp
+            })
+            .collect()
+    };
+    let tx = arp_sent(&mut device);
+    assert_eq!(tx.len(), 1);
+    let frame = EthernetFrame::new_checked(&tx[0][..]).unwrap();
+    assert_eq!(frame.dst_addr(), remote_hw_addr);
+    assert_eq!(
+        ArpRepr::parse(&ArpPacket::new_checked(frame.payload()).unwrap()).unwrap(),
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: local_hw_addr,
+            source_protocol_addr: local_ip_addr,
+            target_hardware_addr: remote_hw_addr,
+            target_protocol_addr: remote_ip_addr,
+        }
+    );
+
+    // The reply confirms the neighbor is reachable, and stops the probes.
+    iface.inner.now = probe_at;
+    iface.inner.process_ethernet(
+        &mut sockets,
+        PacketMeta::default(),
+        &arp_frame(ArpOperation::Reply),
+        &mut iface.fragments,
+    );
+    assert_eq!(iface.poll_at(probe_at, &sockets), None);
+    iface.poll_egress(probe_at + Duration::from_secs(10), &mut device, &mut sockets);
+    assert!(arp_sent(&mut device).is_empty());
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "medium-ethernet", feature = "alloc", feature = "socket-udp"))]
+fn test_neighbor_hold(#[case] medium: Medium) {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.inner.neighbor_hold = HoldQueue::new(1, IFACE_NEIGHBOR_CACHE_COUNT, 1500);
+
+    let local_ip_addr = Ipv4Address::new(192, 168, 1, 1);
+    let remote_ip_addr = Ipv4Address::new(192, 168, 1, 2);
+    let lost_ip_addr = Ipv4Address::new(192, 168, 1, 3);
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]);
+    let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 16]);
+    let handle = sockets.add(udp::Socket::new(rx_buffer, tx_buffer));
+    let socket = sockets.get_mut::<udp::Socket>(handle);
+    socket.bind(68).unwrap();
+    socket.send_slice(b"abcd", (remote_ip_addr, 67)).unwrap();
+
+    // The IPv4 and ARP frames sent, leaving out MLD reports.
+    let sent = |device: &mut crate::tests::TestingDevice| -> Vec<Vec<u8>> {
+        device
+            .tx_queue
+            .drain(..)
+            .filter(|tx| {
+                EthernetFrame::new_checked(&tx[..]).unwrap().ethertype() != EthernetProtocol::Ipv6
+            })
+            .collect()
+    };
+    let ethertype = |tx: &[u8]| EthernetFrame::new_checked(tx).unwrap().ethertype();
+
+    // Only the ARP request goes out, the datagram is held.
+    iface.poll_egress(Instant::ZERO, &mut device, &mut sockets);
+    let tx = sent(&mut device);
+    assert_eq!(tx.len(), 1);
+    assert_eq!(ethertype(&tx[0]), EthernetProtocol::Arp);
+
+    // The reply releases it.
+    let repr = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Reply,
+        source_hardware_addr: remote_hw_addr,
+        source_protocol_addr: remote_ip_addr,
+        target_hardware_addr: local_hw_addr,
+        target_protocol_addr: local_ip_addr,
+    };
+    let mut eth_bytes = vec![0u8; 42];
+    let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+    frame.set_dst_addr(local_hw_addr);
+    frame.set_src_addr(remote_hw_addr);
+    frame.set_ethertype(EthernetProtocol::Arp);
+    repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+    iface.inner.process_ethernet(
+        &mut sockets,
+        PacketMeta::default(),
+        &eth_bytes,
+        &mut iface.fragments,
+    );
+    assert_eq!(iface.poll_at(Instant::ZERO, &sockets), Some(Instant::ZERO));
+    iface.poll_egress(Instant::ZERO, &mut device, &mut sockets);
+    let tx = sent(&mut device);
+    assert_eq!(tx.len(), 1);
+    let frame = EthernetFrame::new_checked(&tx[0][..]).unwrap();
+    assert_eq!(frame.dst_addr(), remote_hw_addr);
+    let packet = Ipv4Packet::new_checked(frame.payload()).unwrap();
+    assert_eq!(packet.dst_addr(), remote_ip_addr);
+    assert_eq!(&packet.payload()[8..], b"abcd");
+
+    // A datagram to a neighbor that never answers is handed back.
+    let socket = sockets.get_mut::<udp::Socket>(handle);
+    socket.send_slice(b"efgh", (lost_ip_addr, 67)).unwrap();
+    let mut now = Instant::ZERO;
+    while let Some(poll_at) = iface.poll_at(now, &sockets) {
+        now = poll_at.max(now);
+        iface.poll_egress(now, &mut device, &mut sockets);
+        for tx in sent(&mut device) {
+            assert_eq!(ethertype(&tx), EthernetProtocol::Arp);
+        }
+        assert!(now < Instant::from_secs(10));
+    }
+    let unresolved = iface.take_unresolved(|neighbor, packet| {
+        let packet = Ipv4Packet::new_checked(packet).unwrap();
+        (neighbor, packet.dst_addr(), packet.payload()[8..].to_vec())
+    });
+    assert_eq!(
+        unresolved,
+        Some((IpAddress::Ipv4(lost_ip_addr), lost_ip_addr, b"efgh".to_vec()))
+    );
+    assert_eq!(iface.take_unresolved(|_, _| ()), None);
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "medium-ethernet", feature = "socket-udp"))]
+fn test_policy_routing(#[case] medium: Medium) {
+    use crate::iface::{Route, RouteRule};
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs
+            .push(IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24))
+            .unwrap();
+    });
+
+    let main_gateway = Ipv4Address::new(192, 168, 1, 254);
+    let uplink_gateway = Ipv4Address::new(10, 0, 0, 254);
+    let routes = iface.routes_mut();
+    routes.add_default_ipv4_route(main_gateway).unwrap();
+    let mut route = Route::new_ipv4_gateway(uplink_gateway);
+    route.table = 1;
+    routes.add_route(route).unwrap();
+    routes.update_rules(|rules| {
+        rules
+            .push(RouteRule::from_src(
+                IpCidr::new(IpAddress::v4(10, 0, 0, 0), 24),
+                1,
+            ))
+            .unwrap();
+    });
+
+    let remote_endpoint = (Ipv4Address::new(198, 51, 100, 1), 53);
+    let socket = |sockets: &mut SocketSet<'_>, addr: Option<Ipv4Address>| {
+        let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]);
+        let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]);
+        let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+        let endpoint = IpListenEndpoint {
+            addr: addr.map(IpAddress::Ipv4),
+            port: 68,
+        };
+        socket.bind(endpoint).unwrap();
+        socket.send_slice(b"abcd", remote_endpoint).unwrap();
+        sockets.add(socket)
+    };
+    // The gateways resolved by the ARP requests sent.
+    let solicited = |device: &mut crate::tests::TestingDevice| -> Vec<Ipv4Address> {
+        device
+            .tx_queue
+            .drain(..)
+            .filter_map(|tx| {
+                let frame = EthernetFrame::new_checked(&tx[..]).unwrap();
+                if frame.ethertype() != EthernetProtocol::Arp {
+                    return None;
+                }
+                match ArpRepr::parse(&ArpPacket::new_checked(frame.payload()).unwrap()) {
+                    Ok(ArpRepr::EthernetIpv4 {
+                        target_protocol_addr,
+                        ..
+                    }) => Some(target_protocol_addr),
+                    _ => None,
+                }
+            })
+            .collect()
+    };
+
+    // A socket bound to the address of the uplink leaves through its gateway.
+    let handle = socket(&mut sockets, Some(Ipv4Address::new(10, 0, 0, 1)));
+    iface.poll_egress(Instant::ZERO, &mut device, &mut sockets);
+    assert_eq!(solicited(&mut device), vec![uplink_gateway]);
+
+    // It keeps waiting for its own gateway when the main one is resolved.
+    iface.inner.neighbor_cache.fill(
+        main_gateway.into(),
+        HardwareAddress::Ethernet(EthernetAddress([0x02, 0, 0, 0, 0, 0xfe])),
+        Instant::ZERO,
+    );
+    assert!(iface.poll_at(Instant::ZERO, &sockets) > Some(Instant::ZERO));
+    iface.poll_egress(Instant::ZERO, &mut device, &mut sockets);
+    assert!(solicited(&mut device).is_empty());
+    iface.inner.neighbor_cache.flush();
+    sockets.remove(handle);
+
+    // Other sockets use the main table.
+    socket(&mut sockets, None);
+    iface.poll_egress(Instant::ZERO, &mut device, &mut sockets);
+    assert_eq!(solicited(&mut device), vec![main_gateway]);
+
+    // The source address of unbound sockets follows the gateway of the main table.
+    assert_eq!(
+        iface.get_source_address_ipv4(&Ipv4Address::new(198, 51, 100, 1)),
+        Some(Ipv4Address::new(192, 168, 1, 1))
+    );
+    iface
+        .routes_mut()
+        .add_default_ipv4_route(uplink_gateway)
+        .unwrap();
+    assert_eq!(
+        iface.get_source_address_ipv4(&Ipv4Address::new(198, 51, 100, 1)),
+        Some(Ipv4Address::new(10, 0, 0, 1))
+    );
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(all(
+    feature = "medium-ethernet",
+    feature = "socket-udp",
+    feature = "packetmeta-mark"
+))]
+fn test_policy_routing_mark_source_address(#[case] medium: Medium) {
+    use crate::iface::{Route, RouteRule};
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs
+            .push(IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24))
+            .unwrap();
+    });
+
+    let main_gateway = Ipv4Address::new(192, 168, 1, 254);
+    let uplink_gateway = Ipv4Address::new(10, 0, 0, 254);
+    let uplink_hw_addr = EthernetAddress([0x02, 0, 0, 0, 0, 0xfe]);
+    let routes = iface.routes_mut();
+    routes.add_default_ipv4_route(main_gateway).unwrap();
+    let mut route = Route::new_ipv4_gateway(uplink_gateway);
+    route.table = 1;
+    routes.add_route(route).unwrap();
+    routes.update_rules(|rules| {
+        rules.push(RouteRule::from_mark(7, 1)).unwrap();
+    });
+    for (gateway, hardware_addr) in [
+        (main_gateway, EthernetAddress([0x02, 0, 0, 0, 0, 0x01])),
+        (uplink_gateway, uplink_hw_addr),
+    ] {
+        iface.inner.neighbor_cache.fill(
+            gateway.into(),
+            HardwareAddress::Ethernet(hardware_addr),
+            Instant::ZERO,
+        );
+    }
+
+    // An unbound socket with a mark takes its source address from the table
+    // its mark selects, and leaves through the gateway of that table.
+    let remote_addr = Ipv4Address::new(198, 51, 100, 1);
+    let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]);
+    let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]);
+    let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+    socket.set_mark(7);
+    socket.bind(68).unwrap();
+    socket.send_slice(b"abcd", (remote_addr, 53)).unwrap();
+    sockets.add(socket);
+
+    iface.poll_egress(Instant::ZERO, &mut device, &mut sockets);
+    let tx = device
+        .tx_queue
+        .drain(..)
+        .find(|tx| {
+            EthernetFrame::new_checked(&tx[..]).unwrap().ethertype() == EthernetProtocol::Ipv4
+        })
+        .unwrap();
+    let frame = EthernetFrame::new_checked(&tx[..]).unwrap();
+    assert_eq!(frame.dst_addr(), uplink_hw_addr);
+    let packet = Ipv4Packet::new_checked(frame.payload()).unwrap();
+    assert_eq!(packet.src_addr(), Ipv4Address::new(10, 0, 0, 1));
+    assert_eq!(packet.dst_addr(), remote_addr);
+
+    // Unmarked packets still use the main table.
+    assert_eq!(
+        iface.get_source_address_ipv4(&remote_addr),
+        Some(Ipv4Address::new(192, 168, 1, 1))
+    );
+}
+
 #[rstest]
 #[case(Medium::Ethernet)]
 #[cfg(feature = "medium-ethernet")]
@@ -1047,6 +1411,7 @@ fn test_handle_other_arp_request(#[case] medium: Medium) {
         iface.inner.lookup_hardware_addr(
             MockTxToken,
             &IpAddress::Ipv4(remote_ip_addr),
+            &Flow::default(),
             &mut iface.fragmenter,
         ),
         Err(DispatchError::NeighborPending)
@@ -1105,6 +1470,7 @@ fn test_arp_flush_after_update_ip(#[case] medium: Medium) {
         iface.inner.lookup_hardware_addr(
             MockTxToken,
             &IpAddress::Ipv4(remote_ip_addr),
+            &Flow::default(),
             &mut iface.fragmenter,
         ),
         Ok((HardwareAddress::Ethernet(remote_hw_addr), MockTxToken))
@@ -1119,7 +1485,11 @@ fn test_arp_flush_after_update_ip(#[case] medium: Medium) {
     });
 
     // ARP cache flush after address change
-    assert!(!iface.inner.has_neighbor(&IpAddress::Ipv4(remote_ip_addr)));
+    assert!(
+        !iface
+            .inner
+            .has_neighbor(&IpAddress::Ipv4(remote_ip_addr), &Flow::default())
+    );
 }
 
 #[rstest]
@@ -1164,6 +1534,7 @@ fn test_icmpv4_socket(#[case] medium: Medium) {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     };
 
     // Open a socket and ensure the packet is handled due to the listening
@@ -1199,6 +1570,67 @@ fn test_icmpv4_socket(#[case] medium: Medium) {
     );
 }
 
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_icmp_frag_required(#[case] medium: Medium) {
+    use crate::wire::Icmpv4Packet;
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+    let remote_addr = Ipv4Address::new(10, 0, 0, 1);
+    let mtu = iface.path_mtu(&remote_addr.into());
+
+    let mut frag_required = |src_addr: Ipv4Address, next_hop_mtu: u16| {
+        let icmp_repr = Icmpv4Repr::DstUnreachable {
+            reason: Icmpv4DstUnreachable::FragRequired,
+            header: Ipv4Repr {
+                src_addr,
+                dst_addr: remote_addr,
+                next_header: IpProtocol::Udp,
+                payload_len: 8,
+                hop_limit: 64,
+                ecn: IpEcn::NotEct,
+            },
+            data: &[0; 8],
+        };
+        let mut bytes = vec![0; icmp_repr.buffer_len()];
+        let mut packet = Icmpv4Packet::new_unchecked(&mut bytes[..]);
+        icmp_repr.emit(&mut packet, &ChecksumCapabilities::default());
+        packet.set_next_hop_mtu(next_hop_mtu);
+        packet.fill_checksum();
+
+        let ipv4_repr = Ipv4Repr {
+            src_addr: Ipv4Address::new(192, 168, 1, 254),
+            dst_addr: Ipv4Address::new(192, 168, 1, 1),
+            next_header: IpProtocol::Icmp,
+            payload_len: bytes.len(),
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        };
+        assert_eq!(
+            iface.inner.process_icmpv4(&mut sockets, ipv4_repr, &bytes),
+            None
+        );
+        iface.path_mtu(&remote_addr.into())
+    };
+
+    // About a packet we didn't send.
+    assert_eq!(frag_required(Ipv4Address::new(192, 168, 1, 2), 1400), mtu);
+
+    assert_eq!(frag_required(Ipv4Address::new(192, 168, 1, 1), 1400), 1400);
+    // The path MTU is never raised by ICMP, nor lowered below the minimum MTU.
+    assert_eq!(frag_required(Ipv4Address::new(192, 168, 1, 1), 1450), 1400);
+    assert_eq!(
+        frag_required(Ipv4Address::new(192, 168, 1, 1), 100),
+        IPV4_MIN_MTU
+    );
+
+    iface.flush_path_mtu_cache();
+    assert_eq!(iface.path_mtu(&remote_addr.into()), mtu);
+}
+
 #[rstest]
 #[case(Medium::Ip)]
 #[cfg(all(feature = "multicast", feature = "medium-ip"))]
@@ -1341,6 +1773,7 @@ fn test_packet_len(#[case] medium: Medium) {
             next_header: IpProtocol::Udp,
             payload_len: 0,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         };
         let udp_repr = UdpRepr {
             src_port: 12345,
@@ -1420,6 +1853,7 @@ fn test_raw_socket_no_reply_udp(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         hop_limit: 64,
         payload_len: udp_repr.header_len() + PAYLOAD_LEN,
+        ecn: IpEcn::NotEct,
     };
 
     // Emit to frame
@@ -1462,6 +1896,8 @@ fn test_raw_socket_no_reply_tcp(#[case] medium: Medium) {
         src_port: 67,
         dst_port: 68,
         control: TcpControl::Syn,
+        ece: false,
+        cwr: false,
         seq_number: TcpSeqNumber(1),
         ack_number: None,
         window_len: 10,
@@ -1470,6 +1906,8 @@ fn test_raw_socket_no_reply_tcp(#[case] medium: Medium) {
         sack_permitted: false,
         sack_ranges: [None, None, None],
         timestamp: None,
+        fast_open: None,
+        auth: None,
         payload: &PAYLOAD,
     };
     let ipv4_repr = Ipv4Repr {
@@ -1478,6 +1916,7 @@ fn test_raw_socket_no_reply_tcp(#[case] medium: Medium) {
         next_header: IpProtocol::Tcp,
         hop_limit: 64,
         payload_len: tcp_repr.header_len() + PAYLOAD_LEN,
+        ecn: IpEcn::NotEct,
     };
 
     // Emit to frame
@@ -1565,6 +2004,7 @@ fn test_raw_socket_with_udp_socket(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         hop_limit: 64,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
+        ecn: IpEcn::NotEct,
     };
 
     // Emit to frame
@@ -1662,6 +2102,7 @@ fn test_raw_socket_tx_fragmentation(#[case] medium: Medium) {
             next_header: IpProtocol::Unknown(92),
             hop_limit: 64,
             payload_len,
+            ecn: IpEcn::NotEct,
         };
         let ip_payload = IpPayload::Raw(&payload);
         let packet = Packet::new_ipv4(ip_repr, ip_payload);
@@ -1718,6 +2159,7 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         hop_limit: 64,
         payload_len: udp_repr.header_len() + MAX_PAYLOAD_LEN,
+        ecn: IpEcn::NotEct,
     };
     let payload = packet.into_inner();
 
@@ -1733,6 +2175,7 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
         payload_len: expected_icmp_repr.buffer_len(),
+        ecn: IpEcn::NotEct,
     };
 
     assert_eq!(
@@ -1834,3 +2277,506 @@ fn get_source_address_empty_interface(#[case] medium: Medium) {
         None
     );
 }
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "socket-tcp", feature = "alloc"))]
+fn test_tcp_time_wait_takeover() {
+    use crate::socket::tcp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+    iface.inner.tcp_time_wait = TcpTimeWait::new(4);
+
+    let mut socket = tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; 64]),
+        tcp::SocketBuffer::new(vec![0; 64]),
+    );
+    socket.set_ack_delay(None);
+    socket.listen(80).unwrap();
+    let handle = sockets.add(socket);
+    iface.register_tcp_listener(handle, 80.into()).unwrap();
+
+    let remote_seq = TcpSeqNumber(1000);
+    let segment =
+        |iface: &mut Interface, sockets: &mut SocketSet, control, seq_number, ack_number| {
+            let tcp_repr = TcpRepr {
+                src_port: 5000,
+                dst_port: 80,
+                control,
+                ece: false,
+                cwr: false,
+                seq_number,
+                ack_number,
+                window_len: 256,
+                window_scale: None,
+                max_seg_size: None,
+                sack_permitted: false,
+                sack_ranges: [None, None, None],
+                timestamp: None,
+                fast_open: None,
+                auth: None,
+                payload: &[],
+            };
+            let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+                src_addr: Ipv4Address::new(192, 168, 1, 2),
+                dst_addr: Ipv4Address::new(192, 168, 1, 1),
+                next_header: IpProtocol::Tcp,
+                payload_len: tcp_repr.buffer_len(),
+                hop_limit: 64,
+                ecn: IpEcn::NotEct,
+            });
+            let mut bytes = vec![0; tcp_repr.buffer_len()];
+            tcp_repr.emit(
+                &mut TcpPacket::new_unchecked(&mut bytes),
+                &ip_repr.src_addr(),
+                &ip_repr.dst_addr(),
+                &ChecksumCapabilities::default(),
+            );
+            let packet = iface.inner.process_tcp(sockets, false, ip_repr, &bytes)?;
+            match packet.payload() {
+                IpPayload::Tcp(tcp_repr) => {
+                    Some((tcp_repr.control, tcp_repr.seq_number, tcp_repr.ack_number))
+                }
+                _ => unreachable!(),
+            }
+        };
+
+    let sent = |device: &mut crate::tests::TestingDevice| {
+        let bytes = device.tx_queue.pop_front().unwrap();
+        let packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+        let tcp_packet = TcpPacket::new_checked(packet.payload()).unwrap();
+        (tcp_packet.syn(), tcp_packet.fin(), tcp_packet.seq_number())
+    };
+
+    // Open a connection, and close it on our side first.
+    segment(&mut iface, &mut sockets, TcpControl::Syn, remote_seq, None);
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    let (true, false, local_seq) = sent(&mut device) else {
+        panic!("expected a SYN-ACK");
+    };
+    segment(
+        &mut iface,
+        &mut sockets,
+        TcpControl::None,
+        remote_seq + 1,
+        Some(local_seq + 1),
+    );
+    assert_eq!(
+        sockets.get::<tcp::Socket>(handle).state(),
+        tcp::State::Established
+    );
+    sockets.get_mut::<tcp::Socket>(handle).close();
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    assert_eq!(sent(&mut device), (false, true, local_seq + 1));
+    segment(
+        &mut iface,
+        &mut sockets,
+        TcpControl::Fin,
+        remote_seq + 1,
+        Some(local_seq + 2),
+    );
+    assert_eq!(
+        sockets.get::<tcp::Socket>(handle).state(),
+        tcp::State::TimeWait
+    );
+
+    // Once the FIN is acknowledged, the interface takes the connection over.
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    assert_eq!(sent(&mut device), (false, false, local_seq + 2));
+    let socket = sockets.get::<tcp::Socket>(handle);
+    assert_eq!(socket.state(), tcp::State::Closed);
+    assert_eq!(socket.close_reason(), Some(tcp::CloseReason::Graceful));
+    assert_eq!(iface.tcp_time_wait_len(), 1);
+
+    // A retransmitted FIN is acknowledged again.
+    assert_eq!(
+        segment(
+            &mut iface,
+            &mut sockets,
+            TcpControl::Fin,
+            remote_seq + 1,
+            Some(local_seq + 2),
+        ),
+        Some((TcpControl::None, local_seq + 2, Some(remote_seq + 2)))
+    );
+
+    // The socket can accept a new connection on the same 4-tuple.
+    sockets.get_mut::<tcp::Socket>(handle).listen(80).unwrap();
+    iface.register_tcp_listener(handle, 80.into()).unwrap();
+    segment(
+        &mut iface,
+        &mut sockets,
+        TcpControl::Syn,
+        remote_seq + 1000,
+        None,
+    );
+    assert_eq!(iface.tcp_time_wait_len(), 0);
+    assert_eq!(
+        sockets.get::<tcp::Socket>(handle).state(),
+        tcp::State::SynReceived
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "socket-tcp", feature = "alloc"))]
+fn test_tcp_repair() {
+    use crate::socket::tcp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+
+    let mut socket = tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; 64]),
+        tcp::SocketBuffer::new(vec![0; 64]),
+    );
+    socket.set_ack_delay(None);
+    socket.listen(80).unwrap();
+    let handle = sockets.add(socket);
+    iface.register_tcp_listener(handle, 80.into()).unwrap();
+
+    let remote_seq = TcpSeqNumber(1000);
+    let segment = |iface: &mut Interface,
+                   sockets: &mut SocketSet,
+                   control,
+                   seq_number,
+                   ack_number,
+                   payload: &[u8]| {
+        let tcp_repr = TcpRepr {
+            src_port: 5000,
+            dst_port: 80,
+            control,
+            ece: false,
+            cwr: false,
+            seq_number,
+            ack_number,
+            window_len: 256,
+            window_scale: None,
+            max_seg_size: None,
+            sack_permitted: false,
+            sack_ranges: [None, None, None],
+            timestamp: None,
+            fast_open: None,
+            auth: None,
+            payload,
+        };
+        let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+            src_addr: Ipv4Address::new(192, 168, 1, 2),
+            dst_addr: Ipv4Address::new(192, 168, 1, 1),
+            next_header: IpProtocol::Tcp,
+            payload_len: tcp_repr.buffer_len(),
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        });
+        let mut bytes = vec![0; tcp_repr.buffer_len()];
+        tcp_repr.emit(
+            &mut TcpPacket::new_unchecked(&mut bytes),
+            &ip_repr.src_addr(),
+            &ip_repr.dst_addr(),
+            &ChecksumCapabilities::default(),
+        );
+        iface.inner.process_tcp(sockets, false, ip_repr, &bytes);
+    };
+
+    // Open a connection, and receive some data.
+    segment(
+        &mut iface,
+        &mut sockets,
+        TcpControl::Syn,
+        remote_seq,
+        None,
+        &[],
+    );
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    let bytes = device.tx_queue.pop_front().unwrap();
+    let packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    let local_seq = TcpPacket::new_checked(packet.payload())
+        .unwrap()
+        .seq_number();
+    segment(
+        &mut iface,
+        &mut sockets,
+        TcpControl::None,
+        remote_seq + 1,
+        Some(local_seq + 1),
+        b"abc",
+    );
+
+    // Move the connection to another socket.
+    let mut checkpoint = vec![0; 256];
+    let len = sockets
+        .get::<tcp::Socket>(handle)
+        .checkpoint(&mut checkpoint)
+        .unwrap();
+    sockets.remove(handle);
+    iface.unregister_tcp_flow(handle);
+    iface.unregister_tcp_listener(handle);
+
+    let socket = tcp::Socket::restore(
+        tcp::SocketBuffer::new(vec![0; 64]),
+        tcp::SocketBuffer::new(vec![0; 64]),
+        &checkpoint[..len],
+    )
+    .unwrap();
+    let handle = sockets.add(socket);
+    iface.register_tcp_socket(&sockets, handle).unwrap();
+
+    // The restored socket receives what follows.
+    segment(
+        &mut iface,
+        &mut sockets,
+        TcpControl::None,
+        remote_seq + 1 + 3,
+        Some(local_seq + 1),
+        b"def",
+    );
+    let socket = sockets.get_mut::<tcp::Socket>(handle);
+    assert_eq!(socket.state(), tcp::State::Established);
+    let mut buffer = [0; 8];
+    assert_eq!(socket.recv_slice(&mut buffer), Ok(6));
+    assert_eq!(&buffer[..6], b"abcdef");
+}
+
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn forwarding_router(mtu: usize) -> Router {
+    Router::new(
+        &[IpCidr::new(IpAddress::v4(192, 168, 1, 1), 24)],
+        &[IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24)],
+        mtu,
+    )
+}
+
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn forwarded_ipv4_packet(dst_addr: Ipv4Address, hop_limit: u8, payload_len: usize) -> Vec<u8> {
+    let repr = Ipv4Repr {
+        src_addr: Ipv4Address::new(192, 168, 1, 2),
+        dst_addr,
+        next_header: IpProtocol::Udp,
+        payload_len,
+        hop_limit,
+        ecn: IpEcn::NotEct,
+    };
+    let mut bytes = vec![0xa5; repr.buffer_len() + payload_len];
+    let mut packet = Ipv4Packet::new_unchecked(&mut bytes[..]);
+    repr.emit(&mut packet, &ChecksumCapabilities::default());
+    packet.set_ident(0x1234);
+    packet.set_dont_frag(false);
+    packet.fill_checksum();
+    bytes
+}
+
+/// Check that `bytes` is an ICMP error of `msg_type` and `msg_code` from the router
+/// about a packet for `dst_addr`, and return the rest of its header.
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn assert_forward_icmpv4_error(
+    bytes: &[u8],
+    msg_type: Icmpv4Message,
+    msg_code: u8,
+    dst_addr: Ipv4Address,
+) -> u16 {
+    let packet = Ipv4Packet::new_checked(bytes).unwrap();
+    assert!(packet.verify_checksum());
+    assert_eq!(packet.src_addr(), Ipv4Address::new(192, 168, 1, 1));
+    assert_eq!(packet.dst_addr(), Ipv4Address::new(192, 168, 1, 2));
+    assert_eq!(packet.next_header(), IpProtocol::Icmp);
+
+    let icmp_packet = Icmpv4Packet::new_checked(packet.payload()).unwrap();
+    assert!(icmp_packet.verify_checksum());
+    assert_eq!(icmp_packet.msg_type(), msg_type);
+    assert_eq!(icmp_packet.msg_code(), msg_code);
+
+    // The quote starts with the original header, and the first octets of its payload.
+    let quote = Ipv4Packet::new_unchecked(icmp_packet.data());
+    assert_eq!(quote.dst_addr(), dst_addr);
+    assert_eq!(quote.ident(), 0x1234);
+    assert_eq!(&icmp_packet.data()[20..28], &[0xa5; 8]);
+    icmp_packet.next_hop_mtu()
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn test_forward_ipv4() {
+    let mut router = forwarding_router(1500);
+    router.ifaces[1]
+        .0
+        .routes_mut()
+        .add_default_ipv4_route(Ipv4Address::new(10, 0, 0, 254))
+        .unwrap();
+
+    let [back, sent] = router.route(forwarded_ipv4_packet(
+        Ipv4Address::new(172, 16, 0, 1),
+        64,
+        32,
+    ));
+    assert!(back.is_empty());
+    assert_eq!(sent.len(), 1);
+
+    let packet = Ipv4Packet::new_checked(&sent[0][..]).unwrap();
+    assert!(packet.verify_checksum());
+    assert_eq!(packet.hop_limit(), 63);
+    assert_eq!(packet.ident(), 0x1234);
+    assert_eq!(packet.dst_addr(), Ipv4Address::new(172, 16, 0, 1));
+    assert_eq!(packet.payload(), &[0xa5; 32][..]);
+
+    let counters = router.forwarder.counters();
+    assert_eq!(counters.forwarded, 1);
+    assert_eq!(counters.icmp_errors, 0);
+}
+
+#[test]
+// Without UDP sockets, the router answers its own packets with Protocol Unreachable.
+#[cfg(all(feature = "medium-ip", feature = "alloc", feature = "socket-udp"))]
+fn test_forward_ipv4_not_for_us() {
+    let mut router = forwarding_router(1500);
+
+    // Packets for the interface itself are not forwarded.
+    let [back, sent] = router.route(forwarded_ipv4_packet(
+        Ipv4Address::new(192, 168, 1, 1),
+        64,
+        32,
+    ));
+    assert!(back.is_empty());
+    assert!(sent.is_empty());
+    assert_eq!(
+        router.forwarder.counters(),
+        crate::iface::ForwardCounters::default()
+    );
+
+    // Nor are those for another interface of the router, which does not deliver
+    // them either.
+    let [back, sent] = router.route(forwarded_ipv4_packet(Ipv4Address::new(10, 0, 0, 1), 64, 32));
+    assert!(back.is_empty());
+    assert!(sent.is_empty());
+    assert_eq!(router.forwarder.counters().discarded, 1);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn test_forward_ipv4_ttl_exceeded() {
+    let mut router = forwarding_router(1500);
+
+    let [back, sent] = router.route(forwarded_ipv4_packet(Ipv4Address::new(10, 0, 0, 2), 1, 32));
+    assert!(sent.is_empty());
+    assert_eq!(back.len(), 1);
+
+    assert_forward_icmpv4_error(
+        &back[0],
+        Icmpv4Message::TimeExceeded,
+        Icmpv4TimeExceeded::TtlExpired.into(),
+        Ipv4Address::new(10, 0, 0, 2),
+    );
+
+    let counters = router.forwarder.counters();
+    assert_eq!(counters.ttl_exceeded, 1);
+    assert_eq!(counters.icmp_errors, 1);
+    assert_eq!(counters.forwarded, 0);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn test_forward_ipv4_ttl_exceeded_options() {
+    let mut router = forwarding_router(1500);
+
+    // Insert four No Operation options in the header.
+    let mut bytes = forwarded_ipv4_packet(Ipv4Address::new(10, 0, 0, 2), 1, 32);
+    bytes.splice(20..20, [0x01; 4]);
+    let mut packet = Ipv4Packet::new_unchecked(&mut bytes[..]);
+    packet.set_header_len(24);
+    packet.set_total_len(24 + 32);
+    packet.fill_checksum();
+
+    let [back, sent] = router.route(bytes.clone());
+    assert!(sent.is_empty());
+    assert_eq!(back.len(), 1);
+
+    // The whole header is quoted, options included.
+    let packet = Ipv4Packet::new_checked(&back[0][..]).unwrap();
+    let icmp_packet = Icmpv4Packet::new_checked(packet.payload()).unwrap();
+    assert!(icmp_packet.verify_checksum());
+    assert_eq!(icmp_packet.msg_type(), Icmpv4Message::TimeExceeded);
+    assert_eq!(icmp_packet.data(), &bytes[..]);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn test_forward_ipv4_no_route() {
+    let mut router = forwarding_router(1500);
+
+    let [back, sent] = router.route(forwarded_ipv4_packet(
+        Ipv4Address::new(172, 16, 0, 1),
+        64,
+        32,
+    ));
+    assert!(sent.is_empty());
+    assert_eq!(back.len(), 1);
+
+    assert_forward_icmpv4_error(
+        &back[0],
+        Icmpv4Message::DstUnreachable,
+        Icmpv4DstUnreachable::NetUnreachable.into(),
+        Ipv4Address::new(172, 16, 0, 1),
+    );
+    assert_eq!(router.forwarder.counters().no_route, 1);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn test_forward_ipv4_frag_required() {
+    let mut router = forwarding_router(580);
+
+    let mut packet = forwarded_ipv4_packet(Ipv4Address::new(10, 0, 0, 2), 64, 1000);
+    let mut ipv4_packet = Ipv4Packet::new_unchecked(&mut packet[..]);
+    ipv4_packet.set_dont_frag(true);
+    ipv4_packet.fill_checksum();
+
+    let [back, sent] = router.route(packet);
+    assert!(sent.is_empty());
+    assert_eq!(back.len(), 1);
+
+    let next_hop_mtu = assert_forward_icmpv4_error(
+        &back[0],
+        Icmpv4Message::DstUnreachable,
+        Icmpv4DstUnreachable::FragRequired.into(),
+        Ipv4Address::new(10, 0, 0, 2),
+    );
+    assert_eq!(next_hop_mtu, 580);
+
+    let counters = router.forwarder.counters();
+    assert_eq!(counters.too_big, 1);
+    assert_eq!(counters.icmp_errors, 1);
+}
+
+#[test]
+#[cfg(all(
+    feature = "medium-ip",
+    feature = "alloc",
+    feature = "proto-ipv4-fragmentation"
+))]
+fn test_forward_ipv4_fragmented() {
+    let mut router = forwarding_router(580);
+
+    let [back, sent] = router.route(forwarded_ipv4_packet(
+        Ipv4Address::new(10, 0, 0, 2),
+        64,
+        1000,
+    ));
+    assert!(back.is_empty());
+    assert_eq!(sent.len(), 2);
+
+    let first = Ipv4Packet::new_checked(&sent[0][..]).unwrap();
+    assert!(first.verify_checksum());
+    assert_eq!(first.total_len(), 580);
+    assert_eq!(first.ident(), 0x1234);
+    assert_eq!(first.hop_limit(), 63);
+    assert!(first.more_frags());
+    assert_eq!(first.frag_offset(), 0);
+
+    let second = Ipv4Packet::new_checked(&sent[1][..]).unwrap();
+    assert!(second.verify_checksum());
+    assert_eq!(second.total_len(), 20 + 1000 - 560);
+    assert_eq!(second.ident(), 0x1234);
+    assert_eq!(second.hop_limit(), 63);
+    assert!(!second.more_frags());
+    assert_eq!(second.frag_offset(), 560);
+
+    let counters = router.forwarder.counters();
+    assert_eq!(counters.forwarded, 1);
+    assert_eq!(counters.fragmented, 1);
+}
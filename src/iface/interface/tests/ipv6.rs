@@ -54,6 +54,7 @@ fn any_ip(#[case] medium: Medium) {
                 hop_limit: 64,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 8,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Icmpv6(Icmpv6Repr::EchoRequest {
                 ident: 0,
@@ -73,9 +74,13 @@ fn any_ip(#[case] medium: Medium) {
                     Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0),
                     64,
                 )),
-                via_router: IpAddress::Ipv6(Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001)),
+                via_router: Some(IpAddress::Ipv6(Ipv6Address::new(
+                    0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001,
+                ))),
+                metric: 0,
                 preferred_until: None,
                 expires_at: None,
+                table: crate::iface::Routes::MAIN_TABLE,
             })
             .unwrap();
     });
@@ -162,6 +167,7 @@ fn hop_by_hop_skip_with_icmp(#[case] medium: Medium) {
             hop_limit: 64,
             next_header: IpProtocol::Icmpv6,
             payload_len: 19,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv6(Icmpv6Repr::EchoReply {
             ident: 42,
@@ -243,6 +249,7 @@ fn hop_by_hop_discard_param_problem(#[case] medium: Medium) {
             next_header: IpProtocol::Icmpv6,
             payload_len: 75,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv6(Icmpv6Repr::ParamProblem {
             reason: Icmpv6ParamProblem::UnrecognizedOption,
@@ -253,6 +260,7 @@ fn hop_by_hop_discard_param_problem(#[case] medium: Medium) {
                 next_header: IpProtocol::HopByHop,
                 payload_len: 27,
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             },
             data: &[
                 0x3a, 0x0, 0xC0, 0x0, 0x40, 0x0, 0x1, 0x0, 0x80, 0x0, 0x2c, 0x88, 0x0, 0x2a, 0x1,
@@ -301,6 +309,7 @@ fn hop_by_hop_discard_with_multicast(#[case] medium: Medium) {
             next_header: IpProtocol::Icmpv6,
             payload_len: 75,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv6(Icmpv6Repr::ParamProblem {
             reason: Icmpv6ParamProblem::UnrecognizedOption,
@@ -311,6 +320,7 @@ fn hop_by_hop_discard_with_multicast(#[case] medium: Medium) {
                 next_header: IpProtocol::HopByHop,
                 payload_len: 27,
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             },
             data: &[
                 0x3a, 0x0, 0x80, 0x0, 0x40, 0x0, 0x1, 0x0, 0x80, 0x0, 0x2c, 0x88, 0x0, 0x2a, 0x1,
@@ -355,6 +365,7 @@ fn imcp_empty_echo_request(#[case] medium: Medium) {
                 hop_limit: 64,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 8,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Icmpv6(Icmpv6Repr::EchoRequest {
                 ident: 0,
@@ -371,6 +382,7 @@ fn imcp_empty_echo_request(#[case] medium: Medium) {
             hop_limit: 64,
             next_header: IpProtocol::Icmpv6,
             payload_len: 8,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv6(Icmpv6Repr::EchoReply {
             ident: 0,
@@ -416,6 +428,7 @@ fn icmp_echo_request(#[case] medium: Medium) {
                 hop_limit: 64,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 19,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Icmpv6(Icmpv6Repr::EchoRequest {
                 ident: 42,
@@ -432,6 +445,7 @@ fn icmp_echo_request(#[case] medium: Medium) {
             hop_limit: 64,
             next_header: IpProtocol::Icmpv6,
             payload_len: 19,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv6(Icmpv6Repr::EchoReply {
             ident: 42,
@@ -477,6 +491,7 @@ fn icmp_echo_reply_as_input(#[case] medium: Medium) {
                 hop_limit: 64,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 19,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Icmpv6(Icmpv6Repr::EchoReply {
                 ident: 0,
@@ -522,6 +537,7 @@ fn unknown_proto_with_multicast_dst_address(#[case] medium: Medium) {
             hop_limit: 64,
             next_header: IpProtocol::Icmpv6,
             payload_len: 48,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv6(Icmpv6Repr::ParamProblem {
             reason: Icmpv6ParamProblem::UnrecognizedNxtHdr,
@@ -532,6 +548,7 @@ fn unknown_proto_with_multicast_dst_address(#[case] medium: Medium) {
                 hop_limit: 64,
                 next_header: IpProtocol::Unknown(0x0c),
                 payload_len: 0,
+                ecn: IpEcn::NotEct,
             },
             data: &[],
         }),
@@ -572,6 +589,7 @@ fn unknown_proto(#[case] medium: Medium) {
             hop_limit: 64,
             next_header: IpProtocol::Icmpv6,
             payload_len: 48,
+            ecn: IpEcn::NotEct,
         },
         IpPayload::Icmpv6(Icmpv6Repr::ParamProblem {
             reason: Icmpv6ParamProblem::UnrecognizedNxtHdr,
@@ -582,6 +600,7 @@ fn unknown_proto(#[case] medium: Medium) {
                 hop_limit: 64,
                 next_header: IpProtocol::Unknown(0x0c),
                 payload_len: 0,
+                ecn: IpEcn::NotEct,
             },
             data: &[],
         }),
@@ -621,6 +640,7 @@ fn ndisc_neighbor_advertisement_ethernet(#[case] medium: Medium) {
                 hop_limit: 255,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 32,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Icmpv6(Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
                 flags: NdiscNeighborFlags::SOLICITED,
@@ -676,6 +696,7 @@ fn ndisc_neighbor_advertisement_ethernet_multicast_addr(#[case] medium: Medium)
                 hop_limit: 255,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 32,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Icmpv6(Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
                 flags: NdiscNeighborFlags::SOLICITED,
@@ -731,6 +752,7 @@ fn ndisc_neighbor_advertisement_ieee802154(#[case] medium: Medium) {
                 hop_limit: 255,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 40,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Icmpv6(Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
                 flags: NdiscNeighborFlags::SOLICITED,
@@ -788,6 +810,7 @@ fn test_handle_valid_ndisc_request(#[case] medium: Medium) {
         next_header: IpProtocol::Icmpv6,
         hop_limit: 0xff,
         payload_len: solicit.buffer_len(),
+        ecn: IpEcn::NotEct,
     });
 
     let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
@@ -814,6 +837,7 @@ fn test_handle_valid_ndisc_request(#[case] medium: Medium) {
         next_header: IpProtocol::Icmpv6,
         hop_limit: 0xff,
         payload_len: icmpv6_expected.buffer_len(),
+        ecn: IpEcn::NotEct,
     };
 
     // Ensure an Neighbor Solicitation triggers a Neighbor Advertisement
@@ -835,6 +859,7 @@ fn test_handle_valid_ndisc_request(#[case] medium: Medium) {
         iface.inner.lookup_hardware_addr(
             MockTxToken,
             &IpAddress::Ipv6(remote_ip_addr),
+            &Flow::default(),
             &mut iface.fragmenter,
         ),
         Ok((HardwareAddress::Ethernet(remote_hw_addr), MockTxToken))
@@ -881,6 +906,55 @@ fn test_solicited_node_addrs(#[case] medium: Medium) {
     );
 }
 
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_icmp_packet_too_big(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+    let remote_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x100);
+
+    let mut packet_too_big = |mtu: u32| {
+        let icmp_repr = Icmpv6Repr::PktTooBig {
+            mtu,
+            header: Ipv6Repr {
+                src_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+                dst_addr: remote_addr,
+                next_header: IpProtocol::Udp,
+                payload_len: 8,
+                hop_limit: 64,
+                ecn: IpEcn::NotEct,
+            },
+            data: &[0; 8],
+        };
+        let ipv6_repr = Ipv6Repr {
+            src_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0xfe),
+            dst_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        };
+        let mut bytes = vec![0; icmp_repr.buffer_len()];
+        let mut packet = Icmpv6Packet::new_unchecked(&mut bytes[..]);
+        icmp_repr.emit(
+            &ipv6_repr.src_addr,
+            &ipv6_repr.dst_addr,
+            &mut packet,
+            &ChecksumCapabilities::default(),
+        );
+        assert_eq!(
+            iface.inner.process_icmpv6(&mut sockets, ipv6_repr, &bytes),
+            None
+        );
+        iface.path_mtu(&remote_addr.into())
+    };
+
+    assert_eq!(packet_too_big(1400), 1400);
+    assert_eq!(packet_too_big(1000), IPV6_MIN_MTU);
+}
+
 #[rstest]
 #[case(Medium::Ip)]
 #[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
@@ -921,6 +995,7 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         hop_limit: 64,
         payload_len: udp_repr.header_len() + MAX_PAYLOAD_LEN,
+        ecn: IpEcn::NotEct,
     };
     let payload = packet.into_inner();
 
@@ -936,6 +1011,7 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         next_header: IpProtocol::Icmpv6,
         hop_limit: 64,
         payload_len: expected_icmp_repr.buffer_len(),
+        ecn: IpEcn::NotEct,
     };
 
     assert_eq!(
@@ -1473,6 +1549,7 @@ fn test_handle_valid_multicast_query(#[case] medium: Medium) {
             next_header: IpProtocol::Icmpv6,
             hop_limit: 1,
             payload_len: query.buffer_len(),
+            ecn: IpEcn::NotEct,
         });
 
         let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
@@ -1617,3 +1694,84 @@ fn test_solicited_node_multicast_autojoin(#[case] medium: Medium) {
     assert!(!iface.has_multicast_group(addr1.solicited_node()));
     assert!(!iface.has_multicast_group(addr2.solicited_node()));
 }
+
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn forwarded_ipv6_packet(hop_limit: u8, payload_len: usize) -> Vec<u8> {
+    let repr = Ipv6Repr {
+        src_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2),
+        dst_addr: Ipv6Address::new(0xfdbf, 0, 0, 0, 0, 0, 0, 2),
+        next_header: IpProtocol::Udp,
+        payload_len,
+        hop_limit,
+        ecn: IpEcn::NotEct,
+    };
+    let mut bytes = vec![0xa5; repr.buffer_len() + payload_len];
+    repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes[..]));
+    bytes
+}
+
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn forwarding_router(mtu: usize) -> Router {
+    Router::new(
+        &[IpCidr::new(IpAddress::v6(0xfdbe, 0, 0, 0, 0, 0, 0, 1), 64)],
+        &[IpCidr::new(IpAddress::v6(0xfdbf, 0, 0, 0, 0, 0, 0, 1), 64)],
+        mtu,
+    )
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn test_forward_ipv6() {
+    let mut router = forwarding_router(1500);
+
+    let [back, sent] = router.route(forwarded_ipv6_packet(64, 32));
+    assert!(back.is_empty());
+    assert_eq!(sent.len(), 1);
+
+    let packet = Ipv6Packet::new_checked(&sent[0][..]).unwrap();
+    assert_eq!(packet.hop_limit(), 63);
+    assert_eq!(
+        packet.dst_addr(),
+        Ipv6Address::new(0xfdbf, 0, 0, 0, 0, 0, 0, 2)
+    );
+    assert_eq!(packet.payload(), &[0xa5; 32][..]);
+    assert_eq!(router.forwarder.counters().forwarded, 1);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+fn test_forward_ipv6_errors() {
+    let mut router = forwarding_router(1280);
+
+    let [back, sent] = router.route(forwarded_ipv6_packet(1, 32));
+    assert!(sent.is_empty());
+    assert_eq!(back.len(), 1);
+    assert!(matches!(
+        parse_ipv6(&back[0]).unwrap(),
+        Packet::Ipv6(PacketV6 {
+            payload: IpPayload::Icmpv6(Icmpv6Repr::TimeExceeded {
+                reason: Icmpv6TimeExceeded::HopLimitExceeded,
+                ..
+            }),
+            ..
+        })
+    ));
+
+    // IPv6 packets are never fragmented on the way.
+    let [back, sent] = router.route(forwarded_ipv6_packet(64, 1400));
+    assert!(sent.is_empty());
+    assert_eq!(back.len(), 1);
+    assert!(matches!(
+        parse_ipv6(&back[0]).unwrap(),
+        Packet::Ipv6(PacketV6 {
+            payload: IpPayload::Icmpv6(Icmpv6Repr::PktTooBig { mtu: 1280, .. }),
+            ..
+        })
+    ));
+
+    let counters = router.forwarder.counters();
+    assert_eq!(counters.ttl_exceeded, 1);
+    assert_eq!(counters.too_big, 1);
+    assert_eq!(counters.icmp_errors, 2);
+    assert_eq!(counters.forwarded, 0);
+}
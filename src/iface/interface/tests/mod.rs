@@ -36,6 +36,58 @@ fn recv_all(device: &mut crate::tests::TestingDevice, timestamp: Instant) -> Vec
     pkts
 }
 
+/// Two interfaces joined by a forwarder.
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+struct Router {
+    ifaces: [(Interface, crate::tests::TestingDevice); 2],
+    forwarder: crate::iface::Forwarder,
+}
+
+#[cfg(all(feature = "medium-ip", feature = "alloc"))]
+impl Router {
+    /// Create a router between a first interface with the `ip_addrs_0` addresses,
+    /// and a second one with the `ip_addrs_1` addresses and an MTU of `mtu_1`.
+    fn new(ip_addrs_0: &[IpCidr], ip_addrs_1: &[IpCidr], mtu_1: usize) -> Self {
+        let iface = |ip_addrs: &[IpCidr], mtu: usize| {
+            let mut device = crate::tests::TestingDevice::new(Medium::Ip);
+            device.set_max_transmission_unit(mtu);
+            let mut config = Config::new(HardwareAddress::Ip);
+            config.forward_queue_capacity = 4;
+            let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+            iface.update_ip_addrs(|addrs| {
+                for cidr in ip_addrs {
+                    addrs.push(*cidr).unwrap();
+                }
+            });
+            (iface, device)
+        };
+        Router {
+            ifaces: [iface(ip_addrs_0, 1500), iface(ip_addrs_1, mtu_1)],
+            forwarder: crate::iface::Forwarder::new(),
+        }
+    }
+
+    /// Receive `packet` on the first interface, forward it, and return what each
+    /// interface sends.
+    fn route(&mut self, packet: Vec<u8>) -> [Vec<Vec<u8>>; 2] {
+        let mut sockets: SocketSet = SocketSet::new(vec![]);
+        let (iface, device) = &mut self.ifaces[0];
+        device.rx_queue.push_back(packet);
+        iface.poll(Instant::ZERO, device, &mut sockets);
+
+        let [(iface_0, _), (iface_1, _)] = &mut self.ifaces;
+        self.forwarder.forward(&mut [iface_0, iface_1]);
+
+        self.ifaces.each_mut().map(|(iface, device)| {
+            // Let the fragmenter send one fragment per poll.
+            for _ in 0..4 {
+                iface.poll(Instant::ZERO, device, &mut sockets);
+            }
+            device.tx_queue.drain(..).collect()
+        })
+    }
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 struct MockTxToken;
@@ -102,6 +154,7 @@ fn test_handle_udp_broadcast(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 0x40,
+        ecn: IpEcn::NotEct,
     });
     #[cfg(all(not(feature = "proto-ipv6"), feature = "proto-ipv4"))]
     let ip_repr = IpRepr::Ipv4(Ipv4Repr {
@@ -164,6 +217,8 @@ pub fn tcp_not_accepted() {
         src_port: 4242,
         dst_port: 4243,
         control: TcpControl::Syn,
+        ece: false,
+        cwr: false,
         seq_number: TcpSeqNumber(-10001),
         ack_number: None,
         window_len: 256,
@@ -172,6 +227,8 @@ pub fn tcp_not_accepted() {
         sack_permitted: false,
         sack_ranges: [None, None, None],
         timestamp: None,
+        fast_open: None,
+        auth: None,
         payload: &[],
     };
 
@@ -194,6 +251,7 @@ pub fn tcp_not_accepted() {
                 next_header: IpProtocol::Tcp,
                 payload_len: tcp.buffer_len(),
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             }),
             &tcp_bytes,
         ),
@@ -204,11 +262,14 @@ pub fn tcp_not_accepted() {
                 next_header: IpProtocol::Tcp,
                 payload_len: tcp.buffer_len(),
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             },
             IpPayload::Tcp(TcpRepr {
                 src_port: 4243,
                 dst_port: 4242,
                 control: TcpControl::Rst,
+                ece: false,
+                cwr: false,
                 seq_number: TcpSeqNumber(0),
                 ack_number: Some(TcpSeqNumber(-10000)),
                 window_len: 0,
@@ -217,6 +278,8 @@ pub fn tcp_not_accepted() {
                 sack_permitted: false,
                 sack_ranges: [None, None, None],
                 timestamp: None,
+                fast_open: None,
+                auth: None,
                 payload: &[],
             })
         ))
@@ -239,6 +302,7 @@ pub fn tcp_not_accepted() {
                 next_header: IpProtocol::Tcp,
                 payload_len: tcp.buffer_len(),
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             }),
             &tcp_bytes,
         ),
@@ -4,10 +4,15 @@ The `iface` module deals with the *network interfaces*. It filters incoming fram
 provides lookup and caching of hardware addresses, and handles management packets.
 */
 
+#[cfg(feature = "alloc")]
+mod forward;
 mod fragmentation;
 mod interface;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 mod neighbor;
+#[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+mod neighbor_hold;
+mod path_mtu;
 mod route;
 #[cfg(feature = "proto-rpl")]
 mod rpl;
@@ -15,9 +20,13 @@ mod socket_meta;
 mod socket_set;
 #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
 mod tcp_flow_cache;
+#[cfg(all(feature = "alloc", feature = "socket-tcp"))]
+mod tcp_time_wait;
 
 mod packet;
 
+#[cfg(feature = "alloc")]
+pub use self::forward::{ForwardCounters, Forwarder};
 #[cfg(feature = "multicast")]
 pub use self::interface::multicast::MulticastError;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
@@ -32,7 +41,14 @@ pub use self::interface::{
     PollIngressSingleGatewayObservation,
 };
 
-pub use self::route::{Route, RouteTableFull, Routes};
+#[cfg(any(
+    feature = "socket-tcp",
+    feature = "socket-udp",
+    feature = "socket-dns",
+    all(feature = "socket-icmp", feature = "proto-ipv4")
+))]
+pub(crate) use self::route::Flow;
+pub use self::route::{Route, RouteChange, RouteRule, RouteTableFull, Routes};
 pub use self::socket_set::{SocketHandle, SocketSet, SocketStorage};
 #[cfg(all(feature = "alloc", feature = "socket-tcp"))]
 pub use self::tcp_flow_cache::TcpFlowCacheError;
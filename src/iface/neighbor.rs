@@ -1,5 +1,6 @@
 // Heads up! Before working on this file you should read, at least,
-// the parts of RFC 1122 that discuss ARP.
+// the parts of RFC 1122 that discuss ARP, and RFC 4861 § 7.3 which
+// describes Neighbor Unreachability Detection.
 
 use core::fmt;
 use heapless::LinearMap;
@@ -34,24 +35,73 @@ pub enum GatewayNeighborUpdate {
     Changed,
 }
 
+/// The configured gateway, whose neighbor entry is pinned in the cache.
 #[derive(Debug, Clone, Copy)]
 struct GatewayNeighbor {
     protocol_addr: IpAddress,
-    hardware_addr: Option<HardwareAddress>,
-    last_observed_at: Option<Instant>,
-    last_probe_at: Option<Instant>,
     soft_stale_after: Duration,
 }
 
+/// The reachability state of a neighbor, as described in RFC 4861 § 7.3.2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum State {
+    /// Address resolution is in progress and the link-layer address is unknown.
+    Incomplete,
+    /// The neighbor was recently confirmed reachable.
+    Reachable,
+    /// The neighbor is no longer known to be reachable, but nothing was sent to
+    /// it since.
+    Stale,
+    /// A packet was sent to a stale neighbor, and a hint from an upper layer
+    /// is awaited before probing it.
+    Delay,
+    /// The neighbor is being probed with unicast solicitations.
+    Probe,
+}
+
 /// A cached neighbor.
 ///
 /// A neighbor mapping translates from a protocol address to a hardware address,
-/// and contains the timestamp past which the mapping should be discarded.
+/// and tracks the reachability of that neighbor.
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Neighbor {
-    hardware_addr: HardwareAddress,
-    expires_at: Instant,
+    hardware_addr: Option<HardwareAddress>,
+    state: State,
+    /// When the current state times out: the end of reachability for `Reachable`,
+    /// the time the entry is forgotten for `Stale`, and the time the next
+    /// solicitation is due for `Incomplete`, `Delay` and `Probe`.
+    timer: Instant,
+    /// Solicitations sent in the `Incomplete` or `Probe` state.
+    probes: u8,
+}
+
+impl Neighbor {
+    /// Return the state of the neighbor at `timestamp`, with the time it times out.
+    fn state_at(&self, timestamp: Instant) -> (State, Instant) {
+        match self.state {
+            State::Reachable if timestamp >= self.timer => {
+                (State::Stale, self.timer + Cache::STALE_LIFETIME)
+            }
+            state => (state, self.timer),
+        }
+    }
+
+    /// Query whether address resolution or reachability confirmation failed
+    /// by `timestamp`.
+    fn failed_at(&self, timestamp: Instant) -> bool {
+        match self.state_at(timestamp) {
+            (State::Incomplete, timer) => {
+                self.probes >= Cache::MAX_MULTICAST_SOLICIT && timestamp >= timer
+            }
+            (State::Probe, timer) => {
+                self.probes >= Cache::MAX_UNICAST_SOLICIT && timestamp >= timer
+            }
+            (State::Stale, timer) => timestamp >= timer,
+            _ => false,
+        }
+    }
 }
 
 /// An answer to a neighbor cache lookup.
@@ -62,8 +112,8 @@ pub(crate) enum Answer {
     Found(HardwareAddress),
     /// The neighbor address is not in the cache, or has expired.
     NotFound,
-    /// The neighbor address is not in the cache, or has expired,
-    /// and a lookup has been made recently.
+    /// The neighbor address is being resolved, and a solicitation has been
+    /// sent recently.
     RateLimited,
 }
 
@@ -77,30 +127,59 @@ impl Answer {
     }
 }
 
+/// A solicitation due to be sent to a neighbor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct Probe {
+    pub protocol_addr: IpAddress,
+    /// The link-layer address to send a unicast probe to, or `None` to
+    /// solicit the neighbor by broadcast or multicast.
+    pub hardware_addr: Option<HardwareAddress>,
+}
+
 /// A neighbor cache backed by a map.
+///
+/// Every neighbor goes through the Neighbor Unreachability Detection state machine
+/// of RFC 4861 § 7.3, which is also used for ARP. The configured gateway is pinned:
+/// it is never evicted, flushed or forgotten, so its last known link-layer address
+/// stays usable, and it can be probed in the background, see
+/// [`gateway_probe_due`](Self::gateway_probe_due).
 #[derive(Debug)]
 pub struct Cache {
     storage: LinearMap<IpAddress, Neighbor, IFACE_NEIGHBOR_CACHE_COUNT>,
-    silent_until: Instant,
     gateway: Option<GatewayNeighbor>,
 }
 
 impl Cache {
-    /// Minimum delay between discovery requests, in milliseconds.
-    pub(crate) const SILENT_TIME: Duration = Duration::from_millis(1_000);
+    /// Delay between solicitations of a neighbor.
+    pub(crate) const RETRANS_TIMER: Duration = Duration::from_millis(1_000);
+
+    /// Time a neighbor is considered reachable after a confirmation.
+    pub(crate) const REACHABLE_TIME: Duration = Duration::from_millis(30_000);
+
+    /// Time to wait for an upper-layer hint before probing a stale neighbor that
+    /// was sent a packet.
+    pub(crate) const DELAY_FIRST_PROBE_TIME: Duration = Duration::from_millis(5_000);
+
+    /// Time an unused stale neighbor is kept for.
+    pub(crate) const STALE_LIFETIME: Duration = Duration::from_millis(60_000);
+
+    /// Number of broadcast or multicast solicitations before address resolution fails.
+    pub(crate) const MAX_MULTICAST_SOLICIT: u8 = 3;
 
-    /// Neighbor entry lifetime, in milliseconds.
-    pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_millis(60_000);
+    /// Number of unicast probes before a neighbor is considered unreachable.
+    pub(crate) const MAX_UNICAST_SOLICIT: u8 = 3;
 
     /// Create a cache.
     pub fn new() -> Self {
         Self {
             storage: LinearMap::new(),
-            silent_until: Instant::from_millis(0),
             gateway: None,
         }
     }
 
+    /// Pin `protocol_addr` in the cache as the gateway, and consider it stale
+    /// when it was not confirmed reachable for `soft_stale_after`.
     pub fn configure_gateway(
         &mut self,
         protocol_addr: IpAddress,
@@ -111,16 +190,38 @@ impl Cache {
             return Err(GatewayNeighborConfigError::InvalidAddress);
         }
 
-        let existing = self.storage.get(&protocol_addr).copied().and_then(|neighbor| {
-            (timestamp < neighbor.expires_at).then_some(neighbor.hardware_addr)
-        });
+        // The previous gateway becomes a regular neighbor.
+        if let Some(previous) = self.gateway.take() {
+            if let Some(neighbor) = self.storage.get(&previous.protocol_addr) {
+                if neighbor.hardware_addr.is_none() {
+                    self.storage.remove(&previous.protocol_addr);
+                }
+            }
+        }
+
+        let existing = self
+            .storage
+            .get(&protocol_addr)
+            .filter(|neighbor| neighbor.hardware_addr.is_some() && !neighbor.failed_at(timestamp))
+            .is_some();
         self.gateway = Some(GatewayNeighbor {
             protocol_addr,
-            hardware_addr: existing,
-            last_observed_at: existing.map(|_| timestamp),
-            last_probe_at: None,
             soft_stale_after,
         });
+        if existing {
+            self.confirm_reachable(protocol_addr, timestamp);
+        } else {
+            self.insert(
+                protocol_addr,
+                Neighbor {
+                    hardware_addr: None,
+                    state: State::Incomplete,
+                    timer: timestamp,
+                    probes: 0,
+                },
+                timestamp,
+            );
+        }
         Ok(())
     }
 
@@ -128,6 +229,8 @@ impl Cache {
         self.gateway.map(|gateway| gateway.protocol_addr)
     }
 
+    /// Record `hardware_addr` as the link-layer address of the gateway, which
+    /// was seen to be reachable through it.
     pub fn observe_gateway_hardware_addr(
         &mut self,
         hardware_addr: HardwareAddress,
@@ -136,42 +239,26 @@ impl Cache {
         if !hardware_addr.is_unicast() {
             return GatewayNeighborUpdate::Ignored;
         }
-        let Some(gateway) = self.gateway.as_mut() else {
+        let Some(gateway) = self.gateway else {
             return GatewayNeighborUpdate::Ignored;
         };
-
-        let update = match gateway.hardware_addr {
-            None => GatewayNeighborUpdate::Resolved,
-            Some(current) if current == hardware_addr => GatewayNeighborUpdate::Unchanged,
-            Some(_) => GatewayNeighborUpdate::Changed,
-        };
-        if update != GatewayNeighborUpdate::Unchanged {
-            gateway.hardware_addr = Some(hardware_addr);
-        }
-        gateway.last_observed_at = Some(timestamp);
-        gateway.last_probe_at = None;
-        let protocol_addr = gateway.protocol_addr;
-        if update != GatewayNeighborUpdate::Unchanged {
-            self.fill_with_expiration(
-                protocol_addr,
-                hardware_addr,
-                timestamp + Self::ENTRY_LIFETIME,
-            );
-        }
-        update
+        self.fill(gateway.protocol_addr, hardware_addr, timestamp)
     }
 
+    /// Return the gateway if it should be probed, because it is not known to be
+    /// reachable and no solicitation was sent to it recently.
+    ///
+    /// Unlike other neighbors, the gateway is probed even if nothing is sent to it,
+    /// and probing it never gives up.
     pub fn gateway_probe_due(&self, timestamp: Instant) -> Option<IpAddress> {
         let gateway = self.gateway?;
-        let stale = match gateway.last_observed_at {
-            Some(last_observed_at) => timestamp >= last_observed_at + gateway.soft_stale_after,
-            None => true,
+        let neighbor = self.storage.get(&gateway.protocol_addr)?;
+        let due = match neighbor.state_at(timestamp) {
+            (State::Reachable, _) => false,
+            (State::Stale | State::Delay, _) => true,
+            (State::Incomplete | State::Probe, timer) => timestamp >= timer,
         };
-        let retry_due = match gateway.last_probe_at {
-            Some(last_probe_at) => timestamp >= last_probe_at + Self::SILENT_TIME,
-            None => true,
-        };
-        (stale && retry_due).then_some(gateway.protocol_addr)
+        due.then_some(gateway.protocol_addr)
     }
 
     pub fn mark_gateway_probe_sent(
@@ -179,97 +266,285 @@ impl Cache {
         protocol_addr: IpAddress,
         timestamp: Instant,
     ) -> bool {
-        let Some(gateway) = self.gateway.as_mut() else {
-            return false;
-        };
-        if gateway.protocol_addr != protocol_addr {
+        if self.configured_gateway() != Some(protocol_addr) {
             return false;
         }
-        gateway.last_probe_at = Some(timestamp);
+        self.mark_solicited(protocol_addr, timestamp);
         true
     }
 
+    /// Keep a stale neighbor from being forgotten, because it sent us a packet
+    /// from `source_hardware_addr`.
+    ///
+    /// This is not a confirmation of reachability, which needs the neighbor to
+    /// have received something from us.
     pub fn reset_expiry_if_existing(
         &mut self,
         protocol_addr: IpAddress,
         source_hardware_addr: HardwareAddress,
         timestamp: Instant,
     ) {
-        if let Some(Neighbor {
-            expires_at,
-            hardware_addr,
-        }) = self.storage.get_mut(&protocol_addr)
-        {
-            if source_hardware_addr == *hardware_addr {
-                *expires_at = timestamp + Self::ENTRY_LIFETIME;
+        if let Some(neighbor) = self.storage.get_mut(&protocol_addr) {
+            if neighbor.hardware_addr == Some(source_hardware_addr)
+                && neighbor.state_at(timestamp).0 == State::Stale
+            {
+                neighbor.state = State::Stale;
+                neighbor.timer = timestamp + Self::STALE_LIFETIME;
             }
         }
     }
 
+    /// Record `hardware_addr` as the link-layer address of `protocol_addr`, which
+    /// is confirmed reachable, for example by a reply to a solicitation.
     pub fn fill(
         &mut self,
         protocol_addr: IpAddress,
         hardware_addr: HardwareAddress,
         timestamp: Instant,
     ) -> GatewayNeighborUpdate {
-        debug_assert!(protocol_addr.is_unicast());
-        debug_assert!(hardware_addr.is_unicast());
-
-        if self
-            .gateway
-            .map(|gateway| gateway.protocol_addr == protocol_addr)
-            .unwrap_or(false)
-        {
-            return self.observe_gateway_hardware_addr(hardware_addr, timestamp);
-        }
-        let expires_at = timestamp + Self::ENTRY_LIFETIME;
-        self.fill_with_expiration(protocol_addr, hardware_addr, expires_at);
-        GatewayNeighborUpdate::Ignored
+        let neighbor = Neighbor {
+            hardware_addr: Some(hardware_addr),
+            state: State::Reachable,
+            timer: timestamp + self.reachable_time(&protocol_addr),
+            probes: 0,
+        };
+        self.update(protocol_addr, neighbor, timestamp)
     }
 
-    pub fn fill_with_expiration(
+    /// Record `hardware_addr` as the link-layer address of `protocol_addr`,
+    /// learned without a confirmation of reachability, for example from a
+    /// solicitation sent by that neighbor.
+    ///
+    /// The state of a neighbor already known with that address is kept.
+    pub fn fill_stale(
         &mut self,
         protocol_addr: IpAddress,
         hardware_addr: HardwareAddress,
-        expires_at: Instant,
-    ) {
-        debug_assert!(protocol_addr.is_unicast());
-        debug_assert!(hardware_addr.is_unicast());
+        timestamp: Instant,
+    ) -> GatewayNeighborUpdate {
+        if let Some(neighbor) = self.storage.get(&protocol_addr) {
+            if neighbor.hardware_addr == Some(hardware_addr) && !neighbor.failed_at(timestamp) {
+                return match self.configured_gateway() == Some(protocol_addr) {
+                    true => GatewayNeighborUpdate::Unchanged,
+                    false => GatewayNeighborUpdate::Ignored,
+                };
+            }
+        }
 
         let neighbor = Neighbor {
-            expires_at,
-            hardware_addr,
+            hardware_addr: Some(hardware_addr),
+            state: State::Stale,
+            timer: timestamp + Self::STALE_LIFETIME,
+            probes: 0,
+        };
+        self.update(protocol_addr, neighbor, timestamp)
+    }
+
+    /// Note that `protocol_addr` was confirmed reachable, for example by an upper
+    /// layer protocol making forward progress.
+    pub(crate) fn confirm_reachable(&mut self, protocol_addr: IpAddress, timestamp: Instant) {
+        let reachable_time = self.reachable_time(&protocol_addr);
+        if let Some(neighbor) = self.storage.get_mut(&protocol_addr) {
+            if neighbor.hardware_addr.is_some() {
+                neighbor.state = State::Reachable;
+                neighbor.timer = timestamp + reachable_time;
+                neighbor.probes = 0;
+            }
+        }
+    }
+
+    /// Note that a packet is being sent to `protocol_addr`, which starts the
+    /// countdown to probing it if it is stale.
+    pub(crate) fn mark_used(&mut self, protocol_addr: IpAddress, timestamp: Instant) {
+        if let Some(neighbor) = self.storage.get_mut(&protocol_addr) {
+            if neighbor.state_at(timestamp).0 == State::Stale {
+                neighbor.state = State::Delay;
+                neighbor.timer = timestamp + Self::DELAY_FIRST_PROBE_TIME;
+                neighbor.probes = 0;
+            }
+        }
+    }
+
+    /// Query whether the link-layer address of `protocol_addr` is being resolved.
+    #[cfg(all(feature = "alloc", feature = "medium-ethernet"))]
+    pub(crate) fn is_resolving(&self, protocol_addr: &IpAddress, timestamp: Instant) -> bool {
+        self.storage.get(protocol_addr).is_some_and(|neighbor| {
+            neighbor.hardware_addr.is_none() && !neighbor.failed_at(timestamp)
+        })
+    }
+
+    /// Note that a solicitation was sent to `protocol_addr`, starting address
+    /// resolution if it is unknown.
+    pub(crate) fn mark_solicited(&mut self, protocol_addr: IpAddress, timestamp: Instant) {
+        let pinned = self.configured_gateway() == Some(protocol_addr);
+        let neighbor = match self.storage.get_mut(&protocol_addr) {
+            Some(neighbor) if pinned || !neighbor.failed_at(timestamp) => neighbor,
+            _ => {
+                self.insert(
+                    protocol_addr,
+                    Neighbor {
+                        hardware_addr: None,
+                        state: State::Incomplete,
+                        timer: timestamp + Self::RETRANS_TIMER,
+                        probes: 1,
+                    },
+                    timestamp,
+                );
+                return;
+            }
         };
+
+        match neighbor.state_at(timestamp).0 {
+            State::Reachable => return,
+            State::Incomplete | State::Probe => neighbor.probes = neighbor.probes.saturating_add(1),
+            State::Stale | State::Delay => {
+                neighbor.state = State::Probe;
+                neighbor.probes = 1;
+            }
+        }
+        neighbor.timer = timestamp + Self::RETRANS_TIMER;
+    }
+
+    /// Return the next solicitation due to be sent, if any.
+    ///
+    /// This does not include the background probes of the gateway, see
+    /// [`gateway_probe_due`](Self::gateway_probe_due), but does include the
+    /// solicitations of the gateway caused by sending it packets.
+    pub(crate) fn probe_due(&self, timestamp: Instant) -> Option<Probe> {
+        self.storage
+            .iter()
+            .find(|(_, neighbor)| {
+                Self::next_probe_at(neighbor).is_some_and(|probe_at| timestamp >= probe_at)
+            })
+            .map(|(protocol_addr, neighbor)| Probe {
+                protocol_addr: *protocol_addr,
+                hardware_addr: match neighbor.state {
+                    State::Incomplete => None,
+                    _ => neighbor.hardware_addr,
+                },
+            })
+    }
+
+    /// Return the time the next solicitation is due to be sent, or a neighbor
+    /// is due to be forgotten because it could not be reached.
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        let gateway = self.configured_gateway();
+        self.storage
+            .iter()
+            .filter_map(|(protocol_addr, neighbor)| {
+                Self::next_probe_at(neighbor).or(match neighbor.state {
+                    State::Incomplete | State::Probe if gateway != Some(*protocol_addr) => {
+                        Some(neighbor.timer)
+                    }
+                    _ => None,
+                })
+            })
+            .min()
+    }
+
+    fn next_probe_at(neighbor: &Neighbor) -> Option<Instant> {
+        match neighbor.state {
+            // The gateway waits for a first solicitation from outside the cache.
+            State::Incomplete
+                if neighbor.probes > 0 && neighbor.probes < Self::MAX_MULTICAST_SOLICIT =>
+            {
+                Some(neighbor.timer)
+            }
+            State::Delay => Some(neighbor.timer),
+            State::Probe if neighbor.probes < Self::MAX_UNICAST_SOLICIT => Some(neighbor.timer),
+            _ => None,
+        }
+    }
+
+    /// Forget the neighbors that could not be reached or went unused, except the
+    /// gateway.
+    pub(crate) fn expire(&mut self, timestamp: Instant) {
+        let gateway = self.configured_gateway();
+        self.storage.retain(|protocol_addr, neighbor| {
+            if gateway != Some(*protocol_addr) && neighbor.failed_at(timestamp) {
+                net_trace!("forgot {}", protocol_addr);
+                return false;
+            }
+            true
+        });
+    }
+
+    fn reachable_time(&self, protocol_addr: &IpAddress) -> Duration {
+        match self.gateway {
+            Some(gateway) if gateway.protocol_addr == *protocol_addr => gateway.soft_stale_after,
+            _ => Self::REACHABLE_TIME,
+        }
+    }
+
+    /// Replace the entry of `protocol_addr` with `neighbor`, and report how
+    /// that changed the gateway.
+    fn update(
+        &mut self,
+        protocol_addr: IpAddress,
+        neighbor: Neighbor,
+        timestamp: Instant,
+    ) -> GatewayNeighborUpdate {
+        debug_assert!(protocol_addr.is_unicast());
+        debug_assert!(neighbor.hardware_addr.is_some_and(|addr| addr.is_unicast()));
+
+        let old_hardware_addr = self.insert(protocol_addr, neighbor, timestamp);
+        if self.configured_gateway() != Some(protocol_addr) {
+            return GatewayNeighborUpdate::Ignored;
+        }
+        match old_hardware_addr {
+            None => GatewayNeighborUpdate::Resolved,
+            Some(addr) if Some(addr) == neighbor.hardware_addr => GatewayNeighborUpdate::Unchanged,
+            Some(_) => GatewayNeighborUpdate::Changed,
+        }
+    }
+
+    /// Insert `neighbor`, evicting another one if the cache is full, and return
+    /// the link-layer address previously known for `protocol_addr`.
+    ///
+    /// Nothing is inserted if the cache is full and only holds the gateway.
+    fn insert(
+        &mut self,
+        protocol_addr: IpAddress,
+        neighbor: Neighbor,
+        timestamp: Instant,
+    ) -> Option<HardwareAddress> {
         match self.storage.insert(protocol_addr, neighbor) {
             Ok(Some(old_neighbor)) => {
-                if old_neighbor.hardware_addr != hardware_addr {
+                if old_neighbor.hardware_addr != neighbor.hardware_addr {
                     net_trace!(
-                        "replaced {} => {} (was {})",
+                        "replaced {} => {:?} (was {:?})",
                         protocol_addr,
-                        hardware_addr,
+                        neighbor.hardware_addr,
                         old_neighbor.hardware_addr
                     );
                 }
+                old_neighbor.hardware_addr
             }
             Ok(None) => {
-                net_trace!("filled {} => {} (was empty)", protocol_addr, hardware_addr);
+                net_trace!(
+                    "filled {} => {:?} (was empty)",
+                    protocol_addr,
+                    neighbor.hardware_addr
+                );
+                None
             }
             Err((protocol_addr, neighbor)) => {
                 // If we're going down this branch, it means the cache is full, and we need to evict an entry.
-                let old_protocol_addr = *self
-                    .storage
-                    .iter()
-                    .min_by_key(|(_, neighbor)| neighbor.expires_at)
-                    .expect("empty neighbor cache storage")
-                    .0;
+                let Some(old_protocol_addr) = self.eviction_candidate(timestamp) else {
+                    net_debug!(
+                        "neighbor cache full of pinned entries, dropped {}",
+                        protocol_addr
+                    );
+                    return None;
+                };
 
                 let _old_neighbor = self.storage.remove(&old_protocol_addr).unwrap();
                 match self.storage.insert(protocol_addr, neighbor) {
                     Ok(None) => {
                         net_trace!(
-                            "filled {} => {} (evicted {} => {})",
+                            "filled {} => {:?} (evicted {} => {:?})",
                             protocol_addr,
-                            hardware_addr,
+                            neighbor.hardware_addr,
                             old_protocol_addr,
                             _old_neighbor.hardware_addr
                         );
@@ -277,44 +552,60 @@ impl Cache {
                     // We've covered everything else above.
                     _ => unreachable!(),
                 }
+                None
             }
         }
     }
 
+    /// Return the neighbor to evict to make room for another one, which is never
+    /// the gateway.
+    ///
+    /// The neighbors that failed go first, then the stale ones, then the ones
+    /// being probed, then the reachable ones, and last the ones being resolved,
+    /// so that a resolution in progress is not undone by the next one. Within
+    /// a state, the neighbor whose timer expires first goes first.
+    fn eviction_candidate(&self, timestamp: Instant) -> Option<IpAddress> {
+        let gateway = self.configured_gateway();
+        self.storage
+            .iter()
+            .filter(|(protocol_addr, _)| gateway != Some(**protocol_addr))
+            .min_by_key(|(_, neighbor)| {
+                let (state, timer) = neighbor.state_at(timestamp);
+                let rank = match state {
+                    _ if neighbor.failed_at(timestamp) => 0,
+                    State::Stale => 1,
+                    State::Delay | State::Probe => 2,
+                    State::Reachable => 3,
+                    State::Incomplete => 4,
+                };
+                (rank, timer)
+            })
+            .map(|(protocol_addr, _)| *protocol_addr)
+    }
+
     pub(crate) fn lookup(&self, protocol_addr: &IpAddress, timestamp: Instant) -> Answer {
         assert!(protocol_addr.is_unicast());
 
-        if let Some(gateway) = self.gateway {
-            if gateway.protocol_addr == *protocol_addr {
-                if let Some(hardware_addr) = gateway.hardware_addr {
-                    return Answer::Found(hardware_addr);
-                }
-            }
-        }
+        let Some(neighbor) = self.storage.get(protocol_addr) else {
+            return Answer::NotFound;
+        };
+        let pinned = self.configured_gateway() == Some(*protocol_addr);
 
-        if let Some(&Neighbor {
-            expires_at,
-            hardware_addr,
-        }) = self.storage.get(protocol_addr)
-        {
-            if timestamp < expires_at {
-                return Answer::Found(hardware_addr);
+        match neighbor.hardware_addr {
+            // The last known address of the gateway is better than none.
+            Some(hardware_addr) if pinned || !neighbor.failed_at(timestamp) => {
+                Answer::Found(hardware_addr)
             }
+            None if timestamp < neighbor.timer => Answer::RateLimited,
+            _ => Answer::NotFound,
         }
-
-        if timestamp < self.silent_until {
-            Answer::RateLimited
-        } else {
-            Answer::NotFound
-        }
-    }
-
-    pub(crate) fn limit_rate(&mut self, timestamp: Instant) {
-        self.silent_until = timestamp + Self::SILENT_TIME;
     }
 
+    /// Forget all neighbors, except the gateway.
     pub(crate) fn flush(&mut self) {
-        self.storage.clear()
+        let gateway = self.configured_gateway();
+        self.storage
+            .retain(|protocol_addr, _| gateway == Some(*protocol_addr))
     }
 }
 
@@ -363,7 +654,7 @@ mod test {
             !cache
                 .lookup(
                     &MOCK_IP_ADDR_1.into(),
-                    Instant::from_millis(0) + Cache::ENTRY_LIFETIME * 2
+                    Instant::from_millis(0) + Cache::STALE_LIFETIME * 2
                 )
                 .found(),
         );
@@ -389,7 +680,7 @@ mod test {
             !cache
                 .lookup(
                     &MOCK_IP_ADDR_1.into(),
-                    Instant::from_millis(0) + Cache::ENTRY_LIFETIME * 2
+                    Instant::from_millis(0) + Cache::STALE_LIFETIME * 2
                 )
                 .found(),
         );
@@ -440,6 +731,52 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_evict_by_state() {
+        let mut cache = Cache::new();
+
+        cache.fill(MOCK_IP_ADDR_1.into(), HADDR_A, Instant::from_millis(0));
+        cache.fill_stale(MOCK_IP_ADDR_2.into(), HADDR_B, Instant::from_millis(0));
+        cache.mark_solicited(MOCK_IP_ADDR_3.into(), Instant::from_millis(0));
+
+        // The address being resolved times out first, but the stale neighbor goes.
+        cache.mark_solicited(MOCK_IP_ADDR_4.into(), Instant::from_millis(100));
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_2.into(), Instant::from_millis(100)),
+            Answer::NotFound
+        );
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_3.into(), Instant::from_millis(100)),
+            Answer::RateLimited
+        );
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::from_millis(100)),
+            Answer::Found(HADDR_A)
+        );
+    }
+
+    #[test]
+    fn test_evict_never_gateway() {
+        let mut cache = Cache::new();
+        let gateway = MOCK_IP_ADDR_1.into();
+        cache
+            .configure_gateway(gateway, Instant::from_millis(0), Duration::from_secs(30))
+            .unwrap();
+
+        // A cache that only holds the gateway has nothing to evict.
+        assert_eq!(cache.eviction_candidate(Instant::from_millis(0)), None);
+
+        cache.fill(MOCK_IP_ADDR_2.into(), HADDR_B, Instant::from_millis(0));
+        cache.fill(MOCK_IP_ADDR_3.into(), HADDR_C, Instant::from_millis(10));
+        cache.fill(MOCK_IP_ADDR_4.into(), HADDR_D, Instant::from_millis(20));
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_2.into(), Instant::from_millis(20)),
+            Answer::NotFound
+        );
+        assert_eq!(cache.configured_gateway(), Some(gateway));
+        assert!(cache.storage.contains_key(&gateway));
+    }
+
     #[test]
     fn test_hush() {
         let mut cache = Cache::new();
@@ -449,11 +786,15 @@ mod test {
             Answer::NotFound
         );
 
-        cache.limit_rate(Instant::from_millis(0));
+        cache.mark_solicited(MOCK_IP_ADDR_1.into(), Instant::from_millis(0));
         assert_eq!(
             cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::from_millis(100)),
             Answer::RateLimited
         );
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_2.into(), Instant::from_millis(100)),
+            Answer::NotFound
+        );
         assert_eq!(
             cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::from_millis(2000)),
             Answer::NotFound
@@ -558,4 +899,134 @@ mod test {
         );
         assert_eq!(cache.lookup(&gateway, Instant::from_millis(2)), Answer::Found(HADDR_A));
     }
+
+    #[test]
+    fn test_resolution() {
+        let mut cache = Cache::new();
+        let addr = MOCK_IP_ADDR_1.into();
+
+        cache.mark_solicited(addr, Instant::from_millis(0));
+        assert_eq!(cache.probe_due(Instant::from_millis(999)), None);
+        assert_eq!(cache.poll_at(), Some(Instant::from_millis(1000)));
+        assert_eq!(
+            cache.probe_due(Instant::from_millis(1000)),
+            Some(Probe {
+                protocol_addr: addr,
+                hardware_addr: None,
+            })
+        );
+        cache.mark_solicited(addr, Instant::from_millis(1000));
+        cache.mark_solicited(addr, Instant::from_millis(2000));
+        assert_eq!(cache.probe_due(Instant::from_millis(3000)), None);
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(2500)),
+            Answer::RateLimited
+        );
+
+        // Resolution fails after the last solicitation goes unanswered.
+        cache.expire(Instant::from_millis(3000));
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(3000)),
+            Answer::NotFound
+        );
+        assert_eq!(cache.poll_at(), None);
+
+        cache.mark_solicited(addr, Instant::from_millis(4000));
+        cache.fill(addr, HADDR_A, Instant::from_millis(4500));
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(4500)),
+            Answer::Found(HADDR_A)
+        );
+        assert_eq!(cache.poll_at(), None);
+    }
+
+    #[test]
+    fn test_reachability() {
+        let mut cache = Cache::new();
+        let addr = MOCK_IP_ADDR_1.into();
+        let stale_at = Instant::from_millis(0) + Cache::REACHABLE_TIME;
+
+        cache.fill(addr, HADDR_A, Instant::from_millis(0));
+        cache.mark_used(addr, stale_at - Duration::from_millis(1));
+        assert_eq!(cache.poll_at(), None);
+
+        // Using a stale neighbor schedules a unicast probe.
+        cache.mark_used(addr, stale_at);
+        let probe_at = stale_at + Cache::DELAY_FIRST_PROBE_TIME;
+        assert_eq!(cache.poll_at(), Some(probe_at));
+        assert_eq!(cache.probe_due(probe_at - Duration::from_millis(1)), None);
+        assert_eq!(
+            cache.probe_due(probe_at),
+            Some(Probe {
+                protocol_addr: addr,
+                hardware_addr: Some(HADDR_A),
+            })
+        );
+
+        // The neighbor stays usable while it is probed.
+        let mut now = probe_at;
+        for _ in 0..Cache::MAX_UNICAST_SOLICIT {
+            cache.mark_solicited(addr, now);
+            assert_eq!(cache.lookup(&addr, now), Answer::Found(HADDR_A));
+            now += Cache::RETRANS_TIMER;
+        }
+        assert_eq!(cache.probe_due(now), None);
+        assert_eq!(cache.lookup(&addr, now), Answer::NotFound);
+    }
+
+    #[test]
+    fn test_reachability_hint() {
+        let mut cache = Cache::new();
+        let addr = MOCK_IP_ADDR_1.into();
+        let stale_at = Instant::from_millis(0) + Cache::REACHABLE_TIME;
+
+        cache.fill_stale(addr, HADDR_A, Instant::from_millis(0));
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(0)),
+            Answer::Found(HADDR_A)
+        );
+
+        cache.mark_used(addr, Instant::from_millis(0));
+        assert_eq!(
+            cache.poll_at(),
+            Some(Instant::from_millis(0) + Cache::DELAY_FIRST_PROBE_TIME)
+        );
+        cache.confirm_reachable(addr, Instant::from_millis(1000));
+        assert_eq!(cache.poll_at(), None);
+
+        // Learning the same address again does not make it stale.
+        cache.fill_stale(addr, HADDR_A, Instant::from_millis(2000));
+        cache.mark_used(addr, stale_at);
+        assert_eq!(cache.poll_at(), None);
+
+        // Learning another one does.
+        cache.fill_stale(addr, HADDR_B, Instant::from_millis(3000));
+        cache.mark_used(addr, Instant::from_millis(3000));
+        assert_eq!(
+            cache.poll_at(),
+            Some(Instant::from_millis(3000) + Cache::DELAY_FIRST_PROBE_TIME)
+        );
+    }
+
+    #[test]
+    fn configured_gateway_is_not_given_up() {
+        let mut cache = Cache::new();
+        let gateway = MOCK_IP_ADDR_1.into();
+        cache
+            .configure_gateway(gateway, Instant::from_millis(0), Duration::from_secs(30))
+            .unwrap();
+        cache.fill(gateway, HADDR_A, Instant::from_millis(0));
+
+        let mut now = Instant::from_secs(30);
+        cache.mark_used(gateway, now);
+        for _ in 0..Cache::MAX_UNICAST_SOLICIT {
+            cache.mark_solicited(gateway, now);
+            now += Cache::RETRANS_TIMER;
+        }
+        cache.expire(now);
+        cache.flush();
+        assert_eq!(cache.lookup(&gateway, now), Answer::Found(HADDR_A));
+        assert_eq!(cache.gateway_probe_due(now), Some(gateway));
+        assert_eq!(cache.probe_due(now), None);
+    }
 }
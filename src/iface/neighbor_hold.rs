@@ -0,0 +1,203 @@
+// Packets held while the link-layer address of their next hop is resolved.
+//
+// Without room to hold it (see `Config::neighbor_hold_capacity`), a packet for a
+// neighbor whose link-layer address is unknown is dropped, and the socket sending
+// it retries once the neighbor is resolved or after a while. With it, the interface
+// keeps a few such packets for each neighbor, like `unres_qlen` in Linux, and sends
+// them on its next egress poll after the ARP reply or Neighbor Advertisement. The
+// packets still held when the resolution fails are kept aside until the
+// application takes them, or their room is needed.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::wire::IpAddress;
+
+#[derive(Debug)]
+struct Slot {
+    /// The neighbor the packet is held for, or `None` if the slot is free.
+    neighbor: Option<IpAddress>,
+    /// Whether resolving `neighbor` failed.
+    unresolved: bool,
+    /// Order in which the packet was held.
+    seq: u64,
+    len: usize,
+    buffer: Vec<u8>,
+}
+
+/// A fixed number of raw IP packets held for their next hop.
+#[derive(Debug)]
+pub(crate) struct HoldQueue {
+    slots: Vec<Slot>,
+    per_neighbor: usize,
+    next_seq: u64,
+}
+
+impl HoldQueue {
+    /// Create a queue holding at most `per_neighbor` packets of at most `mtu` octets
+    /// for each of `neighbors` neighbors.
+    pub(crate) fn new(per_neighbor: usize, neighbors: usize, mtu: usize) -> Self {
+        let slots = (0..per_neighbor * neighbors)
+            .map(|_| Slot {
+                neighbor: None,
+                unresolved: false,
+                seq: 0,
+                len: 0,
+                buffer: vec![0; mtu],
+            })
+            .collect();
+        Self {
+            slots,
+            per_neighbor,
+            next_seq: 0,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.slots.is_empty()
+    }
+
+    /// Hold a packet of `len` octets for `neighbor`, written by `f` into a zeroed
+    /// buffer. Return `false` if the packet is too large, or there is no room for it.
+    ///
+    /// The oldest packet of an unresolved neighbor is dropped to make room, if needed.
+    pub(crate) fn push_with(
+        &mut self,
+        neighbor: IpAddress,
+        len: usize,
+        f: impl FnOnce(&mut [u8]),
+    ) -> bool {
+        let held = self
+            .slots
+            .iter()
+            .filter(|slot| slot.neighbor == Some(neighbor) && !slot.unresolved)
+            .count();
+        if held >= self.per_neighbor {
+            return false;
+        }
+
+        let free = self.slots.iter().position(|slot| slot.neighbor.is_none());
+        let Some(index) = free.or_else(|| self.oldest(|slot| slot.unresolved)) else {
+            return false;
+        };
+        let slot = &mut self.slots[index];
+        if len > slot.buffer.len() {
+            return false;
+        }
+
+        slot.neighbor = Some(neighbor);
+        slot.unresolved = false;
+        slot.seq = self.next_seq;
+        slot.len = len;
+        slot.buffer[..len].fill(0);
+        f(&mut slot.buffer[..len]);
+        self.next_seq += 1;
+        true
+    }
+
+    /// Return the index of the oldest packet held for a neighbor for which
+    /// `ready` returns `true`.
+    pub(crate) fn oldest_ready(&self, ready: impl Fn(&IpAddress) -> bool) -> Option<usize> {
+        self.oldest(|slot| !slot.unresolved && slot.neighbor.as_ref().is_some_and(&ready))
+    }
+
+    fn oldest(&self, f: impl Fn(&Slot) -> bool) -> Option<usize> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.neighbor.is_some() && f(slot))
+            .min_by_key(|(_, slot)| slot.seq)
+            .map(|(index, _)| index)
+    }
+
+    /// Return the neighbor the packet at `index` is held for.
+    pub(crate) fn neighbor(&self, index: usize) -> IpAddress {
+        self.slots[index].neighbor.expect("free hold queue slot")
+    }
+
+    /// Note that the neighbor the packet at `index` is held for could not be resolved.
+    pub(crate) fn mark_unresolved(&mut self, index: usize) {
+        self.slots[index].unresolved = true;
+    }
+
+    /// Take the buffer of the packet at `index`, with the length of the packet. The
+    /// slot stays in use until the buffer is given back with [`free`](Self::free).
+    pub(crate) fn take(&mut self, index: usize) -> (Vec<u8>, usize) {
+        let slot = &mut self.slots[index];
+        (mem::take(&mut slot.buffer), slot.len)
+    }
+
+    /// Free the slot at `index`, giving back the buffer taken from it.
+    pub(crate) fn free(&mut self, index: usize, buffer: Vec<u8>) {
+        let slot = &mut self.slots[index];
+        slot.neighbor = None;
+        slot.unresolved = false;
+        slot.buffer = buffer;
+    }
+
+    /// Pass the oldest packet of an unresolved neighbor to `f`, with that neighbor,
+    /// and drop it.
+    pub(crate) fn pop_unresolved<R>(&mut self, f: impl FnOnce(IpAddress, &[u8]) -> R) -> Option<R> {
+        let index = self.oldest(|slot| slot.unresolved)?;
+        let slot = &mut self.slots[index];
+        let result = f(slot.neighbor.take().unwrap(), &slot.buffer[..slot.len]);
+        slot.unresolved = false;
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(all(feature = "proto-ipv4", not(feature = "proto-ipv6")))]
+    use crate::wire::ipv4::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2};
+    #[cfg(feature = "proto-ipv6")]
+    use crate::wire::ipv6::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2};
+
+    fn push(queue: &mut HoldQueue, neighbor: IpAddress, byte: u8) -> bool {
+        queue.push_with(neighbor, 1, |buffer| buffer[0] = byte)
+    }
+
+    #[test]
+    fn test_per_neighbor_limit() {
+        let mut queue = HoldQueue::new(2, 2, 16);
+        assert!(push(&mut queue, MOCK_IP_ADDR_1.into(), 1));
+        assert!(push(&mut queue, MOCK_IP_ADDR_1.into(), 2));
+        assert!(!push(&mut queue, MOCK_IP_ADDR_1.into(), 3));
+        assert!(push(&mut queue, MOCK_IP_ADDR_2.into(), 4));
+        assert!(!queue.push_with(MOCK_IP_ADDR_2.into(), 17, |_| ()));
+
+        // Packets go out in the order they were held.
+        let index = queue.oldest_ready(|neighbor| *neighbor == MOCK_IP_ADDR_1.into()).unwrap();
+        let (buffer, len) = queue.take(index);
+        assert_eq!(&buffer[..len], &[1]);
+        queue.free(index, buffer);
+        let index = queue.oldest_ready(|neighbor| *neighbor == MOCK_IP_ADDR_1.into()).unwrap();
+        assert_eq!(queue.take(index).0[0], 2);
+    }
+
+    #[test]
+    fn test_unresolved() {
+        let mut queue = HoldQueue::new(1, 2, 16);
+        assert!(push(&mut queue, MOCK_IP_ADDR_1.into(), 1));
+        assert!(push(&mut queue, MOCK_IP_ADDR_2.into(), 2));
+
+        let index = queue.oldest_ready(|_| true).unwrap();
+        assert_eq!(queue.neighbor(index), MOCK_IP_ADDR_1.into());
+        queue.mark_unresolved(index);
+        assert_eq!(queue.oldest_ready(|neighbor| *neighbor == MOCK_IP_ADDR_1.into()), None);
+
+        // Unresolved packets make room for new ones.
+        assert!(push(&mut queue, MOCK_IP_ADDR_1.into(), 3));
+        assert_eq!(queue.pop_unresolved(|_, _| ()), None);
+
+        let index = queue.oldest_ready(|neighbor| *neighbor == MOCK_IP_ADDR_2.into()).unwrap();
+        queue.mark_unresolved(index);
+        assert_eq!(
+            queue.pop_unresolved(|neighbor, packet| (neighbor, packet.to_vec())),
+            Some((MOCK_IP_ADDR_2.into(), vec![2]))
+        );
+        assert_eq!(queue.pop_unresolved(|_, _| ()), None);
+    }
+}
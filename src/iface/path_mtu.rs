@@ -0,0 +1,138 @@
+// Path MTU discovery, as described in RFC 1191 (IPv4) and RFC 8201 (IPv6).
+//
+// Routers that cannot forward a packet because it is too big report the MTU of
+// the next hop with an ICMP error. The cache remembers the lowest MTU reported
+// for each destination, until the entry expires and larger packets are tried
+// again.
+
+use heapless::LinearMap;
+
+use crate::config::IFACE_PATH_MTU_CACHE_COUNT;
+use crate::time::{Duration, Instant};
+use crate::wire::IpAddress;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Entry {
+    mtu: usize,
+    expires_at: Instant,
+}
+
+/// A path MTU cache backed by a map.
+#[derive(Debug)]
+pub(crate) struct Cache {
+    storage: LinearMap<IpAddress, Entry, IFACE_PATH_MTU_CACHE_COUNT>,
+}
+
+impl Cache {
+    /// Path MTU entry lifetime, as recommended by RFC 1191 section 6.3 and RFC 8201
+    /// section 4.
+    pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_secs(600);
+
+    /// Create a cache.
+    pub(crate) const fn new() -> Self {
+        Self {
+            storage: LinearMap::new(),
+        }
+    }
+
+    /// Return the path MTU towards `addr`, if a lower MTU than the one of the
+    /// interface was reported recently.
+    pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<usize> {
+        self.storage
+            .get(addr)
+            .filter(|entry| timestamp < entry.expires_at)
+            .map(|entry| entry.mtu)
+    }
+
+    /// Remember that the path MTU towards `addr` is `mtu`.
+    ///
+    /// The path MTU is never raised this way, an entry only expires. Returns whether
+    /// the path MTU was lowered.
+    pub(crate) fn fill(&mut self, addr: IpAddress, mtu: usize, timestamp: Instant) -> bool {
+        if self
+            .lookup(&addr, timestamp)
+            .is_some_and(|cached| cached <= mtu)
+        {
+            return false;
+        }
+
+        let entry = Entry {
+            mtu,
+            expires_at: timestamp + Self::ENTRY_LIFETIME,
+        };
+        if let Err((addr, entry)) = self.storage.insert(addr, entry) {
+            // The cache is full, evict the entry closest to expiring.
+            let old_addr = *self
+                .storage
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .expect("empty path MTU cache storage")
+                .0;
+            self.storage.remove(&old_addr);
+            // NOTE(unwrap): we just made room.
+            self.storage.insert(addr, entry).unwrap();
+        }
+        net_trace!("path MTU to {} is {}", addr, mtu);
+        true
+    }
+
+    /// Forget all the path MTUs learnt.
+    pub(crate) fn flush(&mut self) {
+        self.storage.clear()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(all(feature = "proto-ipv4", not(feature = "proto-ipv6")))]
+    use crate::wire::ipv4::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2, MOCK_IP_ADDR_3};
+    #[cfg(feature = "proto-ipv6")]
+    use crate::wire::ipv6::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2, MOCK_IP_ADDR_3};
+
+    #[test]
+    fn test_fill() {
+        let mut cache = Cache::new();
+        let now = Instant::from_secs(0);
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), now), None);
+
+        assert!(cache.fill(MOCK_IP_ADDR_1.into(), 1400, now));
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), now), Some(1400));
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_2.into(), now), None);
+
+        // The path MTU is only lowered.
+        assert!(!cache.fill(MOCK_IP_ADDR_1.into(), 1450, now));
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), now), Some(1400));
+        assert!(cache.fill(MOCK_IP_ADDR_1.into(), 1300, now));
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), now), Some(1300));
+
+        cache.flush();
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), now), None);
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut cache = Cache::new();
+        cache.fill(MOCK_IP_ADDR_1.into(), 1400, Instant::from_secs(0));
+
+        let later = Instant::from_secs(0) + Cache::ENTRY_LIFETIME;
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), later), None);
+        // An expired entry may be raised.
+        assert!(cache.fill(MOCK_IP_ADDR_1.into(), 1450, later));
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), later), Some(1450));
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut cache = Cache::new();
+        cache.fill(MOCK_IP_ADDR_1.into(), 1400, Instant::from_secs(0));
+        cache.fill(MOCK_IP_ADDR_2.into(), 1400, Instant::from_secs(1));
+        cache.fill(MOCK_IP_ADDR_3.into(), 1400, Instant::from_secs(2));
+
+        let now = Instant::from_secs(2);
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), now), None);
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_2.into(), now), Some(1400));
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_3.into(), now), Some(1400));
+    }
+}
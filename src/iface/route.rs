@@ -1,8 +1,9 @@
 use heapless::Vec;
 
-use crate::config::IFACE_MAX_ROUTE_COUNT;
+use crate::config::{IFACE_MAX_ROUTE_COUNT, IFACE_MAX_ROUTE_RULE_COUNT};
+use crate::phy::PacketMeta;
 use crate::time::Instant;
-use crate::wire::{IpAddress, IpCidr};
+use crate::wire::{IpAddress, IpCidr, IpProtocol, IpRepr};
 #[cfg(feature = "proto-ipv4")]
 use crate::wire::{Ipv4Address, Ipv4Cidr};
 #[cfg(feature = "proto-ipv6")]
@@ -21,16 +22,107 @@ impl core::fmt::Display for RouteTableFull {
 #[cfg(feature = "std")]
 impl std::error::Error for RouteTableFull {}
 
-/// A prefix of addresses that should be routed via a router
-#[derive(Debug, Clone, Copy)]
+/// A prefix of addresses that should be routed via a router, or that is on-link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Route {
     pub cidr: IpCidr,
-    pub via_router: IpAddress,
+    /// `None` means the addresses of the prefix are neighbors, reached directly.
+    pub via_router: Option<IpAddress>,
+    /// Among the routes to the same prefix, those with the lowest metric are used.
+    /// Packets are spread across several of them by a hash of their destination
+    /// only, not of their source, protocol or ports: all the traffic to a
+    /// destination takes the same route, whose next hop is resolved once for all
+    /// of it, so a single busy destination does not benefit from several routes.
+    pub metric: u32,
     /// `None` means "forever".
     pub preferred_until: Option<Instant>,
     /// `None` means "forever".
     pub expires_at: Option<Instant>,
+    /// The table the route is in, [`Routes::MAIN_TABLE`] unless a [`RouteRule`]
+    /// selects it.
+    pub table: u8,
+}
+
+/// A rule selecting the table to look routes up in, by the properties of the
+/// packet (ie. "ip rule add from `src` lookup `table`").
+///
+/// Rules are tried in order, and the first one matching a packet whose table
+/// has a route to its destination wins. The main table is used if none does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RouteRule {
+    /// The prefix of the source address of the packets. `None` matches any.
+    pub src: Option<IpCidr>,
+    /// The protocol of the packets. `None` matches any.
+    pub protocol: Option<IpProtocol>,
+    /// The mark of the socket sending the packets, see
+    /// [`PacketMeta::mark`](crate::phy::PacketMeta::mark). `None` matches any.
+    pub mark: Option<u32>,
+    pub table: u8,
+}
+
+impl RouteRule {
+    /// Returns a rule selecting `table` for the packets from `src`.
+    pub fn from_src(src: IpCidr, table: u8) -> RouteRule {
+        RouteRule {
+            src: Some(src),
+            protocol: None,
+            mark: None,
+            table,
+        }
+    }
+
+    /// Returns a rule selecting `table` for the packets of sockets marked with `mark`.
+    pub fn from_mark(mark: u32, table: u8) -> RouteRule {
+        RouteRule {
+            src: None,
+            protocol: None,
+            mark: Some(mark),
+            table,
+        }
+    }
+
+    fn matches(&self, flow: &Flow) -> bool {
+        self.src.is_none_or(|src| {
+            flow.src_addr
+                .is_some_and(|src_addr| src.contains_addr(&src_addr))
+        }) && self
+            .protocol
+            .is_none_or(|protocol| flow.protocol == Some(protocol))
+            && self.mark.is_none_or(|mark| flow.mark == mark)
+    }
+}
+
+/// The properties of a packet that route rules match, as far as they are known.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct Flow {
+    pub src_addr: Option<IpAddress>,
+    pub protocol: Option<IpProtocol>,
+    pub mark: u32,
+}
+
+impl Flow {
+    /// Return the flow of a packet sent with `ip_repr` and `meta`.
+    pub(crate) fn new(ip_repr: &IpRepr, #[allow(unused_variables)] meta: &PacketMeta) -> Self {
+        Flow {
+            src_addr: Some(ip_repr.src_addr()),
+            protocol: Some(ip_repr.next_header()),
+            #[cfg(feature = "packetmeta-mark")]
+            mark: meta.mark,
+            #[cfg(not(feature = "packetmeta-mark"))]
+            mark: 0,
+        }
+    }
+}
+
+/// A change made to a routing table, see [`Routes::set_on_change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RouteChange {
+    Added(Route),
+    Removed(Route),
 }
 
 #[cfg(feature = "proto-ipv4")]
@@ -40,46 +132,187 @@ const IPV6_DEFAULT: IpCidr =
     IpCidr::Ipv6(Ipv6Cidr::new(Ipv6Address::new(0, 0, 0, 0, 0, 0, 0, 0), 0));
 
 impl Route {
-    /// Returns a route to 0.0.0.0/0 via the `gateway`, with no expiry.
-    #[cfg(feature = "proto-ipv4")]
-    pub fn new_ipv4_gateway(gateway: Ipv4Address) -> Route {
+    /// Returns a route to `cidr` via `via_router`, with no expiry.
+    pub fn new(cidr: IpCidr, via_router: IpAddress) -> Route {
         Route {
-            cidr: IPV4_DEFAULT,
-            via_router: gateway.into(),
+            cidr,
+            via_router: Some(via_router),
+            metric: 0,
             preferred_until: None,
             expires_at: None,
+            table: Routes::MAIN_TABLE,
         }
     }
 
-    /// Returns a route to ::/0 via the `gateway`, with no expiry.
-    #[cfg(feature = "proto-ipv6")]
-    pub fn new_ipv6_gateway(gateway: Ipv6Address) -> Route {
+    /// Returns a route to the neighbors in `cidr`, with no expiry.
+    pub fn new_on_link(cidr: IpCidr) -> Route {
         Route {
-            cidr: IPV6_DEFAULT,
-            via_router: gateway.into(),
+            cidr,
+            via_router: None,
+            metric: 0,
             preferred_until: None,
             expires_at: None,
+            table: Routes::MAIN_TABLE,
         }
     }
+
+    /// Returns a route to 0.0.0.0/0 via the `gateway`, with no expiry.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn new_ipv4_gateway(gateway: Ipv4Address) -> Route {
+        Route::new(IPV4_DEFAULT, gateway.into())
+    }
+
+    /// Returns a route to ::/0 via the `gateway`, with no expiry.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn new_ipv6_gateway(gateway: Ipv6Address) -> Route {
+        Route::new(IPV6_DEFAULT, gateway.into())
+    }
+
+    fn is_expired(&self, timestamp: Instant) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| timestamp > expires_at)
+    }
+}
+
+/// Index of a [`Node`] in [`Trie::nodes`], or of a route in [`Routes::storage`].
+type Index = u32;
+
+const NONE: Index = Index::MAX;
+
+/// A node of the prefix tree indexing the routes.
+///
+/// The tree is a binary trie whose chains of nodes with a single child are
+/// compressed into one node: every node either has routes, or two children. A
+/// lookup follows the bits of the address from the root, and goes through at most
+/// one node for each bit.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    /// The prefix of the node, left-aligned, with the bits past `len` cleared.
+    bits: u128,
+    len: u8,
+    /// The nodes for the longer prefixes continuing with a 0 or a 1 bit.
+    children: [Index; 2],
+    /// The routes to the prefix, `start..end` in the routes indexed.
+    start: Index,
+    end: Index,
 }
 
-/// A routing table.
+/// The routing tables of an interface, and the rules selecting them.
+///
+/// Lookups use the most specific route to an address, whatever the number of
+/// routes. The tables are re-indexed on every change, which is meant to be
+/// infrequent.
 #[derive(Debug)]
 pub struct Routes {
+    /// The routes, ordered by table, prefix, then metric.
     storage: Vec<Route, IFACE_MAX_ROUTE_COUNT>,
+    /// There are at most as many tables as routes, and two nodes for each route.
+    trie: Trie<IFACE_MAX_ROUTE_COUNT, { 2 * IFACE_MAX_ROUTE_COUNT }>,
+    rules: Vec<RouteRule, IFACE_MAX_ROUTE_RULE_COUNT>,
+    on_change: Option<fn(RouteChange)>,
 }
 
 impl Routes {
+    /// The table used when no [`RouteRule`] applies.
+    pub const MAIN_TABLE: u8 = 0;
+
     /// Creates a new empty routing table.
     pub fn new() -> Self {
         Self {
             storage: Vec::new(),
+            trie: Trie::new(),
+            rules: Vec::new(),
+            on_change: None,
         }
     }
 
+    /// Set the function called with each route added to, or removed from the table.
+    ///
+    /// It is not called when a route expires, as expired routes are only ignored.
+    pub fn set_on_change(&mut self, on_change: Option<fn(RouteChange)>) {
+        self.on_change = on_change;
+    }
+
+    /// Return an iterator over the routes, ordered by table, prefix, then metric.
+    pub fn iter(&self) -> impl Iterator<Item = &Route> + '_ {
+        self.storage.iter()
+    }
+
+    /// Return the rules selecting the table to look routes up in.
+    pub fn rules(&self) -> &[RouteRule] {
+        &self.rules
+    }
+
+    /// Update the rules selecting the table to look routes up in.
+    pub fn update_rules<F: FnOnce(&mut Vec<RouteRule, IFACE_MAX_ROUTE_RULE_COUNT>)>(
+        &mut self,
+        f: F,
+    ) {
+        f(&mut self.rules);
+    }
+
     /// Update the routes of this node.
+    ///
+    /// The function set with [`set_on_change`](Self::set_on_change) is called
+    /// with the routes that changed. Without the `alloc` feature, there is no room
+    /// to keep the previous routes to compare with, and all of them are reported
+    /// removed, then all the new ones added.
     pub fn update<F: FnOnce(&mut Vec<Route, IFACE_MAX_ROUTE_COUNT>)>(&mut self, f: F) {
-        f(&mut self.storage);
+        match self.on_change {
+            // The table can be too large for a copy on the stack.
+            #[cfg(feature = "alloc")]
+            Some(on_change) => {
+                let old = self.storage.to_vec();
+                f(&mut self.storage);
+                for route in old.iter().filter(|route| !self.storage.contains(route)) {
+                    on_change(RouteChange::Removed(*route));
+                }
+                for route in self.storage.iter().filter(|route| !old.contains(route)) {
+                    on_change(RouteChange::Added(*route));
+                }
+            }
+            #[cfg(not(feature = "alloc"))]
+            Some(on_change) => {
+                for route in self.storage.iter() {
+                    on_change(RouteChange::Removed(*route));
+                }
+                f(&mut self.storage);
+                for route in self.storage.iter() {
+                    on_change(RouteChange::Added(*route));
+                }
+            }
+            None => f(&mut self.storage),
+        }
+        self.reindex();
+    }
+
+    /// Add `route`, replacing the route of the same table to the same prefix via
+    /// the same router, if any (ie. "ip route replace").
+    ///
+    /// On success, returns the replaced route, if any.
+    pub fn add_route(&mut self, route: Route) -> Result<Option<Route>, RouteTableFull> {
+        let old = self.remove_route(route.table, route.cidr, route.via_router);
+        self.storage.push(route).map_err(|_| RouteTableFull)?;
+        self.notify(RouteChange::Added(route));
+        self.reindex();
+        Ok(old)
+    }
+
+    /// Remove the route of `table` to `cidr` via `via_router`, or the on-link one
+    /// if `None`.
+    ///
+    /// Returns the removed route, if any.
+    pub fn remove_route(
+        &mut self,
+        table: u8,
+        cidr: IpCidr,
+        via_router: Option<IpAddress>,
+    ) -> Option<Route> {
+        let i = self
+            .storage
+            .iter()
+            .position(|r| r.table == table && r.cidr == cidr && r.via_router == via_router)?;
+        Some(self.remove(i))
     }
 
     /// Add a default ipv4 gateway (ie. "ip route add 0.0.0.0/0 via `gateway`").
@@ -91,9 +324,7 @@ impl Routes {
         gateway: Ipv4Address,
     ) -> Result<Option<Route>, RouteTableFull> {
         let old = self.remove_default_ipv4_route();
-        self.storage
-            .push(Route::new_ipv4_gateway(gateway))
-            .map_err(|_| RouteTableFull)?;
+        self.add_route(Route::new_ipv4_gateway(gateway))?;
         Ok(old)
     }
 
@@ -106,9 +337,7 @@ impl Routes {
         gateway: Ipv6Address,
     ) -> Result<Option<Route>, RouteTableFull> {
         let old = self.remove_default_ipv6_route();
-        self.storage
-            .push(Route::new_ipv6_gateway(gateway))
-            .map_err(|_| RouteTableFull)?;
+        self.add_route(Route::new_ipv6_gateway(gateway))?;
         Ok(old)
     }
 
@@ -117,16 +346,11 @@ impl Routes {
     /// On success, returns the previous default route, if any.
     #[cfg(feature = "proto-ipv4")]
     pub fn remove_default_ipv4_route(&mut self) -> Option<Route> {
-        if let Some((i, _)) = self
+        let i = self
             .storage
             .iter()
-            .enumerate()
-            .find(|(_, r)| r.cidr == IPV4_DEFAULT)
-        {
-            Some(self.storage.remove(i))
-        } else {
-            None
-        }
+            .position(|r| r.table == Self::MAIN_TABLE && r.cidr == IPV4_DEFAULT)?;
+        Some(self.remove(i))
     }
 
     /// Remove the default ipv6 gateway
@@ -134,36 +358,279 @@ impl Routes {
     /// On success, returns the previous default route, if any.
     #[cfg(feature = "proto-ipv6")]
     pub fn remove_default_ipv6_route(&mut self) -> Option<Route> {
-        if let Some((i, _)) = self
+        let i = self
             .storage
             .iter()
-            .enumerate()
-            .find(|(_, r)| r.cidr == IPV6_DEFAULT)
-        {
-            Some(self.storage.remove(i))
-        } else {
-            None
+            .position(|r| r.table == Self::MAIN_TABLE && r.cidr == IPV6_DEFAULT)?;
+        Some(self.remove(i))
+    }
+
+    fn remove(&mut self, i: usize) -> Route {
+        let route = self.storage.remove(i);
+        self.notify(RouteChange::Removed(route));
+        self.reindex();
+        route
+    }
+
+    fn notify(&self, change: RouteChange) {
+        if let Some(on_change) = self.on_change {
+            on_change(change);
         }
     }
 
+    /// Return the next hop to `addr`: the router of the route to it, or `addr`
+    /// itself if it is on-link.
     pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
+        self.lookup_flow(addr, &Flow::default(), timestamp)
+    }
+
+    /// Return the next hop to `addr` for the packets of `flow`.
+    pub(crate) fn lookup_flow(
+        &self,
+        addr: &IpAddress,
+        flow: &Flow,
+        timestamp: Instant,
+    ) -> Option<IpAddress> {
+        self.lookup_route(addr, flow, timestamp)
+            .map(|route| route.via_router.unwrap_or(*addr))
+    }
+
+    /// Return the most specific route to `addr` that has not expired at `timestamp`,
+    /// in the table selected by the rules for `flow`.
+    pub(crate) fn lookup_route(
+        &self,
+        addr: &IpAddress,
+        flow: &Flow,
+        timestamp: Instant,
+    ) -> Option<&Route> {
         assert!(addr.is_unicast());
 
-        self.storage
+        self.rules
             .iter()
-            // Keep only matching routes
-            .filter(|route| {
-                if let Some(expires_at) = route.expires_at {
-                    if timestamp > expires_at {
-                        return false;
-                    }
-                }
-                route.cidr.contains_addr(addr)
-            })
-            // pick the most specific one (highest prefix_len)
-            .max_by_key(|route| route.cidr.prefix_len())
-            .map(|route| route.via_router)
+            .filter(|rule| rule.matches(flow))
+            .find_map(|rule| self.lookup_table(rule.table, addr, timestamp))
+            .or_else(|| self.lookup_table(Self::MAIN_TABLE, addr, timestamp))
+    }
+
+    /// Return the most specific route of `table` to `addr`.
+    fn lookup_table(&self, table: u8, addr: &IpAddress, timestamp: Instant) -> Option<&Route> {
+        self.trie.lookup(&self.storage, table, addr, timestamp)
+    }
+
+    /// Sort the routes and rebuild the prefix tree after a change.
+    fn reindex(&mut self) {
+        self.storage.sort_unstable_by_key(order);
+        self.trie.rebuild(&self.storage);
+    }
+}
+
+/// The prefix trees of the routes ordered by table then prefix, see [`Node`],
+/// with room for `TABLES` tables and `NODES` nodes.
+#[derive(Debug)]
+struct Trie<const TABLES: usize, const NODES: usize> {
+    /// Each prefix takes a node, and at most one more where it branches off.
+    nodes: Vec<Node, NODES>,
+    /// The root nodes of each table, for each address family.
+    roots: Vec<(u8, [Index; 2]), TABLES>,
+}
+
+impl<const TABLES: usize, const NODES: usize> Trie<TABLES, NODES> {
+    const fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    /// Return the most specific route of `table` to `addr` among `routes`.
+    ///
+    /// Among several routes with the lowest metric, the one used is picked by a
+    /// hash of `addr`, so that all the packets to a destination, and the resolution
+    /// of its next hop, take the same path.
+    fn lookup<'a>(
+        &self,
+        routes: &'a [Route],
+        table: u8,
+        addr: &IpAddress,
+        timestamp: Instant,
+    ) -> Option<&'a Route> {
+        let (family, bits) = key(addr);
+        let max_len = max_prefix_len(family);
+        let mut best = None;
+        let mut index = self.root(table)[family];
+        while index != NONE {
+            let node = &self.nodes[index as usize];
+            if mask(bits, node.len) != node.bits {
+                break;
+            }
+            // Routes to longer prefixes are found further down, and win.
+            let routes = &routes[node.start as usize..node.end as usize];
+            if let Some(route) = select(routes, flow_hash(bits), timestamp) {
+                best = Some(route);
+            }
+            if node.len == max_len {
+                break;
+            }
+            index = node.children[bit(bits, node.len)];
+        }
+        best
+    }
+
+    /// Rebuild the tree for `routes`.
+    fn rebuild(&mut self, routes: &[Route]) {
+        self.nodes.clear();
+        self.roots.clear();
+        let mut start = 0;
+        while start < routes.len() {
+            let Route { table, cidr, .. } = routes[start];
+            let end = start
+                + routes[start..]
+                    .iter()
+                    .take_while(|route| route.table == table && same_prefix(&route.cidr, &cidr))
+                    .count();
+            if self.roots.last().is_none_or(|(last, _)| *last != table) {
+                self.roots.push((table, [NONE; 2])).unwrap();
+            }
+            self.insert(&cidr, start as Index, end as Index);
+            start = end;
+        }
+    }
+
+    /// Return the root nodes of `table`.
+    fn root(&self, table: u8) -> [Index; 2] {
+        self.roots
+            .iter()
+            .find(|(id, _)| *id == table)
+            .map_or([NONE; 2], |(_, root)| *root)
     }
+
+    /// Insert a node for `cidr`, with the routes `start..end`, in the prefix tree
+    /// of the last table.
+    fn insert(&mut self, cidr: &IpCidr, start: Index, end: Index) {
+        let (family, bits) = key(&cidr.address());
+        let len = cidr.prefix_len();
+        let bits = mask(bits, len);
+        let mut leaf = Node {
+            bits,
+            len,
+            children: [NONE; 2],
+            start,
+            end,
+        };
+
+        // The parent and the side of the link to the current node, `None` for a root.
+        let mut link: Option<(Index, usize)> = None;
+        let roots = &mut self.roots.last_mut().unwrap().1;
+        let mut index = roots[family];
+        let new = loop {
+            if index == NONE {
+                break self.push(leaf);
+            }
+            let node = self.nodes[index as usize];
+            let common = (node.bits ^ bits)
+                .leading_zeros()
+                .min(node.len.min(len) as u32) as u8;
+
+            if common == node.len && common == len {
+                // A node branching off at that prefix is already there.
+                let node = &mut self.nodes[index as usize];
+                node.start = start;
+                node.end = end;
+                return;
+            } else if common == node.len {
+                let side = bit(bits, node.len);
+                link = Some((index, side));
+                index = node.children[side];
+            } else if common == len {
+                leaf.children[bit(node.bits, len)] = index;
+                break self.push(leaf);
+            } else {
+                let leaf_index = self.push(leaf);
+                let mut branch = Node {
+                    bits: mask(bits, common),
+                    len: common,
+                    children: [NONE; 2],
+                    start: 0,
+                    end: 0,
+                };
+                branch.children[bit(bits, common)] = leaf_index;
+                branch.children[bit(node.bits, common)] = index;
+                break self.push(branch);
+            }
+        };
+
+        match link {
+            Some((parent, side)) => self.nodes[parent as usize].children[side] = new,
+            None => self.roots.last_mut().unwrap().1[family] = new,
+        }
+    }
+
+    fn push(&mut self, node: Node) -> Index {
+        let index = self.nodes.len() as Index;
+        self.nodes.push(node).unwrap();
+        index
+    }
+}
+
+/// Pick the route to use among `routes`, all to the same prefix and ordered by
+/// metric, for a flow with `hash`.
+fn select(routes: &[Route], hash: u32, timestamp: Instant) -> Option<&Route> {
+    let mut live = routes.iter().filter(|route| !route.is_expired(timestamp));
+    let metric = live.clone().next()?.metric;
+    let count = live
+        .clone()
+        .take_while(|route| route.metric == metric)
+        .count();
+    live.nth(hash as usize % count)
+}
+
+/// Return the key ordering the routes by table, prefix, then metric.
+fn order(route: &Route) -> (u8, (usize, u128, u8), u32, Option<IpAddress>) {
+    let (family, bits) = key(&route.cidr.address());
+    let len = route.cidr.prefix_len();
+    let prefix = (family, mask(bits, len), len);
+    (route.table, prefix, route.metric, route.via_router)
+}
+
+fn same_prefix(a: &IpCidr, b: &IpCidr) -> bool {
+    let (a_family, a_bits) = key(&a.address());
+    let (b_family, b_bits) = key(&b.address());
+    a_family == b_family
+        && a.prefix_len() == b.prefix_len()
+        && mask(a_bits, a.prefix_len()) == mask(b_bits, b.prefix_len())
+}
+
+/// Return the index of the address family of `addr`, and its bits, left-aligned.
+fn key(addr: &IpAddress) -> (usize, u128) {
+    match addr {
+        #[cfg(feature = "proto-ipv4")]
+        IpAddress::Ipv4(addr) => (0, (addr.to_bits() as u128) << 96),
+        #[cfg(feature = "proto-ipv6")]
+        IpAddress::Ipv6(addr) => (1, addr.to_bits()),
+    }
+}
+
+fn max_prefix_len(family: usize) -> u8 {
+    if family == 0 { 32 } else { 128 }
+}
+
+/// Clear the bits of `bits` past the first `len` ones.
+fn mask(bits: u128, len: u8) -> u128 {
+    match len {
+        0 => 0,
+        len => bits & (u128::MAX << (128 - len as u32)),
+    }
+}
+
+/// Return the bit of `bits` at position `index`, counting from the left.
+fn bit(bits: u128, index: u8) -> usize {
+    (bits >> (127 - index as u32)) as usize & 1
+}
+
+/// Hash the bits of a destination address, to spread destinations across routes.
+fn flow_hash(bits: u128) -> u32 {
+    let bits = (bits ^ (bits >> 64)) as u64;
+    (bits.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) as u32
 }
 
 #[cfg(test)]
@@ -232,9 +699,11 @@ mod test {
 
         let route = Route {
             cidr: cidr_1().into(),
-            via_router: ADDR_1A.into(),
+            via_router: Some(ADDR_1A.into()),
+            metric: 0,
             preferred_until: None,
             expires_at: None,
+            table: Routes::MAIN_TABLE,
         };
         routes.update(|storage| {
             storage.push(route).unwrap();
@@ -263,9 +732,11 @@ mod test {
 
         let route2 = Route {
             cidr: cidr_2().into(),
-            via_router: ADDR_2A.into(),
+            via_router: Some(ADDR_2A.into()),
+            metric: 0,
             preferred_until: Some(Instant::from_millis(10)),
             expires_at: Some(Instant::from_millis(10)),
+            table: Routes::MAIN_TABLE,
         };
         routes.update(|storage| {
             storage.push(route2).unwrap();
@@ -313,4 +784,265 @@ mod test {
             Some(ADDR_2A.into())
         );
     }
+
+    #[cfg(feature = "proto-ipv4")]
+    fn cidr(a: u8, b: u8, c: u8, prefix_len: u8) -> IpCidr {
+        IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::new(a, b, c, 0), prefix_len))
+    }
+
+    #[cfg(feature = "proto-ipv4")]
+    fn addr(a: u8, b: u8, c: u8, d: u8) -> IpAddress {
+        IpAddress::Ipv4(Ipv4Address::new(a, b, c, d))
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_longest_prefix_match() {
+        let now = Instant::from_millis(0);
+        let mut routes = Routes::new();
+        routes
+            .add_route(Route::new(cidr(10, 0, 0, 8), addr(192, 0, 2, 8)))
+            .unwrap();
+        routes
+            .add_route(Route::new(cidr(10, 1, 2, 24), addr(192, 0, 2, 24)))
+            .unwrap();
+        routes
+            .add_route(Route::new(cidr(10, 1, 0, 16), addr(192, 0, 2, 16)))
+            .unwrap();
+        routes
+            .add_route(Route::new(cidr(10, 2, 0, 16), addr(192, 0, 2, 17)))
+            .unwrap();
+
+        assert_eq!(
+            routes.lookup(&addr(10, 1, 2, 3), now),
+            Some(addr(192, 0, 2, 24))
+        );
+        assert_eq!(
+            routes.lookup(&addr(10, 1, 3, 3), now),
+            Some(addr(192, 0, 2, 16))
+        );
+        assert_eq!(
+            routes.lookup(&addr(10, 2, 2, 3), now),
+            Some(addr(192, 0, 2, 17))
+        );
+        assert_eq!(
+            routes.lookup(&addr(10, 3, 2, 3), now),
+            Some(addr(192, 0, 2, 8))
+        );
+        assert_eq!(routes.lookup(&addr(11, 1, 2, 3), now), None);
+
+        // Routes are listed by prefix.
+        let prefixes: std::vec::Vec<_> = routes.iter().map(|route| route.cidr).collect();
+        assert_eq!(
+            prefixes,
+            [
+                cidr(10, 0, 0, 8),
+                cidr(10, 1, 0, 16),
+                cidr(10, 1, 2, 24),
+                cidr(10, 2, 0, 16)
+            ]
+        );
+
+        // An expired route leaves the less specific ones.
+        routes.update(|storage| storage[2].expires_at = Some(now));
+        let later = now + crate::time::Duration::from_millis(1);
+        assert_eq!(
+            routes.lookup(&addr(10, 1, 2, 3), later),
+            Some(addr(192, 0, 2, 16))
+        );
+
+        assert!(
+            routes
+                .remove_route(
+                    Routes::MAIN_TABLE,
+                    cidr(10, 1, 0, 16),
+                    Some(addr(192, 0, 2, 16))
+                )
+                .is_some()
+        );
+        assert_eq!(
+            routes.lookup(&addr(10, 1, 2, 3), now),
+            Some(addr(192, 0, 2, 24))
+        );
+        assert_eq!(
+            routes.lookup(&addr(10, 1, 3, 3), now),
+            Some(addr(192, 0, 2, 8))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_longest_prefix_match_random() {
+        use std::boxed::Box;
+
+        const COUNT: usize = 4096;
+        let now = Instant::from_millis(0);
+        let later = now + crate::time::Duration::from_millis(1);
+        let mut rand = crate::rand::Rand::new(1234);
+        // Few values for each octet under 10.0.0.0/8, so that prefixes nest.
+        let random_addr = |rand: &mut crate::rand::Rand| {
+            Ipv4Address::from_bits(0x0a00_0000 | (rand.rand_u32() & 0x0083_c183))
+        };
+
+        let mut routes = std::vec::Vec::new();
+        for _ in 0..COUNT {
+            let prefix_len = 8 + (rand.rand_u32() % 25) as u8;
+            let mut route = Route::new(
+                IpCidr::Ipv4(Ipv4Cidr::new(random_addr(&mut rand), prefix_len)),
+                addr(192, 0, 2, rand.rand_u32() as u8),
+            );
+            route.table = (rand.rand_u32() % 3) as u8;
+            route.metric = rand.rand_u32() % 4;
+            if rand.rand_u32() % 8 == 0 {
+                route.expires_at = Some(now);
+            }
+            routes.push(route);
+        }
+        routes.sort_unstable_by_key(order);
+        let mut trie = Box::new(Trie::<COUNT, { 2 * COUNT }>::new());
+        trie.rebuild(&routes);
+
+        for _ in 0..2000 {
+            let dst = IpAddress::Ipv4(random_addr(&mut rand));
+            let table = (rand.rand_u32() % 4) as u8;
+            let matching = || {
+                routes.iter().filter(|route| {
+                    route.table == table
+                        && !route.is_expired(later)
+                        && route.cidr.contains_addr(&dst)
+                })
+            };
+            let prefix_len = matching().map(|route| route.cidr.prefix_len()).max();
+            let metric = matching()
+                .filter(|route| Some(route.cidr.prefix_len()) == prefix_len)
+                .map(|route| route.metric)
+                .min();
+
+            let found = trie.lookup(&routes, table, &dst, later);
+            assert_eq!(found.map(|route| route.cidr.prefix_len()), prefix_len);
+            assert_eq!(found.map(|route| route.metric), metric);
+            if let Some(found) = found {
+                assert!(matching().any(|route| route == found));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_metric_and_multipath() {
+        let now = Instant::from_millis(0);
+        let mut routes = Routes::new();
+        for (router, metric) in [(1, 10), (2, 10), (3, 5)] {
+            let mut route = Route::new(cidr(10, 0, 0, 8), addr(192, 0, 2, router));
+            route.metric = metric;
+            routes.add_route(route).unwrap();
+        }
+        routes
+            .add_route(Route::new_on_link(cidr(10, 1, 0, 16)))
+            .unwrap();
+
+        // The lowest metric wins.
+        for host in 0..16 {
+            assert_eq!(
+                routes.lookup(&addr(10, 0, 0, host), now),
+                Some(addr(192, 0, 2, 3))
+            );
+        }
+
+        // Destinations are spread across the routes with the same metric.
+        routes.remove_route(
+            Routes::MAIN_TABLE,
+            cidr(10, 0, 0, 8),
+            Some(addr(192, 0, 2, 3)),
+        );
+        let via = |host| routes.lookup(&addr(10, 0, 0, host), now).unwrap();
+        assert!((0..16).any(|host| via(host) == addr(192, 0, 2, 1)));
+        assert!((0..16).any(|host| via(host) == addr(192, 0, 2, 2)));
+
+        // On-link destinations are their own next hop.
+        assert_eq!(
+            routes.lookup(&addr(10, 1, 0, 1), now),
+            Some(addr(10, 1, 0, 1))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_on_change() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static ADDED: AtomicUsize = AtomicUsize::new(0);
+        static REMOVED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut routes = Routes::new();
+        routes.set_on_change(Some(|change| {
+            match change {
+                RouteChange::Added(_) => ADDED.fetch_add(1, Ordering::Relaxed),
+                RouteChange::Removed(_) => REMOVED.fetch_add(1, Ordering::Relaxed),
+            };
+        }));
+        routes
+            .add_default_ipv4_route(Ipv4Address::new(192, 0, 2, 1))
+            .unwrap();
+        routes
+            .add_default_ipv4_route(Ipv4Address::new(192, 0, 2, 2))
+            .unwrap();
+        routes.update(|storage| storage[0].metric = 1);
+
+        assert_eq!(ADDED.load(Ordering::Relaxed), 3);
+        assert_eq!(REMOVED.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_rules() {
+        let now = Instant::from_millis(0);
+        let mut routes = Routes::new();
+        routes
+            .add_default_ipv4_route(Ipv4Address::new(192, 0, 2, 1))
+            .unwrap();
+        for (table, router) in [(1, 2), (2, 3)] {
+            let mut route = Route::new(cidr(10, 0, 0, 8), addr(192, 0, 2, router));
+            route.table = table;
+            routes.add_route(route).unwrap();
+        }
+        routes.update_rules(|rules| {
+            rules.push(RouteRule::from_mark(7, 1)).unwrap();
+            rules
+                .push(RouteRule {
+                    src: Some(cidr(198, 51, 100, 24)),
+                    protocol: Some(IpProtocol::Udp),
+                    mark: None,
+                    table: 2,
+                })
+                .unwrap();
+        });
+
+        let flow = |src_addr, protocol, mark| Flow {
+            src_addr: Some(src_addr),
+            protocol: Some(protocol),
+            mark,
+        };
+        let src_addr = addr(198, 51, 100, 1);
+        let dst_addr = addr(10, 1, 2, 3);
+        assert_eq!(
+            routes.lookup_flow(&dst_addr, &flow(src_addr, IpProtocol::Tcp, 7), now),
+            Some(addr(192, 0, 2, 2))
+        );
+        assert_eq!(
+            routes.lookup_flow(&dst_addr, &flow(src_addr, IpProtocol::Udp, 0), now),
+            Some(addr(192, 0, 2, 3))
+        );
+        assert_eq!(
+            routes.lookup_flow(&dst_addr, &flow(src_addr, IpProtocol::Tcp, 0), now),
+            Some(addr(192, 0, 2, 1))
+        );
+        assert_eq!(routes.lookup(&dst_addr, now), Some(addr(192, 0, 2, 1)));
+
+        // The main table is used when the selected one has no route.
+        assert_eq!(
+            routes.lookup_flow(&addr(11, 1, 2, 3), &flow(src_addr, IpProtocol::Tcp, 7), now),
+            Some(addr(192, 0, 2, 1))
+        );
+    }
 }
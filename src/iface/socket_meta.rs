@@ -1,4 +1,5 @@
 use super::SocketHandle;
+use super::route::Flow;
 use crate::{
     socket::PollAt,
     time::{Duration, Instant},
@@ -15,10 +16,12 @@ enum NeighborState {
     /// Socket can be polled immediately.
     #[default]
     Active,
-    /// Socket should not be polled until either `silent_until` passes or
-    /// `neighbor` appears in the neighbor cache.
+    /// Socket should not be polled until either `silent_until` passes or the
+    /// next hop to `neighbor` for the packets of `flow` appears in the neighbor
+    /// cache.
     Waiting {
         neighbor: IpAddress,
+        flow: Flow,
         silent_until: Instant,
     },
 }
@@ -42,31 +45,34 @@ impl Meta {
     /// Minimum delay between neighbor discovery requests for this particular
     /// socket, in milliseconds.
     ///
-    /// See also `iface::NeighborCache::SILENT_TIME`.
+    /// See also `iface::NeighborCache::RETRANS_TIMER`.
     pub(crate) const DISCOVERY_SILENT_TIME: Duration = Duration::from_millis(1_000);
 
     pub(crate) fn poll_at<F>(&self, socket_poll_at: PollAt, has_neighbor: F) -> PollAt
     where
-        F: Fn(IpAddress) -> bool,
+        F: Fn(IpAddress, &Flow) -> bool,
     {
         match self.neighbor_state {
             NeighborState::Active => socket_poll_at,
-            NeighborState::Waiting { neighbor, .. } if has_neighbor(neighbor) => socket_poll_at,
+            NeighborState::Waiting { neighbor, flow, .. } if has_neighbor(neighbor, &flow) => {
+                socket_poll_at
+            }
             NeighborState::Waiting { silent_until, .. } => PollAt::Time(silent_until),
         }
     }
 
     pub(crate) fn egress_permitted<F>(&mut self, timestamp: Instant, has_neighbor: F) -> bool
     where
-        F: Fn(IpAddress) -> bool,
+        F: Fn(IpAddress, &Flow) -> bool,
     {
         match self.neighbor_state {
             NeighborState::Active => true,
             NeighborState::Waiting {
                 neighbor,
+                flow,
                 silent_until,
             } => {
-                if has_neighbor(neighbor) {
+                if has_neighbor(neighbor, &flow) {
                     net_trace!(
                         "{}: neighbor {} discovered, unsilencing",
                         self.handle,
@@ -88,7 +94,7 @@ impl Meta {
         }
     }
 
-    pub(crate) fn neighbor_missing(&mut self, timestamp: Instant, neighbor: IpAddress) {
+    pub(crate) fn neighbor_missing(&mut self, timestamp: Instant, neighbor: IpAddress, flow: Flow) {
         net_trace!(
             "{}: neighbor {} missing, silencing until t+{}",
             self.handle,
@@ -97,6 +103,7 @@ impl Meta {
         );
         self.neighbor_state = NeighborState::Waiting {
             neighbor,
+            flow,
             silent_until: timestamp + Self::DISCOVERY_SILENT_TIME,
         };
     }
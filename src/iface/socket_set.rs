@@ -234,7 +234,7 @@ mod tests {
             .item_mut_at(handle.index())
             .unwrap()
             .meta
-            .neighbor_missing(Instant::from_millis(0), gateway);
+            .neighbor_missing(Instant::from_millis(0), gateway, Default::default());
 
         let mut activated = alloc::vec::Vec::new();
         assert_eq!(
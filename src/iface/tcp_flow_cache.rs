@@ -417,7 +417,7 @@ fn mix_address(mut hash: u64, address: IpAddress) -> u64 {
 }
 
 #[inline(always)]
-fn flow_hash(key: &TcpFlowKey) -> u64 {
+pub(super) fn flow_hash(key: &TcpFlowKey) -> u64 {
     let hash = mix_address(0x517c_c1b7_2722_0a95, key.local_addr);
     let hash = mix_hash(hash, u64::from(key.local_port));
     let hash = mix_address(hash, key.remote_addr);
@@ -0,0 +1,399 @@
+// TIME-WAIT connections handed over by TCP sockets.
+//
+// A connection closed on our side first stays in the TIME-WAIT state for a while,
+// to acknowledge the FIN of the remote again if our ACK was lost, and to keep old
+// duplicate segments from being taken for a new connection on the same 4-tuple.
+// None of this needs the buffers of a socket, so once a socket has nothing left to
+// deliver, the interface keeps the little state it needs here, and the socket can
+// be used again right away.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::tcp_flow_cache::{TcpFlowKey, flow_hash};
+use crate::socket::tcp::{CLOSE_DELAY, Socket, SocketBuffer, TimeWait};
+use crate::time::Instant;
+use crate::wire::{IpRepr, TcpControl, TcpRepr};
+
+/// A fixed-size table of connections in the TIME-WAIT state.
+#[derive(Debug)]
+pub(crate) struct TcpTimeWait {
+    slots: Vec<Option<TimeWait>>,
+    capacity: usize,
+    len: usize,
+}
+
+impl TcpTimeWait {
+    /// Create a table of at most `capacity` connections. A table of no connection
+    /// never takes any over.
+    pub(crate) fn new(capacity: usize) -> Self {
+        let slot_count = if capacity == 0 {
+            0
+        } else {
+            capacity
+                .checked_mul(2)
+                .and_then(usize::checked_next_power_of_two)
+                .unwrap_or_else(|| capacity.next_power_of_two())
+        };
+        Self {
+            slots: vec![None; slot_count],
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Return the number of connections in the table, expired or not.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Query whether a connection can be taken over at `timestamp`, dropping the
+    /// expired ones if the table is full.
+    pub(crate) fn has_room(&mut self, timestamp: Instant) -> bool {
+        if self.len == self.capacity {
+            self.expire(timestamp);
+        }
+        self.len < self.capacity
+    }
+
+    /// Take over `time_wait`, replacing any connection on the same 4-tuple. The
+    /// table must have room for it.
+    pub(crate) fn insert(&mut self, time_wait: TimeWait) {
+        let key = TcpFlowKey::new(time_wait.local, time_wait.remote);
+        self.remove_key(&key);
+        debug_assert!(self.len < self.capacity);
+
+        let mut index = self.home(&key);
+        while self.slots[index].is_some() {
+            index = self.next(index);
+        }
+        net_trace!(
+            "TIME-WAIT: taking over {} -> {}",
+            time_wait.local,
+            time_wait.remote
+        );
+        self.slots[index] = Some(time_wait);
+        self.len += 1;
+    }
+
+    /// Process a segment received at `timestamp`.
+    ///
+    /// Returns `None` if the segment doesn't belong to a connection in the table, and
+    /// should be handed to the sockets. Otherwise, returns the ACK to reply with, if
+    /// any.
+    pub(crate) fn process(
+        &mut self,
+        timestamp: Instant,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+    ) -> Option<Option<(IpRepr, TcpRepr<'static>)>> {
+        if self.len == 0 {
+            return None;
+        }
+        let key = TcpFlowKey::from_incoming(ip_repr, repr);
+        let index = self.find_slot(&key)?;
+        // NOTE(unwrap): the slot was just found.
+        let time_wait = self.slots[index].as_mut().unwrap();
+
+        if timestamp >= time_wait.expires_at {
+            self.remove_slot(index);
+            return None;
+        }
+
+        match repr.control {
+            // RFC 1337: a reset doesn't end the TIME-WAIT state, or anyone could cut it
+            // short, and let old duplicates into a new connection on the 4-tuple.
+            TcpControl::Rst => {
+                net_trace!("TIME-WAIT: ignoring RST");
+                return Some(None);
+            }
+            // RFC 1122 section 4.2.2.13: a new connection may be opened from the
+            // TIME-WAIT state, if its sequence numbers are past the old ones.
+            TcpControl::Syn
+                if repr.ack_number.is_none() && repr.seq_number > time_wait.remote_seq_no =>
+            {
+                net_trace!("TIME-WAIT: new connection");
+                self.remove_slot(index);
+                return None;
+            }
+            // The remote didn't get our ACK, and sent its FIN again.
+            TcpControl::Fin => time_wait.expires_at = timestamp + CLOSE_DELAY,
+            _ => {}
+        }
+
+        // Acknowledge anything but a bare ACK, and take no data: retransmitted
+        // segments and old duplicates are rejected alike.
+        if repr.control == TcpControl::None && repr.payload.is_empty() {
+            return Some(None);
+        }
+        let (mut ip_reply_repr, mut reply_repr) = Socket::<SocketBuffer<'_>>::reply(ip_repr, repr);
+        reply_repr.seq_number = time_wait.local_seq_no;
+        reply_repr.ack_number = Some(time_wait.remote_seq_no);
+        reply_repr.window_len = time_wait.window_len;
+        reply_repr.timestamp = repr
+            .timestamp
+            .and_then(|tcp_ts| tcp_ts.generate_reply(time_wait.tsval_generator));
+        ip_reply_repr.set_payload_len(reply_repr.buffer_len());
+        Some(Some((ip_reply_repr, reply_repr)))
+    }
+
+    /// Drop the connections that left the TIME-WAIT state at `timestamp`.
+    fn expire(&mut self, timestamp: Instant) {
+        let mut index = 0;
+        while index < self.slots.len() {
+            match self.slots[index] {
+                // Removing an entry may move another one into its slot.
+                Some(time_wait) if timestamp >= time_wait.expires_at => self.remove_slot(index),
+                _ => index += 1,
+            }
+        }
+    }
+
+    fn remove_key(&mut self, key: &TcpFlowKey) {
+        if let Some(index) = self.find_slot(key) {
+            self.remove_slot(index);
+        }
+    }
+
+    fn remove_slot(&mut self, mut hole: usize) {
+        self.slots[hole] = None;
+        self.len -= 1;
+        let mut scan = self.next(hole);
+        while let Some(time_wait) = self.slots[scan] {
+            let home = self.home(&TcpFlowKey::new(time_wait.local, time_wait.remote));
+            if self.probe_distance(home, scan) > self.probe_distance(home, hole) {
+                self.slots[hole] = Some(time_wait);
+                self.slots[scan] = None;
+                hole = scan;
+            }
+            scan = self.next(scan);
+        }
+    }
+
+    fn find_slot(&self, key: &TcpFlowKey) -> Option<usize> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let mut index = self.home(key);
+        loop {
+            match self.slots[index] {
+                Some(time_wait) if TcpFlowKey::new(time_wait.local, time_wait.remote) == *key => {
+                    return Some(index);
+                }
+                Some(_) => index = self.next(index),
+                None => return None,
+            }
+        }
+    }
+
+    fn home(&self, key: &TcpFlowKey) -> usize {
+        flow_hash(key) as usize & (self.slots.len() - 1)
+    }
+
+    fn next(&self, index: usize) -> usize {
+        (index + 1) & (self.slots.len() - 1)
+    }
+
+    fn probe_distance(&self, home: usize, index: usize) -> usize {
+        index.wrapping_sub(home) & (self.slots.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::time::Duration;
+    #[cfg(all(feature = "proto-ipv4", not(feature = "proto-ipv6")))]
+    use crate::wire::ipv4::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2};
+    #[cfg(feature = "proto-ipv6")]
+    use crate::wire::ipv6::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2};
+    use crate::wire::{IpEndpoint, IpProtocol, TcpSeqNumber};
+
+    const LOCAL_SEQ: TcpSeqNumber = TcpSeqNumber(10_000);
+    const REMOTE_SEQ: TcpSeqNumber = TcpSeqNumber(-10_000);
+    const EXPIRES_AT: Instant = Instant::from_millis_const(10_000);
+
+    fn local() -> IpEndpoint {
+        IpEndpoint::new(MOCK_IP_ADDR_1.into(), 80)
+    }
+
+    fn remote(port: u16) -> IpEndpoint {
+        IpEndpoint::new(MOCK_IP_ADDR_2.into(), port)
+    }
+
+    fn time_wait(port: u16) -> TimeWait {
+        TimeWait {
+            local: local(),
+            remote: remote(port),
+            local_seq_no: LOCAL_SEQ,
+            remote_seq_no: REMOTE_SEQ,
+            window_len: 64,
+            tsval_generator: None,
+            expires_at: EXPIRES_AT,
+        }
+    }
+
+    fn segment(
+        port: u16,
+        control: TcpControl,
+        seq_number: TcpSeqNumber,
+    ) -> (IpRepr, TcpRepr<'static>) {
+        let repr = TcpRepr {
+            src_port: port,
+            dst_port: local().port,
+            control,
+            ece: false,
+            cwr: false,
+            seq_number,
+            ack_number: (control != TcpControl::Syn).then_some(LOCAL_SEQ),
+            window_len: 256,
+            window_scale: None,
+            max_seg_size: None,
+            sack_permitted: false,
+            sack_ranges: [None, None, None],
+            timestamp: None,
+            fast_open: None,
+            auth: None,
+            payload: &[],
+        };
+        let ip_repr = IpRepr::new(
+            remote(port).addr,
+            local().addr,
+            IpProtocol::Tcp,
+            repr.buffer_len(),
+            64,
+        );
+        (ip_repr, repr)
+    }
+
+    fn process(
+        table: &mut TcpTimeWait,
+        timestamp: Instant,
+        (ip_repr, repr): (IpRepr, TcpRepr),
+    ) -> Option<Option<(IpRepr, TcpRepr<'static>)>> {
+        table.process(timestamp, &ip_repr, &repr)
+    }
+
+    fn assert_ack(reply: Option<Option<(IpRepr, TcpRepr)>>) {
+        let Some(Some((_, repr))) = reply else {
+            panic!("expected an ACK, got {reply:?}");
+        };
+        assert_eq!(repr.control, TcpControl::None);
+        assert_eq!(repr.seq_number, LOCAL_SEQ);
+        assert_eq!(repr.ack_number, Some(REMOTE_SEQ));
+        assert_eq!(repr.window_len, 64);
+    }
+
+    #[test]
+    fn test_retransmitted_fin() {
+        let mut table = TcpTimeWait::new(4);
+        assert!(table.has_room(Instant::ZERO));
+        table.insert(time_wait(1));
+
+        let now = Instant::from_millis(5_000);
+        assert_eq!(
+            process(&mut table, now, segment(2, TcpControl::Fin, REMOTE_SEQ - 1)),
+            None
+        );
+        assert_ack(process(
+            &mut table,
+            now,
+            segment(1, TcpControl::Fin, REMOTE_SEQ - 1),
+        ));
+
+        // The FIN restarted the timer.
+        let later = EXPIRES_AT + Duration::from_millis(1);
+        assert_eq!(
+            process(&mut table, later, segment(1, TcpControl::None, REMOTE_SEQ)),
+            Some(None)
+        );
+        let expiry = now + CLOSE_DELAY;
+        assert_eq!(
+            process(&mut table, expiry, segment(1, TcpControl::None, REMOTE_SEQ)),
+            None
+        );
+        assert_eq!(table.len(), 0);
+    }
+
+    #[test]
+    fn test_old_duplicate() {
+        let mut table = TcpTimeWait::new(4);
+        table.insert(time_wait(1));
+
+        let (ip_repr, mut repr) = segment(1, TcpControl::None, REMOTE_SEQ - 100);
+        repr.payload = b"abcdef";
+        assert_ack(table.process(Instant::ZERO, &ip_repr, &repr));
+        // An old SYN is acknowledged as well.
+        assert_ack(process(
+            &mut table,
+            Instant::ZERO,
+            segment(1, TcpControl::Syn, REMOTE_SEQ - 100),
+        ));
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_new_connection() {
+        let mut table = TcpTimeWait::new(4);
+        table.insert(time_wait(1));
+        assert_eq!(
+            process(
+                &mut table,
+                Instant::ZERO,
+                segment(1, TcpControl::Syn, REMOTE_SEQ + 1)
+            ),
+            None
+        );
+        assert_eq!(table.len(), 0);
+    }
+
+    #[test]
+    fn test_rst() {
+        let mut table = TcpTimeWait::new(4);
+        table.insert(time_wait(1));
+        // Resets are dropped, in the window or out of it.
+        for seq_number in [REMOTE_SEQ + 1000, REMOTE_SEQ] {
+            assert_eq!(
+                process(
+                    &mut table,
+                    Instant::ZERO,
+                    segment(1, TcpControl::Rst, seq_number)
+                ),
+                Some(None)
+            );
+            assert_eq!(table.len(), 1);
+        }
+        // The entry still acknowledges a retransmitted FIN.
+        assert_ack(process(
+            &mut table,
+            Instant::ZERO,
+            segment(1, TcpControl::Fin, REMOTE_SEQ - 1),
+        ));
+    }
+
+    #[test]
+    fn test_full() {
+        let mut table = TcpTimeWait::new(2);
+        table.insert(time_wait(1));
+        table.insert(time_wait(2));
+        // The same 4-tuple replaces the old entry.
+        table.insert(time_wait(2));
+        assert_eq!(table.len(), 2);
+        assert!(!table.has_room(Instant::ZERO));
+
+        // Expired entries make room.
+        assert!(table.has_room(EXPIRES_AT));
+        assert_eq!(table.len(), 0);
+
+        let mut table = TcpTimeWait::new(0);
+        assert!(!table.has_room(Instant::ZERO));
+        assert_eq!(
+            process(
+                &mut table,
+                Instant::ZERO,
+                segment(1, TcpControl::Fin, REMOTE_SEQ)
+            ),
+            None
+        );
+    }
+}
@@ -132,6 +132,7 @@ compile_error!("You must enable at most one of the following features: defmt, lo
 
 #[macro_use]
 mod macros;
+mod crypto;
 mod parsers;
 mod rand;
 
@@ -146,13 +147,19 @@ pub mod config {
     pub const IFACE_MAX_ADDR_COUNT: usize = 8;
     pub const IFACE_MAX_MULTICAST_GROUP_COUNT: usize = 4;
     pub const IFACE_MAX_ROUTE_COUNT: usize = 4;
+    pub const IFACE_MAX_ROUTE_RULE_COUNT: usize = 4;
     pub const IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT: usize = 4;
     pub const IFACE_NEIGHBOR_CACHE_COUNT: usize = 3;
+    pub const IFACE_PATH_MTU_CACHE_COUNT: usize = 2;
     pub const REASSEMBLY_BUFFER_COUNT: usize = 4;
     pub const REASSEMBLY_BUFFER_SIZE: usize = 1500;
     pub const RPL_RELATIONS_BUFFER_COUNT: usize = 16;
     pub const RPL_PARENTS_BUFFER_COUNT: usize = 8;
     pub const IPV6_HBH_MAX_OPTIONS: usize = 4;
+    pub const TCP_SACK_SCOREBOARD_COUNT: usize = 4;
+    pub const TCP_RACK_SEGMENT_COUNT: usize = 4;
+    pub const TCP_RATE_SEGMENT_COUNT: usize = 4;
+    pub const TCP_FAST_OPEN_CACHE_COUNT: usize = 2;
 }
 
 #[cfg(not(test))]
@@ -144,7 +144,8 @@ pub use self::tuntap_interface::TunTapInterface;
 /// the packet metadata mechanism didn't exist at all.
 ///
 /// Currently only UDP sockets allow setting/retrieving packet metadata. The metadata
-/// for packets emitted with other sockets will be all default values.
+/// for packets emitted with other sockets will be all default values, except for
+/// the mark of TCP sockets.
 ///
 /// This struct is marked as `#[non_exhaustive]`. This means it is not possible to
 /// create it directly by specifying all fields. You have to instead create it with
@@ -164,6 +165,11 @@ pub use self::tuntap_interface::TunTapInterface;
 pub struct PacketMeta {
     #[cfg(feature = "packetmeta-id")]
     pub id: u32,
+    /// A mark selecting the routing table of the packet, see
+    /// [`RouteRule::mark`](crate::iface::RouteRule::mark). UDP and TCP sockets set it
+    /// to their own mark, if the packet has none.
+    #[cfg(feature = "packetmeta-mark")]
+    pub mark: u32,
 }
 
 /// A description of checksum behavior for a particular protocol.
@@ -325,7 +325,7 @@ mod tests {
     #[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
     #[test]
     fn test_tracer_packet_display_ip() {
-        use crate::wire::{IpProtocol, Ipv4Address, Ipv4Repr};
+        use crate::wire::{IpEcn, IpProtocol, Ipv4Address, Ipv4Repr};
 
         let repr = Ipv4Repr {
             src_addr: Ipv4Address::new(10, 0, 0, 1),
@@ -333,6 +333,7 @@ mod tests {
             next_header: IpProtocol::Unknown(255),
             payload_len: 0,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         };
 
         let mut buffer = vec![0_u8; repr.buffer_len()];
@@ -6,7 +6,7 @@ use crate::time::{Duration, Instant};
 use crate::wire::dhcpv4::field as dhcpv4_field;
 use crate::wire::{
     DHCP_CLIENT_PORT, DHCP_MAX_DNS_SERVER_COUNT, DHCP_SERVER_PORT, DhcpMessageType, DhcpPacket,
-    DhcpRepr, IpAddress, IpProtocol, Ipv4Address, Ipv4AddressExt, Ipv4Cidr, Ipv4Repr,
+    DhcpRepr, IpAddress, IpEcn, IpProtocol, Ipv4Address, Ipv4AddressExt, Ipv4Cidr, Ipv4Repr,
     UDP_HEADER_LEN, UdpRepr,
 };
 use crate::wire::{DhcpOption, HardwareAddress};
@@ -612,6 +612,7 @@ impl<'a> Socket<'a> {
             next_header: IpProtocol::Udp,
             payload_len: 0, // filled right before emit
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         };
 
         match &mut self.state {
@@ -911,6 +912,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     };
 
     const IP_BROADCAST_ADDRESSED: Ipv4Repr = Ipv4Repr {
@@ -919,6 +921,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     };
 
     const IP_SERVER_BROADCAST: Ipv4Repr = Ipv4Repr {
@@ -927,6 +930,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     };
 
     const IP_RECV: Ipv4Repr = Ipv4Repr {
@@ -935,6 +939,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     };
 
     const IP_SEND: Ipv4Repr = Ipv4Repr {
@@ -943,6 +948,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     };
 
     const UDP_SEND: UdpRepr = UdpRepr {
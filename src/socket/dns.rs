@@ -6,6 +6,7 @@ use heapless::Vec;
 use managed::ManagedSlice;
 
 use crate::config::{DNS_MAX_NAME_SIZE, DNS_MAX_RESULT_COUNT, DNS_MAX_SERVER_COUNT};
+use crate::iface::Flow;
 use crate::socket::{Context, PollAt};
 use crate::time::{Duration, Instant};
 use crate::wire::dns::{Flags, Opcode, Packet, Question, Rcode, Record, RecordData, Repr, Type};
@@ -616,7 +617,11 @@ impl<'a> Socket<'a> {
                 };
 
                 let dst_addr = servers[pq.server_idx];
-                let src_addr = match cx.get_source_address(&dst_addr) {
+                let flow = Flow {
+                    protocol: Some(IpProtocol::Udp),
+                    ..Flow::default()
+                };
+                let src_addr = match cx.get_source_address_flow(&dst_addr, &flow) {
                     Some(src_addr) => src_addr,
                     None => {
                         net_trace!("no source address for destination {}", dst_addr);
@@ -2,6 +2,8 @@ use core::cmp;
 #[cfg(feature = "async")]
 use core::task::Waker;
 
+#[cfg(feature = "proto-ipv4")]
+use crate::iface::Flow;
 use crate::phy::ChecksumCapabilities;
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
@@ -13,7 +15,7 @@ use crate::wire::IcmpRepr;
 use crate::wire::{Icmpv4Packet, Icmpv4Repr, Ipv4Repr};
 #[cfg(feature = "proto-ipv6")]
 use crate::wire::{Icmpv6Packet, Icmpv6Repr, Ipv6Repr};
-use crate::wire::{IpAddress, IpListenEndpoint, IpProtocol, IpRepr};
+use crate::wire::{IpAddress, IpEcn, IpListenEndpoint, IpProtocol, IpRepr};
 use crate::wire::{UdpPacket, UdpRepr};
 
 /// Error returned by [`Socket::bind`]
@@ -572,7 +574,11 @@ impl<'a> Socket<'a> {
             match *remote_endpoint {
                 #[cfg(feature = "proto-ipv4")]
                 IpAddress::Ipv4(dst_addr) => {
-                    let src_addr = match cx.get_source_address_ipv4(&dst_addr) {
+                    let flow = Flow {
+                        protocol: Some(IpProtocol::Icmp),
+                        ..Flow::default()
+                    };
+                    let src_addr = match cx.get_source_address_ipv4_flow(&dst_addr, &flow) {
                         Some(addr) => addr,
                         None => {
                             net_trace!(
@@ -599,6 +605,7 @@ impl<'a> Socket<'a> {
                         next_header: IpProtocol::Icmp,
                         payload_len: repr.buffer_len(),
                         hop_limit,
+                        ecn: IpEcn::NotEct,
                     });
                     emit(cx, (ip_repr, IcmpRepr::Ipv4(repr)))
                 }
@@ -628,6 +635,7 @@ impl<'a> Socket<'a> {
                         next_header: IpProtocol::Icmpv6,
                         payload_len: repr.buffer_len(),
                         hop_limit,
+                        ecn: IpEcn::NotEct,
                     });
                     emit(cx, (ip_repr, IcmpRepr::Ipv6(repr)))
                 }
@@ -707,6 +715,7 @@ mod test_ipv4 {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 0x40,
+        ecn: IpEcn::NotEct,
     });
 
     static REMOTE_IPV4_REPR: Ipv4Repr = Ipv4Repr {
@@ -715,6 +724,7 @@ mod test_ipv4 {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 0x40,
+        ecn: IpEcn::NotEct,
     };
 
     #[test]
@@ -813,6 +823,7 @@ mod test_ipv4 {
                         next_header: IpProtocol::Icmp,
                         payload_len: ECHOV4_REPR.buffer_len(),
                         hop_limit: 0x2a,
+                        ecn: IpEcn::NotEct,
                     })
                 );
                 Ok::<_, ()>(())
@@ -910,6 +921,7 @@ mod test_ipv4 {
                 next_header: IpProtocol::Icmp,
                 payload_len: 12,
                 hop_limit: 0x40,
+                ecn: IpEcn::NotEct,
             },
             data,
         };
@@ -919,6 +931,7 @@ mod test_ipv4 {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 0x40,
+            ecn: IpEcn::NotEct,
         };
 
         assert!(!socket.can_recv());
@@ -968,6 +981,7 @@ mod test_ipv6 {
         next_header: IpProtocol::Icmpv6,
         payload_len: 24,
         hop_limit: 0x40,
+        ecn: IpEcn::NotEct,
     };
 
     static REMOTE_IPV6_REPR: Ipv6Repr = Ipv6Repr {
@@ -976,6 +990,7 @@ mod test_ipv6 {
         next_header: IpProtocol::Icmpv6,
         payload_len: 24,
         hop_limit: 0x40,
+        ecn: IpEcn::NotEct,
     };
 
     #[test]
@@ -1074,6 +1089,7 @@ mod test_ipv6 {
                         next_header: IpProtocol::Icmpv6,
                         payload_len: ECHOV6_REPR.buffer_len(),
                         hop_limit: 0x2a,
+                        ecn: IpEcn::NotEct,
                     })
                 );
                 Ok::<_, ()>(())
@@ -1202,6 +1218,7 @@ mod test_ipv6 {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 12,
                 hop_limit: 0x40,
+                ecn: IpEcn::NotEct,
             },
             data,
         };
@@ -1211,6 +1228,7 @@ mod test_ipv6 {
             next_header: IpProtocol::Icmpv6,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 0x40,
+            ecn: IpEcn::NotEct,
         };
 
         assert!(!socket.can_recv());
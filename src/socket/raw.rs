@@ -483,7 +483,7 @@ mod test {
     use rstest::*;
 
     use super::*;
-    use crate::wire::IpRepr;
+    use crate::wire::{IpEcn, IpRepr};
     #[cfg(feature = "proto-ipv4")]
     use crate::wire::{Ipv4Address, Ipv4Repr};
     #[cfg(feature = "proto-ipv6")]
@@ -516,6 +516,7 @@ mod test {
             next_header: IpProtocol::Unknown(IP_PROTO),
             payload_len: 4,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         });
         pub const PACKET_BYTES: [u8; 24] = [
             0x45, 0x00, 0x00, 0x18, 0x00, 0x00, 0x40, 0x00, 0x40, 0x3f, 0x00, 0x00, 0x0a, 0x00,
@@ -547,6 +548,7 @@ mod test {
             next_header: IpProtocol::Unknown(IP_PROTO),
             payload_len: 4,
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         });
 
         pub const PACKET_BYTES: [u8; 44] = [
@@ -931,6 +933,7 @@ mod test {
                 next_header: proto,
                 payload_len: 4,
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             });
             assert!(socket.accepts(&header_repr));
         }
@@ -942,6 +945,7 @@ mod test {
                 next_header: proto,
                 payload_len: 4,
                 hop_limit: 64,
+                ecn: IpEcn::NotEct,
             });
             assert!(socket.accepts(&header_repr));
         }
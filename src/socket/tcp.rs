@@ -8,22 +8,49 @@ use core::task::Waker;
 use core::marker::PhantomData;
 use core::{fmt, mem};
 
+use managed::{Managed, ManagedSlice};
+
 #[cfg(feature = "latency-probe")]
 use crate::latency_probe;
 
+use crate::iface::Flow;
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
 use crate::socket::{Context, PollAt};
-use crate::storage::{Assembler, RingBuffer, SocketBufferT};
+use crate::storage::{Assembler, Full, RingBuffer, SocketBufferT};
 #[cfg(feature = "alloc")]
 use crate::storage::LinearBuffer;
 use crate::time::{Duration, Instant};
 use crate::wire::{
-    IpAddress, IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, TCP_HEADER_LEN, TcpControl,
-    TcpRepr, TcpSeqNumber, TcpTimestampGenerator, TcpTimestampRepr,
+    IpAddress, IpEcn, IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, TCP_HEADER_LEN, TcpControl,
+    TcpFastOpenCookie, TcpRepr, TcpSeqNumber, TcpTimestampGenerator, TcpTimestampRepr,
 };
 
-mod congestion;
+mod ack_policy;
+mod auth;
+mod autotune;
+pub mod congestion;
+mod fast_open;
+mod info;
+mod listener;
+mod lossy;
+mod plpmtud;
+mod rack;
+mod rate;
+mod repair;
+mod sack;
+mod syn_cookie;
+mod tx_chunk;
+
+pub use self::ack_policy::AckPolicy;
+pub use self::auth::Authentication;
+pub(crate) use self::fast_open::FastOpen;
+pub use self::info::TcpInfo;
+pub use self::listener::{Listener, PoolError};
+pub use self::lossy::{LossyRecord, RecvGap};
+pub use self::repair::{RepairError, RepairState};
+pub(crate) use self::syn_cookie::{SynCookie, SynCookies};
+pub use self::tx_chunk::{TxChunk, TxChunkSlot};
 
 macro_rules! tcp_trace {
     ($($arg:expr),*) => (net_log!(trace, $($arg),*));
@@ -87,6 +114,26 @@ impl Display for SendError {
 #[cfg(feature = "std")]
 impl std::error::Error for SendError {}
 
+/// Error returned by [`Socket::send_chunk`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SendChunkError {
+    InvalidState,
+    Full,
+}
+
+impl Display for SendChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SendChunkError::InvalidState => write!(f, "invalid state"),
+            SendChunkError::Full => write!(f, "chunk queue full"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SendChunkError {}
+
 /// Error returned by [`Socket::recv`]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -116,12 +163,15 @@ impl std::error::Error for RecvError {}
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum LossyTailCommitError {
     InvalidState,
+    /// The segment failed authentication, see [`Socket::set_authentication`].
+    Unauthenticated,
 }
 
 impl Display for LossyTailCommitError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             LossyTailCommitError::InvalidState => write!(f, "TCP sequence space is not synchronized"),
+            LossyTailCommitError::Unauthenticated => write!(f, "segment failed authentication"),
         }
     }
 }
@@ -184,10 +234,64 @@ impl fmt::Display for State {
     }
 }
 
+/// Why a TCP socket moved to the `CLOSED` state, see [Socket::close_reason].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum CloseReason {
+    /// Both endpoints sent a FIN, and the connection was closed cleanly.
+    Graceful,
+    /// The remote endpoint sent a reset.
+    Reset,
+    /// The socket was [aborted](Socket::abort), or [closed](Socket::close) before a
+    /// connection was established.
+    Aborted,
+    /// The remote endpoint sent nothing for longer than the
+    /// [timeout](Socket::set_timeout).
+    Timeout,
+    /// The remote endpoint answered no keep-alive packet within the
+    /// [timeout](Socket::set_timeout).
+    KeepAliveTimeout,
+    /// The retransmission timer expired more times in a row than the
+    /// [RTO configuration](Socket::set_rto_config) allows.
+    RetransmissionLimit,
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CloseReason::Graceful => write!(f, "closed"),
+            CloseReason::Reset => write!(f, "reset by peer"),
+            CloseReason::Aborted => write!(f, "aborted"),
+            CloseReason::Timeout => write!(f, "timed out"),
+            CloseReason::KeepAliveTimeout => write!(f, "keep-alive timed out"),
+            CloseReason::RetransmissionLimit => write!(f, "too many retransmissions"),
+        }
+    }
+}
+
+/// A connection in the TIME-WAIT state, handed over by a socket to the interface,
+/// see [Socket::take_time_wait].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TimeWait {
+    pub(crate) local: IpEndpoint,
+    pub(crate) remote: IpEndpoint,
+    /// The sequence number past the FIN sent.
+    pub(crate) local_seq_no: TcpSeqNumber,
+    /// The sequence number past the FIN received.
+    pub(crate) remote_seq_no: TcpSeqNumber,
+    /// The window field of the ACKs to send.
+    pub(crate) window_len: u16,
+    pub(crate) tsval_generator: Option<TcpTimestampGenerator>,
+    /// When the connection leaves the TIME-WAIT state.
+    pub(crate) expires_at: Instant,
+}
+
 /// RFC 6298: (2.1) Until a round-trip time (RTT) measurement has been made for a
 /// segment sent between the sender and receiver, the sender SHOULD
 /// set RTO <- 1 second,
-const RTTE_INITIAL_RTO: u32 = 1000;
+const RTTE_INITIAL_RTO: Duration = Duration::from_millis(1000);
 
 // Minimum "safety margin" for the RTO that kicks in when the
 // variance gets very low.
@@ -198,15 +302,57 @@ const RTTE_K: u32 = 4;
 
 // RFC 6298 (2.4): Whenever RTO is computed, if it is less than 1 second, then the
 // RTO SHOULD be rounded up to 1 second.
-const RTTE_MIN_RTO: u32 = 1000;
+const RTTE_MIN_RTO: Duration = Duration::from_millis(1000);
 
 // RFC 6298 (2.5) A maximum value MAY be placed on RTO provided it is at least 60
 // seconds
-const RTTE_MAX_RTO: u32 = 60_000;
+const RTTE_MAX_RTO: Duration = Duration::from_millis(60_000);
+
+/// Retransmission timeout parameters of a socket, see
+/// [Socket::set_rto_config](Socket::set_rto_config).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RtoConfig {
+    /// The retransmission timeout until a round-trip time is measured.
+    pub initial: Duration,
+    /// The lowest retransmission timeout computed from round-trip times.
+    pub min: Duration,
+    /// The highest retransmission timeout, backoff included.
+    pub max: Duration,
+    /// The factor the retransmission timeout is multiplied by each time it expires.
+    pub backoff: u32,
+    /// How many times in a row the retransmission timer may expire before the
+    /// connection is aborted, or `None` to retransmit until the
+    /// [timeout](Socket::set_timeout) expires.
+    pub max_retransmissions: Option<u8>,
+}
 
+impl RtoConfig {
+    /// The parameters recommended by RFC 6298: an initial timeout of 1 second, at
+    /// least 1 second and at most 60 seconds, doubled on expiry, with no limit on
+    /// retransmissions.
+    pub const DEFAULT: RtoConfig = RtoConfig {
+        initial: RTTE_INITIAL_RTO,
+        min: RTTE_MIN_RTO,
+        max: RTTE_MAX_RTO,
+        backoff: 2,
+        max_retransmissions: None,
+    };
+}
+
+impl Default for RtoConfig {
+    fn default() -> Self {
+        RtoConfig::DEFAULT
+    }
+}
+
+/// Round-trip time estimator of a TCP socket, as described in RFC 6298.
+///
+/// Congestion controllers receive a reference to it in
+/// [`Controller::on_ack`](congestion::Controller::on_ack).
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-struct RttEstimator {
+pub struct RttEstimator {
     /// true if we have made at least one rtt measurement.
     have_measurement: bool,
     // Using u32 instead of Duration to save space (Duration is i64)
@@ -216,31 +362,90 @@ struct RttEstimator {
     rttvar: u32,
     /// Retransmission Time-Out
     rto: u32,
+    /// The most recent RTT sample.
+    latest: Option<u32>,
     timestamp: Option<(Instant, TcpSeqNumber)>,
     max_seq_sent: Option<TcpSeqNumber>,
     rto_count: u8,
+    config: RtoConfig,
 }
 
 impl Default for RttEstimator {
     fn default() -> Self {
+        Self::new(RtoConfig::DEFAULT)
+    }
+}
+
+impl RttEstimator {
+    fn new(config: RtoConfig) -> Self {
         Self {
             have_measurement: false,
             srtt: 0,   // ignored, will be overwritten on first measurement.
             rttvar: 0, // ignored, will be overwritten on first measurement.
-            rto: RTTE_INITIAL_RTO,
+            rto: config.initial.total_millis() as u32,
+            latest: None,
             timestamp: None,
             max_seq_sent: None,
             rto_count: 0,
+            config,
         }
     }
-}
 
-impl RttEstimator {
-    fn retransmission_timeout(&self) -> Duration {
+    /// Replace the parameters of the retransmission timeout, and bring the current
+    /// timeout within the new bounds.
+    fn set_config(&mut self, config: RtoConfig) {
+        self.config = config;
+        self.rto = if self.have_measurement || self.rto_count > 0 {
+            self.rto.clamp(self.min_rto(), self.max_rto())
+        } else {
+            config.initial.total_millis() as u32
+        };
+    }
+
+    /// Take over the estimate of a socket restored from a checkpoint.
+    fn restore(&mut self, rtt: Option<(Duration, Duration)>, rto: Duration) {
+        if let Some((srtt, rttvar)) = rtt {
+            self.have_measurement = true;
+            self.srtt = srtt.total_millis() as u32;
+            self.rttvar = rttvar.total_millis() as u32;
+        }
+        self.rto = (rto.total_millis() as u32).clamp(self.min_rto(), self.max_rto());
+    }
+
+    fn min_rto(&self) -> u32 {
+        self.config.min.total_millis() as u32
+    }
+
+    fn max_rto(&self) -> u32 {
+        self.config.max.total_millis() as u32
+    }
+
+    /// Return the retransmission timeout.
+    pub fn retransmission_timeout(&self) -> Duration {
         Duration::from_millis(self.rto as _)
     }
 
+    /// Return the smoothed round-trip time, or `None` if no RTT has been measured
+    /// yet, or the estimate was discarded after repeated retransmission timeouts.
+    pub fn smoothed_rtt(&self) -> Option<Duration> {
+        self.have_measurement
+            .then(|| Duration::from_millis(self.srtt as _))
+    }
+
+    /// Return the round-trip time variation, or `None` if no RTT has been measured
+    /// yet, or the estimate was discarded after repeated retransmission timeouts.
+    pub fn rtt_variance(&self) -> Option<Duration> {
+        self.have_measurement
+            .then(|| Duration::from_millis(self.rttvar as _))
+    }
+
+    /// Return the most recent round-trip time sample, or `None` if no RTT has been measured yet.
+    pub fn latest_rtt(&self) -> Option<Duration> {
+        self.latest.map(|rtt| Duration::from_millis(rtt as _))
+    }
+
     fn sample(&mut self, new_rtt: u32) {
+        self.latest = Some(new_rtt);
         if self.have_measurement {
             // RFC 6298 (2.3) When a subsequent RTT measurement R' is made, a host MUST set (...)
             let diff = (self.srtt as i32 - new_rtt as i32).unsigned_abs();
@@ -255,7 +460,7 @@ impl RttEstimator {
 
         // RFC 6298 (2.2), (2.3)
         let margin = RTTE_MIN_MARGIN.max(self.rttvar * RTTE_K);
-        self.rto = (self.srtt + margin).clamp(RTTE_MIN_RTO, RTTE_MAX_RTO);
+        self.rto = (self.srtt + margin).clamp(self.min_rto(), self.max_rto());
 
         self.rto_count = 0;
 
@@ -291,6 +496,15 @@ impl RttEstimator {
         }
     }
 
+    fn on_fast_retransmit(&mut self) {
+        // Karn's algorithm: an acknowledgement may now be for either transmission,
+        // but unlike a retransmission timeout this is no reason to back off.
+        if self.timestamp.is_some() {
+            tcp_trace!("rtte: abort sampling due to fast retransmit");
+        }
+        self.timestamp = None;
+    }
+
     fn on_retransmit(&mut self) {
         if self.timestamp.is_some() {
             tcp_trace!("rtte: abort sampling due to retransmit");
@@ -300,8 +514,11 @@ impl RttEstimator {
         // RFC 6298 (5.5) The host MUST set RTO <- RTO * 2 ("back off the timer").  The
         // maximum value discussed in (2.5) above may be used to provide
         // an upper bound to this doubling operation.
-        self.rto = (self.rto * 2).min(RTTE_MAX_RTO);
-        tcp_trace!("rtte: doubling rto to {:?}", self.rto);
+        self.rto = self
+            .rto
+            .saturating_mul(self.config.backoff)
+            .min(self.max_rto());
+        tcp_trace!("rtte: backing off rto to {:?}", self.rto);
 
         // RFC 6298: a TCP implementation MAY clear SRTT and RTTVAR after
         // backing off the timer multiple times as it is likely that the current
@@ -327,6 +544,9 @@ enum Timer {
         expires_at: Instant,
     },
     FastRetransmit,
+    TailLossProbe {
+        expires_at: Instant,
+    },
     ZeroWindowProbe {
         expires_at: Instant,
         delay: Duration,
@@ -337,7 +557,10 @@ enum Timer {
 }
 
 const ACK_DELAY_DEFAULT: Duration = Duration::from_millis(10);
-const CLOSE_DELAY: Duration = Duration::from_millis(10_000);
+/// The worst case delayed ACK timer of the remote, added to the tail loss probe
+/// timeout when a single segment is in flight (RFC 8985 `WCDelAckT`).
+const TLP_WORST_CASE_ACK_DELAY: Duration = Duration::from_millis(200);
+pub(crate) const CLOSE_DELAY: Duration = Duration::from_millis(10_000);
 
 impl Timer {
     fn new() -> Timer {
@@ -363,6 +586,13 @@ impl Timer {
         }
     }
 
+    fn should_tail_loss_probe(&self, timestamp: Instant) -> bool {
+        match *self {
+            Timer::TailLossProbe { expires_at } if timestamp >= expires_at => true,
+            _ => false,
+        }
+    }
+
     fn should_close(&self, timestamp: Instant) -> bool {
         match *self {
             Timer::Close { expires_at } if timestamp >= expires_at => true,
@@ -388,6 +618,7 @@ impl Timer {
             Timer::ZeroWindowProbe { expires_at, .. } => PollAt::Time(expires_at),
             Timer::Retransmit { expires_at, .. } => PollAt::Time(expires_at),
             Timer::FastRetransmit => PollAt::Now,
+            Timer::TailLossProbe { expires_at } => PollAt::Time(expires_at),
             Timer::Close { expires_at } => PollAt::Time(expires_at),
         }
     }
@@ -417,6 +648,7 @@ impl Timer {
             Timer::Idle { .. }
             | Timer::FastRetransmit
             | Timer::Retransmit { .. }
+            | Timer::TailLossProbe { .. }
             | Timer::ZeroWindowProbe { .. } => {
                 *self = Timer::Retransmit {
                     expires_at: timestamp + delay,
@@ -426,6 +658,21 @@ impl Timer {
         }
     }
 
+    fn set_for_tail_loss_probe(&mut self, timestamp: Instant, delay: Duration) {
+        match *self {
+            Timer::Idle { .. }
+            | Timer::FastRetransmit
+            | Timer::Retransmit { .. }
+            | Timer::TailLossProbe { .. }
+            | Timer::ZeroWindowProbe { .. } => {
+                *self = Timer::TailLossProbe {
+                    expires_at: timestamp + delay,
+                }
+            }
+            Timer::Close { .. } => (),
+        }
+    }
+
     fn set_for_fast_retransmit(&mut self) {
         *self = Timer::FastRetransmit
     }
@@ -443,9 +690,9 @@ impl Timer {
         }
     }
 
-    fn rewind_zero_window_probe(&mut self, timestamp: Instant) {
+    fn rewind_zero_window_probe(&mut self, timestamp: Instant, max_delay: Duration) {
         if let Timer::ZeroWindowProbe { mut delay, .. } = *self {
-            delay = (delay * 2).min(Duration::from_millis(RTTE_MAX_RTO as _));
+            delay = (delay * 2).min(max_delay);
             *self = Timer::ZeroWindowProbe {
                 expires_at: timestamp + delay,
                 delay,
@@ -462,7 +709,14 @@ impl Timer {
     }
 
     fn is_retransmit(&self) -> bool {
-        matches!(self, Timer::Retransmit { .. } | Timer::FastRetransmit)
+        matches!(
+            self,
+            Timer::Retransmit { .. } | Timer::FastRetransmit | Timer::TailLossProbe { .. }
+        )
+    }
+
+    fn is_tail_loss_probe(&self) -> bool {
+        matches!(self, Timer::TailLossProbe { .. })
     }
 }
 
@@ -497,6 +751,42 @@ pub enum CongestionControl {
 
     #[cfg(feature = "socket-tcp-cubic")]
     Cubic,
+
+    #[cfg(feature = "socket-tcp-bbr")]
+    Bbr,
+}
+
+/// The congestion control algorithm a socket uses, see
+/// [`Socket::congestion_control`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ActiveCongestionControl {
+    /// A built-in algorithm, set with [`Socket::set_congestion_control`].
+    BuiltIn(CongestionControl),
+    /// A user-defined [`Controller`](congestion::Controller), installed with
+    /// [`Socket::set_custom_congestion_control`].
+    Custom,
+}
+
+impl PartialEq<CongestionControl> for ActiveCongestionControl {
+    fn eq(&self, other: &CongestionControl) -> bool {
+        *self == ActiveCongestionControl::BuiltIn(*other)
+    }
+}
+
+/// How a TCP socket spaces out the segments it sends.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pacing {
+    /// Send as many segments as the windows allow at once.
+    Disabled,
+
+    /// Use the pacing rate of the congestion controller, if it has one.
+    #[default]
+    Controller,
+
+    /// Send at most the given number of octets per second.
+    Fixed(u64),
 }
 
 /// A Transmission Control Protocol socket.
@@ -504,7 +794,8 @@ pub enum CongestionControl {
 /// A TCP socket may passively listen for connections or actively connect to another endpoint.
 /// Note that, for listening sockets, there is no "backlog"; to be able to simultaneously
 /// accept several connections, as many sockets must be allocated, or any new connection
-/// attempts will be reset.
+/// attempts will be reset. A [`Listener`] manages such a pool of sockets, and queues the
+/// connections they establish.
 ///
 /// # Type Parameters
 ///
@@ -515,16 +806,32 @@ pub struct Socket<'a, B: SocketBufferT<'a> = SocketBuffer<'a>> {
     state: State,
     timer: Timer,
     rtte: RttEstimator,
+    /// How many times in a row the retransmission timer expired since new data was
+    /// last acknowledged.
+    rto_retransmissions: u8,
+    /// Why the socket last moved to the `CLOSED` state, kept until the next
+    /// `connect` or `listen`.
+    close_reason: Option<CloseReason>,
     assembler: Assembler,
     rx_buffer: B,
     rx_fin_received: bool,
+    /// Whether holes in the received data are skipped, see `lossy`.
+    lossy_receive: bool,
+    recv_gaps: lossy::GapQueue<'a>,
+    /// Receive buffer autotuning, if enabled, see `autotune`.
+    rx_autotune: Option<autotune::Autotune>,
     tx_buffer: B,
+    /// Chunks sent without copying, interleaved with the data of `tx_buffer`.
+    tx_chunks: tx_chunk::TxChunkQueue<'a>,
     /// Interval after which, if no inbound packets are received, the connection is aborted.
     timeout: Option<Duration>,
     /// Interval at which keep-alive packets will be sent.
     keep_alive: Option<Duration>,
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The mark of outgoing packets, selecting their routing table.
+    #[cfg(feature = "packetmeta-mark")]
+    mark: u32,
     /// Address passed to listen(). Listen address is set when listen() is called and
     /// used every time the socket is reset back to the LISTEN state.
     listen_endpoint: IpListenEndpoint,
@@ -554,6 +861,34 @@ pub struct Socket<'a, B: SocketBufferT<'a> = SocketBuffer<'a>> {
     remote_win_scale: Option<u8>,
     /// Whether or not the remote supports selective ACK as described in RFC 2018.
     remote_has_sack: bool,
+    /// Explicit Congestion Notification enabled.
+    ecn: bool,
+    /// Whether or not ECN was negotiated with the remote, as described in RFC 3168.
+    remote_has_ecn: bool,
+    /// Whether we received a segment marked Congestion Experienced, and must set ECE
+    /// on outgoing segments until the remote sends CWR.
+    ecn_echo: bool,
+    /// Whether the next segment carrying new data must have CWR set.
+    ecn_cwr_pending: bool,
+    /// The highest sequence number sent when we last reacted to ECE. Further ECEs are
+    /// ignored until it is acknowledged.
+    ecn_recover: Option<TcpSeqNumber>,
+    /// TCP Fast Open enabled when listening, as described in RFC 7413.
+    fast_open: bool,
+    /// Whether this connection was opened with `connect_fast_open`.
+    fast_open_connect: bool,
+    /// The Fast Open cookie to hand out in our SYN|ACK.
+    fast_open_reply: Option<TcpFastOpenCookie>,
+    /// SYN cookies enabled when listening, as described in RFC 4987.
+    syn_cookies: bool,
+    /// Whether the socket listens only to open connections from SYN cookies, leaving
+    /// SYNs to the other sockets listening on its endpoint.
+    syn_cookie_standby: bool,
+    /// Segment authentication with TCP-MD5 or TCP-AO.
+    auth: auth::Authenticator,
+    /// The octets the remote has selectively acknowledged, and the state of
+    /// SACK-based loss recovery as described in RFC 6675.
+    sack_scoreboard: sack::Scoreboard,
     /// The maximum number of data octets that the remote side may receive.
     remote_mss: usize,
     /// The timestamp of the last packet received.
@@ -565,9 +900,13 @@ pub struct Socket<'a, B: SocketBufferT<'a> = SocketBuffer<'a>> {
     /// The number of packets received directly after
     /// each other which have the same ACK number.
     local_rx_dup_acks: u8,
+    /// Statistics of the connection, see `info()`.
+    counters: info::Counters,
 
-    /// Duration for Delayed ACK. If None no ACKs will be delayed.
-    ack_delay: Option<Duration>,
+    /// When to acknowledge received data, see `ack_policy`.
+    ack_policy: AckPolicy,
+    /// The state of quick-ACK mode.
+    quick_ack: ack_policy::QuickAck,
     /// Delayed ack timer. If set, packets containing exclusively
     /// ACK or window updates (ie, no data) won't be sent until expiry.
     ack_delay_timer: AckDelayTimer,
@@ -578,8 +917,25 @@ pub struct Socket<'a, B: SocketBufferT<'a> = SocketBuffer<'a>> {
     /// Nagle's Algorithm enabled.
     nagle: bool,
 
+    /// RACK-TLP time-based loss detection enabled.
+    rack_tlp: bool,
+    /// The state of RACK loss detection, as described in RFC 8985.
+    rack: rack::Rack,
+    /// Delivery rate samples for the congestion controller.
+    rate: rate::RateSampler,
+
+    /// Packetization layer path MTU discovery enabled.
+    mtu_probing: bool,
+    /// The state of path MTU discovery, as described in RFC 8899.
+    plpmtud: plpmtud::Plpmtud,
+
     /// The congestion control algorithm.
-    congestion_controller: congestion::AnyController,
+    congestion_controller: congestion::AnyController<'a>,
+
+    /// Where the pacing rate comes from.
+    pacing: Pacing,
+    /// The earliest time the next segment carrying data may be sent, if paced.
+    pacing_next: Option<Instant>,
 
     /// tsval generator - if some, tcp timestamp is enabled
     tsval_generator: Option<TcpTimestampGenerator>,
@@ -587,6 +943,9 @@ pub struct Socket<'a, B: SocketBufferT<'a> = SocketBuffer<'a>> {
     /// 0 if not seen or timestamp not enabled
     last_remote_tsval: u32,
 
+    /// 0 if not sent or timestamp not enabled
+    last_local_tsval: u32,
+
     #[cfg(feature = "async")]
     rx_waker: WakerRegistration,
     #[cfg(feature = "async")]
@@ -626,13 +985,21 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             state: State::Closed,
             timer: Timer::new(),
             rtte: RttEstimator::default(),
+            rto_retransmissions: 0,
+            close_reason: None,
             assembler: Assembler::new(),
             tx_buffer,
+            tx_chunks: tx_chunk::TxChunkQueue::new(),
             rx_buffer,
             rx_fin_received: false,
+            lossy_receive: false,
+            recv_gaps: lossy::GapQueue::new(),
+            rx_autotune: None,
             timeout: None,
             keep_alive: None,
             hop_limit: None,
+            #[cfg(feature = "packetmeta-mark")]
+            mark: 0,
             listen_endpoint: IpListenEndpoint::default(),
             tuple: None,
             local_seq_no: TcpSeqNumber::default(),
@@ -644,18 +1011,40 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             remote_win_shift: rx_cap_log2.saturating_sub(16) as u8,
             remote_win_scale: None,
             remote_has_sack: false,
+            ecn: false,
+            remote_has_ecn: false,
+            ecn_echo: false,
+            ecn_cwr_pending: false,
+            ecn_recover: None,
+            fast_open: false,
+            fast_open_connect: false,
+            fast_open_reply: None,
+            syn_cookies: false,
+            syn_cookie_standby: false,
+            auth: auth::Authenticator::new(),
+            sack_scoreboard: sack::Scoreboard::new(),
             remote_mss: DEFAULT_MSS,
             remote_last_ts: None,
             local_rx_last_ack: None,
             local_rx_last_seq: None,
             local_rx_dup_acks: 0,
-            ack_delay: Some(ACK_DELAY_DEFAULT),
+            counters: info::Counters::new(),
+            ack_policy: AckPolicy::DEFAULT,
+            quick_ack: ack_policy::QuickAck::new(),
             ack_delay_timer: AckDelayTimer::Idle,
             challenge_ack_timer: Instant::from_secs(0),
             nagle: true,
+            rack_tlp: false,
+            rack: rack::Rack::new(),
+            rate: rate::RateSampler::new(),
+            mtu_probing: false,
+            plpmtud: plpmtud::Plpmtud::new(),
             tsval_generator: None,
             last_remote_tsval: 0,
+            last_local_tsval: 0,
             congestion_controller: congestion::AnyController::new(),
+            pacing: Pacing::Controller,
+            pacing_next: None,
 
             #[cfg(feature = "async")]
             rx_waker: WakerRegistration::new(),
@@ -699,6 +1088,16 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     /// * Kernel-mode code on desktop processors usually avoids FPU operations to reduce the penalty of saving and restoring FPU registers.
     ///
     /// In all these cases, `CongestionControl::Reno` is a better choice of congestion control algorithm.
+    ///
+    /// `CongestionControl::Bbr`, available with the `socket-tcp-bbr` feature, estimates the
+    /// bottleneck bandwidth and round-trip time of the path instead of reacting to loss.
+    /// It keeps the window open on paths with random loss and shallow buffers, where Reno
+    /// and Cubic shrink it, and uses only integer arithmetic. The bandwidth is estimated from
+    /// per-segment delivery rate samples, ignoring those taken while the application didn't
+    /// send enough data to fill the window.
+    ///
+    /// A user-defined algorithm is installed with
+    /// [`set_custom_congestion_control`](#method.set_custom_congestion_control) instead.
     pub fn set_congestion_control(&mut self, congestion_control: CongestionControl) {
         use congestion::*;
 
@@ -710,14 +1109,38 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
 
             #[cfg(feature = "socket-tcp-cubic")]
             CongestionControl::Cubic => AnyController::Cubic(cubic::Cubic::new()),
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            CongestionControl::Bbr => AnyController::Bbr(bbr::Bbr::new()),
+        }
+    }
+
+    /// Set a user-defined congestion control algorithm.
+    ///
+    /// The controller can be owned (a `Box<dyn Controller>`, with the `alloc` feature),
+    /// or borrowed for the lifetime of the socket (a `&'a mut dyn Controller`).
+    /// While it is installed, [`congestion_control`](#method.congestion_control)
+    /// returns `ActiveCongestionControl::Custom`.
+    ///
+    /// The socket informs the controller of its current MSS and the remote window
+    /// as soon as they are known, so it can be installed at any time.
+    pub fn set_custom_congestion_control<C>(&mut self, controller: C)
+    where
+        C: Into<Managed<'a, dyn congestion::Controller + 'static>>,
+    {
+        let mut controller = controller.into();
+        if self.remote_win_len > 0 {
+            controller.set_remote_window(self.remote_win_len);
         }
+        controller.set_mss(self.remote_mss);
+        self.congestion_controller = congestion::AnyController::Custom(controller);
     }
 
     /// Return the current congestion control algorithm.
-    pub fn congestion_control(&self) -> CongestionControl {
+    pub fn congestion_control(&self) -> ActiveCongestionControl {
         use congestion::*;
 
-        match self.congestion_controller {
+        let congestion_control = match self.congestion_controller {
             AnyController::None(_) => CongestionControl::None,
 
             #[cfg(feature = "socket-tcp-reno")]
@@ -725,7 +1148,13 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
 
             #[cfg(feature = "socket-tcp-cubic")]
             AnyController::Cubic(_) => CongestionControl::Cubic,
-        }
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(_) => CongestionControl::Bbr,
+
+            AnyController::Custom(_) => return ActiveCongestionControl::Custom,
+        };
+        ActiveCongestionControl::BuiltIn(congestion_control)
     }
 
     /// Register a waker for receive operations.
@@ -770,11 +1199,25 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         self.timeout
     }
 
+    /// Return the retransmission timeout parameters.
+    ///
+    /// See also the [set_rto_config](#method.set_rto_config) method.
+    pub fn rto_config(&self) -> RtoConfig {
+        self.rtte.config
+    }
+
     /// Return the ACK delay duration.
     ///
     /// See also the [set_ack_delay](#method.set_ack_delay) method.
     pub fn ack_delay(&self) -> Option<Duration> {
-        self.ack_delay
+        self.ack_policy.delay
+    }
+
+    /// Return the policy for acknowledging received data.
+    ///
+    /// See also the [set_ack_policy](#method.set_ack_policy) method.
+    pub fn ack_policy(&self) -> AckPolicy {
+        self.ack_policy
     }
 
     /// Return whether Nagle's Algorithm is enabled.
@@ -784,6 +1227,53 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         self.nagle
     }
 
+    /// Return whether RACK-TLP loss detection is enabled.
+    ///
+    /// See also the [set_rack_tlp_enabled](#method.set_rack_tlp_enabled) method.
+    pub fn rack_tlp_enabled(&self) -> bool {
+        self.rack_tlp
+    }
+
+    /// Return whether Explicit Congestion Notification is enabled.
+    ///
+    /// See also the [set_ecn_enabled](#method.set_ecn_enabled) method.
+    pub fn ecn_enabled(&self) -> bool {
+        self.ecn
+    }
+
+    /// Return whether packetization layer path MTU discovery is enabled.
+    ///
+    /// See also the [set_mtu_probing_enabled](#method.set_mtu_probing_enabled) method.
+    pub fn mtu_probing_enabled(&self) -> bool {
+        self.mtu_probing
+    }
+
+    /// Return the largest receive window autotuning may grow to, or `None` if
+    /// receive buffer autotuning is disabled.
+    ///
+    /// See also the [set_rx_autotuning](#method.set_rx_autotuning) method.
+    pub fn rx_autotuning(&self) -> Option<usize> {
+        self.rx_autotune.as_ref().map(|autotune| autotune.max())
+    }
+
+    /// Return where the pacing rate comes from.
+    ///
+    /// See also the [set_pacing](#method.set_pacing) method.
+    pub fn pacing(&self) -> Pacing {
+        self.pacing
+    }
+
+    /// Return the current pacing rate in octets per second, or `None` if segments
+    /// are not paced.
+    pub fn pacing_rate(&self) -> Option<u64> {
+        match self.pacing {
+            Pacing::Disabled => None,
+            Pacing::Controller => self.congestion_controller.inner().pacing_rate(),
+            Pacing::Fixed(rate) => Some(rate),
+        }
+        .filter(|&rate| rate > 0)
+    }
+
     /// Pause sending of SYN|ACK packets.
     ///
     /// When this flag is set, the socket will get stuck in `SynReceived` state without sending
@@ -799,7 +1289,38 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     /// Used in internal calculations as well as packet generation.
     #[inline]
     fn scaled_window(&self) -> u16 {
-        u16::try_from(self.rx_buffer.window() >> self.remote_win_shift).unwrap_or(u16::MAX)
+        u16::try_from(self.rx_window() >> self.remote_win_shift).unwrap_or(u16::MAX)
+    }
+
+    /// Return the receive window, in octets, limited by autotuning.
+    fn rx_window(&self) -> usize {
+        let window = self.rx_buffer.window();
+        match &self.rx_autotune {
+            Some(autotune) => window.min(autotune.window().saturating_sub(self.rx_buffer.len())),
+            None => window,
+        }
+    }
+
+    /// Return the largest receive window autotuning may grow to, which is the
+    /// capacity of the receive buffer unless it can grow.
+    fn rx_autotune_limit(&self) -> Option<usize> {
+        let max = self.rx_autotune.as_ref()?.max();
+        if self.rx_buffer.can_grow() {
+            Some(max)
+        } else {
+            Some(max.min(self.rx_buffer.capacity()))
+        }
+    }
+
+    /// Return the window scaling factor to advertise, large enough for the largest
+    /// receive window.
+    fn rx_win_shift(&self) -> u8 {
+        let max_window = self
+            .rx_autotune_limit()
+            .unwrap_or(0)
+            .max(self.rx_buffer.capacity());
+        let max_window_log2 = mem::size_of::<usize>() * 8 - max_window.leading_zeros() as usize;
+        max_window_log2.saturating_sub(16) as u8
     }
 
     /// Return the last window field value, including scaling according to RFC 1323.
@@ -836,11 +1357,58 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         self.timeout = duration
     }
 
+    /// Set the retransmission timeout parameters.
+    ///
+    /// The retransmission timeout is computed from round-trip times as described in
+    /// RFC 6298, within the bounds of `config`, and multiplied by its backoff factor
+    /// each time it expires. If the timer expires more than `max_retransmissions`
+    /// times in a row, the connection is aborted with a reset, and
+    /// [close_reason](#method.close_reason) returns
+    /// [CloseReason::RetransmissionLimit].
+    ///
+    /// By default, the parameters recommended by RFC 6298 are used, see
+    /// [RtoConfig::DEFAULT]. A minimum below 1 second suits links with a low latency.
+    ///
+    /// # Panics
+    /// This function panics if the minimum is larger than the maximum, the initial
+    /// timeout is not within them, or the backoff factor is zero.
+    pub fn set_rto_config(&mut self, config: RtoConfig) {
+        assert!(
+            config.min <= config.max,
+            "minimum RTO larger than the maximum"
+        );
+        assert!(
+            (config.min..=config.max).contains(&config.initial),
+            "initial RTO outside of the minimum and maximum"
+        );
+        assert!(config.backoff >= 1, "RTO backoff factor must be at least 1");
+        self.rtte.set_config(config);
+    }
+
     /// Set the ACK delay duration.
     ///
-    /// By default, the ACK delay is set to 10ms.
+    /// By default, the ACK delay is set to 10ms. This is a shorthand for setting the
+    /// delay of the [ACK policy](#method.set_ack_policy).
     pub fn set_ack_delay(&mut self, duration: Option<Duration>) {
-        self.ack_delay = duration
+        self.ack_policy.delay = duration
+    }
+
+    /// Set the policy for acknowledging received data.
+    ///
+    /// ACKs are delayed by up to `policy.delay`, unless `policy.every_segments` full-sized
+    /// segments are unacknowledged, or the segment received has the PSH flag set and
+    /// `policy.ack_on_push` is true. Out-of-order segments, and segments that fill a hole in
+    /// the received data, are always acknowledged at once.
+    ///
+    /// In quick-ACK mode, segments are acknowledged at once regardless of the delay. The mode
+    /// lasts for `policy.quick_ack_segments` segments, and is entered when the connection
+    /// starts, when a hole in the received data is filled, and when the first segment after
+    /// receiving no data for longer than the retransmission timeout arrives. Acknowledging
+    /// quickly at these times lets the remote grow its congestion window faster.
+    ///
+    /// See [AckPolicy::DEFAULT] for the default policy.
+    pub fn set_ack_policy(&mut self, policy: AckPolicy) {
+        self.ack_policy = policy
     }
 
     /// Enable or disable Nagle's Algorithm.
@@ -859,6 +1427,187 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         self.nagle = enabled
     }
 
+    /// Enable or disable RACK-TLP loss detection, as described in RFC 8985.
+    ///
+    /// When enabled, a segment is considered lost once a segment sent sufficiently
+    /// later has been acknowledged, rather than only after three duplicate ACKs.
+    /// This requires the remote to support selective ACK. Also, when no ACK arrives
+    /// for about two round trips, the last segment is sent again as a tail loss
+    /// probe, so that a lost tail doesn't have to wait for the retransmission timeout.
+    ///
+    /// RACK-TLP is disabled by default.
+    pub fn set_rack_tlp_enabled(&mut self, enabled: bool) {
+        self.rack_tlp = enabled
+    }
+
+    /// Enable or disable Explicit Congestion Notification, as described in RFC 3168.
+    ///
+    /// When enabled, ECN is negotiated with the remote in the handshake of the next
+    /// connection. If the remote agrees, new data is sent ECN-capable, congestion marks
+    /// set by the network are echoed back to the remote, and congestion echoed by the
+    /// remote is reported to the congestion controller, which can then slow down
+    /// before any packet is lost.
+    ///
+    /// Only the classic feedback of RFC 3168 is implemented, which signals at most
+    /// one congestion event per round trip. Accurate ECN (AccECN), which counts the
+    /// marked segments, is not negotiated.
+    ///
+    /// ECN is disabled by default.
+    pub fn set_ecn_enabled(&mut self, enabled: bool) {
+        self.ecn = enabled
+    }
+
+    /// Enable or disable packetization layer path MTU discovery, as described in
+    /// RFC 8899.
+    ///
+    /// Without it, segments are sized for the path MTU known to the interface, which
+    /// only goes down when a router reports it with ICMP. On a path that drops these
+    /// reports, full-sized segments are lost forever. When enabled, segments start
+    /// out sized for a conservative MTU of 1200 octets, and larger segments of new
+    /// data probe the path for the largest MTU it delivers. If segments stop getting
+    /// through, the socket falls back to the conservative MTU.
+    ///
+    /// Path MTU discovery is disabled by default.
+    pub fn set_mtu_probing_enabled(&mut self, enabled: bool) {
+        if self.mtu_probing != enabled {
+            self.plpmtud = plpmtud::Plpmtud::new();
+        }
+        self.mtu_probing = enabled
+    }
+
+    /// Enable or disable receive buffer autotuning, growing the receive window up to
+    /// `max_window` octets.
+    ///
+    /// Without it, the socket advertises all the free space of its receive buffer.
+    /// When enabled, the window starts out at about 50 KiB, and once per round trip
+    /// grows to about twice the octets the application read, as Linux does. If the
+    /// storage of the receive buffer is owned, the buffer grows along with the window;
+    /// otherwise the window is limited to the capacity of the buffer.
+    ///
+    /// The window scaling factor is chosen when the connection is opened, so that
+    /// windows up to `max_window` octets can be advertised. Autotuning should be
+    /// enabled before calling [listen](#method.listen) or [connect](#method.connect);
+    /// if it is enabled on an open connection, the window starts out at the capacity
+    /// of the receive buffer.
+    ///
+    /// Receive buffer autotuning is disabled by default.
+    ///
+    /// # Panics
+    /// This function panics if `max_window` is larger than 1 GiB.
+    pub fn set_rx_autotuning(&mut self, max_window: Option<usize>) {
+        #[cfg(not(target_pointer_width = "16"))] // Prevent overflow
+        if max_window.is_some_and(|max_window| max_window > (1 << 30)) {
+            panic!("receive window too large, cannot exceed 1 GiB")
+        }
+
+        self.rx_autotune = max_window.map(autotune::Autotune::new);
+        if let Some(limit) = self.rx_autotune_limit() {
+            // NOTE(unwrap): there is a limit only if autotuning is enabled.
+            let autotune = self.rx_autotune.as_mut().unwrap();
+            autotune.reset(limit);
+            if !matches!(self.state, State::Closed | State::Listen) {
+                // Never shrink a window that may already be advertised.
+                autotune.open(self.rx_buffer.capacity());
+            }
+        }
+        if matches!(self.state, State::Closed | State::Listen) {
+            self.remote_win_shift = self.rx_win_shift();
+        }
+    }
+
+    /// Return whether TCP Fast Open is enabled when listening.
+    ///
+    /// See also the [set_fast_open_enabled](#method.set_fast_open_enabled) method.
+    pub fn fast_open_enabled(&self) -> bool {
+        self.fast_open
+    }
+
+    /// Enable or disable TCP Fast Open when listening, as described in RFC 7413.
+    ///
+    /// When enabled, clients asking for a Fast Open cookie are handed one, and data
+    /// carried by a SYN with a valid cookie is accepted right away, and can be received
+    /// before the handshake completes. Cookies are derived from the client address with
+    /// a key drawn from the interface random number generator.
+    ///
+    /// To open a connection with TCP Fast Open, use
+    /// [connect_fast_open](#method.connect_fast_open) instead.
+    ///
+    /// TCP Fast Open is disabled by default.
+    pub fn set_fast_open_enabled(&mut self, enabled: bool) {
+        self.fast_open = enabled
+    }
+
+    /// Return whether SYN cookies are enabled when listening.
+    ///
+    /// See also the [set_syn_cookies_enabled](#method.set_syn_cookies_enabled) method.
+    pub fn syn_cookies_enabled(&self) -> bool {
+        self.syn_cookies
+    }
+
+    /// Enable or disable SYN cookies when listening, as described in RFC 4987.
+    ///
+    /// A SYN is taken by a socket listening on its endpoint if there is one. When
+    /// there is none, such as during a flood of SYNs that took every listening socket
+    /// to the SYN-RECEIVED state, the interface answers it on behalf of a socket that
+    /// listens, or listened, on the endpoint with SYN cookies enabled: the MSS,
+    /// window scale and SACK options of the SYN are encoded in the initial sequence
+    /// number of the SYN|ACK, and nothing is remembered about the SYN. The ACK
+    /// completing the handshake then opens the connection on any socket listening on
+    /// the endpoint with SYN cookies enabled, if it carries a valid cookie back;
+    /// a [`Listener`] keeps sockets of its pool listening for those. Other options,
+    /// such as timestamps, ECN or TCP Fast Open, are not negotiated for such
+    /// connections, and the MSS is rounded down to one of a few common values.
+    ///
+    /// Cookies are derived from the connection with a key drawn from the interface
+    /// random number generator, and expire after two to three minutes.
+    ///
+    /// SYN cookies are disabled by default.
+    pub fn set_syn_cookies_enabled(&mut self, enabled: bool) {
+        self.syn_cookies = enabled
+    }
+
+    /// Set whether the socket, while listening, only opens connections from SYN
+    /// cookies and leaves SYNs to the other sockets listening on its endpoint.
+    ///
+    /// Listening again with [listen](#method.listen) clears it.
+    pub(crate) fn set_syn_cookie_standby(&mut self, standby: bool) {
+        self.syn_cookie_standby = standby
+    }
+
+    /// Return how segments are authenticated, if they are.
+    ///
+    /// See also the [set_authentication](#method.set_authentication) method.
+    pub fn authentication(&self) -> Option<Authentication> {
+        self.auth.config()
+    }
+
+    /// Set how segments are authenticated, with TCP-MD5 (RFC 2385) or TCP-AO (RFC 5925).
+    ///
+    /// When set, every segment sent is signed, and every segment received must be
+    /// signed with the same kind of option and a valid MAC. Other segments are silently
+    /// dropped. The remote must be configured to match before connecting.
+    ///
+    /// This should be set before connecting or listening. Segments are not
+    /// authenticated by default.
+    pub fn set_authentication(&mut self, authentication: Option<Authentication>) {
+        self.auth.set_config(authentication)
+    }
+
+    /// Set where the pacing rate comes from.
+    ///
+    /// When paced, segments carrying data are spread out over time at the pacing rate,
+    /// rather than sent in a burst as large as the windows allow. Pure ACKs, and
+    /// control segments without data, are never delayed.
+    ///
+    /// By default, the pacing rate of the congestion controller is used. Of the built-in
+    /// algorithms, only `CongestionControl::Bbr` has one.
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+        if self.pacing_rate().is_none() {
+            self.pacing_next = None;
+        }
+    }
+
     /// Return the keep-alive interval.
     ///
     /// See also the [set_keep_alive](#method.set_keep_alive) method.
@@ -914,6 +1663,36 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the mark of outgoing packets.
+    ///
+    /// See also the [set_mark](#method.set_mark) method
+    #[cfg(feature = "packetmeta-mark")]
+    pub fn mark(&self) -> u32 {
+        self.mark
+    }
+
+    /// Set the mark of outgoing packets (ie. `SO_MARK`), used by the
+    /// [route rules](crate::iface::RouteRule) of the interface to select the
+    /// routing table.
+    ///
+    /// A socket without a mark uses zero.
+    #[cfg(feature = "packetmeta-mark")]
+    pub fn set_mark(&mut self, mark: u32) {
+        self.mark = mark
+    }
+
+    /// Return the flow the route rules match for the packets sent from `src_addr`.
+    fn flow(&self, src_addr: Option<IpAddress>) -> Flow {
+        Flow {
+            src_addr,
+            protocol: Some(IpProtocol::Tcp),
+            #[cfg(feature = "packetmeta-mark")]
+            mark: self.mark,
+            #[cfg(not(feature = "packetmeta-mark"))]
+            mark: 0,
+        }
+    }
+
     /// Return the listen endpoint
     #[inline]
     pub fn listen_endpoint(&self) -> IpListenEndpoint {
@@ -938,17 +1717,48 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         self.state
     }
 
-    fn reset(&mut self) {
-        let rx_cap_log2 =
-            mem::size_of::<usize>() * 8 - self.rx_buffer.capacity().leading_zeros() as usize;
+    /// Return why the socket last moved to the `CLOSED` state, or `None` if it did
+    /// not since the last [connect](#method.connect) or [listen](#method.listen) call.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.close_reason
+    }
+
+    /// Return a snapshot of the state and statistics of the connection.
+    pub fn info(&self) -> TcpInfo {
+        let controller = self.congestion_controller.inner();
+        TcpInfo {
+            state: self.state,
+            smoothed_rtt: self.rtte.smoothed_rtt(),
+            rtt_variance: self.rtte.rtt_variance(),
+            retransmission_timeout: self.rtte.retransmission_timeout(),
+            congestion_window: controller.window(),
+            slow_start_threshold: controller.slow_start_threshold(),
+            bytes_sent: self.counters.bytes_sent,
+            bytes_retransmitted: self.counters.bytes_retransmitted,
+            bytes_acked: self.counters.bytes_acked,
+            segments_in: self.counters.segments_in,
+            segments_out: self.counters.segments_out,
+            duplicate_acks: self.counters.duplicate_acks,
+            retransmission_timeouts: self.counters.retransmission_timeouts,
+            zero_window_probes: self.counters.zero_window_probes,
+            acks_coalesced: self.counters.acks_coalesced,
+            remote_window: self.remote_win_len,
+            remote_window_scale: self.remote_win_scale,
+            remote_mss: self.remote_mss,
+        }
+    }
 
+    fn reset(&mut self) {
         self.state = State::Closed;
         self.timer = Timer::new();
-        self.rtte = RttEstimator::default();
+        self.rtte = RttEstimator::new(self.rtte.config);
+        self.rto_retransmissions = 0;
         self.assembler = Assembler::new();
         self.tx_buffer.clear();
+        self.tx_chunks.clear();
         self.rx_buffer.clear();
         self.rx_fin_received = false;
+        self.recv_gaps.clear();
         self.listen_endpoint = IpListenEndpoint::default();
         self.tuple = None;
         self.local_seq_no = TcpSeqNumber::default();
@@ -958,10 +1768,29 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         self.remote_last_win = 0;
         self.remote_win_len = 0;
         self.remote_win_scale = None;
-        self.remote_win_shift = rx_cap_log2.saturating_sub(16) as u8;
+        self.remote_win_shift = self.rx_win_shift();
+        if let Some(limit) = self.rx_autotune_limit() {
+            // NOTE(unwrap): there is a limit only if autotuning is enabled.
+            self.rx_autotune.as_mut().unwrap().reset(limit);
+        }
+        self.sack_scoreboard = sack::Scoreboard::new();
+        self.remote_has_ecn = false;
+        self.ecn_echo = false;
+        self.ecn_cwr_pending = false;
+        self.ecn_recover = None;
+        self.fast_open_connect = false;
+        self.fast_open_reply = None;
+        self.syn_cookie_standby = false;
+        self.auth.reset();
+        self.rack = rack::Rack::new();
+        self.rate = rate::RateSampler::new();
+        self.plpmtud = plpmtud::Plpmtud::new();
+        self.pacing_next = None;
         self.remote_mss = DEFAULT_MSS;
         self.remote_last_ts = None;
+        self.counters = info::Counters::new();
         self.ack_delay_timer = AckDelayTimer::Idle;
+        self.quick_ack.reset(&self.ack_policy);
         self.challenge_ack_timer = Instant::from_secs(0);
 
         #[cfg(feature = "async")]
@@ -995,6 +1824,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             // before listening again, but this means that incoming connections can actually
             // get aborted between the abort() and the next listen().
             if matches!(self.state, State::Listen) && self.listen_endpoint == local_endpoint {
+                self.syn_cookie_standby = false;
                 return Ok(());
             } else {
                 return Err(ListenError::InvalidState);
@@ -1002,6 +1832,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         }
 
         self.reset();
+        self.close_reason = None;
         self.listen_endpoint = local_endpoint;
         self.tuple = None;
         self.set_state(State::Listen);
@@ -1081,7 +1912,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                     addr
                 }
                 None => cx
-                    .get_source_address(&remote_endpoint.addr)
+                    .get_source_address_flow(&remote_endpoint.addr, &self.flow(None))
                     .ok_or(ConnectError::Unaddressable)?,
             },
             port: local_endpoint.port,
@@ -1092,6 +1923,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         }
 
         self.reset();
+        self.close_reason = None;
         self.tuple = Some(Tuple {
             local: local_endpoint,
             remote: remote_endpoint,
@@ -1101,17 +1933,201 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         let seq = Self::random_seq_no(cx);
         self.local_seq_no = seq;
         self.remote_last_seq = seq;
+        self.auth.set_local_isn(seq);
         Ok(())
     }
 
-    #[cfg(test)]
-    fn random_seq_no(_cx: &mut Context) -> TcpSeqNumber {
-        TcpSeqNumber(10000)
+    /// Connect to a given endpoint with TCP Fast Open, as described in RFC 7413, and
+    /// enqueue `data` to be sent.
+    ///
+    /// If the interface has a Fast Open cookie from an earlier connection to the same
+    /// server, as much of `data` as fits in a segment is sent along with the SYN. Otherwise
+    /// the SYN asks for a cookie to use next time, and `data` is sent once the
+    /// connection is established. Either way, if the server doesn't accept the data
+    /// in the SYN, it is sent again after the handshake.
+    ///
+    /// Returns the number of octets enqueued, like [send_slice](#method.send_slice).
+    /// See [connect](#method.connect) for the other arguments and the errors returned.
+    pub fn connect_fast_open<T, U>(
+        &mut self,
+        cx: &mut Context,
+        remote_endpoint: T,
+        local_endpoint: U,
+        data: &[u8],
+    ) -> Result<usize, ConnectError>
+    where
+        T: Into<IpEndpoint>,
+        U: Into<IpListenEndpoint>,
+    {
+        self.connect(cx, remote_endpoint, local_endpoint)?;
+        self.fast_open_connect = true;
+        Ok(self.tx_buffer.enqueue_slice(data))
     }
 
-    #[cfg(not(test))]
-    fn random_seq_no(cx: &mut Context) -> TcpSeqNumber {
-        TcpSeqNumber(cx.rand().rand_u32() as i32)
+    /// Return the protocol state of the connection, as [checkpoint](#method.checkpoint)
+    /// saves it.
+    ///
+    /// The connection must be synchronized, and not in the `TIME-WAIT` state. It is
+    /// not saved if chunks were sent without copying, gaps were skipped in lossy
+    /// receive mode, or segments are authenticated.
+    pub fn repair_state(&self) -> Result<RepairState, RepairError> {
+        match self.state {
+            State::Established
+            | State::FinWait1
+            | State::FinWait2
+            | State::CloseWait
+            | State::Closing
+            | State::LastAck => (),
+            _ => return Err(RepairError::InvalidState),
+        }
+        if self.tx_chunks.has_chunks() || !self.recv_gaps.is_empty() || self.auth.config().is_some()
+        {
+            return Err(RepairError::Unsupported);
+        }
+        let Some(tuple) = self.tuple else {
+            return Err(RepairError::InvalidState);
+        };
+
+        let controller = self.congestion_controller.inner();
+        Ok(RepairState {
+            state: self.state,
+            local: tuple.local,
+            remote: tuple.remote,
+            local_seq_no: self.local_seq_no,
+            remote_seq_no: self.remote_seq_no,
+            remote_last_ack: self
+                .remote_last_ack
+                .unwrap_or(self.remote_seq_no + self.rx_buffer.len()),
+            remote_last_win: self.remote_last_win,
+            remote_win_len: self.remote_win_len,
+            remote_win_shift: self.remote_win_shift,
+            remote_win_scale: self.remote_win_scale,
+            remote_mss: self.remote_mss,
+            remote_has_sack: self.remote_has_sack,
+            remote_has_ecn: self.remote_has_ecn,
+            timestamps: self.tsval_generator.is_some(),
+            last_remote_tsval: self.last_remote_tsval,
+            last_local_tsval: self.last_local_tsval,
+            rx_fin_received: self.rx_fin_received,
+            rtt: self.rtte.smoothed_rtt().zip(self.rtte.rtt_variance()),
+            retransmission_timeout: self.rtte.retransmission_timeout(),
+            congestion_control: self.congestion_control(),
+            congestion_window: controller.window(),
+            slow_start_threshold: controller.slow_start_threshold(),
+            rx_len: self.rx_buffer.len(),
+            tx_len: self.tx_buffer.len(),
+        })
+    }
+
+    /// Save the protocol state of the connection, followed by the data in the receive
+    /// and transmit buffers, into `buffer`, so that another socket can take the
+    /// connection over with [restore](#method.restore).
+    ///
+    /// Returns the length of the checkpoint, that is [RepairState::buffer_len].
+    /// The socket is left unchanged: once the checkpoint is restored elsewhere, it
+    /// should be dropped without being polled again.
+    pub fn checkpoint(&self, buffer: &mut [u8]) -> Result<usize, RepairError> {
+        let repair = self.repair_state()?;
+        if buffer.len() < repair.buffer_len() {
+            return Err(RepairError::BufferTooSmall);
+        }
+        repair.emit(buffer);
+        Self::copy_allocated(&self.rx_buffer, &mut buffer[repair.rx_range()]);
+        Self::copy_allocated(&self.tx_buffer, &mut buffer[repair.tx_range()]);
+        Ok(repair.buffer_len())
+    }
+
+    fn copy_allocated(buffer: &B, data: &mut [u8]) {
+        let mut offset = 0;
+        while offset < data.len() {
+            let chunk = buffer.get_allocated(offset, data.len() - offset);
+            data[offset..offset + chunk.len()].copy_from_slice(chunk);
+            offset += chunk.len();
+        }
+    }
+
+    /// Create a socket using the given buffers, that takes over the connection saved
+    /// in `checkpoint` by [checkpoint](#method.checkpoint), without a handshake.
+    ///
+    /// The data sent and not acknowledged is retransmitted when the socket is first
+    /// polled. What the saved socket was configured with isn't restored, and should
+    /// be set again; in particular, if [RepairState::timestamps] is set, a timestamp
+    /// generator must be set with [set_tsval_generator](#method.set_tsval_generator).
+    /// Its values must continue from [RepairState::last_local_tsval]: the remote
+    /// drops segments with an older timestamp than the last one it received, to
+    /// protect against wrapped sequence numbers (PAWS, RFC 7323).
+    /// A socket added to an interface is registered with
+    /// [Interface::register_tcp_socket](crate::iface::Interface::register_tcp_socket).
+    ///
+    /// This function returns `Err(RepairError::BufferTooSmall)` if a buffer can't hold
+    /// the data saved for it.
+    pub fn restore<T>(
+        rx_buffer: T,
+        tx_buffer: T,
+        checkpoint: &[u8],
+    ) -> Result<Socket<'a, B>, RepairError>
+    where
+        T: Into<B>,
+    {
+        let repair = RepairState::parse(checkpoint)?;
+        let mut socket = Socket::new(rx_buffer, tx_buffer);
+        if socket.rx_buffer.capacity() < repair.rx_len
+            || socket.tx_buffer.capacity() < repair.tx_len
+        {
+            return Err(RepairError::BufferTooSmall);
+        }
+        socket
+            .rx_buffer
+            .enqueue_slice(&checkpoint[repair.rx_range()]);
+        socket
+            .tx_buffer
+            .enqueue_slice(&checkpoint[repair.tx_range()]);
+
+        socket.tuple = Some(Tuple {
+            local: repair.local,
+            remote: repair.remote,
+        });
+        socket.local_seq_no = repair.local_seq_no;
+        // Everything in the transmit buffer is sent again.
+        socket.remote_last_seq = repair.local_seq_no;
+        socket.remote_seq_no = repair.remote_seq_no;
+        socket.remote_last_ack = Some(repair.remote_last_ack);
+        socket.remote_last_win = repair.remote_last_win;
+        socket.remote_win_len = repair.remote_win_len;
+        socket.remote_win_shift = repair.remote_win_shift;
+        socket.remote_win_scale = repair.remote_win_scale;
+        socket.remote_mss = repair.remote_mss;
+        socket.remote_has_sack = repair.remote_has_sack;
+        socket.ecn = repair.remote_has_ecn;
+        socket.remote_has_ecn = repair.remote_has_ecn;
+        socket.last_remote_tsval = repair.last_remote_tsval;
+        socket.last_local_tsval = repair.last_local_tsval;
+        socket.rx_fin_received = repair.rx_fin_received;
+        socket
+            .rtte
+            .restore(repair.rtt, repair.retransmission_timeout);
+
+        if let ActiveCongestionControl::BuiltIn(congestion_control) = repair.congestion_control {
+            socket.set_congestion_control(congestion_control);
+        }
+        let controller = socket.congestion_controller.inner_mut();
+        controller.set_mss(repair.remote_mss);
+        controller.set_remote_window(repair.remote_win_len);
+        controller.restore(repair.congestion_window, repair.slow_start_threshold);
+
+        tcp_trace!("restored from checkpoint");
+        socket.set_state(repair.state);
+        Ok(socket)
+    }
+
+    #[cfg(test)]
+    fn random_seq_no(_cx: &mut Context) -> TcpSeqNumber {
+        TcpSeqNumber(10000)
+    }
+
+    #[cfg(not(test))]
+    fn random_seq_no(cx: &mut Context) -> TcpSeqNumber {
+        TcpSeqNumber(cx.rand().rand_u32() as i32)
     }
 
     /// Close the transmit half of the full-duplex connection.
@@ -1122,10 +2138,10 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     pub fn close(&mut self) {
         match self.state {
             // In the LISTEN state there is no established connection.
-            State::Listen => self.set_state(State::Closed),
+            State::Listen => self.set_closed(CloseReason::Aborted),
             // In the SYN-SENT state the remote endpoint is not yet synchronized and, upon
             // receiving an RST, will abort the connection.
-            State::SynSent => self.set_state(State::Closed),
+            State::SynSent => self.set_closed(CloseReason::Aborted),
             // In the SYN-RECEIVED, ESTABLISHED and CLOSE-WAIT states the transmit half
             // of the connection is open, and needs to be explicitly closed with a FIN.
             State::SynReceived | State::Established => self.set_state(State::FinWait1),
@@ -1150,7 +2166,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     /// In terms of the TCP state machine, the socket may be in any state and is moved to
     /// the `CLOSED` state.
     pub fn abort(&mut self) {
-        self.set_state(State::Closed);
+        self.set_closed(CloseReason::Aborted);
     }
 
     /// Return whether the socket is passively listening for incoming connections.
@@ -1278,16 +2294,22 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         !self.rx_buffer.is_empty()
     }
 
+    /// Return the amount of octets queued to be sent, in the transmit buffer and in
+    /// chunks.
+    fn tx_len(&self) -> usize {
+        self.tx_buffer.len() + self.tx_chunks.len()
+    }
+
     fn send_impl<'b, F, R>(&'b mut self, f: F) -> Result<R, SendError>
     where
-        F: FnOnce(&'b mut B) -> (usize, R),
+        F: FnOnce(&'b mut B, &mut tx_chunk::TxChunkQueue<'a>) -> (usize, R),
     {
         if !self.may_send() {
             return Err(SendError::InvalidState);
         }
 
-        let old_length = self.tx_buffer.len();
-        let (size, result) = f(&mut self.tx_buffer);
+        let old_length = self.tx_len();
+        let (size, result) = f(&mut self.tx_buffer, &mut self.tx_chunks);
         if size > 0 {
             // The connection might have been idle for a long time, and so remote_last_ts
             // would be far in the past. Unless we clear it here, we'll abort the connection
@@ -1326,7 +2348,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     where
         F: FnOnce(&'b mut [u8]) -> (usize, R),
     {
-        self.send_impl(|tx_buffer| tx_buffer.enqueue_many_with(f))
+        self.send_impl(|tx_buffer, _| tx_buffer.enqueue_many_with(f))
     }
 
     /// Enqueue a sequence of octets to be sent, and fill it from a slice.
@@ -1336,16 +2358,74 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     ///
     /// See also [send](#method.send).
     pub fn send_slice(&mut self, data: &[u8]) -> Result<usize, SendError> {
-        self.send_impl(|tx_buffer| {
+        self.send_impl(|tx_buffer, _| {
             let size = tx_buffer.enqueue_slice(data);
             (size, size)
         })
     }
 
+    /// Set the storage for the chunks queued with [send_chunk](#method.send_chunk).
+    ///
+    /// Each slot holds one chunk, from the time it is queued until it is taken back
+    /// with [take_completed_chunk](#method.take_completed_chunk). Completed chunks left
+    /// in the previous storage are dropped. A socket has no storage for chunks by
+    /// default.
+    ///
+    /// # Panics
+    /// This function panics if chunks are still being sent.
+    pub fn set_tx_chunk_storage<S>(&mut self, storage: S)
+    where
+        S: Into<ManagedSlice<'a, TxChunkSlot<'a>>>,
+    {
+        if self.tx_chunks.len() > 0 {
+            panic!("chunks are still being sent")
+        }
+        self.tx_chunks.set_storage(storage.into());
+    }
+
+    /// Enqueue a chunk of data to be sent without copying it.
+    ///
+    /// The socket keeps the chunk and builds segments straight from it until the remote
+    /// acknowledges all of it; the chunk then completes, and can be taken back with
+    /// [take_completed_chunk](#method.take_completed_chunk). Chunks and octets enqueued
+    /// with [send](#method.send) or [send_slice](#method.send_slice) are sent in the
+    /// order they were enqueued.
+    ///
+    /// This function returns `Err(SendChunkError::InvalidState)` if the transmit half of
+    /// the connection is not open (see [may_send](#method.may_send)), and
+    /// `Err(SendChunkError::Full)` if no slot is free in the storage set with
+    /// [set_tx_chunk_storage](#method.set_tx_chunk_storage).
+    pub fn send_chunk<T>(&mut self, chunk: T) -> Result<(), SendChunkError>
+    where
+        T: Into<TxChunk<'a>>,
+    {
+        let chunk = chunk.into();
+        let result = self.send_impl(|tx_buffer, tx_chunks| {
+            let size = chunk.len();
+            match tx_chunks.push(tx_buffer.len(), chunk) {
+                Ok(()) => (size, Ok(())),
+                Err(Full) => (0, Err(SendChunkError::Full)),
+            }
+        });
+        match result {
+            Ok(result) => result,
+            Err(SendError::InvalidState) => Err(SendChunkError::InvalidState),
+        }
+    }
+
+    /// Take back the oldest chunk the remote acknowledged in full, if any.
+    ///
+    /// Chunks complete in the order they were enqueued with
+    /// [send_chunk](#method.send_chunk), and keep their slot until they are taken back.
+    pub fn take_completed_chunk(&mut self) -> Option<TxChunk<'a>> {
+        self.tx_chunks.pop_completed()
+    }
+
     fn recv_error_check(&mut self) -> Result<(), RecvError> {
-        // We may have received some data inside the initial SYN, but until the connection
-        // is fully open we must not dequeue any data, as it may be overwritten by e.g.
-        // another (stale) SYN. (We do not support TCP Fast Open.)
+        // Until the connection is fully open, only data already in the receive buffer
+        // may be dequeued. Before then, it holds data only if the SYN carried a valid
+        // TCP Fast Open cookie; the data in other SYNs is not accepted, as it may come
+        // from e.g. a stale SYN.
         if !self.may_recv() {
             if self.rx_fin_received {
                 return Err(RecvError::Finished);
@@ -1365,6 +2445,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         let _old_length = self.rx_buffer.len();
         let (size, result) = f(&mut self.rx_buffer);
         self.remote_seq_no += size;
+        self.recv_gaps.pass(self.remote_seq_no);
         if size > 0 {
             #[cfg(any(test, feature = "verbose"))]
             tcp_trace!(
@@ -1406,6 +2487,78 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         })
     }
 
+    /// Return whether lossy receive mode is enabled.
+    pub fn lossy_receive_enabled(&self) -> bool {
+        self.lossy_receive
+    }
+
+    /// Enable or disable lossy receive mode.
+    ///
+    /// In lossy receive mode, when a segment carrying data arrives after a hole in the
+    /// received data, the socket skips the hole and acknowledges the segment rather than
+    /// waiting for the missing octets to be retransmitted. Each hole skipped is reported
+    /// as a [RecvGap] by [recv_lossy](#method.recv_lossy), between the data received
+    /// before and after it.
+    ///
+    /// Gaps are queued in the storage set with
+    /// [set_recv_gap_storage](#method.set_recv_gap_storage). While it is full, or if
+    /// there is none, holes are waited for as usual.
+    ///
+    /// Gaps skipped by [commit_lossy_tail](#method.commit_lossy_tail) are reported too.
+    pub fn set_lossy_receive_enabled(&mut self, enabled: bool) {
+        self.lossy_receive = enabled;
+    }
+
+    /// Set the storage for the gaps skipped in lossy receive mode, see
+    /// [set_lossy_receive_enabled](#method.set_lossy_receive_enabled).
+    ///
+    /// Each slot holds one gap, from the time it is skipped until it is returned by
+    /// [recv_lossy](#method.recv_lossy). Gaps left in the previous storage are dropped.
+    /// A socket has no storage for gaps by default.
+    ///
+    /// # Panics
+    /// This function panics if data received after a gap is still in the receive
+    /// buffer.
+    pub fn set_recv_gap_storage<S>(&mut self, storage: S)
+    where
+        S: Into<ManagedSlice<'a, RecvGap>>,
+    {
+        if self.recv_gaps.has_pending() {
+            panic!("data received after a gap is still buffered")
+        }
+        self.recv_gaps.set_storage(storage.into());
+    }
+
+    /// Dequeue the next record of received data, up to `max_size` octets, or gap.
+    ///
+    /// Data records are tagged with the sequence number of their first octet, and end
+    /// at the next gap, if any. A gap is returned once all the data received before it
+    /// was read, by this function or otherwise. This function returns `Ok(None)` if
+    /// there is nothing to return yet, and otherwise errors like
+    /// [recv](#method.recv).
+    pub fn recv_lossy(&mut self, max_size: usize) -> Result<Option<LossyRecord<'_>>, RecvError> {
+        if let Some(gap) = self.recv_gaps.pop_passed() {
+            return Ok(Some(LossyRecord::Gap(gap)));
+        }
+
+        let seq_number = self.recv_gaps.head(self.remote_seq_no);
+        let size = match self.recv_gaps.readable(self.remote_seq_no) {
+            Some(readable) => readable.min(max_size),
+            None => max_size,
+        };
+        let payload = self.recv_impl(|rx_buffer| {
+            let payload = rx_buffer.dequeue_many(size);
+            (payload.len(), &*payload)
+        })?;
+        if payload.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(LossyRecord::Data {
+            seq_number,
+            payload,
+        }))
+    }
+
     /// Peek at a sequence of received octets without removing them from
     /// the receive buffer, and return a pointer to it.
     ///
@@ -1429,11 +2582,12 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         Ok(self.rx_buffer.read_allocated(0, data))
     }
 
-    /// Return the amount of octets queued in the transmit buffer.
+    /// Return the amount of octets queued in the transmit buffer, and in chunks not
+    /// acknowledged yet.
     ///
     /// Note that the Berkeley sockets interface does not have an equivalent of this API.
     pub fn send_queue(&self) -> usize {
-        self.tx_buffer.len()
+        self.tx_len()
     }
 
     /// Return the amount of octets queued in the receive buffer. This value can be larger than
@@ -1470,11 +2624,22 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         }
     }
 
+    /// Move to the `CLOSED` state, recording why.
+    fn set_closed(&mut self, reason: CloseReason) {
+        if self.state != State::Closed {
+            tcp_trace!("closing: {}", reason);
+            self.close_reason = Some(reason);
+        }
+        self.set_state(State::Closed);
+    }
+
     pub(crate) fn reply(ip_repr: &IpRepr, repr: &TcpRepr) -> (IpRepr, TcpRepr<'static>) {
         let reply_repr = TcpRepr {
             src_port: repr.dst_port,
             dst_port: repr.src_port,
             control: TcpControl::None,
+            ece: false,
+            cwr: false,
             seq_number: TcpSeqNumber(0),
             ack_number: None,
             window_len: 0,
@@ -1483,6 +2648,8 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             sack_permitted: false,
             sack_ranges: [None, None, None],
             timestamp: None,
+            fast_open: None,
+            auth: None,
             payload: &[],
         };
         let ip_reply_repr = IpRepr::new(
@@ -1495,6 +2662,42 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         (ip_reply_repr, reply_repr)
     }
 
+    /// Hand over the connection in the TIME-WAIT state, and close the socket so that
+    /// it can be used again right away.
+    ///
+    /// This is only possible once everything received was acknowledged and read, and
+    /// the chunks sent were taken back. Authenticated connections are not handed
+    /// over, since their segments could not be signed or verified.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn take_time_wait(&mut self) -> Option<TimeWait> {
+        let Timer::Close { expires_at } = self.timer else {
+            return None;
+        };
+        if self.state != State::TimeWait
+            || self.ack_to_transmit()
+            || !self.rx_buffer.is_empty()
+            || !self.recv_gaps.is_empty()
+            || self.tx_chunks.has_chunks()
+            || self.auth.config().is_some()
+        {
+            return None;
+        }
+        let tuple = self.tuple?;
+        let time_wait = TimeWait {
+            local: tuple.local,
+            remote: tuple.remote,
+            local_seq_no: self.remote_last_seq,
+            remote_seq_no: self.remote_seq_no,
+            window_len: self.scaled_window(),
+            tsval_generator: self.tsval_generator,
+            expires_at,
+        };
+        tcp_trace!("handing over TIME-WAIT");
+        self.reset();
+        self.close_reason = Some(CloseReason::Graceful);
+        Some(time_wait)
+    }
+
     pub(crate) fn rst_reply(ip_repr: &IpRepr, repr: &TcpRepr) -> (IpRepr, TcpRepr<'static>) {
         debug_assert!(repr.control != TcpControl::Rst);
 
@@ -1516,6 +2719,9 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         reply_repr.timestamp = repr
             .timestamp
             .and_then(|tcp_ts| tcp_ts.generate_reply(self.tsval_generator));
+        if let Some(tcp_ts) = reply_repr.timestamp {
+            self.last_local_tsval = tcp_ts.tsval;
+        }
 
         // From RFC 793:
         // [...] an empty acknowledgment segment containing the current send-sequence number
@@ -1523,7 +2729,10 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         // to be received.
         reply_repr.seq_number = self.remote_last_seq;
         reply_repr.ack_number = Some(self.remote_seq_no + self.rx_buffer.len());
+        reply_repr.ece = self.remote_has_ecn && self.ecn_echo;
         self.remote_last_ack = reply_repr.ack_number;
+        // Everything received is acknowledged, there is no ACK to delay anymore.
+        self.ack_delay_timer = AckDelayTimer::Idle;
 
         // From RFC 1323:
         // The window field [...] of every outgoing segment, with the exception of SYN
@@ -1587,6 +2796,132 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         Some(self.ack_reply(ip_repr, repr))
     }
 
+    /// Return whether the socket uses SYN cookies for segments sent to the endpoint
+    /// of `repr`, that is whether it has them enabled, and listens or listened there.
+    pub(crate) fn uses_syn_cookies(&self, ip_repr: &IpRepr, repr: &TcpRepr) -> bool {
+        self.syn_cookies
+            && matches!(self.state, State::Listen | State::SynReceived)
+            && repr.dst_port != 0
+            && repr.dst_port == self.listen_endpoint.port
+            && self
+                .listen_endpoint
+                .addr
+                .is_none_or(|addr| addr == ip_repr.dst_addr())
+    }
+
+    /// Answer the SYN `repr`, that no socket took, with a SYN|ACK carrying a SYN
+    /// cookie, on behalf of this socket and without changing its state.
+    ///
+    /// Return `None` if the SYN is to be ignored.
+    pub(crate) fn syn_cookie_reply(
+        &self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+    ) -> Option<(IpRepr, TcpRepr<'static>)> {
+        debug_assert!(self.uses_syn_cookies(ip_repr, repr));
+        let local = IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port);
+        let remote = IpEndpoint::new(ip_repr.src_addr(), repr.src_port);
+        let mut auth = self.auth.clone();
+        auth.set_remote_isn(repr.seq_number);
+        if !auth.verify(local, remote, repr) {
+            net_debug!("dropping unauthenticated segment");
+            return None;
+        }
+        if repr.max_seg_size == Some(0) {
+            tcp_trace!("received SYN with zero MSS, ignoring");
+            return None;
+        }
+        let cookie = SynCookie::new(
+            repr.max_seg_size.unwrap_or(DEFAULT_MSS as u16),
+            repr.window_scale,
+            repr.sack_permitted,
+        );
+        let isn = cx.generate_tcp_syn_cookie(local, remote, repr.seq_number, cookie);
+        tcp_trace!("answering SYN with a cookie");
+
+        let (mut ip_reply_repr, mut reply_repr) = Self::reply(ip_repr, repr);
+        reply_repr.control = TcpControl::Syn;
+        reply_repr.seq_number = isn;
+        reply_repr.ack_number = Some(repr.seq_number + 1);
+        // window len must NOT be scaled in SYNs.
+        reply_repr.window_len = u16::try_from(self.rx_window()).unwrap_or(u16::MAX);
+        // The socket may be connected to a peer without window scaling, so the
+        // shift is computed afresh.
+        reply_repr.window_scale = cookie.window_scale.map(|_| self.rx_win_shift());
+        reply_repr.sack_permitted = cookie.sack_permitted;
+        let max_segment_size = cx.ip_mtu() - ip_reply_repr.header_len() - TCP_HEADER_LEN;
+        reply_repr.max_seg_size = Some(max_segment_size as u16);
+        reply_repr.auth = auth.sign(local, remote, &reply_repr);
+        ip_reply_repr.set_payload_len(reply_repr.buffer_len());
+        Some((ip_reply_repr, reply_repr))
+    }
+
+    /// Open a connection from the ACK `repr`, which carries back the valid SYN cookie
+    /// `cookie`, and process it.
+    pub(crate) fn process_syn_cookie(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+        cookie: SynCookie,
+    ) -> Option<(IpRepr, TcpRepr<'static>)> {
+        debug_assert!(self.state == State::Listen && self.uses_syn_cookies(ip_repr, repr));
+        let (listen_endpoint, standby) = (self.listen_endpoint, self.syn_cookie_standby);
+        self.restore_syn_cookie(cx, ip_repr, repr, cookie);
+
+        let local = IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port);
+        let remote = IpEndpoint::new(ip_repr.src_addr(), repr.src_port);
+        if !self.auth.verify(local, remote, repr) {
+            net_debug!("dropping unauthenticated segment");
+            self.reset();
+            self.listen_endpoint = listen_endpoint;
+            self.syn_cookie_standby = standby;
+            self.set_state(State::Listen);
+            return None;
+        }
+        self.process_verified(cx, ip_repr, repr)
+    }
+
+    /// Rebuild the SYN-RECEIVED state from the SYN cookie `cookie` carried by the ACK
+    /// `repr`, as if the SYN|ACK it acknowledges had been sent from that state.
+    fn restore_syn_cookie(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+        cookie: SynCookie,
+    ) {
+        tcp_trace!("received a valid SYN cookie");
+
+        self.tuple = Some(Tuple {
+            local: IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port),
+            remote: IpEndpoint::new(ip_repr.src_addr(), repr.src_port),
+        });
+        // NOTE(unwrap): the cookie was carried by the acknowledgement number.
+        self.local_seq_no = repr.ack_number.unwrap() - 1;
+        self.remote_seq_no = repr.seq_number;
+        self.remote_last_seq = self.local_seq_no + 1;
+        self.remote_last_ack = Some(repr.seq_number);
+        self.remote_last_win = u16::try_from(self.rx_window()).unwrap_or(u16::MAX);
+        self.auth.set_local_isn(self.local_seq_no);
+        self.auth.set_remote_isn(repr.seq_number - 1);
+        self.remote_mss = cookie.mss as usize;
+        self.congestion_controller
+            .inner_mut()
+            .set_mss(self.remote_mss);
+        self.remote_has_sack = cookie.sack_permitted;
+        self.remote_win_scale = cookie.window_scale;
+        if self.remote_win_scale.is_none() {
+            self.remote_win_shift = 0;
+        }
+        // Timestamps were not negotiated in the SYN|ACK.
+        self.tsval_generator = None;
+        self.syn_cookie_standby = false;
+        self.set_state(State::SynReceived);
+        self.timer.set_for_idle(cx.now(), self.keep_alive);
+    }
+
     pub(crate) fn accepts(&self, _cx: &mut Context, ip_repr: &IpRepr, repr: &TcpRepr) -> bool {
         if self.state == State::Closed {
             return false;
@@ -1594,9 +2929,13 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
 
         // If we're still listening for SYNs and the packet has an ACK or a RST,
         // it cannot be destined to this socket, but another one may well listen
-        // on the same local endpoint.
+        // on the same local endpoint. An ACK carrying a SYN cookie back is handed
+        // to `process_syn_cookie()` instead, and a socket standing by for those
+        // leaves SYNs to the other sockets.
         if self.state == State::Listen
-            && (repr.ack_number.is_some() || repr.control == TcpControl::Rst)
+            && (self.syn_cookie_standby
+                || repr.ack_number.is_some()
+                || repr.control == TcpControl::Rst)
         {
             return false;
         }
@@ -1649,6 +2988,12 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 return Err(LossyTailCommitError::InvalidState)
             }
         }
+        if let Some(tuple) = self.tuple
+            && !self.auth.verify(tuple.local, tuple.remote, repr)
+        {
+            return Err(LossyTailCommitError::Unauthenticated);
+        }
+        self.counters.segments_in += 1;
 
         let sent_fin = matches!(self.state, State::FinWait1 | State::LastAck | State::Closing);
         let tx_buffer_start_seq = self.local_seq_no;
@@ -1658,19 +3003,26 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         let mut acknowledged = peer_ack
             .map(|ack_number| ack_number - tx_buffer_start_seq)
             .unwrap_or(0)
-            .min(self.tx_buffer.len());
+            .min(self.tx_len());
         let ack_of_fin = peer_ack
-            .map(|ack_number| {
-                sent_fin && ack_number - tx_buffer_start_seq > self.tx_buffer.len()
-            })
+            .map(|ack_number| sent_fin && ack_number - tx_buffer_start_seq > self.tx_len())
             .unwrap_or(false);
         if ack_of_fin {
-            acknowledged = self.tx_buffer.len();
+            acknowledged = self.tx_len();
         }
         let ack_all = peer_ack
             .map(|ack_number| self.remote_last_seq <= ack_number)
             .unwrap_or(false);
 
+        let window_start = self.remote_seq_no + self.rx_buffer.len();
+        if self.lossy_receive && repr.seq_number > window_start {
+            self.recv_gaps.push_passed(RecvGap {
+                seq_number: window_start,
+                len: repr.seq_number - window_start,
+                timestamp,
+            });
+        }
+
         self.remote_seq_no = repr.seq_number + repr.segment_len();
         self.local_rx_last_seq = Some(repr.seq_number);
         if repr.control == TcpControl::Fin {
@@ -1705,6 +3057,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     pub fn prepare_lossy_tail(&mut self) {
         self.rx_buffer.clear();
         self.assembler.clear();
+        self.recv_gaps.pass_all();
     }
 
     fn apply_lossy_tail_control(
@@ -1715,7 +3068,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     ) {
         match (self.state, control.quash_psh(), ack_of_fin) {
             (_, TcpControl::Rst, _) => {
-                self.set_state(State::Closed);
+                self.set_closed(CloseReason::Reset);
                 self.tuple = None;
             }
             (State::SynReceived | State::Established, TcpControl::Fin, _) => {
@@ -1736,7 +3089,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 self.timer.set_for_close(timestamp);
             }
             (State::LastAck, _, true) => {
-                self.set_state(State::Closed);
+                self.set_closed(CloseReason::Graceful);
                 self.tuple = None;
             }
             (State::TimeWait, TcpControl::Fin, _) => self.timer.set_for_close(timestamp),
@@ -1768,14 +3121,26 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         }
 
         if ack_len > 0 {
-            debug_assert!(self.tx_buffer.len() >= ack_len);
-            self.tx_buffer.dequeue_allocated(ack_len);
+            debug_assert!(self.tx_len() >= ack_len);
+            self.tx_chunks.dequeue(&mut self.tx_buffer, ack_len);
+            self.counters.bytes_acked += ack_len as u64;
+            if let Some(ack_number) = peer_ack {
+                self.plpmtud.on_ack(ack_number);
+            }
 
             #[cfg(feature = "async")]
             self.tx_waker.wake();
         }
 
         if let Some(ack_number) = peer_ack {
+            if self.remote_has_sack {
+                // The transmit buffer now starts at `ack_number`, and nothing past
+                // its end can have been sent.
+                let high_data = ack_number + self.tx_len();
+                self.sack_scoreboard
+                    .on_ack(ack_number, high_data, &repr.sack_ranges);
+            }
+
             match self.local_rx_last_ack {
                 Some(last_rx_ack)
                     if repr.payload.is_empty()
@@ -1784,10 +3149,21 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                         && !is_window_update =>
                 {
                     self.local_rx_dup_acks = self.local_rx_dup_acks.saturating_add(1);
+                    self.counters.duplicate_acks += 1;
                     self.congestion_controller
                         .inner_mut()
                         .on_duplicate_ack(timestamp);
-                    if self.local_rx_dup_acks == 3 {
+                    if self.remote_has_sack {
+                        // RFC 6675 section 5: enter loss recovery after DupThresh
+                        // duplicate ACKs, or once the SACKed octets show that the
+                        // first unacknowledged segment was lost.
+                        let dup_acks = self.local_rx_dup_acks as usize;
+                        if dup_acks >= sack::DUP_THRESH
+                            || self.sack_scoreboard.is_lost(ack_number, self.remote_mss)
+                        {
+                            self.enter_sack_recovery(timestamp);
+                        }
+                    } else if self.local_rx_dup_acks == 3 {
                         self.timer.set_for_fast_retransmit();
                     }
                 }
@@ -1797,10 +3173,42 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 }
             }
 
+            // RFC 3168 section 6.1.2: react to congestion echoed by the remote at
+            // most once per window of data, and not on top of loss recovery.
+            if self.remote_has_ecn
+                && repr.ece
+                && repr.control != TcpControl::Syn
+                && !self.sack_scoreboard.in_recovery()
+                && self.ecn_recover.is_none_or(|recover| ack_number > recover)
+            {
+                tcp_trace!("received ECE, reducing congestion window");
+                self.congestion_controller.inner_mut().on_ecn(timestamp);
+                self.ecn_recover = Some(self.remote_last_seq);
+                self.ecn_cwr_pending = true;
+            }
+
+            if ack_number > self.local_seq_no {
+                self.rto_retransmissions = 0;
+            }
             self.local_seq_no = ack_number;
             if self.remote_last_seq < self.local_seq_no {
                 self.remote_last_seq = self.local_seq_no;
             }
+
+            if let Some(sample) = self
+                .rate
+                .on_ack(timestamp, ack_number, &self.sack_scoreboard)
+            {
+                self.congestion_controller
+                    .inner_mut()
+                    .on_rate_sample(timestamp, &sample);
+            }
+
+            if self.rack_tlp {
+                self.rack
+                    .on_ack(timestamp, ack_number, &self.sack_scoreboard);
+                self.rack_detect_loss(timestamp);
+            }
         }
 
         if let Some(tcp_timestamp) = repr.timestamp {
@@ -1808,22 +3216,18 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         }
 
         match self.timer {
-            Timer::Retransmit { .. } | Timer::FastRetransmit => {
+            Timer::Retransmit { .. } | Timer::FastRetransmit | Timer::TailLossProbe { .. } => {
                 if ack_all {
                     self.timer.set_for_idle(timestamp, self.keep_alive);
                 } else if ack_len > 0 {
-                    let rto = self.rtte.retransmission_timeout();
-                    self.timer.set_for_retransmit(timestamp, rto);
+                    self.set_timer_for_retransmit(timestamp);
                 }
             }
             Timer::Idle { .. } => self.timer.set_for_idle(timestamp, self.keep_alive),
             _ => {}
         }
 
-        if self.remote_win_len == 0
-            && !self.tx_buffer.is_empty()
-            && (self.timer.is_idle() || ack_len > 0)
-        {
+        if self.remote_win_len == 0 && self.tx_len() > 0 && (self.timer.is_idle() || ack_len > 0) {
             let delay = self.rtte.retransmission_timeout();
             self.timer.set_for_zero_window_probe(timestamp, delay);
         }
@@ -1840,6 +3244,41 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     ) -> Option<(IpRepr, TcpRepr<'static>)> {
         debug_assert!(self.accepts(cx, ip_repr, repr));
 
+        // RFC 2385 section 2.0 and RFC 5925 section 7.3: segments failing
+        // authentication are silently discarded, without a reply.
+        let local = IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port);
+        let remote = IpEndpoint::new(ip_repr.src_addr(), repr.src_port);
+        if !self.auth.verify(local, remote, repr) {
+            net_debug!("dropping unauthenticated segment");
+            return None;
+        }
+        self.process_verified(cx, ip_repr, repr)
+    }
+
+    /// Process the segment `repr`, once authenticated.
+    fn process_verified(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+    ) -> Option<(IpRepr, TcpRepr<'static>)> {
+        let local = IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port);
+        let remote = IpEndpoint::new(ip_repr.src_addr(), repr.src_port);
+        self.counters.segments_in += 1;
+
+        let (mut ip_reply_repr, mut reply_repr) = self.process_authenticated(cx, ip_repr, repr)?;
+        reply_repr.auth = self.auth.sign(local, remote, &reply_repr);
+        ip_reply_repr.set_payload_len(reply_repr.buffer_len());
+        self.counters.segments_out += 1;
+        Some((ip_reply_repr, reply_repr))
+    }
+
+    fn process_authenticated(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+    ) -> Option<(IpRepr, TcpRepr<'static>)> {
         // Consider how much the sequence number space differs from the transmit buffer space.
         let (sent_syn, sent_fin) = match self.state {
             // In SYN-SENT or SYN-RECEIVED, we've just sent a SYN.
@@ -1872,9 +3311,12 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             (State::Listen, _, None) => (),
             // This case is handled in `accepts()`.
             (State::Listen, _, Some(_)) => unreachable!(),
-            // SYN|ACK in the SYN-SENT state must have the exact ACK number.
+            // SYN|ACK in the SYN-SENT state must have the exact ACK number, or
+            // with TCP Fast Open, acknowledge some of the data sent with our SYN.
             (State::SynSent, TcpControl::Syn, Some(ack_number)) => {
-                if ack_number != self.local_seq_no + 1 {
+                if ack_number < self.local_seq_no + 1
+                    || ack_number > self.remote_last_seq.max(self.local_seq_no + 1)
+                {
                     net_debug!("unacceptable SYN|ACK in response to initial SYN");
                     return Some(Self::rst_reply(ip_repr, repr));
                 }
@@ -1920,7 +3362,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             }
             // Every acknowledgement must be for transmitted but unacknowledged data.
             (_, _, Some(ack_number)) => {
-                let unacknowledged = self.tx_buffer.len() + control_len;
+                let unacknowledged = self.tx_len() + control_len;
 
                 // Acceptable ACK range (both inclusive)
                 let mut ack_min = self.local_seq_no;
@@ -1953,7 +3395,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             }
         }
 
-        let window_start = self.remote_seq_no + self.rx_buffer.len();
+        let mut window_start = self.remote_seq_no + self.rx_buffer.len();
         let window_end = if let Some(last_ack) = self.remote_last_ack {
             last_ack + ((self.remote_last_win as usize) << self.remote_win_shift)
         } else {
@@ -1962,7 +3404,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         let segment_start = repr.seq_number;
         let segment_end = repr.seq_number + repr.payload.len();
 
-        let (payload, payload_offset) = match self.state {
+        let (payload, mut payload_offset) = match self.state {
             // In LISTEN and SYN-SENT states, we have not yet synchronized with the remote end.
             State::Listen | State::SynSent => (&[][..], 0),
             _ => {
@@ -2044,6 +3486,40 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             }
         };
 
+        // In lossy receive mode, skip the octets missing before a segment carrying data
+        // rather than waiting for them to be retransmitted.
+        let carries_data = match repr.control.quash_psh() {
+            TcpControl::None => !payload.is_empty(),
+            TcpControl::Fin => true,
+            _ => false,
+        };
+        if self.lossy_receive
+            && payload_offset > 0
+            && carries_data
+            && matches!(
+                self.state,
+                State::Established | State::FinWait1 | State::FinWait2
+            )
+            && self.assembler.is_empty()
+            && self.recv_gaps.has_room()
+        {
+            let gap = RecvGap {
+                seq_number: window_start,
+                len: payload_offset,
+                timestamp: cx.now(),
+            };
+            net_debug!(
+                "lossy receive: skipping {} octets at {}",
+                gap.len,
+                gap.seq_number
+            );
+            self.remote_seq_no += payload_offset;
+            // NOTE(unwrap): we just checked there is room.
+            self.recv_gaps.push(self.remote_seq_no, gap).unwrap();
+            window_start += payload_offset;
+            payload_offset = 0;
+        }
+
         // Compute the amount of acknowledged octets, removing the SYN and FIN bits
         // from the sequence space.
         let mut ack_len = 0;
@@ -2061,7 +3537,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
 
                     // We could've sent data before the FIN, so only remove FIN from the sequence
                     // space if all of that data is acknowledged.
-                    if sent_fin && self.tx_buffer.len() + 1 == ack_len {
+                    if sent_fin && self.tx_len() + 1 == ack_len {
                         ack_len -= 1;
                         tcp_trace!("received ACK of FIN");
                         ack_of_fin = true;
@@ -2074,6 +3550,13 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 self.congestion_controller
                     .inner_mut()
                     .on_ack(cx.now(), ack_len, &self.rtte);
+
+                // New data being acknowledged shows that the remote endpoint receives
+                // what we send, so its next hop needn't be probed.
+                if ack_len != 0 || ack_of_fin {
+                    let flow = self.flow(Some(ip_repr.dst_addr()));
+                    cx.confirm_neighbor_reachable(&ip_repr.src_addr(), &flow);
+                }
             }
         }
 
@@ -2104,6 +3587,8 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             (State::SynReceived, TcpControl::Rst) if self.listen_endpoint.port != 0 => {
                 tcp_trace!("received RST");
                 self.tuple = None;
+                // Forget about data accepted with TCP Fast Open.
+                self.rx_buffer.clear();
                 self.set_state(State::Listen);
                 return None;
             }
@@ -2111,7 +3596,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             // RSTs in any other state close the socket.
             (_, TcpControl::Rst) => {
                 tcp_trace!("received RST");
-                self.set_state(State::Closed);
+                self.set_closed(CloseReason::Reset);
                 self.tuple = None;
                 return None;
             }
@@ -2119,11 +3604,11 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             // SYN packets in the LISTEN state change it to SYN-RECEIVED.
             (State::Listen, TcpControl::Syn) => {
                 tcp_trace!("received SYN");
+                if repr.max_seg_size == Some(0) {
+                    tcp_trace!("received SYNACK with zero MSS, ignoring");
+                    return None;
+                }
                 if let Some(max_seg_size) = repr.max_seg_size {
-                    if max_seg_size == 0 {
-                        tcp_trace!("received SYNACK with zero MSS, ignoring");
-                        return None;
-                    }
                     self.congestion_controller
                         .inner_mut()
                         .set_mss(max_seg_size as usize);
@@ -2137,7 +3622,11 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 self.local_seq_no = Self::random_seq_no(cx);
                 self.remote_seq_no = repr.seq_number + 1;
                 self.remote_last_seq = self.local_seq_no;
+                self.auth.set_local_isn(self.local_seq_no);
+                self.auth.set_remote_isn(repr.seq_number);
                 self.remote_has_sack = repr.sack_permitted;
+                // RFC 3168 section 6.1.1: an ECN-setup SYN has both ECE and CWR set.
+                self.remote_has_ecn = self.ecn && repr.ece && repr.cwr;
                 self.remote_win_scale = repr.window_scale;
                 // Remote doesn't support window scaling, don't do it.
                 if self.remote_win_scale.is_none() {
@@ -2149,6 +3638,26 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 }
                 self.set_state(State::SynReceived);
                 self.timer.set_for_idle(cx.now(), self.keep_alive);
+
+                self.fast_open_reply = None;
+                if let Some(cookie) = repr.fast_open.filter(|_| self.fast_open) {
+                    let valid = cx.generate_tcp_fast_open_cookie(&ip_repr.src_addr());
+                    if cookie == valid {
+                        // RFC 7413 section 4.2.2: accept the data in a SYN with a valid cookie.
+                        let len = self.rx_buffer.enqueue_slice(repr.payload);
+                        tcp_trace!("fast open: accepted {} octets", len);
+
+                        #[cfg(feature = "async")]
+                        if len > 0 {
+                            self.rx_waker.wake();
+                        }
+                    } else {
+                        // A cookie request, or a cookie we don't recognize. Hand out a valid
+                        // cookie, and leave the data to be sent after the handshake.
+                        tcp_trace!("fast open: handing out cookie");
+                        self.fast_open_reply = Some(valid);
+                    }
+                }
             }
 
             // ACK packets in the SYN-RECEIVED state change it to ESTABLISHED.
@@ -2187,7 +3696,14 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 self.remote_seq_no = repr.seq_number + 1;
                 self.remote_last_seq = self.local_seq_no + 1;
                 self.remote_last_ack = Some(repr.seq_number);
+                self.auth.set_remote_isn(repr.seq_number);
                 self.remote_has_sack = repr.sack_permitted;
+                // RFC 3168 section 6.1.1: an ECN-setup SYN|ACK has only ECE set.
+                self.remote_has_ecn = if repr.ack_number.is_some() {
+                    self.ecn && repr.ece && !repr.cwr
+                } else {
+                    self.ecn && repr.ece && repr.cwr
+                };
                 self.remote_win_scale = repr.window_scale;
                 // Remote doesn't support window scaling, don't do it.
                 if self.remote_win_scale.is_none() {
@@ -2198,6 +3714,13 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                     self.tsval_generator = None;
                 }
 
+                if let Some(cookie) = repr.fast_open.filter(|cookie| !cookie.is_request()) {
+                    if self.fast_open_connect {
+                        tcp_trace!("fast open: received cookie");
+                        cx.set_tcp_fast_open_cookie(ip_repr.src_addr(), cookie);
+                    }
+                }
+
                 if repr.ack_number.is_some() {
                     self.set_state(State::Established);
                 } else {
@@ -2259,7 +3782,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             (State::LastAck, TcpControl::None) => {
                 if ack_of_fin {
                     // Clear the remote endpoint, or we'll send an RST there.
-                    self.set_state(State::Closed);
+                    self.set_closed(CloseReason::Graceful);
                     self.tuple = None;
                 }
             }
@@ -2270,6 +3793,18 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             }
         }
 
+        if self.remote_has_ecn && repr.control != TcpControl::Syn {
+            // RFC 3168 section 6.1.3: echo congestion marks until the remote
+            // confirms it reduced its congestion window.
+            if repr.cwr {
+                self.ecn_echo = false;
+            }
+            if ip_repr.ecn() == IpEcn::Ce {
+                tcp_trace!("received CE mark");
+                self.ecn_echo = true;
+            }
+        }
+
         self.apply_peer_sender_state(cx.now(), repr, repr.ack_number, ack_len, ack_all);
 
         let payload_len = payload.len();
@@ -2277,7 +3812,15 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             return None;
         }
 
+        if let Some(autotune) = &mut self.rx_autotune {
+            autotune.on_segment(payload_len);
+        }
+
         let assembler_was_empty = self.assembler.is_empty();
+        if self.ack_to_transmit() {
+            // The ACK owed for earlier segments will cover this one too.
+            self.counters.acks_coalesced += 1;
+        }
 
         // Try adding payload octets to the assembler.
         let Ok(contig_len) = self
@@ -2344,14 +3887,28 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         }
 
         // Handle delayed acks
-        if let Some(ack_delay) = self.ack_delay {
+        let filled_hole = !assembler_was_empty && self.assembler.is_empty();
+        let quick_ack = self.quick_ack.on_data(
+            &self.ack_policy,
+            cx.now(),
+            self.rtte.retransmission_timeout(),
+            filled_hole,
+        );
+        let ack_now = quick_ack
+            || (self.ack_policy.ack_on_push && repr.control == TcpControl::Psh)
+            || self.immediate_ack_to_transmit();
+        if let Some(ack_delay) = self.ack_policy.delay {
             if self.ack_to_transmit() {
                 self.ack_delay_timer = match self.ack_delay_timer {
+                    AckDelayTimer::Idle if ack_now => {
+                        tcp_trace!("acking at once");
+                        AckDelayTimer::Immediate
+                    }
                     AckDelayTimer::Idle => {
                         tcp_trace!("starting delayed ack timer");
                         AckDelayTimer::Waiting(cx.now() + ack_delay)
                     }
-                    AckDelayTimer::Waiting(_) if self.immediate_ack_to_transmit() => {
+                    AckDelayTimer::Waiting(_) if ack_now => {
                         tcp_trace!("delayed ack timer already started, forcing expiry");
                         AckDelayTimer::Immediate
                     }
@@ -2389,18 +3946,74 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
     }
 
     fn seq_to_transmit(&self, cx: &mut Context) -> bool {
+        !self.pacing_delayed(cx.now()) && self.seq_to_transmit_unpaced(cx)
+    }
+
+    /// Return whether pacing forbids sending data at `timestamp`.
+    fn pacing_delayed(&self, timestamp: Instant) -> bool {
+        self.pacing_next.is_some_and(|next| timestamp < next)
+    }
+
+    /// Return the octets of headers in each segment.
+    fn header_overhead(&self) -> usize {
         let ip_header_len = match self.tuple.unwrap().local.addr {
             #[cfg(feature = "proto-ipv4")]
             IpAddress::Ipv4(_) => crate::wire::IPV4_HEADER_LEN,
             #[cfg(feature = "proto-ipv6")]
             IpAddress::Ipv6(_) => crate::wire::IPV6_HEADER_LEN,
         };
+        ip_header_len + TCP_HEADER_LEN + self.auth.option_len()
+    }
+
+    /// Return the effective max segment size, taking into account the MTU of the path
+    /// and the limit of the remote.
+    fn effective_mss(&self, cx: &Context) -> usize {
+        let mut mtu = cx.path_mtu(&self.tuple.unwrap().remote.addr);
+        if self.mtu_probing {
+            mtu = self.plpmtud.mtu(mtu);
+        }
+        (mtu - self.header_overhead()).min(self.remote_mss)
+    }
 
-        // Max segment size we're able to send due to MTU limitations.
-        let local_mss = cx.ip_mtu() - ip_header_len - TCP_HEADER_LEN;
+    /// Return the MTU and length of a segment probing the path MTU, if one should be
+    /// sent now with the new data at `seq_number`.
+    ///
+    /// A probe is only sent if the windows allow for all of it.
+    fn mtu_probe(
+        &mut self,
+        cx: &Context,
+        seq_number: TcpSeqNumber,
+        win_limit: usize,
+    ) -> Option<(usize, usize)> {
+        if !self.mtu_probing || self.state != State::Established {
+            return None;
+        }
+        let overhead = self.header_overhead();
+        let max_mtu = cx
+            .path_mtu(&self.tuple.unwrap().remote.addr)
+            .min(self.remote_mss + overhead);
+        let mtu = self.plpmtud.probe_mtu(cx.now(), max_mtu)?;
+        let len = mtu - overhead;
+
+        let offset = seq_number - self.local_seq_no;
+        let sacked = self.remote_has_sack
+            && self
+                .sack_scoreboard
+                .next_sacked(seq_number)
+                .is_some_and(|sacked| sacked < seq_number + len);
+        if offset + len > self.tx_len()
+            || len > win_limit
+            || len > self.congestion_window_available(self.effective_mss(cx))
+            || sacked
+        {
+            return None;
+        }
+        Some((mtu, len))
+    }
 
-        // The effective max segment size, taking into account our and remote's limits.
-        let effective_mss = local_mss.min(self.remote_mss);
+    /// Like `seq_to_transmit`, but ignoring pacing.
+    fn seq_to_transmit_unpaced(&self, cx: &mut Context) -> bool {
+        let effective_mss = self.effective_mss(cx);
 
         // Have we sent data that hasn't been ACKed yet?
         let data_in_flight = self.remote_last_seq != self.local_seq_no;
@@ -2410,19 +4023,26 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             return true;
         }
 
+        // During SACK loss recovery, lost segments are retransmitted first.
+        if self.sack_retransmission(effective_mss).is_some() {
+            return true;
+        }
+
         // max sequence number we can send.
-        let max_send_seq =
-            self.local_seq_no + core::cmp::min(self.remote_win_len, self.tx_buffer.len());
+        let max_send_seq = self.local_seq_no + core::cmp::min(self.remote_win_len, self.tx_len());
+
+        // Next sequence number we would send, skipping octets the remote already has.
+        let next_seq = self.next_seq_to_send();
 
         // Max amount of octets we can send.
-        let max_send = if max_send_seq >= self.remote_last_seq {
-            max_send_seq - self.remote_last_seq
+        let max_send = if max_send_seq >= next_seq {
+            max_send_seq - next_seq
         } else {
             0
         };
 
         // Compare max_send with the congestion window.
-        let max_send = max_send.min(self.congestion_controller.inner().window());
+        let max_send = max_send.min(self.congestion_window_available(effective_mss));
 
         // Can we send at least 1 octet?
         let mut can_send = max_send != 0;
@@ -2450,40 +4070,186 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         // 1. We have unsent data that fits in the remote window.
         // 2. We have no unsent data.
         // This condition matches only if #2, because #1 is already covered by can_data and we're ORing them.
-        let can_fin = want_fin && self.remote_last_seq == self.local_seq_no + self.tx_buffer.len();
+        let can_fin = want_fin && self.remote_last_seq == self.local_seq_no + self.tx_len();
 
         can_send || can_fin
     }
 
-    fn delayed_ack_expired(&self, timestamp: Instant) -> bool {
-        match self.ack_delay_timer {
-            AckDelayTimer::Idle => true,
-            AckDelayTimer::Waiting(t) => t <= timestamp,
-            AckDelayTimer::Immediate => true,
+    /// Return the sequence number new or go-back-N retransmitted data starts at.
+    ///
+    /// This is `remote_last_seq`, unless the remote has selectively acknowledged
+    /// the octets there, in which case they are skipped.
+    fn next_seq_to_send(&self) -> TcpSeqNumber {
+        if self.remote_has_sack {
+            self.sack_scoreboard.skip_sacked(self.remote_last_seq)
+        } else {
+            self.remote_last_seq
         }
     }
 
-    fn ack_to_transmit(&self) -> bool {
-        if let Some(remote_last_ack) = self.remote_last_ack {
-            remote_last_ack < self.remote_seq_no + self.rx_buffer.len()
+    /// Return how many more octets the congestion controller allows in flight.
+    ///
+    /// During SACK loss recovery, the octets in flight are estimated from the
+    /// scoreboard (the "pipe"). Otherwise, they are everything sent but not
+    /// acknowledged if the controller's window bounds them, see
+    /// [`AnyController::bounds_in_flight`](congestion::AnyController::bounds_in_flight),
+    /// and the whole window is available if not.
+    fn congestion_window_available(&self, mss: usize) -> usize {
+        let cwnd = self.congestion_controller.inner().window();
+        if self.sack_scoreboard.in_recovery() {
+            let pipe = self
+                .sack_scoreboard
+                .pipe(self.local_seq_no, self.remote_last_seq, mss);
+            cwnd.saturating_sub(pipe)
+        } else if self.congestion_controller.bounds_in_flight() {
+            cwnd.saturating_sub(self.remote_last_seq - self.local_seq_no)
         } else {
-            false
+            cwnd
         }
     }
 
-    /// Return whether to send ACK immediately due to the amount of unacknowledged data.
-    ///
+    /// Return the sequence number and length of the hole to retransmit next
+    /// during SACK loss recovery, if any and if the congestion window allows it.
+    fn sack_retransmission(&self, mss: usize) -> Option<(TcpSeqNumber, usize)> {
+        let (seq, len) = self.sack_scoreboard.next_seg(self.local_seq_no, mss)?;
+        if self.sack_scoreboard.must_retransmit() || self.congestion_window_available(mss) >= mss {
+            Some((seq, len))
+        } else {
+            None
+        }
+    }
+
+    /// Run RACK loss detection, and enter SACK loss recovery if anything was found lost.
+    fn rack_detect_loss(&mut self, timestamp: Instant) {
+        // Without SACK, nothing above the cumulative ACK is known to be delivered.
+        if !self.remote_has_sack {
+            return;
+        }
+
+        if self.rack.detect_loss(timestamp, &mut self.sack_scoreboard) {
+            self.enter_sack_recovery(timestamp);
+        }
+    }
+
+    /// Enter SACK loss recovery, and let the congestion controller know unless the
+    /// only data lost is an MTU probe.
+    fn enter_sack_recovery(&mut self, timestamp: Instant) {
+        if !self
+            .sack_scoreboard
+            .enter_recovery(self.local_seq_no, self.remote_last_seq)
+        {
+            return;
+        }
+        if self.mtu_probe_lost() {
+            // RFC 4821 section 7.5: the probe was most likely too big for the path,
+            // which says nothing about congestion.
+            self.plpmtud.on_probe_lost();
+        } else {
+            self.congestion_controller
+                .inner_mut()
+                .on_retransmit(timestamp);
+        }
+    }
+
+    /// Return whether the outstanding MTU probe is the only data lost: it is the
+    /// oldest data outstanding, and either data sent after it was delivered without
+    /// any other loss, or nothing was sent after it.
+    fn mtu_probe_lost(&self) -> bool {
+        let Some((start, end)) = self.plpmtud.probe() else {
+            return false;
+        };
+        if start != self.local_seq_no {
+            return false;
+        }
+        if !self.remote_has_sack {
+            return end == self.remote_last_seq || self.local_rx_dup_acks > 0;
+        }
+        let next_sacked = self.sack_scoreboard.next_sacked(start);
+        self.sack_scoreboard.skip_sacked(start) == start
+            && next_sacked.is_none_or(|sacked| sacked >= end)
+            && (end == self.remote_last_seq || next_sacked.is_some())
+            && !self.sack_scoreboard.is_lost_above(end, self.remote_mss)
+    }
+
+    /// Mark the delivery rate samples to come as application-limited if the
+    /// application doesn't give the socket enough data to fill the congestion
+    /// window, see draft-cheng-iccrg-delivery-rate-estimation section 3.3.4.
+    fn check_app_limited(&mut self, cx: &mut Context) {
+        let effective_mss = self.effective_mss(cx);
+        let in_flight = self.remote_last_seq - self.local_seq_no;
+        if self.tx_len().saturating_sub(in_flight) < effective_mss
+            && self.sack_retransmission(effective_mss).is_none()
+            && in_flight < self.congestion_controller.inner().window()
+        {
+            self.rate.on_app_limited(in_flight);
+        }
+    }
+
+    /// Return the tail loss probe timeout (RFC 8985 section 7.2), if a probe
+    /// should be scheduled instead of a retransmission timeout.
+    fn tail_loss_probe_timeout(&self) -> Option<Duration> {
+        if !self.rack_tlp || !self.rtte.have_measurement || self.sack_scoreboard.in_recovery() {
+            return None;
+        }
+        if !matches!(
+            self.state,
+            State::Established
+                | State::FinWait1
+                | State::Closing
+                | State::CloseWait
+                | State::LastAck
+        ) {
+            return None;
+        }
+
+        let mut pto = Duration::from_millis(2 * self.rtte.srtt as u64);
+        if self.remote_last_seq - self.local_seq_no <= self.remote_mss {
+            pto += TLP_WORST_CASE_ACK_DELAY;
+        }
+        Some(pto)
+    }
+
+    /// Start the timer that recovers from a lost tail: the tail loss probe timer
+    /// if RACK-TLP is enabled and it expires first, otherwise the retransmission timer.
+    fn set_timer_for_retransmit(&mut self, timestamp: Instant) {
+        let rto = self.rtte.retransmission_timeout();
+        match self.tail_loss_probe_timeout() {
+            Some(pto) if pto < rto => self.timer.set_for_tail_loss_probe(timestamp, pto),
+            _ => self.timer.set_for_retransmit(timestamp, rto),
+        }
+    }
+
+    fn delayed_ack_expired(&self, timestamp: Instant) -> bool {
+        match self.ack_delay_timer {
+            AckDelayTimer::Idle => true,
+            AckDelayTimer::Waiting(t) => t <= timestamp,
+            AckDelayTimer::Immediate => true,
+        }
+    }
+
+    fn ack_to_transmit(&self) -> bool {
+        if let Some(remote_last_ack) = self.remote_last_ack {
+            remote_last_ack < self.remote_seq_no + self.rx_buffer.len()
+        } else {
+            false
+        }
+    }
+
+    /// Return whether to send ACK immediately due to the amount of unacknowledged data, that is
+    /// when `every_segments` full-sized segments of the ACK policy are unacknowledged.
+    ///
     /// RFC 9293 states "An ACK SHOULD be generated for at least every second full-sized segment or
     /// 2*RMSS bytes of new data (where RMSS is the MSS specified by the TCP endpoint receiving the
     /// segments to be acknowledged, or the default value if not specified) (SHLD-19)."
     ///
     /// Note that the RFC above only says "at least 2*RMSS bytes", which is not a hard requirement.
     /// In practice, we follow the Linux kernel's empirical value of sending an ACK for every RMSS
-    /// byte of new data. For details, see
+    /// byte of new data, which is the default. For details, see
     /// <https://elixir.bootlin.com/linux/v6.11.4/source/net/ipv4/tcp_input.c#L5747>.
     fn immediate_ack_to_transmit(&self) -> bool {
         if let Some(remote_last_ack) = self.remote_last_ack {
-            remote_last_ack + self.remote_mss < self.remote_seq_no + self.rx_buffer.len()
+            let threshold = self.remote_mss * self.ack_policy.every_segments.saturating_sub(1);
+            remote_last_ack + threshold < self.remote_seq_no + self.rx_buffer.len()
         } else {
             false
         }
@@ -2513,6 +4279,34 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         }
     }
 
+    /// Grow the receive window if the application reads faster than it allows, see
+    /// `autotune`.
+    fn autotune_rx(&mut self, now: Instant) {
+        if !matches!(
+            self.state,
+            State::Established | State::FinWait1 | State::FinWait2
+        ) {
+            return;
+        }
+        let Some(limit) = self.rx_autotune_limit() else {
+            return;
+        };
+        // Until an RTT is measured, the retransmission timeout bounds it.
+        let rtt = self
+            .rtte
+            .smoothed_rtt()
+            .unwrap_or_else(|| self.rtte.retransmission_timeout());
+        // NOTE(unwrap): there is a limit only if autotuning is enabled.
+        let autotune = self.rx_autotune.as_mut().unwrap();
+        let Some(window) = autotune.adjust(now, self.remote_seq_no, rtt, limit) else {
+            return;
+        };
+        net_debug!("autotune: receive window grows to {}", window);
+        if window > self.rx_buffer.capacity() {
+            self.rx_buffer.grow(window);
+        }
+    }
+
     pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
     where
         F: FnOnce(&mut Context, (IpRepr, TcpRepr)) -> Result<(), E>,
@@ -2536,18 +4330,64 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             .inner_mut()
             .pre_transmit(cx.now());
 
+        self.autotune_rx(cx.now());
+
+        // Check if RACK would now consider outstanding segments lost.
+        if self
+            .rack
+            .reo_timeout()
+            .is_some_and(|reo_timeout| cx.now() >= reo_timeout)
+        {
+            self.rack_detect_loss(cx.now());
+        }
+
+        let mut is_tail_loss_probe = false;
+
         // Check if any state needs to be changed because of a timer.
         if self.timed_out(cx.now()) {
             // If a timeout expires, we should abort the connection.
             net_debug!("timeout exceeded");
-            self.set_state(State::Closed);
+            // With nothing in flight, only keep-alive packets went unanswered.
+            if self.keep_alive.is_some() && self.remote_last_seq == self.local_seq_no {
+                self.set_closed(CloseReason::KeepAliveTimeout);
+            } else {
+                self.set_closed(CloseReason::Timeout);
+            }
+        } else if !self.seq_to_transmit(cx)
+            && self.timer.should_retransmit(cx.now())
+            && !self.sack_scoreboard.in_recovery()
+            && self.mtu_probe_lost()
+        {
+            // The retransmission timer doubles as the probe timer, see RFC 8899
+            // section 5.1.1. The probe was most likely too big for the path, so
+            // its data is resent in smaller segments as if it had never been sent,
+            // without backing off or letting the congestion controller know.
+            net_debug!("mtu probe lost");
+            self.plpmtud.on_probe_lost();
+            self.remote_last_seq = self.local_seq_no;
+            self.timer.set_for_idle(cx.now(), self.keep_alive);
+            self.rtte.on_fast_retransmit();
+        } else if !self.seq_to_transmit(cx)
+            && self.timer.should_retransmit(cx.now())
+            && self
+                .rtte
+                .config
+                .max_retransmissions
+                .is_some_and(|max| self.rto_retransmissions >= max)
+        {
+            // If the retransmit timer expired too many times, give up.
+            net_debug!("retransmission limit exceeded");
+            self.set_closed(CloseReason::RetransmissionLimit);
         } else if !self.seq_to_transmit(cx) && self.timer.should_retransmit(cx.now()) {
             // If a retransmit timer expired, we should resend data starting at the last ACK.
             net_debug!("retransmitting");
 
             // Rewind "last sequence number sent", as if we never
             // had sent them. This will cause all data in the queue
-            // to be sent again.
+            // to be sent again, except what the remote has SACKed.
+            if self.remote_has_sack {
+                self.sack_scoreboard.on_rto(self.remote_last_seq);
+            }
             self.remote_last_seq = self.local_seq_no;
 
             // Clear the `should_retransmit` state. If we can't retransmit right
@@ -2558,11 +4398,29 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
 
             // Inform RTTE, so that it can avoid bogus measurements.
             self.rtte.on_retransmit();
+            self.rto_retransmissions = self.rto_retransmissions.saturating_add(1);
+            self.counters.retransmission_timeouts += 1;
+            self.plpmtud.on_timeout();
 
             // Inform the congestion controller that we're retransmitting.
             self.congestion_controller
                 .inner_mut()
                 .on_retransmit(cx.now());
+        } else if self.timer.should_tail_loss_probe(cx.now()) {
+            // If the tail loss probe timer expired, send new data if possible,
+            // otherwise send the last segment again, to elicit an ACK that
+            // reveals which segments were lost.
+            net_debug!("sending tail loss probe");
+
+            if !self.seq_to_transmit(cx) {
+                let in_flight = self.remote_last_seq - self.local_seq_no;
+                self.remote_last_seq = self.remote_last_seq - in_flight.min(self.remote_mss);
+                is_tail_loss_probe = true;
+            }
+
+            // Only a single probe is sent, then we fall back to the retransmission timer.
+            let rto = self.rtte.retransmission_timeout();
+            self.timer.set_for_retransmit(cx.now(), rto);
         }
 
         #[cfg(feature = "socket-tcp-pause-synack")]
@@ -2570,8 +4428,15 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             return Ok(());
         }
 
+        if matches!(self.state, State::Established | State::CloseWait) {
+            self.check_app_limited(cx);
+        }
+
         // Decide whether we're sending a packet.
-        if self.seq_to_transmit(cx) {
+        if is_tail_loss_probe {
+            // The probe may not pass Nagle's algorithm, send it anyway.
+            tcp_trace!("outgoing segment will be a tail loss probe");
+        } else if self.seq_to_transmit(cx) {
             // If we have data to transmit and it fits into partner's window, do it.
             tcp_trace!("outgoing segment will send data or flags");
         } else if self.ack_to_transmit() && self.delayed_ack_expired(cx.now()) {
@@ -2592,6 +4457,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             // If we have spent enough time in the TIME-WAIT state, close the socket.
             tcp_trace!("TIME-WAIT timer expired");
             self.reset();
+            self.close_reason = Some(CloseReason::Graceful);
             return Ok(());
         } else {
             return Ok(());
@@ -2616,6 +4482,8 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             src_port: tuple.local.port,
             dst_port: tuple.remote.port,
             control: TcpControl::None,
+            ece: self.remote_has_ecn && self.ecn_echo,
+            cwr: false,
             seq_number: self.remote_last_seq,
             ack_number: Some(self.remote_seq_no + self.rx_buffer.len()),
             window_len: self.scaled_window(),
@@ -2627,10 +4495,14 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 self.tsval_generator,
                 self.last_remote_tsval,
             ),
+            fast_open: None,
+            auth: None,
             payload: &[],
         };
 
         let mut is_zero_window_probe = false;
+        let mut is_sack_retransmit = false;
+        let mut mtu_probe = None;
 
         match self.state {
             // We transmit an RST in the CLOSED state. If we ended up in the CLOSED state
@@ -2648,14 +4520,36 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 repr.control = TcpControl::Syn;
                 repr.seq_number = self.local_seq_no;
                 // window len must NOT be scaled in SYNs.
-                repr.window_len = u16::try_from(self.rx_buffer.window()).unwrap_or(u16::MAX);
+                repr.window_len = u16::try_from(self.rx_window()).unwrap_or(u16::MAX);
                 if self.state == State::SynSent {
                     repr.ack_number = None;
                     repr.window_scale = Some(self.remote_win_shift);
                     repr.sack_permitted = true;
+                    repr.ece = self.ecn;
+                    repr.cwr = self.ecn;
+                    if self.fast_open_connect {
+                        match cx.tcp_fast_open_cookie(&tuple.remote.addr) {
+                            Some(cookie) => {
+                                repr.fast_open = Some(cookie);
+                                // RFC 7413 section 4.1.3: send data with the first SYN only, a
+                                // retransmitted SYN may have been dropped because of it.
+                                if self.rtte.max_seq_sent.is_none() {
+                                    let mss = self.remote_mss.min(
+                                        cx.path_mtu(&tuple.remote.addr)
+                                            - ip_repr.header_len()
+                                            - TCP_HEADER_LEN,
+                                    );
+                                    repr.payload = self.tx_chunks.get(&self.tx_buffer, 0, mss);
+                                }
+                            }
+                            None => repr.fast_open = Some(TcpFastOpenCookie::request()),
+                        }
+                    }
                 } else {
                     repr.sack_permitted = self.remote_has_sack;
                     repr.window_scale = self.remote_win_scale.map(|_| self.remote_win_shift);
+                    repr.ece = self.remote_has_ecn;
+                    repr.fast_open = self.fast_open_reply;
                 }
             }
 
@@ -2666,52 +4560,101 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             | State::Closing
             | State::CloseWait
             | State::LastAck => {
-                // Extract as much data as the remote side can receive in this packet
-                // from the transmit buffer.
+                // MSS we can send, limited by the path MTU and the MSS the remote accepts.
+                let effective_mss = self.effective_mss(cx);
 
-                // Right edge of window, ie the max sequence number we're allowed to send.
-                let win_right_edge = self.local_seq_no + self.remote_win_len;
+                // A tail loss probe was due, so it is exempt from pacing.
+                let paced = self.pacing_delayed(cx.now()) && !is_tail_loss_probe;
 
-                // Max amount of octets we're allowed to send according to the remote window.
-                let mut win_limit = if win_right_edge >= self.remote_last_seq {
-                    win_right_edge - self.remote_last_seq
+                if let Some((seq_number, size)) =
+                    self.sack_retransmission(effective_mss).filter(|_| !paced)
+                {
+                    // Retransmit a hole in the SACK scoreboard. This doesn't affect
+                    // the highest sequence number sent.
+                    repr.seq_number = seq_number;
+                    repr.payload =
+                        self.tx_chunks
+                            .get(&self.tx_buffer, seq_number - self.local_seq_no, size);
+                    is_sack_retransmit = true;
                 } else {
-                    // This can happen if we've sent some data and later the remote side
-                    // has shrunk its window so that data is no longer inside the window.
-                    // This should be very rare and is strongly discouraged by the RFCs,
-                    // but it does happen in practice.
-                    // http://www.tcpipguide.com/free/t_TCPWindowManagementIssues.htm
-                    0
-                };
+                    // Extract as much data as the remote side can receive in this packet
+                    // from the transmit buffer.
+
+                    // Skip over octets the remote has already selectively acknowledged.
+                    repr.seq_number = self.next_seq_to_send();
+
+                    // Right edge of window, ie the max sequence number we're allowed to send.
+                    let win_right_edge = self.local_seq_no + self.remote_win_len;
+
+                    // Max amount of octets we're allowed to send according to the remote window.
+                    let mut win_limit = if win_right_edge >= repr.seq_number {
+                        win_right_edge - repr.seq_number
+                    } else {
+                        // This can happen if we've sent some data and later the remote side
+                        // has shrunk its window so that data is no longer inside the window.
+                        // This should be very rare and is strongly discouraged by the RFCs,
+                        // but it does happen in practice.
+                        // http://www.tcpipguide.com/free/t_TCPWindowManagementIssues.htm
+                        0
+                    };
+
+                    // To send a zero-window-probe, force the window limit to at least 1 byte.
+                    if win_limit == 0 && self.timer.should_zero_window_probe(cx.now()) {
+                        win_limit = 1;
+                        is_zero_window_probe = true;
+                    }
 
-                // To send a zero-window-probe, force the window limit to at least 1 byte.
-                if win_limit == 0 && self.timer.should_zero_window_probe(cx.now()) {
-                    win_limit = 1;
-                    is_zero_window_probe = true;
-                }
+                    // Maximum size we're allowed to send. This can be limited by 3 factors:
+                    // 1. remote window
+                    // 2. MSS the remote is willing to accept, probably determined by their MTU
+                    // 3. MSS we can send, determined by our MTU.
+                    let mut size = win_limit.min(effective_mss);
 
-                // Maximum size we're allowed to send. This can be limited by 3 factors:
-                // 1. remote window
-                // 2. MSS the remote is willing to accept, probably determined by their MTU
-                // 3. MSS we can send, determined by our MTU.
-                let size = win_limit
-                    .min(self.remote_mss)
-                    .min(cx.ip_mtu() - ip_repr.header_len() - TCP_HEADER_LEN);
-
-                let offset = self.remote_last_seq - self.local_seq_no;
-                repr.payload = self.tx_buffer.get_allocated(offset, size);
-
-                // If we've sent everything we had in the buffer, follow it with the PSH or FIN
-                // flags, depending on whether the transmit half of the connection is open.
-                if offset + repr.payload.len() == self.tx_buffer.len() {
-                    match self.state {
-                        State::FinWait1 | State::LastAck | State::Closing => {
-                            repr.control = TcpControl::Fin
+                    // Zero window probes are exempt from congestion control and pacing.
+                    if !is_zero_window_probe {
+                        if self.congestion_controller.bounds_in_flight() {
+                            size = size.min(self.congestion_window_available(effective_mss));
                         }
-                        State::Established | State::CloseWait if !repr.payload.is_empty() => {
-                            repr.control = TcpControl::Psh
+                        if paced {
+                            size = 0;
+                        }
+                    }
+
+                    // Don't resend octets the remote has already selectively acknowledged.
+                    if self.remote_has_sack {
+                        if let Some(sacked) = self.sack_scoreboard.next_sacked(repr.seq_number) {
+                            size = size.min(sacked - repr.seq_number);
+                        }
+                    }
+
+                    // Make a full-sized segment of new data bigger, to probe the path MTU.
+                    let is_new_data = self
+                        .rtte
+                        .max_seq_sent
+                        .is_none_or(|max_seq_sent| repr.seq_number >= max_seq_sent);
+                    if size == effective_mss && is_new_data && !is_tail_loss_probe {
+                        if let Some((mtu, len)) = self.mtu_probe(cx, repr.seq_number, win_limit) {
+                            tcp_trace!("outgoing segment will probe a path MTU of {}", mtu);
+                            size = len;
+                            mtu_probe = Some(mtu);
+                        }
+                    }
+
+                    let offset = repr.seq_number - self.local_seq_no;
+                    repr.payload = self.tx_chunks.get(&self.tx_buffer, offset, size);
+
+                    // If we've sent everything we had in the buffer, follow it with the PSH or FIN
+                    // flags, depending on whether the transmit half of the connection is open.
+                    if offset + repr.payload.len() == self.tx_buffer.len() + self.tx_chunks.len() {
+                        match self.state {
+                            State::FinWait1 | State::LastAck | State::Closing => {
+                                repr.control = TcpControl::Fin
+                            }
+                            State::Established | State::CloseWait if !repr.payload.is_empty() => {
+                                repr.control = TcpControl::Psh
+                            }
+                            _ => (),
                         }
-                        _ => (),
                     }
                 }
             }
@@ -2733,6 +4676,23 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             is_keep_alive = false;
         }
 
+        // RFC 3168 section 6.1.5: only new data is sent ECN-capable; retransmissions,
+        // probes, and segments without data are not.
+        let is_retransmission = is_sack_retransmit
+            || self
+                .rtte
+                .max_seq_sent
+                .is_some_and(|max_seq_sent| repr.seq_number < max_seq_sent);
+        if self.remote_has_ecn
+            && !repr.payload.is_empty()
+            && !is_keep_alive
+            && !is_zero_window_probe
+            && !is_retransmission
+        {
+            ip_repr.set_ecn(IpEcn::Ect0);
+            repr.cwr = self.ecn_cwr_pending;
+        }
+
         // Trace a summary of what will be sent.
         if is_keep_alive {
             tcp_trace!("sending a keep-alive");
@@ -2740,7 +4700,7 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
             tcp_trace!(
                 "tx buffer: sending {} octets at offset {}",
                 repr.payload.len(),
-                self.remote_last_seq - self.local_seq_no
+                repr.seq_number - self.local_seq_no
             );
         }
         if repr.control != TcpControl::None || repr.payload.is_empty() {
@@ -2769,9 +4729,32 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         // Bailing out if the packet isn't placed in the device buffer allows us
         // to not waste time waiting for the retransmit timer on packets that we know
         // for sure will not be successfully transmitted.
+        repr.auth = self.auth.sign(tuple.local, tuple.remote, &repr);
+
         ip_repr.set_payload_len(repr.buffer_len());
         emit(cx, (ip_repr, repr))?;
 
+        if let Some(tcp_ts) = repr.timestamp {
+            self.last_local_tsval = tcp_ts.tsval;
+        }
+        self.counters.segments_out += 1;
+        if !is_keep_alive {
+            self.counters.bytes_sent += repr.payload.len() as u64;
+            if is_retransmission {
+                self.counters.bytes_retransmitted += repr.payload.len() as u64;
+            }
+        }
+        if is_zero_window_probe {
+            self.counters.zero_window_probes += 1;
+        }
+        if is_retransmission && repr.segment_len() > 0 {
+            self.plpmtud.on_retransmit(repr.seq_number);
+        }
+        if let Some(mtu) = mtu_probe {
+            self.plpmtud
+                .on_probe_sent(mtu, repr.seq_number, repr.seq_number + repr.segment_len());
+        }
+
         // We've sent something, whether useful data or a keep-alive packet, so rewind
         // the keep-alive timer.
         self.timer.rewind_keep_alive(cx.now(), self.keep_alive);
@@ -2790,7 +4773,8 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
 
         // Leave the rest of the state intact if sending a zero-window probe.
         if is_zero_window_probe {
-            self.timer.rewind_zero_window_probe(cx.now());
+            self.timer
+                .rewind_zero_window_probe(cx.now(), self.rtte.config.max);
             return Ok(());
         }
 
@@ -2801,11 +4785,39 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
         }
 
         // We've sent a packet successfully, so we can update the internal state now.
-        self.remote_last_seq = repr.seq_number + repr.segment_len();
+        if is_sack_retransmit {
+            self.sack_scoreboard
+                .on_retransmit(repr.seq_number + repr.segment_len());
+            self.rtte.on_fast_retransmit();
+        } else {
+            self.remote_last_seq = repr.seq_number + repr.segment_len();
+        }
         self.remote_last_ack = repr.ack_number;
         self.remote_last_win = repr.window_len;
+        if repr.cwr && repr.control != TcpControl::Syn {
+            self.ecn_cwr_pending = false;
+        }
+
+        if repr.segment_len() > 0 && self.rack_tlp {
+            let end = repr.seq_number + repr.segment_len();
+            let retransmitted = self
+                .rtte
+                .max_seq_sent
+                .is_some_and(|max_seq_sent| repr.seq_number < max_seq_sent);
+            if is_tail_loss_probe && retransmitted {
+                self.rtte.on_fast_retransmit();
+            }
+            self.rack
+                .on_send(repr.seq_number, end, cx.now(), retransmitted);
+        }
 
         if repr.segment_len() > 0 {
+            self.rate.on_send(
+                repr.seq_number,
+                repr.seq_number + repr.segment_len(),
+                cx.now(),
+                is_retransmission,
+            );
             self.rtte
                 .on_send(cx.now(), repr.seq_number + repr.segment_len());
             self.congestion_controller
@@ -2813,12 +4825,23 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 .post_transmit(cx.now(), repr.segment_len());
         }
 
-        if repr.segment_len() > 0 && !self.timer.is_retransmit() {
+        if !repr.payload.is_empty() {
+            // Hold off the next segment for as long as this one takes at the pacing rate.
+            self.pacing_next = self.pacing_rate().map(|rate| {
+                let micros = (repr.payload.len() as u64).saturating_mul(1_000_000) / rate;
+                cx.now() + Duration::from_micros(micros)
+            });
+        }
+
+        if repr.segment_len() > 0
+            && (!self.timer.is_retransmit() || self.timer.is_tail_loss_probe())
+        {
             // RFC 6298 (5.1) Every time a packet containing data is sent (including a
             // retransmission), if the timer is not running, start it running
             // so that it will expire after RTO seconds.
-            let rto = self.rtte.retransmission_timeout();
-            self.timer.set_for_retransmit(cx.now(), rto);
+            //
+            // RFC 8985 (7.2) The tail loss probe timer is restarted on every transmission.
+            self.set_timer_for_retransmit(cx.now());
         }
 
         if self.state == State::Closed {
@@ -2870,11 +4893,28 @@ impl<'a, B: SocketBufferT<'a>> Socket<'a, B> {
                 (_, _) => PollAt::Ingress,
             };
 
+            let rack_poll_at = match self.rack.reo_timeout() {
+                Some(reo_timeout) => PollAt::Time(reo_timeout),
+                None => PollAt::Ingress,
+            };
+
+            // If only pacing holds back the next segment, send it as soon as it may go.
+            let pacing_poll_at = match self.pacing_next {
+                Some(pacing_next) if self.seq_to_transmit_unpaced(cx) => PollAt::Time(pacing_next),
+                _ => PollAt::Ingress,
+            };
+
             // We wait for the earliest of our timers to fire.
-            *[self.timer.poll_at(), timeout_poll_at, delayed_ack_poll_at]
-                .iter()
-                .min()
-                .unwrap_or(&PollAt::Ingress)
+            *[
+                self.timer.poll_at(),
+                timeout_poll_at,
+                delayed_ack_poll_at,
+                rack_poll_at,
+                pacing_poll_at,
+            ]
+            .iter()
+            .min()
+            .unwrap_or(&PollAt::Ingress)
         }
     }
 }
@@ -2928,7 +4968,10 @@ impl<'a, B: SocketBufferT<'a>> fmt::Write for Socket<'a, B> {
 #[cfg(all(test, feature = "medium-ip"))]
 mod test {
     use super::*;
-    use crate::wire::IpRepr;
+    use crate::phy::ChecksumCapabilities;
+    use crate::wire::{
+        IpRepr, TcpAoAlgorithm, TcpAoTrafficKey, TcpAuth, TcpAuthKey, TcpAuthSecret, TcpPacket,
+    };
     use std::ops::{Deref, DerefMut};
     use std::vec::Vec;
 
@@ -2997,11 +5040,14 @@ mod test {
         next_header: IpProtocol::Tcp,
         payload_len: 20,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     });
     const SEND_TEMPL: TcpRepr<'static> = TcpRepr {
         src_port: REMOTE_PORT,
         dst_port: LOCAL_PORT,
         control: TcpControl::None,
+        ece: false,
+        cwr: false,
         seq_number: TcpSeqNumber(0),
         ack_number: Some(TcpSeqNumber(0)),
         window_len: 256,
@@ -3010,6 +5056,8 @@ mod test {
         sack_permitted: false,
         sack_ranges: [None, None, None],
         timestamp: None,
+        fast_open: None,
+        auth: None,
         payload: &[],
     };
     const _RECV_IP_TEMPL: IpRepr = IpReprIpvX(IpvXRepr {
@@ -3018,11 +5066,14 @@ mod test {
         next_header: IpProtocol::Tcp,
         payload_len: 20,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     });
     const RECV_TEMPL: TcpRepr<'static> = TcpRepr {
         src_port: LOCAL_PORT,
         dst_port: REMOTE_PORT,
         control: TcpControl::None,
+        ece: false,
+        cwr: false,
         seq_number: TcpSeqNumber(0),
         ack_number: Some(TcpSeqNumber(0)),
         window_len: 64,
@@ -3031,6 +5082,8 @@ mod test {
         sack_permitted: false,
         sack_ranges: [None, None, None],
         timestamp: None,
+        fast_open: None,
+        auth: None,
         payload: &[],
     };
 
@@ -3070,6 +5123,7 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: repr.buffer_len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         });
         net_trace!("send: {}", repr);
 
@@ -6816,78 +8870,344 @@ mod test {
         recv_nothing!(s);
     }
 
-    #[test]
-    fn test_retransmit_exponential_backoff() {
+    fn sack_range(left: TcpSeqNumber, right: TcpSeqNumber) -> Option<(u32, u32)> {
+        Some((left.0 as u32, right.0 as u32))
+    }
+
+    fn socket_established_sack_with_data() -> TestSocket {
         let mut s = socket_established();
-        s.send_slice(b"abcdef").unwrap();
-        recv!(s, time 0, Ok(TcpRepr {
+        s.remote_has_sack = true;
+        s.remote_mss = 6;
+
+        // Normal ACK of previously received segment
+        send!(s, time 0, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            ..SEND_TEMPL
+        });
+
+        s.send_slice(b"xxxxxxyyyyyywwwwwwzzzzzz").unwrap();
+        for (i, chunk) in [b"xxxxxx", b"yyyyyy", b"wwwwww", b"zzzzzz"]
+            .iter()
+            .enumerate()
+        {
+            recv!(s, time 1000, Ok(TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 6 * i,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload:    &chunk[..],
+                ..RECV_TEMPL
+            }));
+        }
+        s
+    }
+
+    #[test]
+    fn test_sack_recovery_retransmits_holes_only() {
+        let mut s = socket_established_sack_with_data();
+
+        // "xxxxxx" and "wwwwww" are lost.
+        let sacks = [
+            sack_range(LOCAL_SEQ + 1 + 6, LOCAL_SEQ + 1 + 12),
+            sack_range(LOCAL_SEQ + 1 + 18, LOCAL_SEQ + 1 + 24),
+            None,
+        ];
+        for _ in 0..3 {
+            recv_nothing!(s, time 1050);
+            send!(s, time 1050, TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                sack_ranges: sacks,
+                ..SEND_TEMPL
+            });
+        }
+        assert!(s.sack_scoreboard.in_recovery());
+
+        // Only the first hole is retransmitted, nothing SACKed is sent again.
+        recv!(s, time 1100, Ok(TcpRepr {
             seq_number: LOCAL_SEQ + 1,
             ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"abcdef"[..],
+            payload:    &b"xxxxxx"[..],
             ..RECV_TEMPL
         }));
+        recv_nothing!(s, time 1100);
 
-        let expected_retransmission_instant = s.rtte.retransmission_timeout().total_millis() as i64;
-        recv_nothing!(s, time expected_retransmission_instant - 1);
-        recv!(s, time expected_retransmission_instant, Ok(TcpRepr {
-            seq_number: LOCAL_SEQ + 1,
+        // The partial ACK reveals the second hole, which is retransmitted next.
+        send!(s, time 1150, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 12),
+            sack_ranges: [sacks[1], None, None],
+            ..SEND_TEMPL
+        });
+        recv!(s, time 1150, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 12,
             ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"abcdef"[..],
+            payload:    &b"wwwwww"[..],
             ..RECV_TEMPL
         }));
+        recv_nothing!(s, time 1150);
 
-        // "current time" is expected_retransmission_instant, and we want to wait 2 * retransmission timeout
-        let expected_retransmission_instant = 3 * expected_retransmission_instant;
+        // Everything is acknowledged, recovery is over.
+        send!(s, time 1200, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 24),
+            ..SEND_TEMPL
+        });
+        assert!(!s.sack_scoreboard.in_recovery());
+        assert!(s.timer.is_idle());
+    }
 
-        recv_nothing!(s, time expected_retransmission_instant - 1);
-        recv!(s, time expected_retransmission_instant, Ok(TcpRepr {
+    #[test]
+    fn test_sack_recovery_on_lost_first_segment() {
+        let mut s = socket_established_sack_with_data();
+
+        // A single duplicate ACK SACKing more than 2 * MSS octets is enough
+        // to consider the first segment lost.
+        send!(s, time 1050, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            sack_ranges: [sack_range(LOCAL_SEQ + 1 + 6, LOCAL_SEQ + 1 + 24), None, None],
+            ..SEND_TEMPL
+        });
+        recv!(s, time 1050, Ok(TcpRepr {
             seq_number: LOCAL_SEQ + 1,
             ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"abcdef"[..],
+            payload:    &b"xxxxxx"[..],
             ..RECV_TEMPL
         }));
+        recv_nothing!(s, time 1050);
     }
 
     #[test]
-    fn test_data_retransmit_ack_more_than_expected() {
+    fn test_sack_retransmit_timeout_skips_sacked() {
+        let mut s = socket_established_sack_with_data();
+
+        // Not enough evidence to enter loss recovery.
+        send!(s, time 1050, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            sack_ranges: [
+                sack_range(LOCAL_SEQ + 1 + 6, LOCAL_SEQ + 1 + 12),
+                sack_range(LOCAL_SEQ + 1 + 18, LOCAL_SEQ + 1 + 24),
+                None,
+            ],
+            ..SEND_TEMPL
+        });
+        assert!(!s.sack_scoreboard.in_recovery());
+        recv_nothing!(s, time 1050);
+
+        // The retransmission timeout resends only what wasn't SACKed.
+        recv!(s, time 2000, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"xxxxxx"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 2000, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 12,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"wwwwww"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 2000);
+    }
+
+    fn socket_established_rack_tlp(rack_tlp: bool) -> TestSocket {
         let mut s = socket_established();
+        s.remote_has_sack = true;
         s.remote_mss = 6;
-        s.send_slice(b"aaaaaabbbbbbcccccc").unwrap();
+        s.set_rack_tlp_enabled(rack_tlp);
 
+        // Take an RTT sample of 50ms.
+        s.send_slice(b"abcdef").unwrap();
         recv!(s, time 0, Ok(TcpRepr {
             seq_number: LOCAL_SEQ + 1,
             ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"aaaaaa"[..],
+            payload:    &b"abcdef"[..],
             ..RECV_TEMPL
         }));
-        recv!(s, time 0, Ok(TcpRepr {
+        send!(s, time 50, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.rtte.srtt, 50);
+
+        s.send_slice(b"xxxxxxyyyyyy").unwrap();
+        recv!(s, time 100, Ok(TcpRepr {
             seq_number: LOCAL_SEQ + 1 + 6,
             ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"bbbbbb"[..],
+            payload:    &b"xxxxxx"[..],
             ..RECV_TEMPL
         }));
-        recv!(s, time 0, Ok(TcpRepr {
+        recv!(s, time 100, Ok(TcpRepr {
             seq_number: LOCAL_SEQ + 1 + 12,
             ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"cccccc"[..],
+            payload:    &b"yyyyyy"[..],
             ..RECV_TEMPL
         }));
-        recv_nothing!(s, time 0);
+        s
+    }
 
-        recv_nothing!(s, time 50);
+    #[test]
+    fn test_tail_loss_probe() {
+        let mut s = socket_established_rack_tlp(true);
+        assert_eq!(
+            s.timer,
+            Timer::TailLossProbe {
+                expires_at: Instant::from_millis(200)
+            }
+        );
 
-        // retransmit timer expires, we want to retransmit all 3 packets
-        // but we only manage to retransmit 2 (due to e.g. lack of device buffer space)
-        assert!(s.timer.is_retransmit());
-        recv!(s, time 1000, Ok(TcpRepr {
-            seq_number: LOCAL_SEQ + 1,
+        // After two round trips without an ACK, the last segment is sent again.
+        recv_nothing!(s, time 199);
+        recv!(s, time 200, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 12,
             ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"aaaaaa"[..],
+            payload:    &b"yyyyyy"[..],
             ..RECV_TEMPL
         }));
-        recv!(s, time 1000, Ok(TcpRepr {
-            seq_number: LOCAL_SEQ + 1 + 6,
-            ack_number: Some(REMOTE_SEQ + 1),
+        recv_nothing!(s, time 200);
+        assert!(matches!(s.timer, Timer::Retransmit { .. }));
+
+        // The probe is SACKed, which shows that "xxxxxx" was lost.
+        send!(s, time 250, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            sack_ranges: [sack_range(LOCAL_SEQ + 1 + 12, LOCAL_SEQ + 1 + 18), None, None],
+            ..SEND_TEMPL
+        });
+        recv!(s, time 250, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"xxxxxx"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 250);
+
+        send!(s, time 300, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 18),
+            ..SEND_TEMPL
+        });
+        assert!(s.timer.is_idle());
+    }
+
+    #[test]
+    fn test_tail_loss_probe_disabled() {
+        let mut s = socket_established_rack_tlp(false);
+        assert_eq!(
+            s.timer,
+            Timer::Retransmit {
+                expires_at: Instant::from_millis(1100)
+            }
+        );
+
+        // Without RACK-TLP, the lost tail waits for the retransmission timeout.
+        recv_nothing!(s, time 1099);
+        recv!(s, time 1100, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"xxxxxx"[..],
+            ..RECV_TEMPL
+        }));
+    }
+
+    #[test]
+    fn test_rack_detects_loss_by_time() {
+        let mut s = socket_established_rack_tlp(true);
+
+        // A single duplicate ACK SACKing "yyyyyy". This isn't enough to consider
+        // "xxxxxx" lost by counting, but it is once the reordering window of
+        // min_rtt / 4 has passed since the RTT of "yyyyyy".
+        send!(s, time 160, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            sack_ranges: [sack_range(LOCAL_SEQ + 1 + 12, LOCAL_SEQ + 1 + 18), None, None],
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.rack.reo_timeout(), Some(Instant::from_micros(172_500)));
+        recv_nothing!(s, time 172);
+        recv!(s, time 173, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"xxxxxx"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 173);
+    }
+
+    #[test]
+    fn test_retransmit_exponential_backoff() {
+        let mut s = socket_established();
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+
+        let expected_retransmission_instant = s.rtte.retransmission_timeout().total_millis() as i64;
+        recv_nothing!(s, time expected_retransmission_instant - 1);
+        recv!(s, time expected_retransmission_instant, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+
+        // "current time" is expected_retransmission_instant, and we want to wait 2 * retransmission timeout
+        let expected_retransmission_instant = 3 * expected_retransmission_instant;
+
+        recv_nothing!(s, time expected_retransmission_instant - 1);
+        recv!(s, time expected_retransmission_instant, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+    }
+
+    #[test]
+    fn test_data_retransmit_ack_more_than_expected() {
+        let mut s = socket_established();
+        s.remote_mss = 6;
+        s.send_slice(b"aaaaaabbbbbbcccccc").unwrap();
+
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"aaaaaa"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"bbbbbb"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 12,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"cccccc"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 0);
+
+        recv_nothing!(s, time 50);
+
+        // retransmit timer expires, we want to retransmit all 3 packets
+        // but we only manage to retransmit 2 (due to e.g. lack of device buffer space)
+        assert!(s.timer.is_retransmit());
+        recv!(s, time 1000, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"aaaaaa"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 1000, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
             payload:    &b"bbbbbb"[..],
             ..RECV_TEMPL
         }));
@@ -7284,7 +9604,7 @@ mod test {
         let mut s = socket_established();
         s.rx_buffer = SocketBuffer::new(vec![0; 6]);
         s.assembler = Assembler::new();
-        s.ack_delay = None;
+        s.set_ack_delay(None);
 
         send!(
             s,
@@ -7382,7 +9702,7 @@ mod test {
     #[test]
     fn test_window_update_with_delay_ack() {
         let mut s = socket_established_with_buffer_sizes(6, 6);
-        s.ack_delay = Some(Duration::from_millis(10));
+        s.set_ack_delay(Some(Duration::from_millis(10)));
 
         send!(
             s,
@@ -7926,6 +10246,7 @@ mod test {
             ..RECV_TEMPL
         }));
         assert_eq!(s.state, State::Closed);
+        assert_eq!(s.close_reason(), Some(CloseReason::Timeout));
     }
 
     #[test]
@@ -7973,6 +10294,7 @@ mod test {
         }));
         recv_nothing!(s, time 205);
         assert_eq!(s.state, State::Closed);
+        assert_eq!(s.close_reason(), Some(CloseReason::KeepAliveTimeout));
     }
 
     #[test]
@@ -8137,6 +10459,15 @@ mod test {
         s.set_hop_limit(Some(0));
     }
 
+    #[test]
+    #[cfg(feature = "packetmeta-mark")]
+    fn test_set_mark() {
+        let mut s = socket_syn_received();
+        s.set_mark(7);
+        s.reset();
+        assert_eq!(s.mark(), 7);
+    }
+
     // =========================================================================================//
     // Tests for reassembly.
     // =========================================================================================//
@@ -8732,89 +11063,3203 @@ mod test {
     }
 
     // =========================================================================================//
-    // Tests for packet filtering.
+    // Tests for pacing
     // =========================================================================================//
 
     #[test]
-    fn test_doesnt_accept_wrong_port() {
+    fn test_pacing_spreads_segments() {
         let mut s = socket_established();
-        s.rx_buffer = SocketBuffer::new(vec![0; 6]);
-        s.assembler = Assembler::new();
+        s.remote_mss = 6;
+        // One 6-octet segment per millisecond.
+        s.set_pacing(Pacing::Fixed(6000));
+        assert_eq!(s.pacing_rate(), Some(6000));
+        s.send_slice(b"abcdef123456ABCDEF").unwrap();
 
-        let tcp_repr = TcpRepr {
-            seq_number: REMOTE_SEQ + 1,
-            ack_number: Some(LOCAL_SEQ + 1),
-            dst_port: LOCAL_PORT + 1,
-            ..SEND_TEMPL
-        };
-        assert!(!s.socket.accepts(&mut s.cx, &SEND_IP_TEMPL, &tcp_repr));
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 0);
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(1))
+        );
+        recv!(s, time 1, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"123456"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 1);
+        recv!(s, time 2, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 12,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"ABCDEF"[..],
+            ..RECV_TEMPL
+        }));
+    }
 
-        let tcp_repr = TcpRepr {
+    #[test]
+    fn test_pacing_doesnt_delay_ack() {
+        let mut s = socket_established();
+        s.remote_mss = 6;
+        s.set_ack_delay(None);
+        s.set_pacing(Pacing::Fixed(6000));
+        s.send_slice(b"abcdef123456").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+
+        // Incoming data is acknowledged right away, without piggybacking paced data.
+        send!(s, time 0, TcpRepr {
             seq_number: REMOTE_SEQ + 1,
             ack_number: Some(LOCAL_SEQ + 1),
-            src_port: REMOTE_PORT + 1,
+            payload:    &b"xyz"[..],
             ..SEND_TEMPL
-        };
-        assert!(!s.socket.accepts(&mut s.cx, &SEND_IP_TEMPL, &tcp_repr));
+        });
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 1, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            payload:    &b"123456"[..],
+            window_len: 61,
+            ..RECV_TEMPL
+        }));
     }
 
     #[test]
-    fn test_doesnt_accept_wrong_ip() {
+    fn test_pacing_disabled() {
         let mut s = socket_established();
+        assert_eq!(s.pacing(), Pacing::Controller);
+        assert_eq!(s.pacing_rate(), None);
+        s.set_pacing(Pacing::Fixed(1000));
+        s.set_pacing(Pacing::Disabled);
+        assert_eq!(s.pacing_rate(), None);
 
-        let tcp_repr = TcpRepr {
-            seq_number: REMOTE_SEQ + 1,
-            ack_number: Some(LOCAL_SEQ + 1),
-            payload: &b"abcdef"[..],
-            ..SEND_TEMPL
-        };
+        s.remote_mss = 6;
+        s.send_slice(b"abcdef123456").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"123456"[..],
+            ..RECV_TEMPL
+        }));
+    }
 
-        let ip_repr = IpReprIpvX(IpvXRepr {
-            src_addr: REMOTE_ADDR,
-            dst_addr: LOCAL_ADDR,
-            next_header: IpProtocol::Tcp,
-            payload_len: tcp_repr.buffer_len(),
-            hop_limit: 64,
-        });
-        assert!(s.socket.accepts(&mut s.cx, &ip_repr, &tcp_repr));
+    // =========================================================================================//
+    // Tests for ECN
+    // =========================================================================================//
 
-        let ip_repr_wrong_src = IpReprIpvX(IpvXRepr {
-            src_addr: OTHER_ADDR,
+    fn send_with_ecn(s: &mut TestSocket, timestamp: Instant, ecn: IpEcn, repr: &TcpRepr) {
+        s.cx.set_now(timestamp);
+        let mut ip_repr = IpReprIpvX(IpvXRepr {
+            src_addr: REMOTE_ADDR,
             dst_addr: LOCAL_ADDR,
             next_header: IpProtocol::Tcp,
-            payload_len: tcp_repr.buffer_len(),
+            payload_len: repr.buffer_len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         });
-        assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_src, &tcp_repr));
+        ip_repr.set_ecn(ecn);
+        assert!(s.socket.accepts(&mut s.cx, &ip_repr, repr));
+        assert_eq!(s.socket.process(&mut s.cx, &ip_repr, repr), None);
+    }
 
-        let ip_repr_wrong_dst = IpReprIpvX(IpvXRepr {
-            src_addr: REMOTE_ADDR,
-            dst_addr: OTHER_ADDR,
-            next_header: IpProtocol::Tcp,
-            payload_len: tcp_repr.buffer_len(),
-            hop_limit: 64,
+    #[track_caller]
+    fn recv_with_ecn(s: &mut TestSocket, timestamp: Instant, ecn: IpEcn, expected: &TcpRepr) {
+        s.cx.set_now(timestamp);
+        let mut sent = 0;
+        let result: Result<(), ()> = s.socket.dispatch(&mut s.cx, |_, (ip_repr, mut tcp_repr)| {
+            tcp_repr.control = tcp_repr.control.quash_psh();
+            assert_eq!(ip_repr.ecn(), ecn);
+            assert_eq!(&tcp_repr, expected);
+            sent += 1;
+            Ok(())
         });
-        assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_dst, &tcp_repr));
+        assert_eq!(result, Ok(()));
+        assert_eq!(sent, 1, "Exactly one packet should be sent");
     }
 
-    // =========================================================================================//
-    // Timer tests
-    // =========================================================================================//
+    fn socket_established_ecn() -> TestSocket {
+        let mut s = socket_established();
+        s.set_ecn_enabled(true);
+        s.remote_has_ecn = true;
+        s
+    }
 
     #[test]
-    fn test_timer_retransmit() {
-        const RTO: Duration = Duration::from_millis(100);
-        let mut r = Timer::new();
-        assert!(!r.should_retransmit(Instant::from_secs(1)));
-        r.set_for_retransmit(Instant::from_millis(1000), RTO);
-        assert!(!r.should_retransmit(Instant::from_millis(1000)));
-        assert!(!r.should_retransmit(Instant::from_millis(1050)));
-        assert!(r.should_retransmit(Instant::from_millis(1101)));
-        r.set_for_retransmit(Instant::from_millis(1101), RTO);
-        assert!(!r.should_retransmit(Instant::from_millis(1101)));
-        assert!(!r.should_retransmit(Instant::from_millis(1150)));
-        assert!(!r.should_retransmit(Instant::from_millis(1200)));
-        assert!(r.should_retransmit(Instant::from_millis(1301)));
+    fn test_ecn_passive_open() {
+        let mut s = socket_listen();
+        s.set_ecn_enabled(true);
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                ece: true,
+                cwr: true,
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                ece: true,
+                ..RECV_TEMPL
+            }]
+        );
+        assert!(s.remote_has_ecn);
+    }
+
+    #[test]
+    fn test_ecn_passive_open_disabled() {
+        let mut s = socket_listen();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                ece: true,
+                cwr: true,
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                ..RECV_TEMPL
+            }]
+        );
+        assert!(!s.remote_has_ecn);
+    }
+
+    #[test]
+    fn test_ecn_active_open() {
+        let mut s = socket_syn_sent();
+        s.set_ecn_enabled(true);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ece: true,
+                cwr: true,
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1),
+                max_seg_size: Some(BASE_MSS - 80),
+                window_scale: Some(0),
+                ece: true,
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        assert!(s.remote_has_ecn);
+    }
+
+    #[test]
+    fn test_ecn_active_open_refused() {
+        let mut s = socket_syn_sent();
+        s.set_ecn_enabled(true);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ece: true,
+                cwr: true,
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1),
+                max_seg_size: Some(BASE_MSS - 80),
+                window_scale: Some(0),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        assert!(!s.remote_has_ecn);
+
+        // Without ECN, data isn't sent ECN-capable.
+        s.send_slice(b"abcdef").unwrap();
+        recv_with_ecn(
+            &mut s,
+            Instant::from_millis(0),
+            IpEcn::NotEct,
+            &TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            },
+        );
+    }
+
+    #[test]
+    fn test_ecn_data_is_ect() {
+        let mut s = socket_established_ecn();
+        s.send_slice(b"abcdef").unwrap();
+        recv_with_ecn(
+            &mut s,
+            Instant::from_millis(0),
+            IpEcn::Ect0,
+            &TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            },
+        );
+
+        // Retransmissions are not.
+        recv_with_ecn(
+            &mut s,
+            Instant::from_millis(1000),
+            IpEcn::NotEct,
+            &TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            },
+        );
+    }
+
+    #[test]
+    fn test_ecn_echo_until_cwr() {
+        let mut s = socket_established_ecn();
+        send_with_ecn(
+            &mut s,
+            Instant::from_millis(0),
+            IpEcn::Ce,
+            &TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            },
+        );
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            ece: true,
+            ..RECV_TEMPL
+        }));
+
+        // ECE is repeated on every segment, even without further CE marks.
+        send_with_ecn(
+            &mut s,
+            Instant::from_millis(0),
+            IpEcn::Ect0,
+            &TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"def"[..],
+                ..SEND_TEMPL
+            },
+        );
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 6),
+            window_len: 58,
+            ece: true,
+            ..RECV_TEMPL
+        }));
+
+        // Until the remote confirms it reduced its window.
+        send_with_ecn(
+            &mut s,
+            Instant::from_millis(0),
+            IpEcn::Ect0,
+            &TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"ghi"[..],
+                cwr: true,
+                ..SEND_TEMPL
+            },
+        );
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 9),
+            window_len: 55,
+            ..RECV_TEMPL
+        }));
+    }
+
+    #[derive(Debug, Default)]
+    struct CountEcn {
+        ecn: usize,
+    }
+
+    impl congestion::Controller for CountEcn {
+        fn window(&self) -> usize {
+            usize::MAX
+        }
+
+        fn on_ecn(&mut self, _now: Instant) {
+            self.ecn += 1;
+        }
+    }
+
+    #[test]
+    fn test_ecn_reaction_once_per_window() {
+        let mut s = socket_established_ecn();
+        let controller: Box<dyn congestion::Controller> = Box::new(CountEcn::default());
+        s.set_custom_congestion_control(controller);
+        s.remote_mss = 6;
+        s.send_slice(b"abcdef123456").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"123456"[..],
+            ..RECV_TEMPL
+        }));
+
+        // Two echoes for the same window only cause one reaction.
+        send!(s, time 10, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            ece: true,
+            ..SEND_TEMPL
+        });
+        send!(s, time 10, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 12),
+            ece: true,
+            ..SEND_TEMPL
+        });
+        let count = |s: &TestSocket| {
+            let congestion::AnyController::Custom(controller) = &s.congestion_controller else {
+                panic!("custom controller was replaced");
+            };
+            format!("{:?}", &**controller)
+        };
+        assert_eq!(count(&s), format!("{:?}", CountEcn { ecn: 1 }));
+
+        // The next new data segment tells the remote we reacted.
+        s.send_slice(b"ABCDEF").unwrap();
+        recv_with_ecn(
+            &mut s,
+            Instant::from_millis(10),
+            IpEcn::Ect0,
+            &TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 12,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"ABCDEF"[..],
+                cwr: true,
+                ..RECV_TEMPL
+            },
+        );
+        assert!(!s.ecn_cwr_pending);
+
+        // Echoes for data sent after the reaction cause another one.
+        send!(s, time 20, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 18),
+            ece: true,
+            ..SEND_TEMPL
+        });
+        assert_eq!(count(&s), format!("{:?}", CountEcn { ecn: 2 }));
+    }
+
+    // =========================================================================================//
+    // Tests for TCP Fast Open
+    // =========================================================================================//
+
+    const FAST_OPEN_REQUEST: TcpFastOpenCookie = TcpFastOpenCookie::request();
+
+    fn fast_open_cookie() -> TcpFastOpenCookie {
+        TcpFastOpenCookie::new(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap()
+    }
+
+    #[test]
+    fn test_fast_open_cookie_request() {
+        let mut s = socket();
+        assert_eq!(
+            s.socket
+                .connect_fast_open(&mut s.cx, REMOTE_END, LOCAL_END.port, b"abcdef"),
+            Ok(6)
+        );
+        // Without a cookie, the SYN asks for one and carries no data.
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                fast_open: Some(FAST_OPEN_REQUEST),
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1),
+                max_seg_size: Some(BASE_MSS - 80),
+                window_scale: Some(0),
+                fast_open: Some(fast_open_cookie()),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        assert_eq!(
+            s.cx.tcp_fast_open_cookie(&REMOTE_END.addr),
+            Some(fast_open_cookie())
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_fast_open_data_in_syn() {
+        let mut s = socket();
+        s.cx.set_tcp_fast_open_cookie(REMOTE_END.addr, fast_open_cookie());
+        s.socket
+            .connect_fast_open(&mut s.cx, REMOTE_END, LOCAL_END.port, b"abcdef")
+            .unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                fast_open: Some(fast_open_cookie()),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1 + 6),
+                max_seg_size: Some(BASE_MSS - 80),
+                window_scale: Some(0),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        assert!(s.tx_buffer.is_empty());
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 6,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_fast_open_data_in_syn_not_accepted() {
+        let mut s = socket();
+        s.cx.set_tcp_fast_open_cookie(REMOTE_END.addr, fast_open_cookie());
+        s.socket
+            .connect_fast_open(&mut s.cx, REMOTE_END, LOCAL_END.port, b"abcdef")
+            .unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                fast_open: Some(fast_open_cookie()),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            }]
+        );
+        // The server only acknowledges the SYN, so the data is sent again.
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1),
+                max_seg_size: Some(BASE_MSS - 80),
+                window_scale: Some(0),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_fast_open_no_data_in_retransmitted_syn() {
+        let mut s = socket();
+        s.cx.set_tcp_fast_open_cookie(REMOTE_END.addr, fast_open_cookie());
+        s.socket
+            .connect_fast_open(&mut s.cx, REMOTE_END, LOCAL_END.port, b"abcdef")
+            .unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            control: TcpControl::Syn,
+            seq_number: LOCAL_SEQ,
+            ack_number: None,
+            max_seg_size: Some(BASE_MSS),
+            window_scale: Some(0),
+            sack_permitted: true,
+            fast_open: Some(fast_open_cookie()),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 1000, Ok(TcpRepr {
+            control: TcpControl::Syn,
+            seq_number: LOCAL_SEQ,
+            ack_number: None,
+            max_seg_size: Some(BASE_MSS),
+            window_scale: Some(0),
+            sack_permitted: true,
+            fast_open: Some(fast_open_cookie()),
+            ..RECV_TEMPL
+        }));
+    }
+
+    #[test]
+    fn test_fast_open_listen_hands_out_cookie() {
+        let mut s = socket_listen();
+        s.set_fast_open_enabled(true);
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                fast_open: Some(FAST_OPEN_REQUEST),
+                payload: &b"abcdef"[..],
+                ..SEND_TEMPL
+            }
+        );
+        let cookie = s.cx.generate_tcp_fast_open_cookie(&REMOTE_END.addr);
+        // The data isn't accepted without a valid cookie.
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                fast_open: Some(cookie),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_fast_open_listen_accepts_data() {
+        let mut s = socket_listen();
+        s.set_fast_open_enabled(true);
+        let cookie = s.cx.generate_tcp_fast_open_cookie(&REMOTE_END.addr);
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                fast_open: Some(cookie),
+                payload: &b"abcdef"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1 + 6),
+                max_seg_size: Some(BASE_MSS),
+                window_len: 58,
+                ..RECV_TEMPL
+            }]
+        );
+
+        // The data can be received before the handshake completes.
+        assert_eq!(s.state, State::SynReceived);
+        let mut buf = [0; 6];
+        assert_eq!(s.recv_slice(&mut buf), Ok(6));
+        assert_eq!(&buf, b"abcdef");
+
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                ack_number: Some(LOCAL_SEQ + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+    }
+
+    #[test]
+    fn test_fast_open_listen_disabled() {
+        let mut s = socket_listen();
+        let cookie = s.cx.generate_tcp_fast_open_cookie(&REMOTE_END.addr);
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                fast_open: Some(cookie),
+                payload: &b"abcdef"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    // =========================================================================================//
+    // Tests for TCP-MD5 and TCP-AO
+    // =========================================================================================//
+
+    fn auth_secret() -> TcpAuthSecret {
+        TcpAuthSecret::new(b"correct horse battery staple").unwrap()
+    }
+
+    fn md5_key() -> TcpAuthKey {
+        TcpAuthKey::Md5(auth_secret())
+    }
+
+    /// Sign `repr` with `key` as the remote would, and return it as received from
+    /// `buffer`.
+    fn signed<'b>(repr: TcpRepr, key: TcpAuthKey, buffer: &'b mut [u8]) -> TcpRepr<'b> {
+        let repr = TcpRepr {
+            auth: Some(TcpAuth::Sign(key)),
+            ..repr
+        };
+        let bytes = &mut buffer[..repr.buffer_len()];
+        repr.emit(
+            &mut TcpPacket::new_unchecked(&mut *bytes),
+            &REMOTE_ADDR.into(),
+            &LOCAL_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        );
+        TcpRepr::parse(
+            &TcpPacket::new_unchecked(&*bytes),
+            &REMOTE_ADDR.into(),
+            &LOCAL_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        )
+        .unwrap()
+    }
+
+    fn ao_config() -> Authentication {
+        Authentication::Ao {
+            algorithm: TcpAoAlgorithm::HmacSha1,
+            send_id: 1,
+            recv_id: 2,
+            master_key: auth_secret(),
+        }
+    }
+
+    fn ao_key(
+        key_id: u8,
+        rnext_key_id: u8,
+        src: IpEndpoint,
+        dst: IpEndpoint,
+        src_isn: TcpSeqNumber,
+        dst_isn: TcpSeqNumber,
+    ) -> TcpAuthKey {
+        TcpAuthKey::Ao {
+            key_id,
+            rnext_key_id,
+            traffic_key: TcpAoTrafficKey::derive(
+                TcpAoAlgorithm::HmacSha1,
+                &auth_secret(),
+                src,
+                dst,
+                src_isn,
+                dst_isn,
+            ),
+            sne: 0,
+        }
+    }
+
+    #[test]
+    fn test_md5_connect() {
+        let mut buffer = [0; 128];
+        let mut s = socket();
+        s.set_authentication(Some(Authentication::Md5 { key: auth_secret() }));
+        s.socket
+            .connect(&mut s.cx, REMOTE_END, LOCAL_END.port)
+            .unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                auth: Some(TcpAuth::Sign(md5_key())),
+                ..RECV_TEMPL
+            }]
+        );
+
+        let syn_ack = TcpRepr {
+            control: TcpControl::Syn,
+            seq_number: REMOTE_SEQ,
+            ack_number: Some(LOCAL_SEQ + 1),
+            max_seg_size: Some(BASE_MSS - 80),
+            window_scale: Some(0),
+            ..SEND_TEMPL
+        };
+        // Unsigned, or signed with another key: dropped without a reply.
+        send!(s, syn_ack);
+        let other = TcpAuthKey::Md5(TcpAuthSecret::new(b"wrong").unwrap());
+        send!(s, signed(syn_ack, other, &mut buffer));
+        assert_eq!(s.state, State::SynSent);
+
+        send!(s, signed(syn_ack, md5_key(), &mut buffer));
+        assert_eq!(s.state, State::Established);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                auth: Some(TcpAuth::Sign(md5_key())),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_md5_listen() {
+        let mut buffer = [0; 128];
+        let mut s = socket_listen();
+        s.set_authentication(Some(Authentication::Md5 { key: auth_secret() }));
+        let syn = TcpRepr {
+            control: TcpControl::Syn,
+            seq_number: REMOTE_SEQ,
+            ack_number: None,
+            ..SEND_TEMPL
+        };
+        send!(s, syn);
+        assert_eq!(s.state, State::Listen);
+
+        send!(s, signed(syn, md5_key(), &mut buffer));
+        assert_eq!(s.state, State::SynReceived);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                auth: Some(TcpAuth::Sign(md5_key())),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_md5_replies_signed() {
+        let mut buffer = [0; 128];
+        let mut s = socket_established();
+        s.set_authentication(Some(Authentication::Md5 { key: auth_secret() }));
+        // An out of window segment gets an ACK, which must be signed as well.
+        send!(
+            s,
+            signed(
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1 + 256,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    payload: &b"abc"[..],
+                    ..SEND_TEMPL
+                },
+                md5_key(),
+                &mut buffer
+            ),
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                auth: Some(TcpAuth::Sign(md5_key())),
+                ..RECV_TEMPL
+            })
+        );
+    }
+
+    #[test]
+    fn test_ao_connect() {
+        let mut buffer = [0; 128];
+        let mut s = socket();
+        s.set_authentication(Some(ao_config()));
+        s.socket
+            .connect(&mut s.cx, REMOTE_END, LOCAL_END.port)
+            .unwrap();
+        // The SYN is signed before the remote has an initial sequence number.
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                auth: Some(TcpAuth::Sign(ao_key(
+                    1,
+                    2,
+                    LOCAL_END,
+                    REMOTE_END,
+                    LOCAL_SEQ,
+                    TcpSeqNumber(0)
+                ))),
+                ..RECV_TEMPL
+            }]
+        );
+
+        let syn_ack = TcpRepr {
+            control: TcpControl::Syn,
+            seq_number: REMOTE_SEQ,
+            ack_number: Some(LOCAL_SEQ + 1),
+            max_seg_size: Some(BASE_MSS - 80),
+            window_scale: Some(0),
+            ..SEND_TEMPL
+        };
+        // The wrong key ID, or a traffic key for the wrong initial sequence numbers.
+        send!(
+            s,
+            signed(
+                syn_ack,
+                ao_key(1, 2, REMOTE_END, LOCAL_END, REMOTE_SEQ, LOCAL_SEQ),
+                &mut buffer
+            )
+        );
+        send!(
+            s,
+            signed(
+                syn_ack,
+                ao_key(2, 1, REMOTE_END, LOCAL_END, REMOTE_SEQ, TcpSeqNumber(0)),
+                &mut buffer
+            )
+        );
+        assert_eq!(s.state, State::SynSent);
+
+        send!(
+            s,
+            signed(
+                syn_ack,
+                ao_key(2, 1, REMOTE_END, LOCAL_END, REMOTE_SEQ, LOCAL_SEQ),
+                &mut buffer
+            )
+        );
+        assert_eq!(s.state, State::Established);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                auth: Some(TcpAuth::Sign(ao_key(
+                    1, 2, LOCAL_END, REMOTE_END, LOCAL_SEQ, REMOTE_SEQ
+                ))),
+                ..RECV_TEMPL
+            }]
+        );
+
+        // Data must be signed with the same traffic key as the SYN|ACK.
+        let data = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload: &b"abcdef"[..],
+            ..SEND_TEMPL
+        };
+        send!(
+            s,
+            signed(
+                data,
+                ao_key(2, 1, REMOTE_END, LOCAL_END, REMOTE_SEQ, TcpSeqNumber(0)),
+                &mut buffer
+            )
+        );
+        assert_eq!(s.rx_buffer.len(), 0);
+        send!(
+            s,
+            signed(
+                data,
+                ao_key(2, 1, REMOTE_END, LOCAL_END, REMOTE_SEQ, LOCAL_SEQ),
+                &mut buffer
+            )
+        );
+        assert_eq!(s.rx_buffer.dequeue_many(6), &b"abcdef"[..]);
+    }
+
+    #[test]
+    fn test_auth_not_configured() {
+        let mut buffer = [0; 128];
+        // Without a key, signatures are ignored.
+        let mut s = socket_established();
+        send!(
+            s,
+            signed(
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    payload: &b"abcdef"[..],
+                    ..SEND_TEMPL
+                },
+                md5_key(),
+                &mut buffer
+            )
+        );
+        assert_eq!(s.rx_buffer.dequeue_many(6), &b"abcdef"[..]);
+    }
+
+    // =========================================================================================//
+    // Tests for SYN cookies
+    // =========================================================================================//
+
+    fn socket_syn_cookies() -> TestSocket {
+        let mut s = socket_listen();
+        s.set_syn_cookies_enabled(true);
+        s
+    }
+
+    fn ip_repr(repr: &TcpRepr) -> IpRepr {
+        IpReprIpvX(IpvXRepr {
+            src_addr: REMOTE_ADDR,
+            dst_addr: LOCAL_ADDR,
+            next_header: IpProtocol::Tcp,
+            payload_len: repr.buffer_len(),
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        })
+    }
+
+    fn accepts(s: &mut TestSocket, repr: &TcpRepr) -> bool {
+        s.socket.accepts(&mut s.cx, &ip_repr(repr), repr)
+    }
+
+    /// Answer the SYN `repr` with a cookie on behalf of `s`, as the interface does
+    /// when no socket takes it.
+    fn syn_cookie_reply(
+        s: &mut TestSocket,
+        timestamp: Instant,
+        repr: &TcpRepr,
+    ) -> Option<TcpRepr<'static>> {
+        s.cx.set_now(timestamp);
+        assert!(s.socket.uses_syn_cookies(&ip_repr(repr), repr));
+        s.socket
+            .syn_cookie_reply(&mut s.cx, &ip_repr(repr), repr)
+            .map(|(_, reply)| reply)
+    }
+
+    /// Send a SYN that no socket takes, and return the initial sequence number of the
+    /// SYN|ACK answering it on behalf of `s`.
+    fn send_syn_cookie(s: &mut TestSocket, timestamp: Instant) -> TcpSeqNumber {
+        let reply = syn_cookie_reply(
+            s,
+            timestamp,
+            &TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                max_seg_size: Some(1400),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ..SEND_TEMPL
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            reply,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: reply.seq_number,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ..RECV_TEMPL
+            }
+        );
+        reply.seq_number
+    }
+
+    /// Return the SYN cookie carried back by the ACK `repr`, if it is valid.
+    fn syn_cookie(s: &mut TestSocket, repr: &TcpRepr) -> Option<SynCookie> {
+        s.cx.check_tcp_syn_cookie(
+            LOCAL_END,
+            REMOTE_END,
+            repr.seq_number - 1,
+            repr.ack_number? - 1,
+        )
+    }
+
+    /// Open a connection on `s` from the ACK `repr`, which carries a valid SYN cookie
+    /// back.
+    fn send_syn_cookie_ack(s: &mut TestSocket, repr: &TcpRepr) -> Option<TcpRepr<'static>> {
+        let cookie = syn_cookie(s, repr).unwrap();
+        assert!(!accepts(s, repr));
+        s.socket
+            .process_syn_cookie(&mut s.cx, &ip_repr(repr), repr, cookie)
+            .map(|(_, reply)| reply)
+    }
+
+    #[test]
+    fn test_syn_cookie_listen() {
+        let mut s = socket_syn_cookies();
+        let isn = send_syn_cookie(&mut s, Instant::from_secs(0));
+        // Nothing is remembered about the SYN.
+        assert_eq!(s.state, State::Listen);
+        assert_eq!(s.tuple, None);
+        recv_nothing!(s);
+
+        let ack = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(isn + 1),
+            ..SEND_TEMPL
+        };
+        assert_eq!(send_syn_cookie_ack(&mut s, &ack), None);
+        assert_eq!(s.state, State::Established);
+        assert_eq!(s.remote_endpoint(), Some(REMOTE_END));
+        assert_eq!(s.remote_mss, 1300);
+        assert_eq!(s.remote_win_scale, Some(0));
+        assert!(s.remote_has_sack);
+
+        s.send_slice(b"abcdef").unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: isn + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_syn_cookie_syn_received() {
+        // A listening socket takes a SYN itself, and still answers the next ones with
+        // cookies once in SYN-RECEIVED.
+        let mut s = socket_syn_cookies();
+        let reply = send(
+            &mut s,
+            Instant::from_secs(0),
+            &TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                ..SEND_TEMPL
+            },
+        );
+        assert_eq!(reply, None);
+        assert_eq!(s.state, State::SynReceived);
+        let syn = TcpRepr {
+            control: TcpControl::Syn,
+            src_port: REMOTE_PORT + 1,
+            seq_number: REMOTE_SEQ,
+            ack_number: None,
+            ..SEND_TEMPL
+        };
+        assert!(!accepts(&mut s, &syn));
+        let reply = syn_cookie_reply(&mut s, Instant::from_secs(0), &syn).unwrap();
+        assert_eq!(reply.dst_port, REMOTE_PORT + 1);
+        assert_eq!(reply.ack_number, Some(REMOTE_SEQ + 1));
+        assert_eq!(s.state, State::SynReceived);
+        assert_eq!(s.remote_endpoint(), Some(REMOTE_END));
+    }
+
+    #[test]
+    fn test_syn_cookie_standby() {
+        let mut s = socket_syn_cookies();
+        s.set_syn_cookie_standby(true);
+        let syn = TcpRepr {
+            control: TcpControl::Syn,
+            seq_number: REMOTE_SEQ,
+            ack_number: None,
+            ..SEND_TEMPL
+        };
+        assert!(!accepts(&mut s, &syn));
+
+        let isn = send_syn_cookie(&mut s, Instant::from_secs(0));
+        let ack = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(isn + 1),
+            ..SEND_TEMPL
+        };
+        assert_eq!(send_syn_cookie_ack(&mut s, &ack), None);
+        assert_eq!(s.state, State::Established);
+        assert!(!s.syn_cookie_standby);
+    }
+
+    #[test]
+    fn test_syn_cookie_with_data() {
+        let mut s = socket_syn_cookies();
+        let isn = send_syn_cookie(&mut s, Instant::from_secs(0));
+        let ack = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(isn + 1),
+            payload: &b"abcdef"[..],
+            ..SEND_TEMPL
+        };
+        assert_eq!(send_syn_cookie_ack(&mut s, &ack), None);
+        assert_eq!(s.state, State::Established);
+        assert_eq!(s.rx_buffer.dequeue_many(6), &b"abcdef"[..]);
+    }
+
+    #[test]
+    fn test_syn_cookie_invalid() {
+        let mut s = socket_syn_cookies();
+        let isn = send_syn_cookie(&mut s, Instant::from_secs(0));
+        for (seq_number, ack_number) in [
+            (REMOTE_SEQ + 1, isn + 0x10000),
+            (REMOTE_SEQ + 0x10000, isn + 1),
+        ] {
+            let ack = TcpRepr {
+                seq_number,
+                ack_number: Some(ack_number),
+                ..SEND_TEMPL
+            };
+            assert_eq!(syn_cookie(&mut s, &ack), None);
+        }
+        let rst = TcpRepr {
+            control: TcpControl::Rst,
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(isn + 1),
+            ..SEND_TEMPL
+        };
+        assert!(!accepts(&mut s, &rst));
+
+        // The listening socket never takes the ACK itself, and without SYN cookies,
+        // it is not opened from it either.
+        let ack = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(isn + 1),
+            ..SEND_TEMPL
+        };
+        assert!(syn_cookie(&mut s, &ack).is_some());
+        assert!(!accepts(&mut s, &ack));
+        assert!(s.socket.uses_syn_cookies(&ip_repr(&ack), &ack));
+        s.set_syn_cookies_enabled(false);
+        assert!(!s.socket.uses_syn_cookies(&ip_repr(&ack), &ack));
+    }
+
+    #[test]
+    fn test_syn_cookie_expired() {
+        let mut s = socket_syn_cookies();
+        let isn = send_syn_cookie(&mut s, Instant::from_secs(0));
+        let ack = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(isn + 1),
+            ..SEND_TEMPL
+        };
+        s.cx.set_now(Instant::from_secs(2 * 64));
+        assert!(syn_cookie(&mut s, &ack).is_some());
+        s.cx.set_now(Instant::from_secs(3 * 64));
+        assert!(syn_cookie(&mut s, &ack).is_none());
+    }
+
+    #[test]
+    fn test_syn_cookie_md5() {
+        let mut buffer = [0; 128];
+        let mut s = socket_syn_cookies();
+        s.set_authentication(Some(Authentication::Md5 { key: auth_secret() }));
+        let syn = TcpRepr {
+            control: TcpControl::Syn,
+            seq_number: REMOTE_SEQ,
+            ack_number: None,
+            ..SEND_TEMPL
+        };
+        // An unsigned SYN is not answered.
+        assert_eq!(syn_cookie_reply(&mut s, Instant::from_secs(0), &syn), None);
+        let reply = syn_cookie_reply(
+            &mut s,
+            Instant::from_secs(0),
+            &signed(syn, md5_key(), &mut buffer),
+        )
+        .unwrap();
+        assert_eq!(reply.auth, Some(TcpAuth::Sign(md5_key())));
+
+        // An unsigned ACK does not set up the connection.
+        let ack = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(reply.seq_number + 1),
+            ..SEND_TEMPL
+        };
+        assert_eq!(send_syn_cookie_ack(&mut s, &ack), None);
+        assert_eq!(s.state, State::Listen);
+        assert_eq!(s.tuple, None);
+
+        assert_eq!(
+            send_syn_cookie_ack(&mut s, &signed(ack, md5_key(), &mut buffer)),
+            None
+        );
+        assert_eq!(s.state, State::Established);
+    }
+
+    // =========================================================================================//
+    // Tests for TcpInfo.
+    // =========================================================================================//
+
+    #[test]
+    fn test_info_established() {
+        let mut s = socket_established();
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 100, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            window_len: 128,
+            ..SEND_TEMPL
+        });
+
+        let info = s.info();
+        assert_eq!(info.state, State::Established);
+        assert_eq!(info.smoothed_rtt, Some(Duration::from_millis(100)));
+        assert_eq!(info.rtt_variance, Some(Duration::from_millis(50)));
+        assert_eq!(info.retransmission_timeout, s.rtte.retransmission_timeout());
+        assert_eq!(
+            info.congestion_window,
+            s.congestion_controller.inner().window()
+        );
+        assert_eq!(info.bytes_sent, 6);
+        assert_eq!(info.bytes_retransmitted, 0);
+        assert_eq!(info.bytes_acked, 6);
+        assert_eq!(info.segments_in, 1);
+        assert_eq!(info.segments_out, 1);
+        assert_eq!(info.remote_window, 128);
+        assert_eq!(info.remote_window_scale, None);
+        assert_eq!(info.remote_mss, DEFAULT_MSS);
+    }
+
+    #[test]
+    fn test_info_retransmission() {
+        let mut s = socket_established();
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 1050, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+
+        let info = s.info();
+        assert_eq!(info.bytes_sent, 12);
+        assert_eq!(info.bytes_retransmitted, 6);
+        assert_eq!(info.retransmission_timeouts, 1);
+        assert_eq!(info.segments_out, 2);
+    }
+
+    #[test]
+    fn test_info_duplicate_acks() {
+        let mut s = socket_established();
+        s.remote_mss = 6;
+        s.send_slice(b"abcdef012345").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"012345"[..],
+            ..RECV_TEMPL
+        }));
+        for _ in 0..3 {
+            send!(s, time 5, TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 6),
+                ..SEND_TEMPL
+            });
+        }
+
+        let info = s.info();
+        assert_eq!(info.duplicate_acks, 2);
+        assert_eq!(info.bytes_acked, 6);
+        assert_eq!(info.segments_in, 3);
+    }
+
+    #[test]
+    fn test_info_zero_window_probe() {
+        let mut s = socket_established();
+        s.remote_win_len = 0;
+        s.send_slice(b"abcdef").unwrap();
+        recv_nothing!(s, time 0);
+        recv!(s, time 1000, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"a"[..],
+            ..RECV_TEMPL
+        }));
+
+        let info = s.info();
+        assert_eq!(info.zero_window_probes, 1);
+        assert_eq!(info.bytes_sent, 1);
+        assert_eq!(info.remote_window, 0);
+    }
+
+    #[test]
+    fn test_info_reset() {
+        let mut s = socket_established();
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+
+        // The statistics outlive the connection.
+        s.abort();
+        assert_eq!(s.info().bytes_sent, 6);
+
+        s.listen(LOCAL_END).unwrap();
+        let info = s.info();
+        assert_eq!(info.state, State::Listen);
+        assert_eq!(info.bytes_sent, 0);
+        assert_eq!(info.segments_out, 0);
+    }
+
+    // =========================================================================================//
+    // Tests for path MTU discovery.
+    // =========================================================================================//
+
+    fn recv_payload_len(s: &mut TestSocket, seq_number: TcpSeqNumber, len: usize) {
+        recv(s, Instant::from_millis(0), |result| {
+            let repr = result.unwrap();
+            assert_eq!(repr.seq_number, seq_number);
+            assert_eq!(repr.payload.len(), len);
+        });
+    }
+
+    #[test]
+    fn test_path_mtu_icmp() {
+        let mut s = socket_established_with_buffer_sizes(4096, 64);
+        s.remote_mss = BASE_MSS as usize;
+        s.set_congestion_control(CongestionControl::None);
+        s.remote_win_len = 4096;
+        s.send_slice(&[0xa5; 4096]).unwrap();
+
+        // The MTU is the minimum of IPv6, which IPv4 allows too.
+        let overhead = s.cx.ip_mtu() - BASE_MSS as usize;
+        s.cx.process_packet_too_big(LOCAL_ADDR.into(), REMOTE_ADDR.into(), 1280);
+        recv_payload_len(&mut s, LOCAL_SEQ + 1, 1280 - overhead);
+        recv_payload_len(&mut s, LOCAL_SEQ + 1 + 1280 - overhead, 1280 - overhead);
+    }
+
+    #[test]
+    fn test_mtu_probing() {
+        let mut s = socket_established_with_buffer_sizes(8192, 64);
+        s.remote_mss = BASE_MSS as usize;
+        s.set_congestion_control(CongestionControl::None);
+        s.remote_win_len = 8192;
+        s.set_mtu_probing_enabled(true);
+        s.send_slice(&[0xa5; 8192]).unwrap();
+
+        // The first full-sized segment probes the largest MTU, the others are sized for
+        // the base MTU.
+        let base_len = plpmtud::BASE_PLPMTU - (s.cx.ip_mtu() - BASE_MSS as usize);
+        let mut seq = LOCAL_SEQ + 1;
+        recv_payload_len(&mut s, seq, BASE_MSS as usize);
+        seq += BASE_MSS as usize;
+        recv_payload_len(&mut s, seq, base_len);
+        seq += base_len;
+
+        // Once the probe is acknowledged, segments are sized for the largest MTU.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(seq),
+                window_len: 8192,
+                ..SEND_TEMPL
+            }
+        );
+        recv_payload_len(&mut s, seq, BASE_MSS as usize);
+    }
+
+    #[test]
+    fn test_mtu_probe_lost() {
+        let mut s = socket_established_with_buffer_sizes(4096, 64);
+        s.remote_mss = BASE_MSS as usize;
+        s.set_congestion_control(CongestionControl::None);
+        s.remote_win_len = 4096;
+        s.set_mtu_probing_enabled(true);
+        s.send_slice(&[0xa5; BASE_MSS as usize]).unwrap();
+
+        let base_len = plpmtud::BASE_PLPMTU - (s.cx.ip_mtu() - BASE_MSS as usize);
+        recv_payload_len(&mut s, LOCAL_SEQ + 1, BASE_MSS as usize);
+
+        // The probe times out and its data is resent in segments of the base size.
+        recv(&mut s, Instant::from_millis(1050), |result| {
+            let repr = result.unwrap();
+            assert_eq!(repr.seq_number, LOCAL_SEQ + 1);
+            assert_eq!(repr.payload.len(), base_len);
+        });
+        assert_eq!(s.plpmtud.mtu(s.cx.ip_mtu()), plpmtud::BASE_PLPMTU);
+    }
+
+    #[derive(Debug, Default)]
+    struct CountRetransmit {
+        retransmits: usize,
+    }
+
+    impl congestion::Controller for CountRetransmit {
+        fn window(&self) -> usize {
+            usize::MAX
+        }
+
+        fn on_retransmit(&mut self, _now: Instant) {
+            self.retransmits += 1;
+        }
+    }
+
+    fn socket_mtu_probing_counted() -> TestSocket {
+        let mut s = socket_established_with_buffer_sizes(8192, 64);
+        s.remote_mss = BASE_MSS as usize;
+        let controller: Box<dyn congestion::Controller> = Box::new(CountRetransmit::default());
+        s.set_custom_congestion_control(controller);
+        s.set_mtu_probing_enabled(true);
+
+        // Normal ACK of previously received segment
+        send!(s, time 0, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            window_len: 8192,
+            ..SEND_TEMPL
+        });
+        s
+    }
+
+    fn retransmits(s: &TestSocket) -> String {
+        let congestion::AnyController::Custom(controller) = &s.congestion_controller else {
+            panic!("custom controller was replaced");
+        };
+        format!("{:?}", &**controller)
+    }
+
+    #[test]
+    fn test_mtu_probe_timeout_not_congestion() {
+        let mut s = socket_mtu_probing_counted();
+        s.send_slice(&[0xa5; BASE_MSS as usize]).unwrap();
+        recv_payload_len(&mut s, LOCAL_SEQ + 1, BASE_MSS as usize);
+        let rto = s.rtte.retransmission_timeout();
+
+        // The probe times out, which neither backs off the timer nor counts as
+        // congestion.
+        let base_len = plpmtud::BASE_PLPMTU - (s.cx.ip_mtu() - BASE_MSS as usize);
+        recv(&mut s, Instant::from_millis(1050), |result| {
+            let repr = result.unwrap();
+            assert_eq!(repr.seq_number, LOCAL_SEQ + 1);
+            assert_eq!(repr.payload.len(), base_len);
+        });
+        assert_eq!(s.rtte.retransmission_timeout(), rto);
+        assert_eq!(s.counters.retransmission_timeouts, 0);
+        assert_eq!(
+            retransmits(&s),
+            format!("{:?}", CountRetransmit { retransmits: 0 })
+        );
+        assert_eq!(s.plpmtud.probe(), None);
+    }
+
+    #[test]
+    fn test_mtu_probe_lost_sack() {
+        let mut s = socket_mtu_probing_counted();
+        s.remote_has_sack = true;
+        s.send_slice(&[0xa5; 8192]).unwrap();
+
+        let base_len = plpmtud::BASE_PLPMTU - (s.cx.ip_mtu() - BASE_MSS as usize);
+        let probe_end = LOCAL_SEQ + 1 + BASE_MSS as usize;
+        recv_payload_len(&mut s, LOCAL_SEQ + 1, BASE_MSS as usize);
+        for i in 0..3 {
+            recv_payload_len(&mut s, probe_end + i * base_len, base_len);
+        }
+
+        // Only the probe is lost, as the SACKs of the segments after it show.
+        send!(s, time 50, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            window_len: 8192,
+            sack_ranges: [sack_range(probe_end, probe_end + 3 * base_len), None, None],
+            ..SEND_TEMPL
+        });
+        assert!(s.sack_scoreboard.in_recovery());
+        assert_eq!(s.plpmtud.probe(), None);
+        assert_eq!(
+            retransmits(&s),
+            format!("{:?}", CountRetransmit { retransmits: 0 })
+        );
+
+        // Its data is resent in smaller segments.
+        recv(&mut s, Instant::from_millis(50), |result| {
+            let repr = result.unwrap();
+            assert_eq!(repr.seq_number, LOCAL_SEQ + 1);
+            assert_eq!(repr.payload.len(), base_len);
+        });
+    }
+
+    #[test]
+    fn test_mtu_probe_lost_with_other_data() {
+        let mut s = socket_mtu_probing_counted();
+        s.remote_has_sack = true;
+        s.send_slice(&[0xa5; 8192]).unwrap();
+
+        let base_len = plpmtud::BASE_PLPMTU - (s.cx.ip_mtu() - BASE_MSS as usize);
+        let probe_end = LOCAL_SEQ + 1 + BASE_MSS as usize;
+        recv_payload_len(&mut s, LOCAL_SEQ + 1, BASE_MSS as usize);
+        for i in 0..5 {
+            recv_payload_len(&mut s, probe_end + i * base_len, base_len);
+        }
+
+        // The segment after the probe is lost too, which is a sign of congestion.
+        send!(s, time 50, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            window_len: 8192,
+            sack_ranges: [
+                sack_range(probe_end + base_len, probe_end + 5 * base_len),
+                None,
+                None,
+            ],
+            ..SEND_TEMPL
+        });
+        assert!(s.sack_scoreboard.in_recovery());
+        assert_eq!(
+            retransmits(&s),
+            format!("{:?}", CountRetransmit { retransmits: 1 })
+        );
+    }
+
+    #[test]
+    fn test_mtu_probing_disabled() {
+        let mut s = socket_established_with_buffer_sizes(4096, 64);
+        s.remote_mss = BASE_MSS as usize;
+        s.set_congestion_control(CongestionControl::None);
+        s.remote_win_len = 4096;
+        assert!(!s.mtu_probing_enabled());
+        s.send_slice(&[0xa5; 4096]).unwrap();
+        recv_payload_len(&mut s, LOCAL_SEQ + 1, BASE_MSS as usize);
+        recv_payload_len(&mut s, LOCAL_SEQ + 1 + BASE_MSS as usize, BASE_MSS as usize);
+    }
+
+    // =========================================================================================//
+    // Tests for zero-copy transmission.
+    // =========================================================================================//
+
+    #[test]
+    fn test_send_chunk() {
+        let mut s = socket_established();
+        s.set_nagle_enabled(false);
+        s.set_tx_chunk_storage(vec![TxChunkSlot::EMPTY, TxChunkSlot::EMPTY]);
+
+        s.send_slice(b"ab").unwrap();
+        s.send_chunk(&b"cdef"[..]).unwrap();
+        s.send_slice(b"gh").unwrap();
+        assert_eq!(s.send_queue(), 8);
+        recv!(
+            s,
+            [
+                TcpRepr {
+                    seq_number: LOCAL_SEQ + 1,
+                    ack_number: Some(REMOTE_SEQ + 1),
+                    payload: &b"ab"[..],
+                    ..RECV_TEMPL
+                },
+                TcpRepr {
+                    seq_number: LOCAL_SEQ + 1 + 2,
+                    ack_number: Some(REMOTE_SEQ + 1),
+                    payload: &b"cdef"[..],
+                    ..RECV_TEMPL
+                },
+                TcpRepr {
+                    seq_number: LOCAL_SEQ + 1 + 6,
+                    ack_number: Some(REMOTE_SEQ + 1),
+                    payload: &b"gh"[..],
+                    ..RECV_TEMPL
+                }
+            ]
+        );
+
+        // The chunk completes once all of it is acknowledged.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 4),
+                ..SEND_TEMPL
+            }
+        );
+        assert!(s.take_completed_chunk().is_none());
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 6),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.take_completed_chunk().as_deref(), Some(&b"cdef"[..]));
+        assert!(s.take_completed_chunk().is_none());
+        assert_eq!(s.send_queue(), 2);
+    }
+
+    #[test]
+    fn test_send_chunk_retransmit() {
+        let mut s = socket_established();
+        s.set_tx_chunk_storage(vec![TxChunkSlot::EMPTY]);
+
+        s.send_chunk(&b"abcdef"[..]).unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 100, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 2),
+            ..SEND_TEMPL
+        });
+        recv!(s, time 1500, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 2,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"cdef"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 1600, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.take_completed_chunk().as_deref(), Some(&b"abcdef"[..]));
+    }
+
+    #[test]
+    fn test_send_chunk_errors() {
+        let mut s = socket();
+        s.set_tx_chunk_storage(vec![TxChunkSlot::EMPTY]);
+        assert_eq!(s.send_chunk(&b"abc"[..]), Err(SendChunkError::InvalidState));
+
+        let mut s = socket_established();
+        assert_eq!(s.send_chunk(&b"abc"[..]), Err(SendChunkError::Full));
+        s.set_tx_chunk_storage(vec![TxChunkSlot::EMPTY]);
+        assert_eq!(s.send_chunk(&b"abc"[..]), Ok(()));
+        assert_eq!(s.send_chunk(&b"def"[..]), Err(SendChunkError::Full));
+    }
+
+    #[test]
+    fn test_send_chunk_shared() {
+        let chunk: alloc::sync::Arc<[u8]> = alloc::sync::Arc::from(&b"abcdef"[..]);
+        let mut s = socket_established();
+        s.set_tx_chunk_storage(vec![TxChunkSlot::EMPTY]);
+
+        s.send_chunk(chunk.clone()).unwrap();
+        assert_eq!(alloc::sync::Arc::strong_count(&chunk), 2);
+        recv!(
+            s,
+            Ok(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            })
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 6),
+                ..SEND_TEMPL
+            }
+        );
+        drop(s.take_completed_chunk());
+        assert_eq!(alloc::sync::Arc::strong_count(&chunk), 1);
+    }
+
+    #[test]
+    fn test_send_chunk_dropped_on_reset() {
+        let chunk: alloc::sync::Arc<[u8]> = alloc::sync::Arc::from(&b"abcdef"[..]);
+        let mut s = socket_established();
+        s.set_tx_chunk_storage(vec![TxChunkSlot::EMPTY]);
+        s.send_chunk(chunk.clone()).unwrap();
+        s.abort();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        s.listen(LOCAL_END).unwrap();
+        assert_eq!(alloc::sync::Arc::strong_count(&chunk), 1);
+        assert_eq!(s.send_queue(), 0);
+    }
+
+    // =========================================================================================//
+    // Tests for lossy receive mode.
+    // =========================================================================================//
+
+    fn socket_lossy() -> TestSocket {
+        let mut s = socket_established();
+        s.set_lossy_receive_enabled(true);
+        s.set_recv_gap_storage(vec![RecvGap::EMPTY; 2]);
+        s
+    }
+
+    #[test]
+    fn test_lossy_receive_skip() {
+        let mut s = socket_lossy();
+        send!(s, time 10, TcpRepr {
+            seq_number: REMOTE_SEQ + 1 + 3,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload:    &b"def"[..],
+            ..SEND_TEMPL
+        });
+        recv!(s, time 10, [TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 6),
+            window_len: 61,
+            ..RECV_TEMPL
+        }]);
+
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Gap(RecvGap {
+                seq_number: REMOTE_SEQ + 1,
+                len: 3,
+                timestamp: Instant::from_millis(10),
+            })))
+        );
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Data {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                payload: &b"def"[..],
+            }))
+        );
+        assert_eq!(s.recv_lossy(64), Ok(None));
+
+        // The skipped octets are acknowledged, so retransmissions are ignored.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 6),
+                ..RECV_TEMPL
+            })
+        );
+        assert_eq!(s.recv_lossy(64), Ok(None));
+    }
+
+    #[test]
+    fn test_lossy_receive_records() {
+        let mut s = socket_lossy();
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            }
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"ghi"[..],
+                ..SEND_TEMPL
+            }
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 10,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"kl"[..],
+                ..SEND_TEMPL
+            }
+        );
+
+        assert_eq!(
+            s.recv_lossy(2),
+            Ok(Some(LossyRecord::Data {
+                seq_number: REMOTE_SEQ + 1,
+                payload: &b"ab"[..],
+            }))
+        );
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Data {
+                seq_number: REMOTE_SEQ + 1 + 2,
+                payload: &b"c"[..],
+            }))
+        );
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Gap(RecvGap {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                len: 3,
+                timestamp: Instant::from_millis(0),
+            })))
+        );
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Data {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                payload: &b"ghi"[..],
+            }))
+        );
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Gap(RecvGap {
+                seq_number: REMOTE_SEQ + 1 + 9,
+                len: 1,
+                timestamp: Instant::from_millis(0),
+            })))
+        );
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Data {
+                seq_number: REMOTE_SEQ + 1 + 10,
+                payload: &b"kl"[..],
+            }))
+        );
+        assert_eq!(s.recv_lossy(64), Ok(None));
+    }
+
+    #[test]
+    fn test_lossy_receive_recv_past_gap() {
+        let mut s = socket_lossy();
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            }
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"ghi"[..],
+                ..SEND_TEMPL
+            }
+        );
+
+        let mut data = [0; 4];
+        assert_eq!(s.recv_slice(&mut data), Ok(4));
+        assert_eq!(&data, b"abcg");
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Gap(RecvGap {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                len: 3,
+                timestamp: Instant::from_millis(0),
+            })))
+        );
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Data {
+                seq_number: REMOTE_SEQ + 1 + 7,
+                payload: &b"hi"[..],
+            }))
+        );
+    }
+
+    #[test]
+    fn test_lossy_receive_full() {
+        let mut s = socket_lossy();
+        s.set_recv_gap_storage(vec![RecvGap::EMPTY]);
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"b"[..],
+                ..SEND_TEMPL
+            }
+        );
+
+        // With no room for another gap, holes are waited for.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"d"[..],
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 2),
+                window_len: 63,
+                ..RECV_TEMPL
+            })
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 2,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"c"[..],
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 4),
+                window_len: 61,
+                ..RECV_TEMPL
+            })
+        );
+
+        assert!(matches!(s.recv_lossy(64), Ok(Some(LossyRecord::Gap(_)))));
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Data {
+                seq_number: REMOTE_SEQ + 1 + 1,
+                payload: &b"bcd"[..],
+            }))
+        );
+    }
+
+    #[test]
+    fn test_lossy_receive_disabled() {
+        let mut s = socket_lossy();
+        s.set_lossy_receive_enabled(false);
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"def"[..],
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            })
+        );
+        assert_eq!(s.recv_lossy(64), Ok(None));
+    }
+
+    #[test]
+    fn test_lossy_receive_fin() {
+        let mut s = socket_lossy();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Fin,
+                seq_number: REMOTE_SEQ + 1 + 3,
+                ack_number: Some(LOCAL_SEQ + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::CloseWait);
+        assert!(matches!(s.recv_lossy(64), Ok(Some(LossyRecord::Gap(_)))));
+        assert_eq!(s.recv_lossy(64), Err(RecvError::Finished));
+    }
+
+    #[test]
+    fn test_lossy_tail_reports_gap() {
+        let mut s = socket_lossy();
+        s.prepare_lossy_tail();
+        s.commit_lossy_tail(
+            Instant::from_millis(5),
+            &TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 10,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: b"tail",
+                ..SEND_TEMPL
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            s.recv_lossy(64),
+            Ok(Some(LossyRecord::Gap(RecvGap {
+                seq_number: REMOTE_SEQ + 1,
+                len: 10,
+                timestamp: Instant::from_millis(5),
+            })))
+        );
+        assert_eq!(s.recv_lossy(64), Ok(None));
+    }
+
+    // =========================================================================================//
+    // Tests for receive buffer autotuning.
+    // =========================================================================================//
+
+    /// A buffer with owned storage that doesn't override the growth methods, like
+    /// one implemented outside this crate.
+    #[derive(Debug)]
+    struct FixedBuffer(SocketBuffer<'static>);
+
+    impl SocketBufferT<'static> for FixedBuffer {
+        fn new<S: Into<ManagedSlice<'static, u8>>>(storage: S) -> Self {
+            FixedBuffer(SocketBuffer::new(storage))
+        }
+
+        fn clear(&mut self) {
+            self.0.clear()
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.capacity()
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn window(&self) -> usize {
+            self.0.window()
+        }
+
+        fn contiguous_window(&self) -> usize {
+            self.0.contiguous_window()
+        }
+
+        fn enqueue_many_with<'b, R, F>(&'b mut self, f: F) -> (usize, R)
+        where
+            F: FnOnce(&'b mut [u8]) -> (usize, R),
+        {
+            self.0.enqueue_many_with(f)
+        }
+
+        fn dequeue_many_with<'b, R, F>(&'b mut self, f: F) -> (usize, R)
+        where
+            F: FnOnce(&'b mut [u8]) -> (usize, R),
+        {
+            self.0.dequeue_many_with(f)
+        }
+
+        fn get_unallocated(&mut self, offset: usize, size: usize) -> &mut [u8] {
+            self.0.get_unallocated(offset, size)
+        }
+
+        fn write_unallocated(&mut self, offset: usize, data: &[u8]) -> usize {
+            self.0.write_unallocated(offset, data)
+        }
+
+        fn enqueue_unallocated(&mut self, count: usize) {
+            self.0.enqueue_unallocated(count)
+        }
+
+        fn get_allocated(&self, offset: usize, size: usize) -> &[u8] {
+            self.0.get_allocated(offset, size)
+        }
+
+        fn read_allocated(&mut self, offset: usize, data: &mut [u8]) -> usize {
+            self.0.read_allocated(offset, data)
+        }
+
+        fn dequeue_allocated(&mut self, count: usize) {
+            self.0.dequeue_allocated(count)
+        }
+
+        fn enqueue_slice(&mut self, data: &[u8]) -> usize {
+            self.0.enqueue_slice(data)
+        }
+
+        fn dequeue_slice(&mut self, data: &mut [u8]) -> usize {
+            self.0.dequeue_slice(data)
+        }
+
+        fn enqueue_many(&mut self, size: usize) -> &mut [u8] {
+            self.0.enqueue_many(size)
+        }
+
+        fn dequeue_many(&mut self, size: usize) -> &mut [u8] {
+            self.0.dequeue_many(size)
+        }
+    }
+
+    impl TestBufferFactory<'static> for FixedBuffer {
+        fn create(size: usize) -> Self {
+            FixedBuffer::new(vec![0u8; size])
+        }
+    }
+
+    fn socket_rx_autotuned<B: TestBufferFactory<'static>>(
+        rx_buffer: B,
+        max_window: usize,
+    ) -> TestSocket<B> {
+        let mut s = socket_generic::<B>(64, 64);
+        s.rx_buffer = rx_buffer;
+        s.set_rx_autotuning(Some(max_window));
+        s.state = State::Listen;
+        s.listen_endpoint = LISTEN_END;
+
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                window_scale: Some(0),
+                ..SEND_TEMPL
+            }
+        );
+        recv(&mut s, Instant::from_millis(0), |result| {
+            assert_eq!(result.unwrap().control, TcpControl::Syn)
+        });
+        send!(s, time 100, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.state, State::Established);
+        assert_eq!(s.rtte.smoothed_rtt(), Some(Duration::from_millis(100)));
+        // Start measuring.
+        recv_nothing!(s, time 100);
+        s
+    }
+
+    /// Send `count` full-sized segments, and read them back.
+    fn rx_autotune_drain<B: SocketBufferT<'static>>(s: &mut TestSocket<B>, count: usize) {
+        let payload = [0xaa; 1460];
+        for i in 0..count {
+            let repr = TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + i * 1460,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &payload,
+                ..SEND_TEMPL
+            };
+            assert_eq!(send(s, Instant::from_millis(110), &repr), None);
+        }
+        let mut data = vec![0; count * 1460];
+        assert_eq!(s.recv_slice(&mut data), Ok(count * 1460));
+        recv(s, Instant::from_millis(110), |result| {
+            assert!(result.is_ok())
+        });
+    }
+
+    #[test]
+    fn test_rx_autotuning_window_cap() {
+        let mut s = socket_rx_autotuned(FixedBuffer::create(100_000), 1 << 20);
+        assert_eq!(s.rx_autotuning(), Some(1 << 20));
+        // The window can't grow past the capacity of a buffer that can't grow.
+        assert_eq!(s.remote_win_shift, 1);
+        assert_eq!(s.rx_window(), 52560);
+
+        rx_autotune_drain(&mut s, 36);
+        // The window doesn't double, so no window update is sent.
+        recv_nothing!(s, time 250);
+        assert_eq!(s.rx_window(), 100_000);
+        assert_eq!(s.rx_buffer.capacity(), 100_000);
+    }
+
+    #[test]
+    fn test_rx_autotuning_grow_buffer() {
+        let mut s = socket_rx_autotuned(SocketBuffer::new(vec![0; 32768]), 1 << 20);
+        assert_eq!(s.remote_win_shift, 5);
+        assert_eq!(s.rx_window(), 32768);
+
+        rx_autotune_drain(&mut s, 22);
+        // Not a round trip yet.
+        recv_nothing!(s, time 150);
+
+        recv!(s, time 250, [TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 22 * 1460),
+            window_len: (297840 >> 5) as u16,
+            ..RECV_TEMPL
+        }]);
+        assert_eq!(s.rx_buffer.capacity(), 297840);
+        assert_eq!(s.rx_window(), 297840);
+    }
+
+    #[test]
+    fn test_rx_autotuning_slow_reader() {
+        let mut s = socket_rx_autotuned(SocketBuffer::new(vec![0; 32768]), 1 << 20);
+        rx_autotune_drain(&mut s, 5);
+        recv_nothing!(s, time 250);
+        assert_eq!(s.rx_buffer.capacity(), 32768);
+    }
+
+    #[test]
+    fn test_rx_autotuning_enable_established() {
+        let mut s = socket_established_with_buffer_sizes(64, 1024);
+        s.set_rx_autotuning(Some(1 << 20));
+        // The window already advertised doesn't shrink, nor does the window scale change.
+        assert_eq!(s.rx_window(), 1024);
+        assert_eq!(s.remote_win_shift, 0);
+
+        s.set_rx_autotuning(None);
+        assert_eq!(s.rx_autotuning(), None);
+        assert_eq!(s.rx_window(), 1024);
+    }
+
+    // =========================================================================================//
+    // Tests for the ACK policy.
+    // =========================================================================================//
+
+    fn socket_ack_policy(policy: AckPolicy) -> TestSocket {
+        let mut s = socket_established();
+        s.set_ack_policy(AckPolicy {
+            delay: Some(ACK_DELAY_DEFAULT),
+            ..policy
+        });
+        s
+    }
+
+    #[test]
+    fn test_ack_policy_every_segments() {
+        let mut s = socket_established_with_buffer_sizes(64, DEFAULT_MSS * 4);
+        s.set_ack_policy(AckPolicy {
+            every_segments: 3,
+            ..AckPolicy::DEFAULT
+        });
+        assert_eq!(s.ack_delay(), Some(ACK_DELAY_DEFAULT));
+        for i in 0..2 {
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1 + i * DEFAULT_MSS,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    payload: &[0; DEFAULT_MSS],
+                    ..SEND_TEMPL
+                }
+            );
+            recv_nothing!(s);
+        }
+
+        // More than two full-sized segments are unacknowledged.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 2 * DEFAULT_MSS,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"a"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 2 * DEFAULT_MSS + 1),
+                window_len: (2 * DEFAULT_MSS - 1) as u16,
+                ..RECV_TEMPL
+            }]
+        );
+        assert_eq!(s.info().acks_coalesced, 2);
+    }
+
+    #[test]
+    fn test_ack_policy_every_segment() {
+        let mut s = socket_ack_policy(AckPolicy {
+            every_segments: 1,
+            ..AckPolicy::DEFAULT
+        });
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 3),
+                window_len: 61,
+                ..RECV_TEMPL
+            }]
+        );
+        assert_eq!(s.info().acks_coalesced, 0);
+    }
+
+    #[test]
+    fn test_ack_policy_push() {
+        let mut s = socket_ack_policy(AckPolicy {
+            ack_on_push: true,
+            ..AckPolicy::DEFAULT
+        });
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Psh,
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 3),
+                window_len: 61,
+                ..RECV_TEMPL
+            }]
+        );
+
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"def"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv_nothing!(s);
+    }
+
+    #[test]
+    fn test_ack_policy_quick_ack_start() {
+        let mut s = socket();
+        s.set_ack_policy(AckPolicy {
+            quick_ack_segments: 2,
+            ..AckPolicy::DEFAULT
+        });
+        s.listen(LISTEN_END).unwrap();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                ..SEND_TEMPL
+            }
+        );
+        recv(&mut s, Instant::from_millis(0), |result| {
+            assert_eq!(result.unwrap().control, TcpControl::Syn)
+        });
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+
+        // The first two segments are acknowledged at once.
+        for (i, payload) in [b"abc", b"def"].iter().enumerate() {
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1 + i * 3,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    payload: &payload[..],
+                    ..SEND_TEMPL
+                }
+            );
+            recv!(
+                s,
+                [TcpRepr {
+                    seq_number: LOCAL_SEQ + 1,
+                    ack_number: Some(REMOTE_SEQ + 1 + (i + 1) * 3),
+                    window_len: 64 - (i as u16 + 1) * 3,
+                    ..RECV_TEMPL
+                }]
+            );
+        }
+
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"ghi"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv_nothing!(s);
+    }
+
+    #[test]
+    fn test_ack_policy_quick_ack_idle() {
+        let mut s = socket_ack_policy(AckPolicy {
+            quick_ack_segments: 1,
+            ..AckPolicy::DEFAULT
+        });
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv_nothing!(s);
+        recv!(s, time 10, [TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            ..RECV_TEMPL
+        }]);
+
+        // Nothing was received for longer than the retransmission timeout.
+        send!(s, time 2000, TcpRepr {
+            seq_number: REMOTE_SEQ + 1 + 3,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload: &b"def"[..],
+            ..SEND_TEMPL
+        });
+        recv!(s, time 2000, [TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 6),
+            window_len: 58,
+            ..RECV_TEMPL
+        }]);
+    }
+
+    #[test]
+    fn test_ack_policy_quick_ack_filled_hole() {
+        let mut s = socket_ack_policy(AckPolicy {
+            quick_ack_segments: 1,
+            ..AckPolicy::DEFAULT
+        });
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"def"[..],
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            })
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 6),
+                window_len: 58,
+                ..RECV_TEMPL
+            })
+        );
+
+        // The next segment is acknowledged at once too.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"ghi"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 9),
+                window_len: 55,
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    // =========================================================================================//
+    // Tests for retransmission timeout configuration.
+    // =========================================================================================//
+
+    #[test]
+    fn test_rto_config_initial_and_backoff() {
+        let mut s = socket_established();
+        s.set_rto_config(RtoConfig {
+            initial: Duration::from_millis(200),
+            min: Duration::from_millis(200),
+            backoff: 3,
+            ..RtoConfig::DEFAULT
+        });
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 199);
+        recv!(s, time 200, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 799);
+        recv!(s, time 800, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+    }
+
+    #[test]
+    fn test_rto_config_bounds() {
+        let mut s = socket_established();
+        s.set_rto_config(RtoConfig {
+            initial: Duration::from_millis(300),
+            min: Duration::from_millis(50),
+            max: Duration::from_millis(300),
+            ..RtoConfig::DEFAULT
+        });
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 10, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            ..SEND_TEMPL
+        });
+        // A round trip of 10 ms is well below the RFC 6298 floor of 1 second.
+        assert_eq!(s.rtte.retransmission_timeout(), Duration::from_millis(50));
+
+        s.send_slice(b"ghijkl").unwrap();
+        recv!(s, time 20, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"ghijkl"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 69);
+        recv!(s, time 70, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"ghijkl"[..],
+            ..RECV_TEMPL
+        }));
+
+        // The backoff stops at the maximum.
+        for _ in 0..4 {
+            s.rtte.on_retransmit();
+        }
+        assert_eq!(s.rtte.retransmission_timeout(), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_rto_config_retransmission_limit() {
+        let mut s = socket_established();
+        s.set_rto_config(RtoConfig {
+            initial: Duration::from_millis(100),
+            min: Duration::from_millis(100),
+            max_retransmissions: Some(2),
+            ..RtoConfig::DEFAULT
+        });
+        s.send_slice(b"abc").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abc"[..],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 100, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abc"[..],
+            ..RECV_TEMPL
+        }));
+
+        // New data is acknowledged, so the count starts over.
+        send!(s, time 150, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 3),
+            ..SEND_TEMPL
+        });
+        s.send_slice(b"def").unwrap();
+        recv!(s, time 150, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 3,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"def"[..],
+            ..RECV_TEMPL
+        }));
+        let mut t = 150;
+        for _ in 0..2 {
+            t += s.rtte.retransmission_timeout().total_millis() as i64;
+            recv!(s, time t, Ok(TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 3,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload:    &b"def"[..],
+                ..RECV_TEMPL
+            }));
+        }
+        assert_eq!(s.close_reason(), None);
+
+        t += s.rtte.retransmission_timeout().total_millis() as i64;
+        recv_nothing!(s, time t - 1);
+        recv!(s, time t, Ok(TcpRepr {
+            control:    TcpControl::Rst,
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            ..RECV_TEMPL
+        }));
+        assert_eq!(s.state, State::Closed);
+        assert_eq!(s.close_reason(), Some(CloseReason::RetransmissionLimit));
+        assert_eq!(s.info().retransmission_timeouts, 3);
+
+        // The reason is kept until the socket is used again.
+        s.listen(LOCAL_END).unwrap();
+        assert_eq!(s.close_reason(), None);
+    }
+
+    #[test]
+    fn test_rto_config_kept_across_connections() {
+        let mut s = socket_established();
+        let config = RtoConfig {
+            min: Duration::from_millis(10),
+            max_retransmissions: Some(5),
+            ..RtoConfig::DEFAULT
+        };
+        s.set_rto_config(config);
+        s.abort();
+        s.listen(LOCAL_END).unwrap();
+        assert_eq!(s.rto_config(), config);
+    }
+
+    #[test]
+    #[should_panic(expected = "minimum RTO larger than the maximum")]
+    fn test_rto_config_min_above_max() {
+        let mut s = socket();
+        s.set_rto_config(RtoConfig {
+            min: Duration::from_secs(2),
+            max: Duration::from_secs(1),
+            ..RtoConfig::DEFAULT
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "initial RTO outside of the minimum and maximum")]
+    fn test_rto_config_initial_below_min() {
+        let mut s = socket();
+        s.set_rto_config(RtoConfig {
+            initial: Duration::from_millis(100),
+            ..RtoConfig::DEFAULT
+        });
+    }
+
+    // =========================================================================================//
+    // Tests for close reasons.
+    // =========================================================================================//
+
+    #[test]
+    fn test_close_reason_reset() {
+        let mut s = socket_established();
+        assert_eq!(s.close_reason(), None);
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Closed);
+        assert_eq!(s.close_reason(), Some(CloseReason::Reset));
+
+        // Aborting a closed socket doesn't change the reason.
+        s.abort();
+        assert_eq!(s.close_reason(), Some(CloseReason::Reset));
+    }
+
+    #[test]
+    fn test_close_reason_abort() {
+        let mut s = socket_established();
+        s.abort();
+        assert_eq!(s.close_reason(), Some(CloseReason::Aborted));
+
+        let mut s = socket_listen();
+        s.close();
+        assert_eq!(s.close_reason(), Some(CloseReason::Aborted));
+    }
+
+    #[test]
+    fn test_close_reason_last_ack() {
+        let mut s = socket_last_ack();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Fin,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        assert_eq!(s.close_reason(), None);
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Closed);
+        assert_eq!(s.close_reason(), Some(CloseReason::Graceful));
+    }
+
+    #[test]
+    fn test_close_reason_time_wait() {
+        let mut s = socket_time_wait(false);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        recv_nothing!(s, time 60_000);
+        assert_eq!(s.state, State::Closed);
+        assert_eq!(s.close_reason(), Some(CloseReason::Graceful));
+    }
+
+    #[test]
+    fn test_close_reason_kept_until_listen() {
+        let mut s = socket_established();
+        s.abort();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        assert_eq!(s.close_reason(), Some(CloseReason::Aborted));
+
+        s.listen(LOCAL_END).unwrap();
+        assert_eq!(s.close_reason(), None);
+    }
+
+    // =========================================================================================//
+    // Tests for handing over the TIME-WAIT state.
+    // =========================================================================================//
+
+    #[test]
+    fn test_take_time_wait() {
+        let mut s = socket_time_wait(false);
+        // The FIN of the remote isn't acknowledged yet.
+        assert!(s.take_time_wait().is_none());
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 1),
+                ..RECV_TEMPL
+            }]
+        );
+
+        let time_wait = s.take_time_wait().unwrap();
+        assert_eq!(time_wait.local, LOCAL_END);
+        assert_eq!(time_wait.remote, REMOTE_END);
+        assert_eq!(time_wait.local_seq_no, LOCAL_SEQ + 1 + 1);
+        assert_eq!(time_wait.remote_seq_no, REMOTE_SEQ + 1 + 1);
+        assert_eq!(time_wait.window_len, 64);
+        assert_eq!(time_wait.expires_at, Instant::from_secs(1) + CLOSE_DELAY);
+
+        assert_eq!(s.state, State::Closed);
+        assert_eq!(s.close_reason(), Some(CloseReason::Graceful));
+        recv_nothing!(s);
+        assert!(s.take_time_wait().is_none());
+    }
+
+    #[test]
+    fn test_take_time_wait_unread() {
+        let mut s = socket_fin_wait_2();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Fin,
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 3 + 1),
+                window_len: 61,
+                ..RECV_TEMPL
+            }]
+        );
+        assert_eq!(s.state, State::TimeWait);
+        assert!(s.take_time_wait().is_none());
+
+        let mut buf = [0; 3];
+        assert_eq!(s.recv_slice(&mut buf), Ok(3));
+        assert!(s.take_time_wait().is_some());
+    }
+
+    // =========================================================================================//
+    // Tests for connection repair.
+    // =========================================================================================//
+
+    fn socket_restored(checkpoint: &[u8]) -> TestSocket {
+        let mut s = socket();
+        s.socket = Socket::restore(
+            SocketBuffer::new(vec![0; 64]),
+            SocketBuffer::new(vec![0; 64]),
+            checkpoint,
+        )
+        .unwrap();
+        s.set_ack_delay(None);
+        s
+    }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let mut s = socket_established();
+        s.send_slice(b"abcdef").unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 2),
+                payload: &b"xyz"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 6,
+                ack_number: Some(REMOTE_SEQ + 1 + 3),
+                window_len: 61,
+                ..RECV_TEMPL
+            }]
+        );
+
+        let mut checkpoint = [0; 128];
+        let len = s.checkpoint(&mut checkpoint).unwrap();
+        let checkpoint = &checkpoint[..len];
+        let repair = RepairState::parse(checkpoint).unwrap();
+        assert_eq!(s.repair_state(), Ok(repair));
+        assert_eq!(repair.state, State::Established);
+        assert_eq!((repair.rx_len, repair.tx_len), (3, 4));
+
+        let mut s = socket_restored(checkpoint);
+        assert_eq!(s.repair_state(), Ok(repair));
+
+        // What was not acknowledged is sent again.
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 2,
+                ack_number: Some(REMOTE_SEQ + 1 + 3),
+                payload: &b"cdef"[..],
+                window_len: 61,
+                ..RECV_TEMPL
+            }]
+        );
+        let mut buffer = [0; 8];
+        assert_eq!(s.recv_slice(&mut buffer), Ok(3));
+        assert_eq!(&buffer[..3], b"xyz");
+    }
+
+    #[test]
+    fn test_checkpoint_last_local_tsval() {
+        let mut s = socket_established();
+        s.set_tsval_generator(Some(|| 500));
+        s.send_slice(b"abc").unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abc"[..],
+                timestamp: Some(TcpTimestampRepr::new(500, 0)),
+                ..RECV_TEMPL
+            }]
+        );
+
+        let mut checkpoint = [0; 128];
+        let len = s.checkpoint(&mut checkpoint).unwrap();
+        let repair = RepairState::parse(&checkpoint[..len]).unwrap();
+        assert!(repair.timestamps);
+        assert_eq!(repair.last_local_tsval, 500);
+
+        // It is saved again before the restored socket sends anything.
+        let s = socket_restored(&checkpoint[..len]);
+        assert_eq!(s.repair_state().unwrap().last_local_tsval, 500);
+    }
+
+    #[test]
+    fn test_restore_fin_wait_1() {
+        let s = socket_fin_wait_1();
+        let mut checkpoint = [0; 128];
+        let len = s.checkpoint(&mut checkpoint).unwrap();
+
+        let mut s = socket_restored(&checkpoint[..len]);
+        assert_eq!(s.state, State::FinWait1);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Fin,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_errors() {
+        let s = socket_listen();
+        assert_eq!(s.repair_state(), Err(RepairError::InvalidState));
+
+        let mut s = socket_established();
+        s.send_slice(b"abcdef").unwrap();
+        let mut checkpoint = [0; 128];
+        let len = s.checkpoint(&mut checkpoint).unwrap();
+        assert_eq!(
+            s.checkpoint(&mut checkpoint[..len - 1]),
+            Err(RepairError::BufferTooSmall)
+        );
+
+        // The transmit buffer is too small for the data.
+        assert_eq!(
+            Socket::<SocketBuffer>::restore(
+                SocketBuffer::new(vec![0; 64]),
+                SocketBuffer::new(vec![0; 4]),
+                &checkpoint[..len],
+            )
+            .err(),
+            Some(RepairError::BufferTooSmall)
+        );
+        assert_eq!(
+            Socket::<SocketBuffer>::restore(
+                SocketBuffer::new(vec![0; 64]),
+                SocketBuffer::new(vec![0; 64]),
+                &checkpoint[..len - 1],
+            )
+            .err(),
+            Some(RepairError::Malformed)
+        );
+    }
+
+    // =========================================================================================//
+    // Tests for packet filtering.
+    // =========================================================================================//
+
+    #[test]
+    fn test_doesnt_accept_wrong_port() {
+        let mut s = socket_established();
+        s.rx_buffer = SocketBuffer::new(vec![0; 6]);
+        s.assembler = Assembler::new();
+
+        let tcp_repr = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            dst_port: LOCAL_PORT + 1,
+            ..SEND_TEMPL
+        };
+        assert!(!s.socket.accepts(&mut s.cx, &SEND_IP_TEMPL, &tcp_repr));
+
+        let tcp_repr = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            src_port: REMOTE_PORT + 1,
+            ..SEND_TEMPL
+        };
+        assert!(!s.socket.accepts(&mut s.cx, &SEND_IP_TEMPL, &tcp_repr));
+    }
+
+    #[test]
+    fn test_doesnt_accept_wrong_ip() {
+        let mut s = socket_established();
+
+        let tcp_repr = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload: &b"abcdef"[..],
+            ..SEND_TEMPL
+        };
+
+        let ip_repr = IpReprIpvX(IpvXRepr {
+            src_addr: REMOTE_ADDR,
+            dst_addr: LOCAL_ADDR,
+            next_header: IpProtocol::Tcp,
+            payload_len: tcp_repr.buffer_len(),
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        });
+        assert!(s.socket.accepts(&mut s.cx, &ip_repr, &tcp_repr));
+
+        let ip_repr_wrong_src = IpReprIpvX(IpvXRepr {
+            src_addr: OTHER_ADDR,
+            dst_addr: LOCAL_ADDR,
+            next_header: IpProtocol::Tcp,
+            payload_len: tcp_repr.buffer_len(),
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        });
+        assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_src, &tcp_repr));
+
+        let ip_repr_wrong_dst = IpReprIpvX(IpvXRepr {
+            src_addr: REMOTE_ADDR,
+            dst_addr: OTHER_ADDR,
+            next_header: IpProtocol::Tcp,
+            payload_len: tcp_repr.buffer_len(),
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        });
+        assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_dst, &tcp_repr));
+    }
+
+    // =========================================================================================//
+    // Timer tests
+    // =========================================================================================//
+
+    #[test]
+    fn test_timer_retransmit() {
+        const RTO: Duration = Duration::from_millis(100);
+        let mut r = Timer::new();
+        assert!(!r.should_retransmit(Instant::from_secs(1)));
+        r.set_for_retransmit(Instant::from_millis(1000), RTO);
+        assert!(!r.should_retransmit(Instant::from_millis(1000)));
+        assert!(!r.should_retransmit(Instant::from_millis(1050)));
+        assert!(r.should_retransmit(Instant::from_millis(1101)));
+        r.set_for_retransmit(Instant::from_millis(1101), RTO);
+        assert!(!r.should_retransmit(Instant::from_millis(1101)));
+        assert!(!r.should_retransmit(Instant::from_millis(1150)));
+        assert!(!r.should_retransmit(Instant::from_millis(1200)));
+        assert!(r.should_retransmit(Instant::from_millis(1301)));
         r.set_for_idle(Instant::from_millis(1301), None);
         assert!(!r.should_retransmit(Instant::from_millis(1350)));
     }
@@ -8850,8 +14295,217 @@ mod test {
             assert_eq!(s.congestion_control(), CongestionControl::Cubic);
         }
 
+        #[cfg(feature = "socket-tcp-bbr")]
+        {
+            s.set_congestion_control(CongestionControl::Bbr);
+            assert_eq!(s.congestion_control(), CongestionControl::Bbr);
+        }
+
         s.set_congestion_control(CongestionControl::None);
         assert_eq!(s.congestion_control(), CongestionControl::None);
+        assert_eq!(
+            s.congestion_control(),
+            ActiveCongestionControl::BuiltIn(CongestionControl::None)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "socket-tcp-bbr")]
+    fn test_bbr_late_clock_stays_in_startup() {
+        let mut s = socket_established();
+        s.set_congestion_control(CongestionControl::Bbr);
+        let window = s.congestion_controller.inner().window();
+
+        // The first RTT sample, taken long after the clock started, grows the
+        // window rather than starting a ProbeRtt at the minimum window.
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 1_000_000, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 1_000_040, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.congestion_controller.inner().window(), window + 6);
+    }
+
+    #[derive(Debug, Default)]
+    struct FixedWindow {
+        window: usize,
+        acked: usize,
+        dup_acks: usize,
+        latest_rtt: Option<Duration>,
+    }
+
+    impl congestion::Controller for FixedWindow {
+        fn window(&self) -> usize {
+            self.window
+        }
+
+        fn on_ack(&mut self, _now: Instant, len: usize, rtt: &RttEstimator) {
+            self.acked += len;
+            self.latest_rtt = rtt.latest_rtt();
+        }
+
+        fn on_duplicate_ack(&mut self, _now: Instant) {
+            self.dup_acks += 1;
+        }
+    }
+
+    #[test]
+    fn test_custom_congestion_control() {
+        let mut s = socket_established();
+        let controller: Box<dyn congestion::Controller> = Box::new(FixedWindow {
+            window: 6,
+            ..Default::default()
+        });
+        s.set_custom_congestion_control(controller);
+        assert_eq!(s.congestion_control(), ActiveCongestionControl::Custom);
+
+        // The controller's window limits the data in flight.
+        s.send_slice(b"abcdefghijkl").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 0);
+
+        send!(s, time 40, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            ..SEND_TEMPL
+        });
+        recv!(s, time 40, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"ghijkl"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 50, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            ..SEND_TEMPL
+        });
+
+        let congestion::AnyController::Custom(controller) = &s.congestion_controller else {
+            panic!("custom controller was replaced");
+        };
+        assert_eq!(
+            format!("{:?}", &**controller),
+            format!(
+                "{:?}",
+                FixedWindow {
+                    window: 6,
+                    acked: 6,
+                    dup_acks: 1,
+                    latest_rtt: Some(Duration::from_millis(40)),
+                }
+            )
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct LastRateSample {
+        window: usize,
+        sample: Option<congestion::RateSample>,
+    }
+
+    impl congestion::Controller for LastRateSample {
+        fn window(&self) -> usize {
+            self.window
+        }
+
+        fn on_rate_sample(&mut self, _now: Instant, sample: &congestion::RateSample) {
+            self.sample = Some(*sample);
+        }
+    }
+
+    #[test]
+    fn test_rate_sample() {
+        let mut s = socket_established();
+        let controller: Box<dyn congestion::Controller> = Box::new(LastRateSample {
+            window: 6,
+            ..Default::default()
+        });
+        s.set_custom_congestion_control(controller);
+        s.remote_mss = 6;
+        let last_sample = |s: &TestSocket, sample: congestion::RateSample| {
+            let congestion::AnyController::Custom(controller) = &s.congestion_controller else {
+                panic!("custom controller was replaced");
+            };
+            let expected = LastRateSample {
+                window: 6,
+                sample: Some(sample),
+            };
+            assert_eq!(format!("{:?}", &**controller), format!("{:?}", expected));
+        };
+
+        // The congestion window limits the data in flight, not the application.
+        s.send_slice(b"abcdefghijkl").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 40, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            ..SEND_TEMPL
+        });
+        last_sample(
+            &s,
+            congestion::RateSample {
+                acked: 6,
+                prior_delivered: 0,
+                delivered: 6,
+                interval: Some(Duration::from_millis(40)),
+                rtt: Some(Duration::from_millis(40)),
+                app_limited: false,
+            },
+        );
+        recv!(s, time 40, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"ghijkl"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 80, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 12),
+            ..SEND_TEMPL
+        });
+
+        // Less than a segment is queued, so the application limits the rate.
+        s.send_slice(b"mnop").unwrap();
+        recv!(s, time 80, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1 + 12,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"mnop"[..],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 120, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 16),
+            ..SEND_TEMPL
+        });
+        last_sample(
+            &s,
+            congestion::RateSample {
+                acked: 4,
+                prior_delivered: 12,
+                delivered: 4,
+                interval: Some(Duration::from_millis(40)),
+                rtt: Some(Duration::from_millis(40)),
+                app_limited: true,
+            },
+        );
     }
 
     // =========================================================================================//
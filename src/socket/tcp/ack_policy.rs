@@ -0,0 +1,148 @@
+// Delayed acknowledgements, as described in RFC 9293 section 3.8.6.3, and
+// quick ACKs in the spirit of Linux's quickack mode.
+//
+// Delaying ACKs lets a receiver acknowledge several segments at once, and
+// piggyback ACKs on the data it sends. On the other hand, the sender relies on
+// ACKs to open its congestion window at the start of a connection, and to learn
+// that lost data was recovered. In quick-ACK mode, a number of segments are
+// acknowledged at once regardless of the delay; the mode is entered when the
+// connection starts, after receiving nothing for a while, and when a hole in the
+// received data is filled.
+
+use crate::time::{Duration, Instant};
+
+/// When a socket acknowledges the data it receives, see
+/// [Socket::set_ack_policy](super::Socket::set_ack_policy).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AckPolicy {
+    /// How long an ACK may be delayed, or `None` to acknowledge every segment at once.
+    pub delay: Option<Duration>,
+    /// Acknowledge at once when this many full-sized segments are unacknowledged.
+    /// Zero or one acknowledges every segment at once.
+    pub every_segments: usize,
+    /// Acknowledge at once the segments received in quick-ACK mode, which lasts for
+    /// this many segments.
+    pub quick_ack_segments: usize,
+    /// Acknowledge at once the segments with the PSH flag set.
+    pub ack_on_push: bool,
+}
+
+impl AckPolicy {
+    /// The default policy: delay ACKs by 10 ms, acknowledge at least every second
+    /// full-sized segment, and have no quick-ACK mode.
+    pub const DEFAULT: AckPolicy = AckPolicy {
+        delay: Some(super::ACK_DELAY_DEFAULT),
+        every_segments: 2,
+        quick_ack_segments: 0,
+        ack_on_push: false,
+    };
+}
+
+impl Default for AckPolicy {
+    fn default() -> Self {
+        AckPolicy::DEFAULT
+    }
+}
+
+/// The state of quick-ACK mode.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(super) struct QuickAck {
+    /// How many more segments to acknowledge at once.
+    remaining: usize,
+    /// When the last segment carrying data was received.
+    last_data_at: Option<Instant>,
+}
+
+impl QuickAck {
+    pub(super) const fn new() -> Self {
+        QuickAck {
+            remaining: 0,
+            last_data_at: None,
+        }
+    }
+
+    /// Enter quick-ACK mode for the segments of `policy`.
+    pub(super) fn enter(&mut self, policy: &AckPolicy) {
+        self.remaining = policy.quick_ack_segments;
+    }
+
+    /// Start over for a new connection, in quick-ACK mode.
+    pub(super) fn reset(&mut self, policy: &AckPolicy) {
+        self.enter(policy);
+        self.last_data_at = None;
+    }
+
+    /// Called when a segment carrying data is received. Quick-ACK mode is entered
+    /// again if nothing was received for longer than `idle`, or after the segment if
+    /// `filled_hole` is true.
+    ///
+    /// Returns whether to acknowledge the segment at once.
+    pub(super) fn on_data(
+        &mut self,
+        policy: &AckPolicy,
+        now: Instant,
+        idle: Duration,
+        filled_hole: bool,
+    ) -> bool {
+        if self
+            .last_data_at
+            .is_some_and(|last_data_at| now > last_data_at + idle)
+        {
+            self.enter(policy);
+        }
+        self.last_data_at = Some(now);
+
+        let quick = self.remaining > 0;
+        if quick {
+            self.remaining -= 1;
+        }
+        // The segment that fills a hole is acknowledged at once anyway, the mode
+        // applies to the segments after it.
+        if filled_hole {
+            self.enter(policy);
+        }
+        quick
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const IDLE: Duration = Duration::from_millis(1000);
+
+    #[test]
+    fn test_quick_ack() {
+        let policy = AckPolicy {
+            quick_ack_segments: 2,
+            ..AckPolicy::DEFAULT
+        };
+        let mut quick_ack = QuickAck::new();
+        quick_ack.reset(&policy);
+
+        let t = Instant::from_millis(0);
+        assert!(quick_ack.on_data(&policy, t, IDLE, false));
+        assert!(quick_ack.on_data(&policy, t, IDLE, false));
+        assert!(!quick_ack.on_data(&policy, t, IDLE, false));
+
+        // A hole is filled.
+        assert!(!quick_ack.on_data(&policy, t, IDLE, true));
+        assert!(quick_ack.on_data(&policy, t, IDLE, false));
+        assert!(quick_ack.on_data(&policy, t, IDLE, false));
+        assert!(!quick_ack.on_data(&policy, t + IDLE, IDLE, false));
+
+        // Nothing was received for a while.
+        let later = t + IDLE * 2 + Duration::from_millis(1);
+        assert!(quick_ack.on_data(&policy, later, IDLE, false));
+    }
+
+    #[test]
+    fn test_no_quick_ack() {
+        let policy = AckPolicy::DEFAULT;
+        let mut quick_ack = QuickAck::new();
+        quick_ack.reset(&policy);
+        assert!(!quick_ack.on_data(&policy, Instant::ZERO, IDLE, true));
+    }
+}
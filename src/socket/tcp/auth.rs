@@ -0,0 +1,292 @@
+// Segment authentication with TCP-MD5 (RFC 2385) or TCP-AO (RFC 5925).
+//
+// TCP-MD5 signs every segment with the shared key itself. TCP-AO derives
+// traffic keys from a master key and the initial sequence numbers of both
+// sides, and also covers a sequence number extension (SNE) that counts how many
+// times the sequence number of each direction wrapped around.
+
+use crate::wire::{
+    IpEndpoint, TcpAoAlgorithm, TcpAoTrafficKey, TcpAuth, TcpAuthKey, TcpAuthSecret, TcpControl,
+    TcpRepr, TcpSeqNumber,
+};
+
+/// Segment authentication for a socket, see
+/// [set_authentication](super::Socket::set_authentication).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Authentication {
+    /// TCP-MD5 signatures, as described in RFC 2385.
+    Md5 { key: TcpAuthSecret },
+    /// The TCP Authentication Option, as described in RFC 5925, with a single master
+    /// key tuple.
+    ///
+    /// Segments are sent with `send_id` as their `KeyID`, and must be received with
+    /// `recv_id` as theirs.
+    Ao {
+        algorithm: TcpAoAlgorithm,
+        send_id: u8,
+        recv_id: u8,
+        master_key: TcpAuthSecret,
+    },
+}
+
+impl Authentication {
+    /// Return the length of the option carrying the MAC, padding included.
+    const fn option_len(&self) -> usize {
+        match self {
+            Authentication::Md5 { .. } => 20,
+            Authentication::Ao { .. } => 4 + TcpAoAlgorithm::MAC_LEN,
+        }
+    }
+}
+
+/// The sequence number extension of one direction, as described in RFC 5925
+/// section 6.2.
+#[derive(Debug, Clone, Copy)]
+struct Sne {
+    sne: u32,
+    /// The highest sequence number seen.
+    prev: u32,
+}
+
+impl Sne {
+    fn new(isn: TcpSeqNumber) -> Sne {
+        Sne {
+            sne: 0,
+            prev: isn.0 as u32,
+        }
+    }
+
+    /// Return the extension of `seq`.
+    fn get(&self, seq: TcpSeqNumber) -> u32 {
+        let seq = seq.0 as u32;
+        if seq < self.prev && self.prev - seq > 1 << 31 {
+            // The sequence number wrapped around.
+            self.sne.wrapping_add(1)
+        } else if seq > self.prev && seq - self.prev > 1 << 31 {
+            // A segment from before the sequence number last wrapped around.
+            self.sne.saturating_sub(1)
+        } else {
+            self.sne
+        }
+    }
+
+    /// Return the extension of `seq`, and remember it if `seq` is the highest yet.
+    fn update(&mut self, seq: TcpSeqNumber) -> u32 {
+        let sne = self.get(seq);
+        let ext = |sne: u32, seq: u32| ((sne as u64) << 32) | seq as u64;
+        if ext(sne, seq.0 as u32) > ext(self.sne, self.prev) {
+            self.sne = sne;
+            self.prev = seq.0 as u32;
+        }
+        sne
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Authenticator {
+    config: Option<Authentication>,
+    local_isn: TcpSeqNumber,
+    remote_isn: TcpSeqNumber,
+    /// TCP-AO traffic keys for segments other than SYNs, as `(local_isn, remote_isn,
+    /// send, receive)`.
+    traffic_keys: Option<(TcpSeqNumber, TcpSeqNumber, TcpAoTrafficKey, TcpAoTrafficKey)>,
+    snd_sne: Sne,
+    rcv_sne: Sne,
+}
+
+impl Authenticator {
+    pub(super) const fn new() -> Self {
+        Authenticator {
+            config: None,
+            local_isn: TcpSeqNumber(0),
+            remote_isn: TcpSeqNumber(0),
+            traffic_keys: None,
+            snd_sne: Sne { sne: 0, prev: 0 },
+            rcv_sne: Sne { sne: 0, prev: 0 },
+        }
+    }
+
+    pub(super) fn config(&self) -> Option<Authentication> {
+        self.config
+    }
+
+    pub(super) fn set_config(&mut self, config: Option<Authentication>) {
+        self.config = config;
+        self.traffic_keys = None;
+    }
+
+    /// Forget about the connection, keeping the configuration.
+    pub(super) fn reset(&mut self) {
+        *self = Authenticator {
+            config: self.config,
+            ..Authenticator::new()
+        }
+    }
+
+    pub(super) fn set_local_isn(&mut self, isn: TcpSeqNumber) {
+        self.local_isn = isn;
+        self.snd_sne = Sne::new(isn);
+    }
+
+    pub(super) fn set_remote_isn(&mut self, isn: TcpSeqNumber) {
+        self.remote_isn = isn;
+        self.rcv_sne = Sne::new(isn);
+    }
+
+    /// Return how much room the authentication option takes in each segment.
+    pub(super) fn option_len(&self) -> usize {
+        self.config.map(|config| config.option_len()).unwrap_or(0)
+    }
+
+    /// Return the TCP-AO traffic key for `repr`, sent by us if `sending` and received
+    /// otherwise.
+    fn traffic_key(
+        &mut self,
+        local: IpEndpoint,
+        remote: IpEndpoint,
+        repr: &TcpRepr,
+        sending: bool,
+    ) -> Option<TcpAoTrafficKey> {
+        let Some(Authentication::Ao {
+            algorithm,
+            master_key,
+            ..
+        }) = self.config
+        else {
+            return None;
+        };
+        let (src, dst) = if sending {
+            (local, remote)
+        } else {
+            (remote, local)
+        };
+
+        // RFC 5925 section 5.2: SYNs are signed before the other side has picked an
+        // initial sequence number.
+        if repr.control == TcpControl::Syn && repr.ack_number.is_none() {
+            return Some(TcpAoTrafficKey::derive(
+                algorithm,
+                &master_key,
+                src,
+                dst,
+                repr.seq_number,
+                TcpSeqNumber(0),
+            ));
+        }
+
+        let (local_isn, remote_isn) = match repr.control {
+            // A SYN|ACK carries the initial sequence number of its sender.
+            TcpControl::Syn if sending => (repr.seq_number, self.remote_isn),
+            TcpControl::Syn => (self.local_isn, repr.seq_number),
+            _ => (self.local_isn, self.remote_isn),
+        };
+        let (send, receive) = match self.traffic_keys {
+            Some((local, remote, send, receive)) if local == local_isn && remote == remote_isn => {
+                (send, receive)
+            }
+            _ => {
+                let send = TcpAoTrafficKey::derive(
+                    algorithm,
+                    &master_key,
+                    local,
+                    remote,
+                    local_isn,
+                    remote_isn,
+                );
+                let receive = TcpAoTrafficKey::derive(
+                    algorithm,
+                    &master_key,
+                    remote,
+                    local,
+                    remote_isn,
+                    local_isn,
+                );
+                self.traffic_keys = Some((local_isn, remote_isn, send, receive));
+                (send, receive)
+            }
+        };
+        Some(if sending { send } else { receive })
+    }
+
+    /// Return how to sign `repr`, sent from `local` to `remote`.
+    pub(super) fn sign(
+        &mut self,
+        local: IpEndpoint,
+        remote: IpEndpoint,
+        repr: &TcpRepr,
+    ) -> Option<TcpAuth<'static>> {
+        let key = match self.config? {
+            Authentication::Md5 { key } => TcpAuthKey::Md5(key),
+            Authentication::Ao {
+                send_id, recv_id, ..
+            } => {
+                // SYNs are sent with a zero SNE.
+                let sne = match repr.control {
+                    TcpControl::Syn => 0,
+                    _ => self.snd_sne.update(repr.seq_number),
+                };
+                TcpAuthKey::Ao {
+                    key_id: send_id,
+                    rnext_key_id: recv_id,
+                    // NOTE(unwrap): the configuration is for TCP-AO.
+                    traffic_key: self.traffic_key(local, remote, repr, true).unwrap(),
+                    sne,
+                }
+            }
+        };
+        Some(TcpAuth::Sign(key))
+    }
+
+    /// Return whether `repr`, received by `local` from `remote`, is authentic.
+    ///
+    /// Segments are only checked if authentication is configured.
+    pub(super) fn verify(&mut self, local: IpEndpoint, remote: IpEndpoint, repr: &TcpRepr) -> bool {
+        let Some(config) = self.config else {
+            return true;
+        };
+        let Some(auth) = repr.auth else {
+            return false;
+        };
+        let key = match config {
+            Authentication::Md5 { key } => TcpAuthKey::Md5(key),
+            Authentication::Ao { recv_id, .. } => {
+                let sne = match repr.control {
+                    TcpControl::Syn => 0,
+                    _ => self.rcv_sne.get(repr.seq_number),
+                };
+                TcpAuthKey::Ao {
+                    key_id: recv_id,
+                    rnext_key_id: 0,
+                    // NOTE(unwrap): the configuration is for TCP-AO.
+                    traffic_key: self.traffic_key(local, remote, repr, false).unwrap(),
+                    sne,
+                }
+            }
+        };
+        if !auth.verify(&key, &remote.addr, &local.addr) {
+            return false;
+        }
+        if repr.control != TcpControl::Syn {
+            self.rcv_sne.update(repr.seq_number);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sne() {
+        let mut sne = Sne::new(TcpSeqNumber(-100));
+        assert_eq!(sne.update(TcpSeqNumber(-50)), 0);
+        assert_eq!(sne.update(TcpSeqNumber(50)), 1);
+        // A retransmission from before the wrap around.
+        assert_eq!(sne.update(TcpSeqNumber(-60)), 0);
+        assert_eq!(sne.update(TcpSeqNumber(60)), 1);
+        assert_eq!(sne.update(TcpSeqNumber(i32::MAX)), 1);
+        assert_eq!(sne.update(TcpSeqNumber(-(1 << 30))), 1);
+        assert_eq!(sne.update(TcpSeqNumber(0)), 2);
+    }
+}
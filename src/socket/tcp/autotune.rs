@@ -0,0 +1,207 @@
+// Receive buffer autotuning, in the spirit of Linux's dynamic right-sizing
+// (tcp_rcv_space_adjust).
+//
+// A receive window smaller than what the application drains in a round trip
+// limits the throughput of the connection, while a larger one only lets the
+// remote fill the buffer faster than it is read. Once per round trip, the socket
+// measures how many octets the application read, and sizes the window for about
+// twice that, so that the remote is not held back by the window as long as the
+// application keeps up. The window only grows, within a configured maximum.
+
+use crate::time::{Duration, Instant};
+use crate::wire::TcpSeqNumber;
+
+/// The receive MSS assumed until a segment is received, that of Ethernet.
+const INITIAL_RCV_MSS: usize = 1460;
+
+/// The octets drained per round trip assumed at the start of a connection, in
+/// segments, like the initial congestion window of RFC 6928.
+const INITIAL_SPACE_SEGMENTS: usize = 10;
+
+/// The segments added to twice the octets drained per round trip, to absorb
+/// bursts.
+const WINDOW_MARGIN_SEGMENTS: usize = 16;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(super) struct Autotune {
+    /// The largest window to grow to.
+    max: usize,
+    /// The window the remote may currently fill.
+    window: usize,
+    /// The largest number of octets drained in a round trip so far.
+    space: usize,
+    /// The largest segment received.
+    rcv_mss: usize,
+    /// The first octet not drained when the current measurement started.
+    seq: TcpSeqNumber,
+    /// When the current measurement started.
+    time: Option<Instant>,
+}
+
+impl Autotune {
+    pub(super) fn new(max: usize) -> Self {
+        let mut autotune = Autotune {
+            max,
+            window: 0,
+            space: 0,
+            rcv_mss: INITIAL_RCV_MSS,
+            seq: TcpSeqNumber::default(),
+            time: None,
+        };
+        autotune.reset(max);
+        autotune
+    }
+
+    /// Return the largest window to grow to.
+    pub(super) fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Return the window the remote may currently fill.
+    pub(super) fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Start over for a new connection, with a window of at most `limit` octets.
+    pub(super) fn reset(&mut self, limit: usize) {
+        self.rcv_mss = INITIAL_RCV_MSS;
+        self.space = INITIAL_SPACE_SEGMENTS * self.rcv_mss;
+        self.window = self.window_for(self.space).min(limit);
+        self.time = None;
+    }
+
+    /// Let the window cover `window` octets, if it is smaller.
+    pub(super) fn open(&mut self, window: usize) {
+        self.window = self.window.max(window);
+    }
+
+    /// Called when a segment carrying `len` octets is received.
+    pub(super) fn on_segment(&mut self, len: usize) {
+        self.rcv_mss = self.rcv_mss.max(len);
+    }
+
+    /// Measure how many octets were drained, `seq` being the first octet not
+    /// drained yet, and grow the window if it was more than ever in a round trip.
+    /// The window grows to at most `limit` octets.
+    ///
+    /// Returns the new window, if it grew.
+    pub(super) fn adjust(
+        &mut self,
+        now: Instant,
+        seq: TcpSeqNumber,
+        rtt: Duration,
+        limit: usize,
+    ) -> Option<usize> {
+        let Some(time) = self.time.filter(|time| now >= *time + rtt) else {
+            if self.time.is_none() {
+                self.seq = seq;
+                self.time = Some(now);
+            }
+            return None;
+        };
+
+        let copied = seq - self.seq;
+        self.seq = seq;
+        self.time = Some(now);
+        net_trace!("autotune: {} octets drained in {}", copied, now - time);
+        if copied <= self.space {
+            return None;
+        }
+
+        // Like Linux, grow faster while the drain rate keeps increasing. The product
+        // overflows 32 bits for windows close to the maximum, hence the 64 bits.
+        let window = self.window_for(copied) as u64;
+        let growth = window.saturating_mul((copied - self.space) as u64) / self.space as u64;
+        let window = window.saturating_add(growth.saturating_mul(2));
+        self.space = copied;
+
+        let window = window.min(self.max.min(limit) as u64) as usize;
+        if window <= self.window {
+            return None;
+        }
+        self.window = window;
+        Some(window)
+    }
+
+    fn window_for(&self, space: usize) -> usize {
+        space
+            .saturating_mul(2)
+            .saturating_add(WINDOW_MARGIN_SEGMENTS * self.rcv_mss)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RTT: Duration = Duration::from_millis(100);
+    const SEQ: TcpSeqNumber = TcpSeqNumber(1000);
+    const MAX: usize = 1 << 20;
+
+    #[test]
+    fn test_initial_window() {
+        let autotune = Autotune::new(MAX);
+        assert_eq!(autotune.window(), 52560);
+        let autotune = Autotune::new(4096);
+        assert_eq!(autotune.window(), 4096);
+    }
+
+    #[test]
+    fn test_grow() {
+        let mut autotune = Autotune::new(MAX);
+        let t0 = Instant::from_millis(0);
+        assert_eq!(autotune.adjust(t0, SEQ, RTT, MAX), None);
+
+        // Not a round trip yet.
+        assert_eq!(autotune.adjust(t0 + RTT / 2, SEQ + 30000, RTT, MAX), None);
+
+        // Less than the initial space drained in a round trip.
+        assert_eq!(autotune.adjust(t0 + RTT, SEQ + 10000, RTT, MAX), None);
+        assert_eq!(autotune.window(), 52560);
+
+        // 20000 octets drained in a round trip, up from 14600.
+        let t1 = t0 + RTT;
+        assert_eq!(
+            autotune.adjust(t1 + RTT, SEQ + 30000, RTT, MAX),
+            Some(63360 + 2 * (63360 * 5400 / 14600))
+        );
+
+        // The drain rate no longer increases.
+        let t2 = t1 + RTT;
+        assert_eq!(autotune.adjust(t2 + RTT, SEQ + 50000, RTT, MAX), None);
+
+        // The window is limited.
+        let t3 = t2 + RTT;
+        assert_eq!(
+            autotune.adjust(t3 + RTT, SEQ + 150000, RTT, 200000),
+            Some(200000)
+        );
+        assert_eq!(autotune.window(), 200000);
+    }
+
+    #[test]
+    fn test_grow_large() {
+        let max = 1 << 30;
+        let mut autotune = Autotune::new(max);
+        let t0 = Instant::from_millis(0);
+        assert_eq!(autotune.adjust(t0, SEQ, RTT, max), None);
+
+        // The growth doesn't overflow, and stops at the maximum.
+        assert_eq!(
+            autotune.adjust(t0 + RTT, SEQ + (1 << 29), RTT, max),
+            Some(max)
+        );
+    }
+
+    #[test]
+    fn test_rcv_mss() {
+        let mut autotune = Autotune::new(MAX);
+        autotune.on_segment(536);
+        autotune.on_segment(8960);
+        assert_eq!(autotune.window_for(0), 16 * 8960);
+
+        autotune.reset(MAX);
+        assert_eq!(autotune.window(), 52560);
+    }
+}
@@ -1,6 +1,18 @@
+//! Congestion control for TCP sockets.
+//!
+//! The algorithms built into smoltcp are selected with
+//! [`Socket::set_congestion_control`](super::Socket::set_congestion_control).
+//! Other algorithms can be plugged in by implementing [`Controller`] and installing
+//! it with [`Socket::set_custom_congestion_control`](super::Socket::set_custom_congestion_control).
+
+use core::fmt;
+
+use managed::Managed;
+
 use crate::time::Instant;
 
-use super::RttEstimator;
+pub use super::RttEstimator;
+pub use super::rate::RateSample;
 
 pub(super) mod no_control;
 
@@ -10,31 +22,76 @@ pub(super) mod cubic;
 #[cfg(feature = "socket-tcp-reno")]
 pub(super) mod reno;
 
+#[cfg(feature = "socket-tcp-bbr")]
+pub(super) mod bbr;
+
+/// A congestion control algorithm.
+///
+/// The socket calls these hooks as it sends data and processes acknowledgements,
+/// and limits the octets in flight to [`window`](Controller::window).
+/// All hooks except `window` have empty default implementations.
+///
+/// Controllers implement `Debug` so that the socket holding one can too.
 #[allow(unused_variables)]
-pub(super) trait Controller {
-    /// Returns the number of bytes that can be sent.
+pub trait Controller: fmt::Debug {
+    /// Returns the congestion window: the number of octets that may be in flight.
     fn window(&self) -> usize;
 
+    /// Returns the slow start threshold, or `None` if the algorithm has none or
+    /// hasn't set it yet.
+    fn slow_start_threshold(&self) -> Option<usize> {
+        None
+    }
+
     /// Set the remote window size.
     fn set_remote_window(&mut self, remote_window: usize) {}
 
+    /// Called when `len` previously unacknowledged octets are acknowledged.
+    ///
+    /// `rtt` already includes the RTT sample taken from this acknowledgement, if any.
     fn on_ack(&mut self, now: Instant, len: usize, rtt: &RttEstimator) {}
 
+    /// Called with a delivery rate sample when an acknowledgement delivers data, after
+    /// `on_ack` if it also advances the cumulative acknowledgement.
+    fn on_rate_sample(&mut self, now: Instant, sample: &RateSample) {}
+
+    /// Called when the socket retransmits because of a timeout, or enters loss recovery.
     fn on_retransmit(&mut self, now: Instant) {}
 
+    /// Called for every duplicate acknowledgement.
     fn on_duplicate_ack(&mut self, now: Instant) {}
 
+    /// Called when the remote reports that the network marked segments with
+    /// Congestion Experienced, at most once per window of data.
+    ///
+    /// This is a sign of congestion without loss, so nothing is retransmitted.
+    fn on_ecn(&mut self, now: Instant) {}
+
+    /// Called before the socket checks whether it can send anything.
     fn pre_transmit(&mut self, now: Instant) {}
 
+    /// Called after the socket sends a segment occupying `len` octets of sequence space.
     fn post_transmit(&mut self, now: Instant, len: usize) {}
 
     /// Set the maximum segment size.
     fn set_mss(&mut self, mss: usize) {}
+
+    /// Called when the socket is restored from a checkpoint, with the congestion
+    /// window and slow start threshold of the controller that was saved.
+    fn restore(&mut self, window: usize, slow_start_threshold: Option<usize>) {}
+
+    /// Returns the rate at which the socket should send, in octets per second,
+    /// or `None` to send as fast as the window allows.
+    ///
+    /// The rate is used unless pacing is configured otherwise with
+    /// [`Socket::set_pacing`](super::Socket::set_pacing).
+    fn pacing_rate(&self) -> Option<u64> {
+        None
+    }
 }
 
-#[derive(Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub(super) enum AnyController {
+#[allow(clippy::large_enum_variant)]
+pub(super) enum AnyController<'a> {
     None(no_control::NoControl),
 
     #[cfg(feature = "socket-tcp-reno")]
@@ -42,9 +99,53 @@ pub(super) enum AnyController {
 
     #[cfg(feature = "socket-tcp-cubic")]
     Cubic(cubic::Cubic),
+
+    #[cfg(feature = "socket-tcp-bbr")]
+    Bbr(bbr::Bbr),
+
+    Custom(Managed<'a, dyn Controller + 'static>),
+}
+
+impl fmt::Debug for AnyController<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnyController::None(n) => f.debug_tuple("None").field(n).finish(),
+
+            #[cfg(feature = "socket-tcp-reno")]
+            AnyController::Reno(r) => f.debug_tuple("Reno").field(r).finish(),
+
+            #[cfg(feature = "socket-tcp-cubic")]
+            AnyController::Cubic(c) => f.debug_tuple("Cubic").field(c).finish(),
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(b) => f.debug_tuple("Bbr").field(b).finish(),
+
+            AnyController::Custom(c) => f.debug_tuple("Custom").field(&&**c).finish(),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for AnyController<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            AnyController::None(n) => defmt::write!(f, "None({})", n),
+
+            #[cfg(feature = "socket-tcp-reno")]
+            AnyController::Reno(r) => defmt::write!(f, "Reno({})", r),
+
+            #[cfg(feature = "socket-tcp-cubic")]
+            AnyController::Cubic(c) => defmt::write!(f, "Cubic({})", c),
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(b) => defmt::write!(f, "Bbr({})", b),
+
+            AnyController::Custom(_) => defmt::write!(f, "Custom"),
+        }
+    }
 }
 
-impl AnyController {
+impl AnyController<'_> {
     /// Create a new congestion controller.
     /// `AnyController::new()` selects the best congestion controller based on the features.
     ///
@@ -53,6 +154,7 @@ impl AnyController {
     /// - If both `socket-tcp-cubic` and `socket-tcp-reno` features are enabled, it will use `Cubic`.
     ///    - `Cubic` is more efficient regarding throughput.
     ///    - `Reno` is more conservative and is suitable for low-power devices.
+    /// - `Bbr` is never selected by default, even if the `socket-tcp-bbr` feature is enabled.
     /// - If no congestion controller is available, it will use `NoControl`.
     ///
     /// Users can also select a congestion controller manually by [`super::Socket::set_congestion_control()`]
@@ -83,6 +185,11 @@ impl AnyController {
 
             #[cfg(feature = "socket-tcp-cubic")]
             AnyController::Cubic(c) => c,
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(b) => b,
+
+            AnyController::Custom(c) => &mut **c,
         }
     }
 
@@ -96,6 +203,29 @@ impl AnyController {
 
             #[cfg(feature = "socket-tcp-cubic")]
             AnyController::Cubic(c) => c,
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(b) => b,
+
+            AnyController::Custom(c) => &**c,
+        }
+    }
+
+    /// Returns whether the window bounds all the octets in flight.
+    ///
+    /// Outside of SACK loss recovery, the window of the loss-based algorithms only
+    /// bounds the octets sent at once, as it always has. That of `Bbr` and of a
+    /// user-defined controller bounds everything sent but not acknowledged.
+    #[inline]
+    pub fn bounds_in_flight(&self) -> bool {
+        match self {
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(_) => true,
+
+            AnyController::Custom(_) => true,
+
+            #[allow(unreachable_patterns)]
+            _ => false,
         }
     }
 }
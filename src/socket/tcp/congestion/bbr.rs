@@ -0,0 +1,543 @@
+// A model-based congestion controller in the style of BBR
+// (draft-cardwell-iccrg-bbr-congestion-control).
+//
+// Instead of reacting to loss, BBR estimates the bottleneck bandwidth and the
+// round-trip propagation delay of the path, and keeps about one bandwidth-delay
+// product in flight. This keeps the window from collapsing on paths with random
+// loss and avoids filling shallow buffers.
+//
+// The model is updated from the delivery rate samples the socket takes of each
+// acknowledgement. A round trip ends once data sent after the previous one ended
+// is delivered. Samples taken while the application didn't keep the window full
+// only count if they raise the bandwidth estimate, so idle periods don't lower it.
+//
+// All arithmetic is done on integers, so that it can be used on targets without
+// an FPU. Gains are fixed-point numbers in units of `1 / GAIN_UNIT`.
+
+use crate::time::{Duration, Instant};
+
+use super::{Controller, RateSample};
+
+const GAIN_UNIT: u64 = 256;
+
+/// The gain in `Startup`, 2/ln(2), enough to double the delivery rate every round.
+const HIGH_GAIN: u64 = GAIN_UNIT * 2885 / 1000 + 1;
+
+/// The gain in `Drain`, the inverse of `HIGH_GAIN`.
+const DRAIN_GAIN: u64 = GAIN_UNIT * 1000 / 2885;
+
+/// The window gain in `ProbeBw`, which leaves room for delayed and stretched ACKs.
+const CWND_GAIN: u64 = GAIN_UNIT * 2;
+
+/// The gain cycle in `ProbeBw`: probe for more bandwidth for a round, drain the
+/// queue this may have created for a round, then cruise.
+const PACING_GAIN_CYCLE: [u64; 8] = [
+    GAIN_UNIT * 5 / 4,
+    GAIN_UNIT * 3 / 4,
+    GAIN_UNIT,
+    GAIN_UNIT,
+    GAIN_UNIT,
+    GAIN_UNIT,
+    GAIN_UNIT,
+    GAIN_UNIT,
+];
+
+/// The number of rounds the bottleneck bandwidth is the maximum over.
+const BW_WINDOW_ROUNDS: usize = 10;
+
+/// How long a minimum RTT sample stays valid without being refreshed.
+const MIN_RTT_WINDOW: Duration = Duration::from_secs(10);
+
+/// How long to stay in `ProbeRtt` with a minimal window.
+const PROBE_RTT_DURATION: Duration = Duration::from_millis(200);
+
+/// The number of rounds without 25% bandwidth growth after which the pipe is full.
+const FULL_BW_ROUNDS: u8 = 3;
+
+/// The minimum window, in segments.
+const MIN_CWND_SEGMENTS: usize = 4;
+
+/// The window of a new connection, which grows in `Startup` at least until this
+/// much data was delivered.
+const INITIAL_CWND: usize = 1024 * 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum State {
+    /// Grow the window exponentially to find the bottleneck bandwidth.
+    Startup,
+    /// Drain the queue created during `Startup`.
+    Drain,
+    /// Cycle around the bottleneck bandwidth, probing for more.
+    ProbeBw,
+    /// Shrink the window to let the queue empty and remeasure the minimum RTT.
+    ProbeRtt,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Bbr {
+    state: State,
+    cwnd: usize,
+    min_cwnd: usize,
+    /// The window before entering `ProbeRtt`, restored afterwards.
+    prior_cwnd: usize,
+
+    /// The number of completed rounds.
+    round: u64,
+    /// The round ends once a segment sent after this many octets were delivered
+    /// is delivered.
+    next_round_delivered: u64,
+
+    /// The highest delivery rate of each of the last `BW_WINDOW_ROUNDS` rounds with
+    /// a sample, in octets per second, and the round it was taken in.
+    bw_samples: [(u64, u64); BW_WINDOW_ROUNDS],
+    /// The estimated bottleneck bandwidth: the maximum of `bw_samples` in the window.
+    max_bw: u64,
+
+    /// The estimated round-trip propagation delay, and when it was measured. The
+    /// stamp is meaningless until there is a sample.
+    min_rtt: Option<Duration>,
+    min_rtt_stamp: Instant,
+    /// When `ProbeRtt` ends.
+    probe_rtt_done: Option<Instant>,
+
+    /// Bandwidth at the last time it grew by 25%, and rounds since.
+    full_bw: u64,
+    full_bw_count: u8,
+    full_bw_reached: bool,
+
+    /// Position in `PACING_GAIN_CYCLE`.
+    cycle_index: usize,
+    /// The window is not grown until this round ends, after a loss.
+    hold_until_round: Option<u64>,
+}
+
+impl Bbr {
+    pub fn new() -> Self {
+        Bbr {
+            state: State::Startup,
+            cwnd: INITIAL_CWND,
+            min_cwnd: 1024 * 2,
+            prior_cwnd: 0,
+            round: 0,
+            next_round_delivered: 0,
+            bw_samples: [(0, 0); BW_WINDOW_ROUNDS],
+            max_bw: 0,
+            min_rtt: None,
+            min_rtt_stamp: Instant::ZERO,
+            probe_rtt_done: None,
+            full_bw: 0,
+            full_bw_count: 0,
+            full_bw_reached: false,
+            cycle_index: 0,
+            hold_until_round: None,
+        }
+    }
+
+    /// The pacing gain of the current state.
+    fn pacing_gain(&self) -> u64 {
+        match self.state {
+            State::Startup => HIGH_GAIN,
+            State::Drain => DRAIN_GAIN,
+            State::ProbeBw => PACING_GAIN_CYCLE[self.cycle_index],
+            State::ProbeRtt => GAIN_UNIT,
+        }
+    }
+
+    /// The window gain of the current state.
+    ///
+    /// Pacing may be disabled on the socket, leaving the window as the only means
+    /// of control, so it also carries the gain otherwise applied to the pacing rate.
+    fn cwnd_gain(&self) -> u64 {
+        match self.state {
+            State::Startup => HIGH_GAIN,
+            State::Drain => DRAIN_GAIN * HIGH_GAIN / GAIN_UNIT,
+            State::ProbeBw => CWND_GAIN * PACING_GAIN_CYCLE[self.cycle_index] / GAIN_UNIT,
+            State::ProbeRtt => GAIN_UNIT,
+        }
+    }
+
+    /// The estimated bandwidth-delay product, or `None` if there is no estimate yet.
+    fn bdp(&self) -> Option<u64> {
+        let min_rtt = self.min_rtt?;
+        if self.max_bw == 0 {
+            return None;
+        }
+        let min_rtt = min_rtt.total_micros().max(1000);
+        Some(self.max_bw.saturating_mul(min_rtt) / 1_000_000)
+    }
+
+    fn target_cwnd(&self) -> Option<usize> {
+        let target = self.bdp()?.saturating_mul(self.cwnd_gain()) / GAIN_UNIT;
+        Some((target.min(usize::MAX as u64) as usize).max(self.min_cwnd))
+    }
+
+    /// Take the RTT of `sample`, if it has one, as the minimum RTT if it is lower or
+    /// the minimum has expired.
+    fn update_min_rtt(&mut self, now: Instant, sample: &RateSample) {
+        let expired = self.min_rtt.is_some() && now >= self.min_rtt_stamp + MIN_RTT_WINDOW;
+        if let Some(rtt) = sample.rtt()
+            && (self.min_rtt.is_none_or(|min_rtt| rtt < min_rtt) || expired)
+        {
+            self.min_rtt = Some(rtt);
+            self.min_rtt_stamp = now;
+        }
+
+        if expired && self.state != State::ProbeRtt {
+            net_trace!("bbr: min_rtt expired, entering ProbeRtt");
+            self.state = State::ProbeRtt;
+            self.prior_cwnd = self.prior_cwnd.max(self.cwnd);
+            self.probe_rtt_done = Some(now + PROBE_RTT_DURATION);
+        }
+    }
+
+    /// Count a round if `sample` is of data sent after the current one started.
+    /// Returns whether a round ended.
+    fn update_round(&mut self, sample: &RateSample) -> bool {
+        if sample.prior_delivered() < self.next_round_delivered {
+            return false;
+        }
+        self.next_round_delivered = sample.prior_delivered() + sample.delivered();
+        self.round += 1;
+        true
+    }
+
+    /// Take the delivery rate of `sample` into the bandwidth estimate.
+    fn update_bw(&mut self, sample: &RateSample) {
+        let Some(rate) = sample.delivery_rate() else {
+            return;
+        };
+        // A sample limited by the application only tells the path is at least
+        // that fast.
+        if sample.is_app_limited() && rate < self.max_bw {
+            return;
+        }
+
+        let slot = &mut self.bw_samples[self.round as usize % BW_WINDOW_ROUNDS];
+        if slot.1 != self.round || rate > slot.0 {
+            *slot = (rate, self.round);
+        }
+        let round = self.round;
+        self.max_bw = self
+            .bw_samples
+            .iter()
+            .filter(|(_, sample_round)| round - sample_round < BW_WINDOW_ROUNDS as u64)
+            .map(|(rate, _)| *rate)
+            .max()
+            .unwrap_or(0);
+    }
+
+    fn check_full_bw(&mut self) {
+        if self.full_bw_reached {
+            return;
+        }
+        if self.max_bw >= self.full_bw.saturating_mul(5) / 4 {
+            self.full_bw = self.max_bw;
+            self.full_bw_count = 0;
+            return;
+        }
+        self.full_bw_count += 1;
+        self.full_bw_reached = self.full_bw_count >= FULL_BW_ROUNDS;
+    }
+
+    fn update_state(&mut self, now: Instant, round_ended: bool) {
+        match self.state {
+            State::Startup if self.full_bw_reached => {
+                net_trace!("bbr: pipe full at {} octets/s, entering Drain", self.max_bw);
+                self.state = State::Drain;
+            }
+            State::Drain if round_ended => {
+                self.state = State::ProbeBw;
+                self.cycle_index = 0;
+            }
+            State::ProbeBw if round_ended => {
+                self.cycle_index = (self.cycle_index + 1) % PACING_GAIN_CYCLE.len();
+            }
+            State::ProbeRtt if self.probe_rtt_done.is_some_and(|done| now >= done) => {
+                self.probe_rtt_done = None;
+                self.min_rtt_stamp = now;
+                self.cwnd = self.cwnd.max(self.prior_cwnd);
+                self.prior_cwnd = 0;
+                self.state = if self.full_bw_reached {
+                    State::ProbeBw
+                } else {
+                    State::Startup
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// Grow the window by the `len` octets just delivered, towards the target,
+    /// `delivered` being the octets delivered in total.
+    fn update_cwnd(&mut self, len: usize, delivered: u64) {
+        if self.state == State::ProbeRtt {
+            return;
+        }
+        if self
+            .hold_until_round
+            .is_some_and(|round| self.round < round)
+        {
+            return;
+        }
+        self.hold_until_round = None;
+
+        match self.target_cwnd() {
+            Some(target) if self.full_bw_reached => {
+                self.cwnd = self.cwnd.saturating_add(len).min(target);
+            }
+            Some(target) if self.cwnd >= target && delivered >= INITIAL_CWND as u64 => {}
+            _ => self.cwnd = self.cwnd.saturating_add(len),
+        }
+        self.cwnd = self.cwnd.max(self.min_cwnd);
+    }
+}
+
+impl Controller for Bbr {
+    fn window(&self) -> usize {
+        match self.state {
+            State::ProbeRtt => self.min_cwnd,
+            _ => self.cwnd,
+        }
+    }
+
+    fn on_rate_sample(&mut self, now: Instant, sample: &RateSample) {
+        let round_ended = self.update_round(sample);
+        self.update_min_rtt(now, sample);
+        self.update_bw(sample);
+        // The bandwidth may not have grown only for lack of data to send.
+        if round_ended && self.state == State::Startup && !sample.is_app_limited() {
+            self.check_full_bw();
+        }
+        self.update_state(now, round_ended);
+        let delivered = sample.prior_delivered() + sample.delivered();
+        self.update_cwnd(sample.acked().min(usize::MAX as u64) as usize, delivered);
+    }
+
+    fn on_retransmit(&mut self, _now: Instant) {
+        // Loss is not a congestion signal for BBR, but don't grow the window
+        // while recovering from it.
+        self.hold_until_round = Some(self.round + 1);
+    }
+
+    fn on_ecn(&mut self, _now: Instant) {
+        // BBR paces from its own bandwidth and RTT model rather than from
+        // congestion signals; treat a congestion mark like a loss.
+        self.hold_until_round = Some(self.round + 1);
+    }
+
+    fn set_mss(&mut self, mss: usize) {
+        self.min_cwnd = mss * MIN_CWND_SEGMENTS;
+        self.cwnd = self.cwnd.max(self.min_cwnd);
+    }
+
+    fn restore(&mut self, window: usize, _slow_start_threshold: Option<usize>) {
+        // The model of the path is measured again, from the window it had.
+        self.cwnd = window.max(self.min_cwnd);
+    }
+
+    fn pacing_rate(&self) -> Option<u64> {
+        if self.max_bw == 0 {
+            return None;
+        }
+        Some(self.max_bw.saturating_mul(self.pacing_gain()) / GAIN_UNIT)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MSS: usize = 1000;
+
+    /// A path the window of `bbr` is kept in flight over.
+    struct Path {
+        now: Instant,
+        delivered: u64,
+    }
+
+    impl Path {
+        fn new(now: Instant) -> Self {
+            Path { now, delivered: 0 }
+        }
+
+        /// Feed `bbr` the rate samples of `rounds` rounds of `rtt_ms` each, over a path
+        /// delivering at most `bw` octets per second, with each segment acknowledged
+        /// one round trip after it was sent.
+        fn run(&mut self, bbr: &mut Bbr, rounds: usize, rtt_ms: u32, bw: u64) {
+            self.run_limited(bbr, rounds, rtt_ms, bw, false);
+        }
+
+        fn run_limited(
+            &mut self,
+            bbr: &mut Bbr,
+            rounds: usize,
+            rtt_ms: u32,
+            bw: u64,
+            app_limited: bool,
+        ) {
+            let rtt = Duration::from_millis(rtt_ms as u64);
+            for _ in 0..rounds {
+                let per_round = (bw * rtt_ms as u64 / 1000).min(bbr.window() as u64);
+                for _ in 0..10 {
+                    self.now += rtt / 10;
+                    self.delivered += per_round / 10;
+                    let sample = RateSample {
+                        acked: per_round / 10,
+                        prior_delivered: self.delivered.saturating_sub(per_round),
+                        delivered: per_round.min(self.delivered),
+                        interval: Some(rtt),
+                        rtt: Some(rtt),
+                        app_limited,
+                    };
+                    bbr.on_rate_sample(self.now, &sample);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bbr_startup_grows_window() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        let mut path = Path::new(Instant::from_millis(0));
+        let initial = bbr.window();
+        path.run(&mut bbr, 3, 100, 10_000_000);
+        assert_eq!(bbr.state, State::Startup);
+        assert!(bbr.window() > initial * 4);
+    }
+
+    #[test]
+    fn test_bbr_startup_late_clock() {
+        // The clock of a real system is far past the minimum RTT window when
+        // the connection starts, which must not count as an expired sample.
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        let mut path = Path::new(Instant::from_millis(1_000_000));
+        let initial = bbr.window();
+        path.run(&mut bbr, 3, 100, 10_000_000);
+        assert_eq!(bbr.state, State::Startup);
+        assert_eq!(bbr.min_rtt, Some(Duration::from_millis(100)));
+        assert!(bbr.window() > initial * 4);
+    }
+
+    #[test]
+    fn test_bbr_converges_to_bdp() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        let mut path = Path::new(Instant::from_millis(0));
+        // 1 MB/s over 100ms is a BDP of 100 kB.
+        path.run(&mut bbr, 30, 100, 1_000_000);
+        assert_eq!(bbr.state, State::ProbeBw);
+        assert!(bbr.full_bw_reached);
+        assert!((950_000..=1_050_000).contains(&bbr.max_bw));
+        assert_eq!(bbr.bdp(), Some(bbr.max_bw / 10));
+        assert!(bbr.window() <= 2 * 5 * 100_000 / 4);
+        assert!(bbr.window() >= 100_000);
+    }
+
+    #[test]
+    fn test_bbr_loss_does_not_collapse_window() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        let mut path = Path::new(Instant::from_millis(0));
+        path.run(&mut bbr, 30, 100, 1_000_000);
+        let cwnd = bbr.window();
+
+        for _ in 0..10 {
+            bbr.on_retransmit(path.now);
+            path.run(&mut bbr, 1, 100, 1_000_000);
+            assert!(bbr.window() >= cwnd / 2);
+        }
+    }
+
+    #[test]
+    fn test_bbr_app_limited_keeps_bw() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        let mut path = Path::new(Instant::from_millis(0));
+        path.run(&mut bbr, 30, 100, 1_000_000);
+        let max_bw = bbr.max_bw;
+        let bdp = bbr.bdp();
+
+        // The application sends little for longer than the bandwidth window.
+        path.run_limited(&mut bbr, 2 * BW_WINDOW_ROUNDS, 100, 10_000, true);
+        assert_eq!(bbr.max_bw, max_bw);
+        assert_eq!(bbr.bdp(), bdp);
+    }
+
+    #[test]
+    fn test_bbr_min_rtt_fresh_samples_only() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        let mut path = Path::new(Instant::from_millis(0));
+        path.run(&mut bbr, 3, 100, 1_000_000);
+        let stamp = bbr.min_rtt_stamp;
+
+        // Acknowledgements of retransmissions carry no RTT sample, which doesn't
+        // refresh the minimum.
+        path.now += Duration::from_secs(5);
+        let sample = RateSample {
+            acked: MSS as u64,
+            prior_delivered: path.delivered,
+            delivered: MSS as u64,
+            interval: None,
+            rtt: None,
+            app_limited: false,
+        };
+        bbr.on_rate_sample(path.now, &sample);
+        assert_eq!(bbr.min_rtt, Some(Duration::from_millis(100)));
+        assert_eq!(bbr.min_rtt_stamp, stamp);
+    }
+
+    #[test]
+    fn test_bbr_probe_rtt() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        let mut path = Path::new(Instant::from_millis(0));
+        path.run(&mut bbr, 30, 100, 1_000_000);
+        let cwnd = bbr.window();
+
+        // The RTT never drops below its first sample, so it expires after 10s.
+        let mut rounds = 0;
+        while bbr.state != State::ProbeRtt {
+            path.run(&mut bbr, 1, 100, 1_000_000);
+            rounds += 1;
+        }
+        assert!((65..=75).contains(&rounds));
+        assert_eq!(bbr.window(), MIN_CWND_SEGMENTS * MSS);
+
+        path.run(&mut bbr, 3, 100, 1_000_000);
+        assert_eq!(bbr.state, State::ProbeBw);
+        assert!(bbr.window() >= cwnd / 2);
+    }
+
+    #[test]
+    fn test_bbr_pacing_rate() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        assert_eq!(bbr.pacing_rate(), None);
+
+        let mut path = Path::new(Instant::from_millis(0));
+        path.run(&mut bbr, 30, 100, 1_000_000);
+        assert_eq!(bbr.state, State::ProbeBw);
+        let rate = bbr.pacing_rate().unwrap();
+        assert_eq!(
+            rate,
+            bbr.max_bw * PACING_GAIN_CYCLE[bbr.cycle_index] / GAIN_UNIT
+        );
+        assert!((700_000..=1_300_000).contains(&rate));
+    }
+
+    #[test]
+    fn test_bbr_min_cwnd() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(MSS);
+        let now = Instant::from_millis(0);
+        for _ in 0..100 {
+            bbr.on_retransmit(now);
+            assert!(bbr.window() >= MIN_CWND_SEGMENTS * MSS);
+        }
+    }
+}
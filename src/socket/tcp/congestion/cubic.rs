@@ -38,6 +38,10 @@ impl Controller for Cubic {
         self.cwnd
     }
 
+    fn slow_start_threshold(&self) -> Option<usize> {
+        (self.ssthresh != usize::MAX).then_some(self.ssthresh)
+    }
+
     fn on_retransmit(&mut self, now: Instant) {
         self.w_max = self.cwnd;
         self.ssthresh = self.cwnd >> 1;
@@ -50,6 +54,12 @@ impl Controller for Cubic {
         self.recovery_start = Some(now);
     }
 
+    fn on_ecn(&mut self, now: Instant) {
+        self.w_max = self.cwnd;
+        self.ssthresh = self.cwnd >> 1;
+        self.recovery_start = Some(now);
+    }
+
     fn set_remote_window(&mut self, remote_window: usize) {
         if self.rwnd < remote_window {
             self.rwnd = remote_window;
@@ -107,6 +117,12 @@ impl Controller for Cubic {
     fn set_mss(&mut self, mss: usize) {
         self.min_cwnd = mss;
     }
+
+    fn restore(&mut self, window: usize, slow_start_threshold: Option<usize>) {
+        self.cwnd = window.max(self.min_cwnd);
+        self.w_max = self.cwnd;
+        self.ssthresh = slow_start_threshold.unwrap_or(usize::MAX);
+    }
 }
 
 #[inline]
@@ -282,6 +298,7 @@ mod test {
         let cwnd = cubic.window();
         cubic.on_retransmit(t3);
         assert_eq!(cwnd >> 1, cubic.ssthresh);
+        assert_eq!(cubic.slow_start_threshold(), Some(cwnd >> 1));
     }
 
     #[test]
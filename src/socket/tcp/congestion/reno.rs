@@ -27,6 +27,10 @@ impl Controller for Reno {
         self.cwnd
     }
 
+    fn slow_start_threshold(&self) -> Option<usize> {
+        (self.ssthresh != usize::MAX).then_some(self.ssthresh)
+    }
+
     fn on_ack(&mut self, _now: Instant, len: usize, _rtt: &RttEstimator) {
         let len = if self.cwnd < self.ssthresh {
             // Slow start.
@@ -51,10 +55,20 @@ impl Controller for Reno {
         self.cwnd = (self.cwnd >> 1).max(self.min_cwnd);
     }
 
+    fn on_ecn(&mut self, _now: Instant) {
+        self.ssthresh = (self.cwnd >> 1).max(self.min_cwnd);
+        self.cwnd = self.ssthresh;
+    }
+
     fn set_mss(&mut self, mss: usize) {
         self.min_cwnd = mss;
     }
 
+    fn restore(&mut self, window: usize, slow_start_threshold: Option<usize>) {
+        self.cwnd = window.max(self.min_cwnd);
+        self.ssthresh = slow_start_threshold.unwrap_or(usize::MAX);
+    }
+
     fn set_remote_window(&mut self, remote_window: usize) {
         if self.rwnd < remote_window {
             self.rwnd = remote_window;
@@ -127,4 +141,15 @@ mod test {
 
         println!("{reno:?}");
     }
+
+    #[test]
+    fn test_reno_ecn() {
+        let mut reno = Reno::new();
+        reno.set_mss(1000);
+        let cwnd = reno.window();
+        assert_eq!(reno.slow_start_threshold(), None);
+        reno.on_ecn(Instant::from_millis(0));
+        assert_eq!(reno.window(), cwnd / 2);
+        assert_eq!(reno.slow_start_threshold(), Some(cwnd / 2));
+    }
 }
@@ -0,0 +1,166 @@
+// TCP Fast Open state shared by all sockets of an interface, as described in
+// RFC 7413.
+//
+// Clients remember the cookie each server handed out, so that the next
+// connection to that server can carry data in its SYN. Servers compute cookies
+// from the client address with a keyed hash, so that they don't have to
+// remember anything per client. The key is drawn from the interface `Rand` the
+// first time a cookie is needed.
+
+use heapless::Vec;
+
+use crate::config::TCP_FAST_OPEN_CACHE_COUNT;
+use crate::rand::Rand;
+use crate::wire::{IpAddress, TcpFastOpenCookie};
+
+#[derive(Debug)]
+pub(crate) struct FastOpen {
+    /// Key for cookies handed out as a server.
+    key: Option<[u64; 2]>,
+    /// Cookies received as a client, least recently stored first.
+    cookies: Vec<(IpAddress, TcpFastOpenCookie), TCP_FAST_OPEN_CACHE_COUNT>,
+}
+
+impl FastOpen {
+    pub(crate) const fn new() -> Self {
+        FastOpen {
+            key: None,
+            cookies: Vec::new(),
+        }
+    }
+
+    /// Return the cookie received from the server at `addr`, if any.
+    pub(crate) fn cookie(&self, addr: &IpAddress) -> Option<TcpFastOpenCookie> {
+        self.cookies
+            .iter()
+            .find(|(cached, _)| cached == addr)
+            .map(|(_, cookie)| *cookie)
+    }
+
+    /// Remember the cookie received from the server at `addr`.
+    pub(crate) fn set_cookie(&mut self, addr: IpAddress, cookie: TcpFastOpenCookie) {
+        if let Some(index) = self.cookies.iter().position(|(cached, _)| *cached == addr) {
+            self.cookies.remove(index);
+        } else if self.cookies.is_full() {
+            self.cookies.remove(0);
+        }
+        // NOTE(unwrap): there is room, we just made some if needed.
+        self.cookies.push((addr, cookie)).unwrap();
+    }
+
+    /// Return the cookie for the client at `addr`.
+    pub(crate) fn generate_cookie(
+        &mut self,
+        rand: &mut Rand,
+        addr: &IpAddress,
+    ) -> TcpFastOpenCookie {
+        let key = *self.key.get_or_insert_with(|| {
+            let mut key = [0; 2];
+            for half in key.iter_mut() {
+                *half = (rand.rand_u32() as u64) << 32 | rand.rand_u32() as u64;
+            }
+            key
+        });
+        let hash = match addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(addr) => siphash(key, &addr.octets()),
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(addr) => siphash(key, &addr.octets()),
+        };
+        // NOTE(unwrap): 8 octets is a valid cookie length.
+        TcpFastOpenCookie::new(&hash.to_be_bytes()).unwrap()
+    }
+}
+
+/// SipHash-2-4 of `data` with `key`.
+pub(super) fn siphash(key: [u64; 2], data: &[u8]) -> u64 {
+    let mut v = [
+        key[0] ^ 0x736f6d6570736575,
+        key[1] ^ 0x646f72616e646f6d,
+        key[0] ^ 0x6c7967656e657261,
+        key[1] ^ 0x7465646279746573,
+    ];
+
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    let mut compress = |m: u64| {
+        v[3] ^= m;
+        round(&mut v);
+        round(&mut v);
+        v[0] ^= m;
+    };
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        // NOTE(unwrap): chunks_exact yields 8 octets.
+        compress(u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    let mut last = [0; 8];
+    last[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    last[7] = data.len() as u8;
+    compress(u64::from_le_bytes(last));
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_siphash() {
+        // Test vector from the SipHash paper, appendix A.
+        let key = [
+            u64::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7]),
+            u64::from_le_bytes([8, 9, 10, 11, 12, 13, 14, 15]),
+        ];
+        let data: [u8; 15] = core::array::from_fn(|i| i as u8);
+        assert_eq!(siphash(key, &data), 0xa129ca6149be45e5);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_generate_cookie() {
+        let mut fast_open = FastOpen::new();
+        let mut rand = Rand::new(1234);
+        let a = IpAddress::v4(192, 168, 1, 1);
+        let b = IpAddress::v4(192, 168, 1, 2);
+        let cookie = fast_open.generate_cookie(&mut rand, &a);
+        assert_eq!(cookie.as_bytes().len(), 8);
+        assert_eq!(fast_open.generate_cookie(&mut rand, &a), cookie);
+        assert_ne!(fast_open.generate_cookie(&mut rand, &b), cookie);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_cookie_cache() {
+        let mut fast_open = FastOpen::new();
+        let cookie = TcpFastOpenCookie::new(&[1, 2, 3, 4]).unwrap();
+        for i in 0..TCP_FAST_OPEN_CACHE_COUNT as u8 + 1 {
+            fast_open.set_cookie(IpAddress::v4(10, 0, 0, i), cookie);
+        }
+        // The first cookie stored was forgotten.
+        assert_eq!(fast_open.cookie(&IpAddress::v4(10, 0, 0, 0)), None);
+        assert_eq!(fast_open.cookie(&IpAddress::v4(10, 0, 0, 1)), Some(cookie));
+
+        // Storing a cookie again replaces it.
+        let other = TcpFastOpenCookie::new(&[5, 6, 7, 8]).unwrap();
+        fast_open.set_cookie(IpAddress::v4(10, 0, 0, 1), other);
+        assert_eq!(fast_open.cookie(&IpAddress::v4(10, 0, 0, 1)), Some(other));
+    }
+}
@@ -0,0 +1,87 @@
+// Connection statistics, in the spirit of Linux's `TCP_INFO` socket option.
+//
+// The socket counts what it sends and receives over the lifetime of a
+// connection in `Counters`, and `Socket::info()` combines the counters with a
+// snapshot of the RTT estimator, congestion controller and peer parameters.
+
+use super::State;
+use crate::time::Duration;
+
+/// A snapshot of the state and statistics of a connection, see
+/// [Socket::info](super::Socket::info).
+///
+/// Counters start from zero when the socket starts listening or connecting, and
+/// keep their values after the connection is closed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct TcpInfo {
+    /// The connection state.
+    pub state: State,
+    /// The smoothed round-trip time, or `None` if no RTT has been measured.
+    pub smoothed_rtt: Option<Duration>,
+    /// The round-trip time variation, or `None` if no RTT has been measured.
+    pub rtt_variance: Option<Duration>,
+    /// The retransmission timeout.
+    pub retransmission_timeout: Duration,
+    /// The congestion window, in octets.
+    pub congestion_window: usize,
+    /// The slow start threshold, in octets, or `None` if the congestion controller
+    /// has none or is still in its initial slow start.
+    pub slow_start_threshold: Option<usize>,
+    /// The data octets sent, retransmissions included.
+    pub bytes_sent: u64,
+    /// The data octets retransmitted.
+    pub bytes_retransmitted: u64,
+    /// The data octets acknowledged by the remote.
+    pub bytes_acked: u64,
+    /// The authentic segments received.
+    pub segments_in: u64,
+    /// The segments sent.
+    pub segments_out: u64,
+    /// The duplicate acknowledgements received.
+    pub duplicate_acks: u64,
+    /// How many times the retransmission timer expired.
+    pub retransmission_timeouts: u64,
+    /// The zero window probes sent.
+    pub zero_window_probes: u64,
+    /// The segments received whose acknowledgement was coalesced with that of
+    /// earlier segments.
+    pub acks_coalesced: u64,
+    /// The window last advertised by the remote, in octets.
+    pub remote_window: usize,
+    /// The window scale the remote uses, or `None` if window scaling is not in use.
+    pub remote_window_scale: Option<u8>,
+    /// The maximum segment size of the remote.
+    pub remote_mss: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(super) struct Counters {
+    pub(super) bytes_sent: u64,
+    pub(super) bytes_retransmitted: u64,
+    pub(super) bytes_acked: u64,
+    pub(super) segments_in: u64,
+    pub(super) segments_out: u64,
+    pub(super) duplicate_acks: u64,
+    pub(super) retransmission_timeouts: u64,
+    pub(super) zero_window_probes: u64,
+    pub(super) acks_coalesced: u64,
+}
+
+impl Counters {
+    pub(super) const fn new() -> Self {
+        Counters {
+            bytes_sent: 0,
+            bytes_retransmitted: 0,
+            bytes_acked: 0,
+            segments_in: 0,
+            segments_out: 0,
+            duplicate_acks: 0,
+            retransmission_timeouts: 0,
+            zero_window_probes: 0,
+            acks_coalesced: 0,
+        }
+    }
+}
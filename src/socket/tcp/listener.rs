@@ -0,0 +1,734 @@
+// A listening endpoint with a backlog, backed by a pool of sockets.
+//
+// A socket carries a single connection, so accepting several connections at
+// once takes as many sockets listening on the same endpoint. The listener keeps
+// some of its idle sockets listening (the SYN queue), moves them to the accept
+// queue once their handshake completes, and only arms replacements while the
+// accept queue has room for the connections they would bring.
+
+use core::fmt;
+
+use heapless::{Deque, Vec};
+
+use super::{ListenError, Socket, State};
+use crate::iface::{Interface, SocketHandle, SocketSet};
+use crate::socket::AnySocket;
+use crate::storage::SocketBufferT;
+use crate::wire::IpListenEndpoint;
+
+/// Error returned by [`Listener::add`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PoolError {
+    Full,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PoolError::Full => write!(f, "listener pool is full"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PoolError {}
+
+/// A TCP listener, handing out connections accepted on an endpoint.
+///
+/// The listener owns a pool of up to `N` TCP sockets, added with
+/// [add](#method.add). It keeps up to [syn_backlog](#method.syn_backlog) of
+/// them listening, and queues the connections they establish until they are
+/// taken with [accept](#method.accept). At most [backlog](#method.backlog)
+/// connections are queued or being established at once; connection attempts
+/// beyond that are reset.
+///
+/// The listener must be polled with [poll](#method.poll) after the interface is,
+/// so that it can put idle sockets back to listening.
+///
+/// With [SYN cookies](#method.set_syn_cookies_enabled), the SYNs that find every
+/// listening socket taken are answered with a cookie, and the idle sockets that the
+/// backlog leaves room for stand by in the LISTEN state: they take no SYN, and only
+/// open the connections whose ACK carries a valid cookie back. A flood of SYNs thus
+/// cannot keep connections from being accepted.
+#[derive(Debug)]
+pub struct Listener<const N: usize> {
+    endpoint: IpListenEndpoint,
+    syn_backlog: usize,
+    backlog: usize,
+    syn_cookies: bool,
+    /// Sockets of the pool that are not in either queue, possibly still closing.
+    idle: Vec<SocketHandle, N>,
+    /// Sockets listening or in the SYN-RECEIVED state.
+    syn_queue: Vec<SocketHandle, N>,
+    /// Sockets listening only to open connections from SYN cookies.
+    standby: Vec<SocketHandle, N>,
+    /// Sockets whose handshake completed, in that order.
+    accept_queue: Deque<SocketHandle, N>,
+}
+
+impl<const N: usize> Listener<N> {
+    /// Create a listener for the given endpoint, with an empty pool.
+    ///
+    /// Both backlogs default to `N`.
+    ///
+    /// This function returns `Err(Error::Unaddressable)` if the port in the given
+    /// endpoint is zero.
+    pub fn new<T>(endpoint: T) -> Result<Listener<N>, ListenError>
+    where
+        T: Into<IpListenEndpoint>,
+    {
+        let endpoint = endpoint.into();
+        if endpoint.port == 0 {
+            return Err(ListenError::Unaddressable);
+        }
+        Ok(Listener {
+            endpoint,
+            syn_backlog: N,
+            backlog: N,
+            syn_cookies: false,
+            idle: Vec::new(),
+            syn_queue: Vec::new(),
+            standby: Vec::new(),
+            accept_queue: Deque::new(),
+        })
+    }
+
+    /// Return the endpoint the listener accepts connections on.
+    pub fn endpoint(&self) -> IpListenEndpoint {
+        self.endpoint
+    }
+
+    /// Return the maximum number of connections being established at once.
+    pub fn syn_backlog(&self) -> usize {
+        self.syn_backlog
+    }
+
+    /// Set the maximum number of connections being established at once.
+    ///
+    /// Lowering it does not abort the handshakes in progress.
+    pub fn set_syn_backlog(&mut self, syn_backlog: usize) {
+        self.syn_backlog = syn_backlog
+    }
+
+    /// Return the maximum number of connections either queued for
+    /// [accept](#method.accept) or being established.
+    pub fn backlog(&self) -> usize {
+        self.backlog
+    }
+
+    /// Set the maximum number of connections either queued for
+    /// [accept](#method.accept) or being established.
+    ///
+    /// Lowering it does not abort the connections already queued.
+    pub fn set_backlog(&mut self, backlog: usize) {
+        self.backlog = backlog
+    }
+
+    /// Return whether the listening sockets use SYN cookies.
+    pub fn syn_cookies_enabled(&self) -> bool {
+        self.syn_cookies
+    }
+
+    /// Enable or disable SYN cookies on the listening sockets, see
+    /// [set_syn_cookies_enabled](super::Socket::set_syn_cookies_enabled).
+    ///
+    /// The sockets already listening are updated on the next [poll](#method.poll),
+    /// and those standing by for SYN cookies closed if they are disabled.
+    pub fn set_syn_cookies_enabled(&mut self, enabled: bool) {
+        self.syn_cookies = enabled
+    }
+
+    /// Return the number of sockets listening or establishing a connection.
+    pub fn syn_queue_len(&self) -> usize {
+        self.syn_queue.len()
+    }
+
+    /// Return the number of connections waiting to be accepted.
+    pub fn accept_queue_len(&self) -> usize {
+        self.accept_queue.len()
+    }
+
+    /// Return whether [accept](#method.accept) would return a connection, as of
+    /// the last [poll](#method.poll).
+    pub fn can_accept(&self) -> bool {
+        !self.accept_queue.is_empty()
+    }
+
+    /// Add a socket to the pool.
+    ///
+    /// The socket is only put to use once it is closed, so that an accepted
+    /// connection can be handed back as soon as [close](super::Socket::close) is
+    /// called on it. The options of the socket, such as its buffers, keep-alive or
+    /// authentication, are kept across connections.
+    ///
+    /// Adding a socket that is already in the pool does nothing.
+    pub fn add(&mut self, handle: SocketHandle) -> Result<(), PoolError> {
+        if self.idle.contains(&handle)
+            || self.syn_queue.contains(&handle)
+            || self.standby.contains(&handle)
+            || self.accept_queue.iter().any(|queued| *queued == handle)
+        {
+            return Ok(());
+        }
+        self.idle.push(handle).map_err(|_| PoolError::Full)
+    }
+
+    /// Track the connections of the sockets in the queues, and put idle sockets
+    /// to listening while the backlogs allow.
+    ///
+    /// # Panics
+    /// This function panics if a socket of the pool was removed from `sockets`.
+    pub fn poll<'a, B: SocketBufferT<'a>>(
+        &mut self,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'a, B>,
+    ) where
+        Socket<'a, B>: AnySocket<'a, B>,
+    {
+        self.update(iface, sockets);
+        self.arm(iface, sockets);
+    }
+
+    /// Take the oldest established connection out of the accept queue.
+    ///
+    /// The socket stays out of the pool until it is handed back with
+    /// [add](#method.add).
+    ///
+    /// # Panics
+    /// This function panics if a socket of the pool was removed from `sockets`.
+    pub fn accept<'a, B: SocketBufferT<'a>>(
+        &mut self,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'a, B>,
+    ) -> Option<SocketHandle>
+    where
+        Socket<'a, B>: AnySocket<'a, B>,
+    {
+        self.update(iface, sockets);
+        let handle = self.accept_queue.pop_front();
+        self.arm(iface, sockets);
+        handle
+    }
+
+    /// Stop listening, and reset the connections being established or waiting to be
+    /// accepted. Every socket of the pool but the accepted ones becomes idle.
+    ///
+    /// # Panics
+    /// This function panics if a socket of the pool was removed from `sockets`.
+    pub fn close<'a, B: SocketBufferT<'a>>(
+        &mut self,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'a, B>,
+    ) where
+        Socket<'a, B>: AnySocket<'a, B>,
+    {
+        while let Some(handle) = self
+            .syn_queue
+            .pop()
+            .or_else(|| self.standby.pop())
+            .or_else(|| self.accept_queue.pop_front())
+        {
+            sockets.get_mut::<Socket<'a, B>>(handle).abort();
+            #[cfg(feature = "alloc")]
+            iface.unregister_tcp_listener(handle);
+            // NOTE(unwrap): every socket of the pool fits among the idle ones.
+            self.idle.push(handle).unwrap();
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = iface;
+    }
+
+    /// Move the sockets of the SYN queue that established a connection to the accept
+    /// queue, and those that closed back to the idle ones.
+    fn update<'a, B: SocketBufferT<'a>>(
+        &mut self,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'a, B>,
+    ) where
+        Socket<'a, B>: AnySocket<'a, B>,
+    {
+        // Sockets that opened a connection from a SYN cookie join the SYN queue.
+        let mut index = 0;
+        while index < self.standby.len() {
+            let handle = self.standby[index];
+            let socket = sockets.get_mut::<Socket<'a, B>>(handle);
+            if socket.state() == State::Listen && self.syn_cookies {
+                // A connection whose ACK failed authentication may have dropped it.
+                socket.set_syn_cookie_standby(true);
+                index += 1;
+                continue;
+            }
+            if socket.state() == State::Listen {
+                socket.abort();
+            }
+            self.standby.swap_remove(index);
+            // NOTE(unwrap): every socket of the pool fits in the SYN queue.
+            self.syn_queue.push(handle).unwrap();
+        }
+
+        let mut index = 0;
+        while index < self.syn_queue.len() {
+            let handle = self.syn_queue[index];
+            let socket = sockets.get_mut::<Socket<'a, B>>(handle);
+            match socket.state() {
+                State::Listen => {
+                    socket.set_syn_cookies_enabled(self.syn_cookies);
+                    // A socket whose handshake was reset goes back to listening, and
+                    // must be found by the interface again.
+                    Self::register(iface, self.endpoint, handle);
+                    index += 1;
+                }
+                State::SynReceived => index += 1,
+                State::Closed => {
+                    self.syn_queue.remove(index);
+                    // NOTE(unwrap): every socket of the pool fits among the idle ones.
+                    self.idle.push(handle).unwrap();
+                }
+                _ => {
+                    self.syn_queue.remove(index);
+                    // NOTE(unwrap): every socket of the pool fits in the accept queue.
+                    self.accept_queue.push_back(handle).unwrap();
+                }
+            }
+        }
+
+        // Connections reset before being accepted are not worth handing out.
+        for _ in 0..self.accept_queue.len() {
+            // NOTE(unwrap): the queue has at least as many sockets as iterations.
+            let handle = self.accept_queue.pop_front().unwrap();
+            if sockets.get::<Socket<'a, B>>(handle).state() == State::Closed {
+                // NOTE(unwrap): every socket of the pool fits among the idle ones.
+                self.idle.push(handle).unwrap();
+            } else {
+                // NOTE(unwrap): we just made room.
+                self.accept_queue.push_back(handle).unwrap();
+            }
+        }
+    }
+
+    /// Put closed idle sockets, or else those standing by, to listening while the
+    /// backlogs allow, and with SYN cookies, put the rest of the backlog on standby.
+    fn arm<'a, B: SocketBufferT<'a>>(
+        &mut self,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'a, B>,
+    ) where
+        Socket<'a, B>: AnySocket<'a, B>,
+    {
+        while self.syn_queue.len() < self.syn_backlog
+            && self.syn_queue.len() + self.accept_queue.len() < self.backlog
+        {
+            let handle = match self.closed_idle(sockets) {
+                Some(index) => self.idle.swap_remove(index),
+                None => match self.standby.pop() {
+                    Some(handle) => handle,
+                    None => break,
+                },
+            };
+            let socket = sockets.get_mut::<Socket<'a, B>>(handle);
+            socket.set_syn_cookies_enabled(self.syn_cookies);
+            // NOTE(unwrap): the socket is closed or listening on the endpoint, and the
+            // endpoint has a port.
+            socket.listen(self.endpoint).unwrap();
+            Self::register(iface, self.endpoint, handle);
+            // NOTE(unwrap): every socket of the pool fits in the SYN queue.
+            self.syn_queue.push(handle).unwrap();
+        }
+
+        while self.syn_cookies
+            && self.syn_queue.len() + self.standby.len() + self.accept_queue.len() < self.backlog
+        {
+            let Some(index) = self.closed_idle(sockets) else {
+                break;
+            };
+            let handle = self.idle.swap_remove(index);
+            let socket = sockets.get_mut::<Socket<'a, B>>(handle);
+            socket.set_syn_cookies_enabled(true);
+            // NOTE(unwrap): the socket is closed, and the endpoint has a port.
+            socket.listen(self.endpoint).unwrap();
+            // Left out of the interface index, so that it is not offered SYNs.
+            socket.set_syn_cookie_standby(true);
+            // NOTE(unwrap): every socket of the pool fits on standby.
+            self.standby.push(handle).unwrap();
+        }
+    }
+
+    /// Return the index of an idle socket done closing, if there is one.
+    fn closed_idle<'a, B: SocketBufferT<'a>>(&self, sockets: &SocketSet<'a, B>) -> Option<usize>
+    where
+        Socket<'a, B>: AnySocket<'a, B>,
+    {
+        self.idle
+            .iter()
+            .position(|handle| sockets.get::<Socket<'a, B>>(*handle).state() == State::Closed)
+    }
+
+    #[cfg_attr(not(feature = "alloc"), allow(unused_variables))]
+    fn register(iface: &mut Interface, endpoint: IpListenEndpoint, handle: SocketHandle) {
+        #[cfg(feature = "alloc")]
+        if let Err(error) = iface.register_tcp_listener(handle, endpoint) {
+            net_debug!("TCP listener registration failed: {:?}", error);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "medium-ip", feature = "proto-ipv4"))]
+mod test {
+    use super::*;
+    use crate::phy::{ChecksumCapabilities, Medium};
+    use crate::socket::tcp::{SocketBuffer, SynCookie};
+    use crate::tests::{TestingDevice, setup};
+    use crate::time::Instant;
+    use crate::wire::{
+        IpAddress, IpEcn, IpEndpoint, IpProtocol, IpRepr, Ipv4Address, Ipv4Repr, TcpControl,
+        TcpPacket, TcpRepr, TcpSeqNumber,
+    };
+
+    const LOCAL_PORT: u16 = 80;
+
+    fn socket() -> Socket<'static> {
+        Socket::new(
+            SocketBuffer::new(vec![0; 64]),
+            SocketBuffer::new(vec![0; 64]),
+        )
+    }
+
+    fn listener<const N: usize>(
+        sockets: &mut SocketSet<'static>,
+    ) -> (Listener<N>, std::vec::Vec<SocketHandle>) {
+        let mut listener = Listener::new(LOCAL_PORT).unwrap();
+        let handles: std::vec::Vec<_> = (0..N).map(|_| sockets.add(socket())).collect();
+        for handle in handles.iter() {
+            listener.add(*handle).unwrap();
+        }
+        (listener, handles)
+    }
+
+    /// Send a segment from `remote_port` of the peer to the listening port, and
+    /// return whether it is answered right away.
+    fn send(
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'static>,
+        remote_port: u16,
+        control: TcpControl,
+        seq_number: TcpSeqNumber,
+        ack_number: Option<TcpSeqNumber>,
+    ) -> bool {
+        let tcp_repr = TcpRepr {
+            src_port: remote_port,
+            dst_port: LOCAL_PORT,
+            control,
+            ece: false,
+            cwr: false,
+            seq_number,
+            ack_number,
+            window_len: 256,
+            window_scale: None,
+            max_seg_size: None,
+            sack_permitted: false,
+            sack_ranges: [None, None, None],
+            timestamp: None,
+            fast_open: None,
+            auth: None,
+            payload: &[],
+        };
+        let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+            src_addr: Ipv4Address::new(192, 168, 1, 2),
+            dst_addr: Ipv4Address::new(192, 168, 1, 1),
+            next_header: IpProtocol::Tcp,
+            payload_len: tcp_repr.buffer_len(),
+            hop_limit: 64,
+            ecn: IpEcn::NotEct,
+        });
+        let mut bytes = vec![0; tcp_repr.buffer_len()];
+        tcp_repr.emit(
+            &mut TcpPacket::new_unchecked(&mut bytes),
+            &ip_repr.src_addr(),
+            &ip_repr.dst_addr(),
+            &ChecksumCapabilities::default(),
+        );
+        iface
+            .context()
+            .process_tcp(sockets, false, ip_repr, &bytes)
+            .is_some()
+    }
+
+    /// Return the initial sequence number of the SYN|ACK answering the SYN with the
+    /// initial sequence number `seq` from `remote_port` with a cookie.
+    fn syn_cookie(iface: &mut Interface, remote_port: u16, seq: TcpSeqNumber) -> TcpSeqNumber {
+        iface.context().generate_tcp_syn_cookie(
+            IpEndpoint::new(IpAddress::v4(192, 168, 1, 1), LOCAL_PORT),
+            IpEndpoint::new(IpAddress::v4(192, 168, 1, 2), remote_port),
+            seq,
+            SynCookie::new(536, None, false),
+        )
+    }
+
+    /// Complete a handshake from `remote_port`, and return the socket that took it.
+    fn connect(
+        iface: &mut Interface,
+        device: &mut TestingDevice,
+        sockets: &mut SocketSet<'static>,
+        remote_port: u16,
+    ) -> SocketHandle {
+        let seq = TcpSeqNumber(1000);
+        send(iface, sockets, remote_port, TcpControl::Syn, seq, None);
+        // Send the SYN|ACK.
+        iface.poll(Instant::ZERO, device, sockets);
+        device.tx_queue.clear();
+
+        let (handle, local_seq_no) = sockets
+            .iter()
+            .find_map(|(handle, socket)| match socket {
+                crate::socket::Socket::Tcp(socket)
+                    if socket.remote_endpoint().map(|remote| remote.port) == Some(remote_port) =>
+                {
+                    Some((handle, socket.local_seq_no))
+                }
+                _ => None,
+            })
+            .unwrap();
+        send(
+            iface,
+            sockets,
+            remote_port,
+            TcpControl::None,
+            seq + 1,
+            Some(local_seq_no + 1),
+        );
+        handle
+    }
+
+    #[test]
+    fn test_unaddressable() {
+        assert_eq!(
+            Listener::<1>::new((IpAddress::v4(192, 168, 1, 1), 0)).unwrap_err(),
+            ListenError::Unaddressable
+        );
+    }
+
+    #[test]
+    fn test_pool_full() {
+        let (_, mut sockets, _) = setup(Medium::Ip);
+        let (mut listener, handles) = listener::<2>(&mut sockets);
+        assert_eq!(listener.add(handles[0]), Ok(()));
+        let extra = sockets.add(socket());
+        assert_eq!(listener.add(extra), Err(PoolError::Full));
+    }
+
+    #[test]
+    fn test_accept() {
+        let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+        let (mut listener, _) = listener::<4>(&mut sockets);
+        listener.set_syn_backlog(2);
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.syn_queue_len(), 2);
+        assert!(listener.accept(&mut iface, &mut sockets).is_none());
+
+        let first = connect(&mut iface, &mut device, &mut sockets, 49500);
+        assert_eq!(sockets.get::<Socket>(first).state(), State::Established);
+        listener.poll(&mut iface, &mut sockets);
+        let second = connect(&mut iface, &mut device, &mut sockets, 49501);
+        assert_eq!(sockets.get::<Socket>(second).state(), State::Established);
+        listener.poll(&mut iface, &mut sockets);
+        assert!(listener.can_accept());
+        assert_eq!(listener.accept_queue_len(), 2);
+        assert_eq!(listener.syn_queue_len(), 2);
+
+        assert_eq!(listener.accept(&mut iface, &mut sockets), Some(first));
+        assert_eq!(listener.accept(&mut iface, &mut sockets), Some(second));
+        assert_eq!(listener.accept(&mut iface, &mut sockets), None);
+    }
+
+    #[test]
+    fn test_backlog() {
+        let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+        let (mut listener, _) = listener::<4>(&mut sockets);
+        listener.set_backlog(1);
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.syn_queue_len(), 1);
+
+        let first = connect(&mut iface, &mut device, &mut sockets, 49500);
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.accept_queue_len(), 1);
+        // The accept queue is full, so nothing listens anymore.
+        assert_eq!(listener.syn_queue_len(), 0);
+
+        assert_eq!(listener.accept(&mut iface, &mut sockets), Some(first));
+        assert_eq!(listener.syn_queue_len(), 1);
+    }
+
+    #[test]
+    fn test_reuse() {
+        let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+        let (mut listener, _) = listener::<1>(&mut sockets);
+        listener.poll(&mut iface, &mut sockets);
+
+        let handle = connect(&mut iface, &mut device, &mut sockets, 49500);
+        assert_eq!(listener.accept(&mut iface, &mut sockets), Some(handle));
+        assert_eq!(listener.syn_queue_len(), 0);
+
+        // The socket is not reused until it is closed.
+        listener.add(handle).unwrap();
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.syn_queue_len(), 0);
+        sockets.get_mut::<Socket>(handle).abort();
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.syn_queue_len(), 1);
+        assert!(sockets.get::<Socket>(handle).is_listening());
+    }
+
+    #[test]
+    fn test_reset_before_accept() {
+        let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+        let (mut listener, _) = listener::<1>(&mut sockets);
+        listener.poll(&mut iface, &mut sockets);
+
+        let handle = connect(&mut iface, &mut device, &mut sockets, 49500);
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.accept_queue_len(), 1);
+
+        send(
+            &mut iface,
+            &mut sockets,
+            49500,
+            TcpControl::Rst,
+            TcpSeqNumber(1001),
+            None,
+        );
+        assert_eq!(sockets.get::<Socket>(handle).state(), State::Closed);
+        assert_eq!(listener.accept(&mut iface, &mut sockets), None);
+        assert!(sockets.get::<Socket>(handle).is_listening());
+    }
+
+    #[test]
+    fn test_syn_cookies() {
+        let (mut iface, mut sockets, _) = setup(Medium::Ip);
+        let (mut listener, handles) = listener::<3>(&mut sockets);
+        listener.set_syn_backlog(1);
+        listener.set_syn_cookies_enabled(true);
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.syn_queue_len(), 1);
+        let count = |sockets: &SocketSet, state| {
+            handles
+                .iter()
+                .filter(|handle| sockets.get::<Socket>(**handle).state() == state)
+                .count()
+        };
+        assert_eq!(count(&sockets, State::Listen), 3);
+
+        // The listening socket takes the first SYN of a flood, and the others are
+        // answered with cookies: the sockets standing by take none of them.
+        let seq = TcpSeqNumber(1000);
+        assert!(!send(
+            &mut iface,
+            &mut sockets,
+            49500,
+            TcpControl::Syn,
+            seq,
+            None
+        ));
+        for port in 49501..49504 {
+            assert!(send(
+                &mut iface,
+                &mut sockets,
+                port,
+                TcpControl::Syn,
+                seq,
+                None
+            ));
+        }
+        assert_eq!(count(&sockets, State::SynReceived), 1);
+        assert_eq!(count(&sockets, State::Listen), 2);
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.syn_queue_len(), 1);
+
+        // An ACK without a valid cookie is reset.
+        let isn = syn_cookie(&mut iface, 49502, seq);
+        assert!(send(
+            &mut iface,
+            &mut sockets,
+            49502,
+            TcpControl::None,
+            seq + 1,
+            Some(isn + 0x10000),
+        ));
+        assert_eq!(count(&sockets, State::Listen), 2);
+
+        // The ACK completing a handshake opens a connection on a socket standing by,
+        // which was not reserved at SYN time.
+        assert!(!send(
+            &mut iface,
+            &mut sockets,
+            49502,
+            TcpControl::None,
+            seq + 1,
+            Some(isn + 1),
+        ));
+        let handle = listener.accept(&mut iface, &mut sockets).unwrap();
+        let socket = sockets.get::<Socket>(handle);
+        assert_eq!(socket.state(), State::Established);
+        assert_eq!(socket.remote_endpoint().unwrap().port, 49502);
+        assert_eq!(listener.syn_queue_len(), 1);
+
+        // Without SYN cookies, the last socket standing by closes.
+        listener.set_syn_cookies_enabled(false);
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(count(&sockets, State::Listen), 0);
+        assert_eq!(count(&sockets, State::SynReceived), 1);
+    }
+
+    #[test]
+    fn test_syn_cookies_pool_exhausted() {
+        let (mut iface, mut sockets, _) = setup(Medium::Ip);
+        let (mut listener, handles) = listener::<1>(&mut sockets);
+        listener.set_syn_cookies_enabled(true);
+        listener.poll(&mut iface, &mut sockets);
+
+        // The socket in SYN-RECEIVED still answers SYNs with cookies, but there is no
+        // socket left to open a connection with.
+        let seq = TcpSeqNumber(1000);
+        send(&mut iface, &mut sockets, 49500, TcpControl::Syn, seq, None);
+        assert!(send(
+            &mut iface,
+            &mut sockets,
+            49501,
+            TcpControl::Syn,
+            seq,
+            None
+        ));
+        let isn = syn_cookie(&mut iface, 49501, seq);
+        // The ACK is reset.
+        assert!(send(
+            &mut iface,
+            &mut sockets,
+            49501,
+            TcpControl::None,
+            seq + 1,
+            Some(isn + 1),
+        ));
+        assert_eq!(
+            sockets
+                .get::<Socket>(handles[0])
+                .remote_endpoint()
+                .unwrap()
+                .port,
+            49500
+        );
+    }
+
+    #[test]
+    fn test_close() {
+        let (mut iface, mut sockets, _) = setup(Medium::Ip);
+        let (mut listener, handles) = listener::<2>(&mut sockets);
+        listener.poll(&mut iface, &mut sockets);
+        assert_eq!(listener.syn_queue_len(), 2);
+
+        listener.close(&mut iface, &mut sockets);
+        assert_eq!(listener.syn_queue_len(), 0);
+        for handle in handles {
+            assert_eq!(sockets.get::<Socket>(handle).state(), State::Closed);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// Lossy receive mode.
+//
+// A socket in lossy receive mode doesn't wait for lost segments to be
+// retransmitted: when a segment arrives past a hole, the hole is skipped and
+// acknowledged, and the skip is reported as a gap. The receive buffer only holds
+// the octets received, so the socket remembers where the gaps fall between them,
+// and delivers records tagged with their sequence numbers.
+//
+// `Socket::remote_seq_no` is advanced over the gaps as they are skipped, so it is
+// ahead of the sequence number of the first octet in the receive buffer by the
+// length of the gaps that are not read past yet.
+
+use managed::ManagedSlice;
+
+use crate::storage::Full;
+use crate::time::Instant;
+use crate::wire::TcpSeqNumber;
+
+/// A range of the receive sequence space skipped by a socket in lossy receive mode,
+/// see [Socket::set_lossy_receive_enabled](super::Socket::set_lossy_receive_enabled).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RecvGap {
+    /// The sequence number of the first octet skipped.
+    pub seq_number: TcpSeqNumber,
+    /// How many octets were skipped.
+    pub len: usize,
+    /// When the octets were skipped.
+    pub timestamp: Instant,
+}
+
+impl RecvGap {
+    /// An empty gap, to fill storage with.
+    pub const EMPTY: RecvGap = RecvGap {
+        seq_number: TcpSeqNumber(0),
+        len: 0,
+        timestamp: Instant::ZERO,
+    };
+
+    /// Return the sequence number past the last octet skipped.
+    pub fn end(&self) -> TcpSeqNumber {
+        self.seq_number + self.len
+    }
+}
+
+/// What a socket in lossy receive mode delivers, see
+/// [Socket::recv_lossy](super::Socket::recv_lossy).
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LossyRecord<'b> {
+    /// Octets received, starting at the given sequence number.
+    Data {
+        seq_number: TcpSeqNumber,
+        payload: &'b [u8],
+    },
+    /// Octets skipped.
+    Gap(RecvGap),
+}
+
+/// The gaps skipped by a socket, in order. The gaps that were read past come
+/// first, and wait to be delivered.
+#[derive(Debug)]
+pub(super) struct GapQueue<'a> {
+    storage: ManagedSlice<'a, RecvGap>,
+    /// How many gaps are queued.
+    len: usize,
+    /// How many gaps at the front of the queue were read past.
+    passed: usize,
+    /// The total length of the gaps not read past yet.
+    pending_len: usize,
+}
+
+impl<'a> GapQueue<'a> {
+    pub(super) fn new() -> Self {
+        GapQueue {
+            storage: ManagedSlice::Borrowed(&mut []),
+            len: 0,
+            passed: 0,
+            pending_len: 0,
+        }
+    }
+
+    /// Replace the storage of the queue, dropping all the gaps queued.
+    pub(super) fn set_storage(&mut self, storage: ManagedSlice<'a, RecvGap>) {
+        self.storage = storage;
+        self.clear();
+    }
+
+    /// Query whether no gap is queued, delivered or not.
+    pub(super) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Query whether a gap was skipped that isn't read past yet.
+    pub(super) fn has_pending(&self) -> bool {
+        self.passed < self.len
+    }
+
+    /// Query whether another gap can be queued.
+    pub(super) fn has_room(&self) -> bool {
+        self.len < self.storage.len()
+    }
+
+    /// Return the sequence number of the first octet in a receive buffer, if the
+    /// octets up to `remote_seq_no` were received or skipped.
+    pub(super) fn head(&self, remote_seq_no: TcpSeqNumber) -> TcpSeqNumber {
+        remote_seq_no - self.pending_len
+    }
+
+    /// Return how many octets can be read from the receive buffer before the next
+    /// gap, if there is one. See `head` for `remote_seq_no`.
+    pub(super) fn readable(&self, remote_seq_no: TcpSeqNumber) -> Option<usize> {
+        self.has_pending()
+            .then(|| self.storage[self.passed].seq_number - self.head(remote_seq_no))
+    }
+
+    /// Record that `gap` was skipped, `remote_seq_no` being already advanced past it.
+    pub(super) fn push(&mut self, remote_seq_no: TcpSeqNumber, gap: RecvGap) -> Result<(), Full> {
+        if !self.has_room() {
+            return Err(Full);
+        }
+        self.storage[self.len] = gap;
+        self.len += 1;
+        self.pending_len += gap.len;
+        self.pass(remote_seq_no);
+        Ok(())
+    }
+
+    /// Record that `gap` was skipped while the receive buffer is empty, and was
+    /// read past. If there is no room, the last gap is extended to the end of `gap`
+    /// instead, so that it covers the octets received in between.
+    pub(super) fn push_passed(&mut self, gap: RecvGap) {
+        self.pass_all();
+        if self.has_room() {
+            self.storage[self.len] = gap;
+            self.len += 1;
+            self.passed += 1;
+        } else if let Some(last) = self.storage[..self.len].last_mut() {
+            last.len = gap.end() - last.seq_number;
+            last.timestamp = gap.timestamp;
+        }
+    }
+
+    /// Mark the gaps reached by the head of the receive buffer as read past. See
+    /// `head` for `remote_seq_no`.
+    pub(super) fn pass(&mut self, remote_seq_no: TcpSeqNumber) {
+        while self.has_pending() {
+            let gap = self.storage[self.passed];
+            if gap.seq_number > self.head(remote_seq_no) {
+                break;
+            }
+            self.passed += 1;
+            self.pending_len -= gap.len;
+        }
+    }
+
+    /// Mark all the gaps as read past, the receive buffer being cleared.
+    pub(super) fn pass_all(&mut self) {
+        self.passed = self.len;
+        self.pending_len = 0;
+    }
+
+    /// Take the first gap that was read past.
+    pub(super) fn pop_passed(&mut self) -> Option<RecvGap> {
+        if self.passed == 0 {
+            return None;
+        }
+        let gap = self.storage[0];
+        self.storage.copy_within(1..self.len, 0);
+        self.len -= 1;
+        self.passed -= 1;
+        Some(gap)
+    }
+
+    /// Drop all the gaps.
+    pub(super) fn clear(&mut self) {
+        self.len = 0;
+        self.passed = 0;
+        self.pending_len = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SEQ: TcpSeqNumber = TcpSeqNumber(1000);
+
+    fn gap(offset: usize, len: usize) -> RecvGap {
+        RecvGap {
+            seq_number: SEQ + offset,
+            len,
+            timestamp: Instant::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_pass() {
+        let mut storage = [RecvGap::EMPTY; 2];
+        let mut queue = GapQueue::new();
+        queue.set_storage(ManagedSlice::Borrowed(&mut storage));
+
+        // Two octets received, then a gap of three. `remote_seq_no` is past the gap,
+        // but not past the octets in the buffer.
+        queue.push(SEQ + 3, gap(2, 3)).unwrap();
+        assert_eq!(queue.head(SEQ + 3), SEQ);
+        assert_eq!(queue.readable(SEQ + 3), Some(2));
+        assert_eq!(queue.pop_passed(), None);
+
+        // The two octets are read.
+        queue.pass(SEQ + 5);
+        assert!(!queue.has_pending());
+        assert_eq!(queue.readable(SEQ + 5), None);
+        assert_eq!(queue.pop_passed(), Some(gap(2, 3)));
+        assert_eq!(queue.pop_passed(), None);
+    }
+
+    #[test]
+    fn test_push_passed_full() {
+        let mut storage = [RecvGap::EMPTY; 1];
+        let mut queue = GapQueue::new();
+        queue.set_storage(ManagedSlice::Borrowed(&mut storage));
+
+        queue.push(SEQ + 2, gap(0, 2)).unwrap();
+        assert!(!queue.has_room());
+        assert_eq!(queue.push(SEQ + 4, gap(2, 2)), Err(Full));
+
+        // The last gap grows to cover the new one.
+        queue.push_passed(gap(6, 4));
+        assert_eq!(queue.pop_passed(), Some(gap(0, 10)));
+    }
+}
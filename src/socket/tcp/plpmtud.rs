@@ -0,0 +1,259 @@
+// Packetization Layer Path MTU Discovery, as described in RFC 8899 (and RFC 4821
+// for TCP).
+//
+// Segments are sized for a conservative base MTU, and once in a while a segment
+// carrying new data is made larger to probe the path. An acknowledged probe
+// raises the MTU; a probe lost `MAX_PROBES` times in a row bounds the search,
+// which narrows down the largest MTU the path delivers. Unlike ICMP-based
+// discovery, this works on paths that drop ICMP errors.
+//
+// The socket tells a lost probe apart from congestion (RFC 4821 section 7.5): the
+// probe is lost if it is the oldest data outstanding and later data was delivered,
+// as SACKs or duplicate ACKs show, or if the retransmission timer expires with
+// nothing sent after it. Its data is then resent in smaller segments, without
+// reducing the congestion window or backing off the retransmission timer.
+
+use crate::time::{Duration, Instant};
+use crate::wire::TcpSeqNumber;
+
+/// The MTU segments are sized for before any probe succeeds, see RFC 8899
+/// section 5.1.2.
+pub(super) const BASE_PLPMTU: usize = 1200;
+
+/// How many times a probe of a given size is lost before that size is deemed too
+/// big, and how many timeouts in a row reveal a black hole.
+const MAX_PROBES: u8 = 3;
+
+/// How long to wait after a search completes before looking for a larger MTU.
+const PMTU_RAISE_TIMER: Duration = Duration::from_secs(600);
+
+/// The search stops once it is narrowed down to this many octets.
+const SEARCH_GRANULARITY: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Probe {
+    mtu: usize,
+    /// The sequence space of the probe.
+    start: TcpSeqNumber,
+    end: TcpSeqNumber,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(super) struct Plpmtud {
+    /// The largest MTU the path is known to deliver (PLPMTU).
+    plpmtu: usize,
+    /// The smallest MTU known not to be delivered, if any.
+    too_big: Option<usize>,
+    /// The outstanding probe.
+    probe: Option<Probe>,
+    /// How many probes of the next size were lost.
+    probe_count: u8,
+    /// How many retransmission timeouts in a row fired, with no probe outstanding.
+    timeouts: u8,
+    /// When the search completed.
+    search_done_at: Option<Instant>,
+}
+
+impl Plpmtud {
+    pub(super) const fn new() -> Self {
+        Plpmtud {
+            plpmtu: BASE_PLPMTU,
+            too_big: None,
+            probe: None,
+            probe_count: 0,
+            timeouts: 0,
+            search_done_at: None,
+        }
+    }
+
+    /// Return the MTU to size segments for, on a path whose MTU is at most `max_mtu`.
+    pub(super) fn mtu(&self, max_mtu: usize) -> usize {
+        self.plpmtu.min(max_mtu)
+    }
+
+    /// Return the MTU of the next probe, if one should be sent.
+    pub(super) fn probe_mtu(&mut self, now: Instant, max_mtu: usize) -> Option<usize> {
+        if self.probe.is_some() {
+            return None;
+        }
+        if let Some(search_done_at) = self.search_done_at {
+            if now < search_done_at + PMTU_RAISE_TIMER {
+                return None;
+            }
+            // RFC 8899 section 5.2: the path may have changed, search again.
+            self.search_done_at = None;
+            self.too_big = None;
+        }
+
+        let limit = self
+            .too_big
+            .map_or(max_mtu, |too_big| (too_big - 1).min(max_mtu));
+        if limit > self.plpmtu {
+            match self.too_big {
+                // Most paths deliver the largest MTU, try it first.
+                None => return Some(limit),
+                Some(_) if limit - self.plpmtu >= SEARCH_GRANULARITY => {
+                    return Some((self.plpmtu + limit).div_ceil(2));
+                }
+                Some(_) => {}
+            }
+        }
+        net_trace!("plpmtud: search done, mtu {}", self.plpmtu);
+        self.search_done_at = Some(now);
+        None
+    }
+
+    /// Remember that a probe of `mtu` octets spanning `[start, end)` was sent.
+    pub(super) fn on_probe_sent(&mut self, mtu: usize, start: TcpSeqNumber, end: TcpSeqNumber) {
+        self.probe = Some(Probe { mtu, start, end });
+    }
+
+    /// Return the sequence space `[start, end)` of the outstanding probe, if any.
+    pub(super) fn probe(&self) -> Option<(TcpSeqNumber, TcpSeqNumber)> {
+        self.probe.map(|probe| (probe.start, probe.end))
+    }
+
+    /// Called when the outstanding probe is found lost.
+    pub(super) fn on_probe_lost(&mut self) {
+        let Some(probe) = self.probe.take() else {
+            return;
+        };
+        net_trace!("plpmtud: probe of {} lost", probe.mtu);
+        self.probe_count += 1;
+        if self.probe_count >= MAX_PROBES {
+            self.too_big = Some(probe.mtu);
+            self.probe_count = 0;
+        }
+    }
+
+    /// Called when the remote acknowledges new data, up to `ack_number`.
+    pub(super) fn on_ack(&mut self, ack_number: TcpSeqNumber) {
+        self.timeouts = 0;
+        if let Some(probe) = self.probe
+            && ack_number >= probe.end
+        {
+            net_trace!("plpmtud: probe of {} acknowledged", probe.mtu);
+            self.plpmtu = self.plpmtu.max(probe.mtu);
+            self.probe = None;
+            self.probe_count = 0;
+        }
+    }
+
+    /// Called when the segment starting at `seq_number` is retransmitted.
+    pub(super) fn on_retransmit(&mut self, seq_number: TcpSeqNumber) {
+        // The data of the probe is being resent in smaller segments, along with
+        // other lost data, so it cannot be told whether the probe got through.
+        if self.probe.is_some_and(|probe| seq_number < probe.end) {
+            self.on_probe_lost();
+        }
+    }
+
+    /// Called when the retransmission timer expires.
+    pub(super) fn on_timeout(&mut self) {
+        if self.probe.is_some() {
+            return;
+        }
+        self.timeouts = self.timeouts.saturating_add(1);
+        // RFC 8899 section 4.3: segments of the current size no longer get through,
+        // fall back to the base MTU and search again.
+        if self.timeouts >= MAX_PROBES && self.plpmtu > BASE_PLPMTU {
+            net_debug!("plpmtud: black hole detected at mtu {}", self.plpmtu);
+            *self = Plpmtud {
+                timeouts: self.timeouts,
+                ..Plpmtud::new()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NOW: Instant = Instant::ZERO;
+    const SEQ: TcpSeqNumber = TcpSeqNumber(1000);
+
+    fn lose(plpmtud: &mut Plpmtud, mtu: usize) {
+        for _ in 0..MAX_PROBES {
+            assert_eq!(plpmtud.probe_mtu(NOW, 1500), Some(mtu));
+            plpmtud.on_probe_sent(mtu, SEQ, SEQ + mtu);
+            assert_eq!(plpmtud.probe(), Some((SEQ, SEQ + mtu)));
+            plpmtud.on_probe_lost();
+        }
+    }
+
+    fn deliver(plpmtud: &mut Plpmtud, mtu: usize) {
+        assert_eq!(plpmtud.probe_mtu(NOW, 1500), Some(mtu));
+        plpmtud.on_probe_sent(mtu, SEQ, SEQ + mtu);
+        assert_eq!(plpmtud.probe_mtu(NOW, 1500), None);
+        plpmtud.on_ack(SEQ + mtu);
+        assert_eq!(plpmtud.mtu(1500), mtu);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut plpmtud = Plpmtud::new();
+        assert_eq!(plpmtud.mtu(1500), BASE_PLPMTU);
+        assert_eq!(plpmtud.mtu(1000), 1000);
+
+        // The largest MTU is tried first, then the search narrows down.
+        lose(&mut plpmtud, 1500);
+        assert_eq!(plpmtud.mtu(1500), BASE_PLPMTU);
+        deliver(&mut plpmtud, 1350);
+        lose(&mut plpmtud, 1425);
+        deliver(&mut plpmtud, 1387);
+        deliver(&mut plpmtud, 1406);
+        deliver(&mut plpmtud, 1415);
+        assert_eq!(plpmtud.probe_mtu(NOW, 1500), None);
+
+        // Larger MTUs are tried again after a while.
+        let later = NOW + PMTU_RAISE_TIMER;
+        assert_eq!(plpmtud.probe_mtu(later, 1500), Some(1500));
+    }
+
+    #[test]
+    fn test_probe_not_lost() {
+        let mut plpmtud = Plpmtud::new();
+        assert_eq!(plpmtud.probe_mtu(NOW, 1500), Some(1500));
+        plpmtud.on_probe_sent(1500, SEQ, SEQ + 1460);
+
+        // Data after the probe is retransmitted, and data before it acknowledged.
+        plpmtud.on_retransmit(SEQ + 1460);
+        plpmtud.on_ack(SEQ);
+        assert_eq!(plpmtud.mtu(1500), BASE_PLPMTU);
+
+        plpmtud.on_ack(SEQ + 1460);
+        assert_eq!(plpmtud.mtu(1500), 1500);
+        assert_eq!(plpmtud.probe_mtu(NOW, 1500), None);
+    }
+
+    #[test]
+    fn test_probe_retransmitted() {
+        let mut plpmtud = Plpmtud::new();
+        assert_eq!(plpmtud.probe_mtu(NOW, 1500), Some(1500));
+        plpmtud.on_probe_sent(1500, SEQ, SEQ + 1460);
+
+        // Resending the data of the probe gives it up.
+        plpmtud.on_retransmit(SEQ);
+        assert_eq!(plpmtud.probe(), None);
+        assert_eq!(plpmtud.probe_count, 1);
+        plpmtud.on_ack(SEQ + 1460);
+        assert_eq!(plpmtud.mtu(1500), BASE_PLPMTU);
+    }
+
+    #[test]
+    fn test_black_hole() {
+        let mut plpmtud = Plpmtud::new();
+        deliver(&mut plpmtud, 1500);
+
+        for _ in 0..MAX_PROBES - 1 {
+            plpmtud.on_timeout();
+        }
+        assert_eq!(plpmtud.mtu(1500), 1500);
+        plpmtud.on_timeout();
+        assert_eq!(plpmtud.mtu(1500), BASE_PLPMTU);
+        assert_eq!(plpmtud.probe_mtu(NOW, 1500), Some(1500));
+    }
+}
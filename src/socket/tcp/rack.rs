@@ -0,0 +1,267 @@
+// Time-based loss detection for the sending side, as described in RFC 8985
+// (RACK-TLP).
+//
+// RACK remembers when each outstanding segment was sent. A segment is deemed
+// lost once a segment sent sufficiently later has been delivered, where
+// "sufficiently" is the RTT of that delivery plus a reordering window. The
+// Tail Loss Probe part lives in the socket, as `Timer::TailLossProbe`.
+
+use crate::config::TCP_RACK_SEGMENT_COUNT;
+use crate::time::{Duration, Instant};
+use crate::wire::TcpSeqNumber;
+
+use super::sack::Scoreboard;
+
+/// A transmission of `[start, end)` at `xmit_ts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Sent {
+    start: TcpSeqNumber,
+    end: TcpSeqNumber,
+    xmit_ts: Instant,
+    retransmitted: bool,
+}
+
+impl Sent {
+    /// Return whether this transmission was sent after the given one, in RFC 8985 terms.
+    fn is_newer_than(&self, xmit_ts: Instant, end_seq: TcpSeqNumber) -> bool {
+        self.xmit_ts > xmit_ts || (self.xmit_ts == xmit_ts && self.end > end_seq)
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(super) struct Rack {
+    /// Outstanding transmissions, in the order they were sent.
+    /// Only the first `len` entries are valid.
+    sent: [Sent; TCP_RACK_SEGMENT_COUNT],
+    len: usize,
+    /// Send time and end of the most recently sent segment that has been delivered
+    /// (`RACK.xmit_ts` and `RACK.end_seq`).
+    xmit_ts: Option<Instant>,
+    end_seq: TcpSeqNumber,
+    /// The RTT of the most recently sent segment that has been delivered (`RACK.rtt`).
+    rtt: Duration,
+    /// The minimum RTT observed (`RACK.min_RTT`).
+    min_rtt: Option<Duration>,
+    /// When the earliest outstanding segment not yet deemed lost will be, absent
+    /// further deliveries (the RACK reordering timer).
+    reo_timeout: Option<Instant>,
+}
+
+impl Rack {
+    pub(super) fn new() -> Self {
+        let empty = Sent {
+            start: TcpSeqNumber::default(),
+            end: TcpSeqNumber::default(),
+            xmit_ts: Instant::ZERO,
+            retransmitted: false,
+        };
+        Rack {
+            sent: [empty; TCP_RACK_SEGMENT_COUNT],
+            len: 0,
+            xmit_ts: None,
+            end_seq: TcpSeqNumber::default(),
+            rtt: Duration::ZERO,
+            min_rtt: None,
+            reo_timeout: None,
+        }
+    }
+
+    /// Return the instant at which `detect_loss` should run again, if any.
+    pub(super) fn reo_timeout(&self) -> Option<Instant> {
+        self.reo_timeout
+    }
+
+    /// The reordering window, `RACK.reo_wnd`: a quarter of the minimum RTT.
+    fn reo_wnd(&self) -> Duration {
+        self.min_rtt.unwrap_or(Duration::ZERO) / 4
+    }
+
+    /// Record the transmission of `[start, end)`.
+    pub(super) fn on_send(
+        &mut self,
+        start: TcpSeqNumber,
+        end: TcpSeqNumber,
+        timestamp: Instant,
+        retransmitted: bool,
+    ) {
+        let sent = Sent {
+            start,
+            end,
+            xmit_ts: timestamp,
+            retransmitted,
+        };
+
+        if self.len == TCP_RACK_SEGMENT_COUNT {
+            // Out of space, fold the transmission into the most recent one. Using
+            // the later send time for both only delays loss detection.
+            let last = &mut self.sent[self.len - 1];
+            last.start = last.start.min(start);
+            last.end = last.end.max(end);
+            last.xmit_ts = timestamp;
+            last.retransmitted |= retransmitted;
+        } else {
+            self.sent[self.len] = sent;
+            self.len += 1;
+        }
+    }
+
+    /// Process an acknowledgement: update the RACK state from the transmissions
+    /// it delivers (RFC 8985 section 6.2 step 2), and forget about them.
+    pub(super) fn on_ack(
+        &mut self,
+        timestamp: Instant,
+        snd_una: TcpSeqNumber,
+        scoreboard: &Scoreboard,
+    ) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            let mut sent = self.sent[i];
+            if sent.end <= snd_una || scoreboard.is_range_sacked(sent.start, sent.end) {
+                self.on_delivered(timestamp, &sent);
+                continue;
+            }
+            if sent.start < snd_una {
+                sent.start = snd_una;
+            }
+            self.sent[kept] = sent;
+            kept += 1;
+        }
+        self.len = kept;
+    }
+
+    fn on_delivered(&mut self, timestamp: Instant, sent: &Sent) {
+        let rtt = timestamp - sent.xmit_ts;
+        // An acknowledgement arriving quicker than any round trip must be for
+        // the original transmission, not for this retransmission.
+        if sent.retransmitted && self.min_rtt.is_some_and(|min_rtt| rtt < min_rtt) {
+            return;
+        }
+
+        if self.min_rtt.is_none_or(|min_rtt| rtt < min_rtt) {
+            self.min_rtt = Some(rtt);
+        }
+        match self.xmit_ts {
+            Some(xmit_ts) if !sent.is_newer_than(xmit_ts, self.end_seq) => {}
+            _ => {
+                self.xmit_ts = Some(sent.xmit_ts);
+                self.end_seq = sent.end;
+                self.rtt = rtt;
+            }
+        }
+    }
+
+    /// Mark outstanding transmissions lost in the scoreboard if a transmission
+    /// sent long enough after them has been delivered (RFC 8985 section 6.2 step 5).
+    ///
+    /// Returns whether anything was found lost.
+    pub(super) fn detect_loss(&mut self, timestamp: Instant, scoreboard: &mut Scoreboard) -> bool {
+        self.reo_timeout = None;
+        let Some(xmit_ts) = self.xmit_ts else {
+            return false;
+        };
+        let threshold = self.rtt + self.reo_wnd();
+
+        let mut lost = false;
+        let mut kept = 0;
+        for i in 0..self.len {
+            let sent = self.sent[i];
+            if !sent.is_newer_than(xmit_ts, self.end_seq) {
+                let deadline = sent.xmit_ts + threshold;
+                if timestamp >= deadline {
+                    net_trace!(
+                        "rack: {}..{} sent at {} lost",
+                        sent.start,
+                        sent.end,
+                        sent.xmit_ts
+                    );
+                    scoreboard.mark_lost(sent.start, sent.end, sent.retransmitted);
+                    lost = true;
+                    continue;
+                }
+                if self
+                    .reo_timeout
+                    .is_none_or(|reo_timeout| deadline < reo_timeout)
+                {
+                    self.reo_timeout = Some(deadline);
+                }
+            }
+            self.sent[kept] = sent;
+            kept += 1;
+        }
+        self.len = kept;
+        lost
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seq(n: i32) -> TcpSeqNumber {
+        TcpSeqNumber(n)
+    }
+
+    fn ms(n: i64) -> Instant {
+        Instant::from_millis(n)
+    }
+
+    #[test]
+    fn test_delivery_updates_rtt() {
+        let mut rack = Rack::new();
+        let sb = Scoreboard::new();
+        rack.on_send(seq(0), seq(10), ms(0), false);
+        rack.on_send(seq(10), seq(20), ms(5), false);
+        rack.on_ack(ms(50), seq(20), &sb);
+        assert_eq!(rack.xmit_ts, Some(ms(5)));
+        assert_eq!(rack.rtt, Duration::from_millis(45));
+        assert_eq!(rack.min_rtt, Some(Duration::from_millis(45)));
+        assert_eq!(rack.len, 0);
+    }
+
+    #[test]
+    fn test_detect_loss_after_reordering_window() {
+        let mut rack = Rack::new();
+        let mut sb = Scoreboard::new();
+        rack.on_send(seq(0), seq(10), ms(0), false);
+        rack.on_send(seq(10), seq(20), ms(10), false);
+        sb.on_ack(seq(0), seq(20), &[Some((10, 20)), None, None]);
+        rack.on_ack(ms(50), seq(0), &sb);
+
+        // RACK.rtt is 40ms, the reordering window 10ms.
+        assert!(!rack.detect_loss(ms(49), &mut sb));
+        assert_eq!(rack.reo_timeout(), Some(ms(50)));
+        assert!(!sb.is_lost(seq(0), 1000));
+
+        assert!(rack.detect_loss(ms(50), &mut sb));
+        assert_eq!(rack.reo_timeout(), None);
+        assert!(sb.is_lost(seq(0), 1000));
+    }
+
+    #[test]
+    fn test_spurious_retransmission_ignored() {
+        let mut rack = Rack::new();
+        let sb = Scoreboard::new();
+        rack.on_send(seq(0), seq(10), ms(0), false);
+        rack.on_ack(ms(100), seq(10), &sb);
+        rack.on_send(seq(10), seq(20), ms(100), false);
+        rack.on_send(seq(10), seq(20), ms(190), true);
+        rack.on_ack(ms(200), seq(20), &sb);
+        // 10ms is too quick for the retransmission to be the one delivered.
+        assert_eq!(rack.min_rtt, Some(Duration::from_millis(100)));
+        assert_eq!(rack.xmit_ts, Some(ms(100)));
+    }
+
+    #[test]
+    fn test_full_merges_into_last() {
+        let mut rack = Rack::new();
+        for i in 0..TCP_RACK_SEGMENT_COUNT as i32 + 2 {
+            rack.on_send(seq(i * 10), seq(i * 10 + 10), ms(i as i64), false);
+        }
+        let last = rack.sent[TCP_RACK_SEGMENT_COUNT - 1];
+        assert_eq!(last.start, seq((TCP_RACK_SEGMENT_COUNT as i32 - 1) * 10));
+        assert_eq!(last.end, seq((TCP_RACK_SEGMENT_COUNT as i32 + 2) * 10));
+        assert_eq!(last.xmit_ts, ms(TCP_RACK_SEGMENT_COUNT as i64 + 1));
+    }
+}
@@ -0,0 +1,373 @@
+// Delivery rate estimation for the sending side, as described in
+// draft-cheng-iccrg-delivery-rate-estimation.
+//
+// The sampler remembers, for each outstanding transmission, how many octets had
+// been delivered when it was sent, and when the last of them was. Once the
+// transmission is delivered, the octets delivered since then, over the time that
+// took, are a sample of the rate at which the path delivers data. Samples taken
+// while the application did not keep the congestion window full are marked
+// application-limited, as they may read lower than what the path allows.
+
+use crate::config::TCP_RATE_SEGMENT_COUNT;
+use crate::time::{Duration, Instant};
+use crate::wire::TcpSeqNumber;
+
+use super::sack::Scoreboard;
+
+/// A transmission of `[start, end)` at `xmit_ts`, with the delivery state of the
+/// sampler at that time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Sent {
+    start: TcpSeqNumber,
+    end: TcpSeqNumber,
+    xmit_ts: Instant,
+    retransmitted: bool,
+    delivered: u64,
+    delivered_ts: Instant,
+    first_xmit_ts: Instant,
+    app_limited: bool,
+}
+
+/// A delivery rate sample, taken from an acknowledgement that delivered data.
+///
+/// Congestion controllers receive it in
+/// [`Controller::on_rate_sample`](super::congestion::Controller::on_rate_sample).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RateSample {
+    pub(super) acked: u64,
+    pub(super) prior_delivered: u64,
+    pub(super) delivered: u64,
+    pub(super) interval: Option<Duration>,
+    pub(super) rtt: Option<Duration>,
+    pub(super) app_limited: bool,
+}
+
+impl RateSample {
+    /// Return the number of octets the acknowledgement newly delivered, either
+    /// cumulatively or selectively acknowledged.
+    pub fn acked(&self) -> u64 {
+        self.acked
+    }
+
+    /// Return the total number of octets delivered when the segment the sample
+    /// is taken from was sent.
+    ///
+    /// Together with [`delivered`](RateSample::delivered), this tells rounds
+    /// apart: a round ends once a segment sent after it started is delivered.
+    pub fn prior_delivered(&self) -> u64 {
+        self.prior_delivered
+    }
+
+    /// Return the number of octets delivered since the segment the sample is
+    /// taken from was sent, up to and including this acknowledgement.
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    /// Return the delivery rate in octets per second, or `None` if the sample
+    /// spans less than a round trip, which would overestimate it.
+    pub fn delivery_rate(&self) -> Option<u64> {
+        let interval = self.interval?.total_micros().max(1);
+        Some(self.delivered.saturating_mul(1_000_000) / interval)
+    }
+
+    /// Return the round-trip time of the segment the sample is taken from, or
+    /// `None` if it was retransmitted, and the acknowledgement may be for either
+    /// transmission.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// Return whether the application did not keep the congestion window full
+    /// while the sample was taken, so that the rate may be lower than what the
+    /// path can deliver.
+    pub fn is_app_limited(&self) -> bool {
+        self.app_limited
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(super) struct RateSampler {
+    /// Outstanding transmissions, in the order they were sent.
+    /// Only the first `len` entries are valid.
+    sent: [Sent; TCP_RATE_SEGMENT_COUNT],
+    len: usize,
+    /// Total octets delivered (`C.delivered`), and when that last grew
+    /// (`C.delivered_time`).
+    delivered: u64,
+    delivered_ts: Instant,
+    /// Send time of the most recently sent segment that has been delivered
+    /// (`C.first_sent_time`).
+    first_xmit_ts: Instant,
+    /// Samples are application-limited until `delivered` exceeds this, if nonzero
+    /// (`C.app_limited`).
+    app_limited: u64,
+    /// The cumulative acknowledgement, and the octets SACKed above it, when the
+    /// last acknowledgement was processed.
+    snd_una: Option<TcpSeqNumber>,
+    sacked: usize,
+    /// The minimum RTT of the samples. Samples spanning less are discarded.
+    min_rtt: Option<Duration>,
+}
+
+impl RateSampler {
+    pub(super) fn new() -> Self {
+        let empty = Sent {
+            start: TcpSeqNumber::default(),
+            end: TcpSeqNumber::default(),
+            xmit_ts: Instant::ZERO,
+            retransmitted: false,
+            delivered: 0,
+            delivered_ts: Instant::ZERO,
+            first_xmit_ts: Instant::ZERO,
+            app_limited: false,
+        };
+        RateSampler {
+            sent: [empty; TCP_RATE_SEGMENT_COUNT],
+            len: 0,
+            delivered: 0,
+            delivered_ts: Instant::ZERO,
+            first_xmit_ts: Instant::ZERO,
+            app_limited: 0,
+            snd_una: None,
+            sacked: 0,
+            min_rtt: None,
+        }
+    }
+
+    /// Record the transmission of `[start, end)`.
+    pub(super) fn on_send(
+        &mut self,
+        start: TcpSeqNumber,
+        end: TcpSeqNumber,
+        timestamp: Instant,
+        retransmitted: bool,
+    ) {
+        if self.snd_una.is_none() {
+            self.snd_una = Some(start);
+        }
+        if self.len == 0 {
+            // Nothing is outstanding, so don't count the idle time before this
+            // transmission in the interval of its sample.
+            self.first_xmit_ts = timestamp;
+            self.delivered_ts = timestamp;
+        }
+
+        let sent = Sent {
+            start,
+            end,
+            xmit_ts: timestamp,
+            retransmitted,
+            delivered: self.delivered,
+            delivered_ts: self.delivered_ts,
+            first_xmit_ts: self.first_xmit_ts,
+            app_limited: self.app_limited != 0,
+        };
+
+        if self.len == TCP_RATE_SEGMENT_COUNT {
+            // Out of space, fold the transmission into the most recent one. Keeping
+            // the older delivery state only makes its sample span a longer interval.
+            let last = &mut self.sent[self.len - 1];
+            last.start = last.start.min(start);
+            last.end = last.end.max(end);
+            last.retransmitted |= retransmitted;
+        } else {
+            self.sent[self.len] = sent;
+            self.len += 1;
+        }
+    }
+
+    /// Mark the samples to come as application-limited, until the `in_flight`
+    /// octets now outstanding have been delivered.
+    pub(super) fn on_app_limited(&mut self, in_flight: usize) {
+        self.app_limited = self.delivered.saturating_add(in_flight as u64).max(1);
+    }
+
+    /// Process an acknowledgement, and take a sample from the most recently sent
+    /// transmission it delivers, if it delivers anything.
+    pub(super) fn on_ack(
+        &mut self,
+        timestamp: Instant,
+        snd_una: TcpSeqNumber,
+        scoreboard: &Scoreboard,
+    ) -> Option<RateSample> {
+        let prev_snd_una = *self.snd_una.get_or_insert(snd_una);
+        let acked = if snd_una > prev_snd_una {
+            self.snd_una = Some(snd_una);
+            snd_una - prev_snd_una
+        } else {
+            0
+        };
+        // Octets SACKed before and acknowledged now were delivered already.
+        let sacked = scoreboard.sacked_len();
+        let newly_delivered = (acked + sacked).saturating_sub(self.sacked);
+        self.sacked = sacked;
+
+        let mut latest = None;
+        let mut kept = 0;
+        for i in 0..self.len {
+            let mut sent = self.sent[i];
+            if sent.end <= snd_una || scoreboard.is_range_sacked(sent.start, sent.end) {
+                latest = Some(sent);
+                continue;
+            }
+            if sent.start < snd_una {
+                sent.start = snd_una;
+            }
+            self.sent[kept] = sent;
+            kept += 1;
+        }
+        self.len = kept;
+
+        if newly_delivered == 0 {
+            return None;
+        }
+        let prior_delivered = self.delivered;
+        self.delivered += newly_delivered as u64;
+        self.delivered_ts = timestamp;
+        if self.app_limited != 0 && self.delivered > self.app_limited {
+            self.app_limited = 0;
+        }
+
+        let Some(sent) = latest else {
+            return Some(RateSample {
+                acked: newly_delivered as u64,
+                prior_delivered,
+                delivered: newly_delivered as u64,
+                interval: None,
+                rtt: None,
+                app_limited: self.app_limited != 0,
+            });
+        };
+        self.first_xmit_ts = sent.xmit_ts;
+
+        let rtt = timestamp - sent.xmit_ts;
+        // Karn's algorithm: the acknowledgement may be for an earlier transmission.
+        if !sent.retransmitted && self.min_rtt.is_none_or(|min_rtt| rtt < min_rtt) {
+            self.min_rtt = Some(rtt);
+        }
+
+        // The data may have been sent faster than it was delivered, or the other
+        // way around; the slower of the two is the rate of the path.
+        let send_elapsed = sent.xmit_ts - sent.first_xmit_ts;
+        let ack_elapsed = timestamp - sent.delivered_ts;
+        let interval = send_elapsed.max(ack_elapsed);
+        Some(RateSample {
+            acked: newly_delivered as u64,
+            prior_delivered: sent.delivered,
+            delivered: self.delivered - sent.delivered,
+            interval: self
+                .min_rtt
+                .is_some_and(|min_rtt| interval >= min_rtt)
+                .then_some(interval),
+            rtt: (!sent.retransmitted).then_some(rtt),
+            app_limited: sent.app_limited,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seq(n: i32) -> TcpSeqNumber {
+        TcpSeqNumber(n)
+    }
+
+    fn ms(n: i64) -> Instant {
+        Instant::from_millis(n)
+    }
+
+    #[test]
+    fn test_rate_sample() {
+        let mut rate = RateSampler::new();
+        let sb = Scoreboard::new();
+        rate.on_send(seq(0), seq(1000), ms(0), false);
+        rate.on_send(seq(1000), seq(2000), ms(10), false);
+
+        let sample = rate.on_ack(ms(100), seq(1000), &sb).unwrap();
+        assert_eq!(sample.acked(), 1000);
+        assert_eq!(sample.prior_delivered(), 0);
+        assert_eq!(sample.delivered(), 1000);
+        assert_eq!(sample.rtt(), Some(Duration::from_millis(100)));
+        assert_eq!(sample.delivery_rate(), Some(10_000));
+
+        // The second segment was sent before anything was delivered, so its
+        // sample spans the time since the connection went busy.
+        let sample = rate.on_ack(ms(110), seq(2000), &sb).unwrap();
+        assert_eq!(sample.prior_delivered(), 0);
+        assert_eq!(sample.delivered(), 2000);
+        assert_eq!(sample.rtt(), Some(Duration::from_millis(100)));
+        assert_eq!(sample.delivery_rate(), Some(2000 * 1000 / 110));
+        assert_eq!(rate.len, 0);
+    }
+
+    #[test]
+    fn test_rate_sample_sacked() {
+        let mut rate = RateSampler::new();
+        let mut sb = Scoreboard::new();
+        rate.on_send(seq(0), seq(1000), ms(0), false);
+        rate.on_send(seq(1000), seq(2000), ms(0), false);
+
+        // The second segment is SACKed, then both acknowledged; it is only
+        // delivered once.
+        sb.on_ack(seq(0), seq(2000), &[Some((1000, 2000)), None, None]);
+        let sample = rate.on_ack(ms(100), seq(0), &sb).unwrap();
+        assert_eq!(sample.acked(), 1000);
+        sb.on_ack(seq(2000), seq(2000), &[None, None, None]);
+        let sample = rate.on_ack(ms(110), seq(2000), &sb).unwrap();
+        assert_eq!(sample.acked(), 1000);
+        assert_eq!(sample.delivered(), 2000);
+        assert_eq!(rate.on_ack(ms(120), seq(2000), &sb), None);
+    }
+
+    #[test]
+    fn test_rate_sample_short_interval_discarded() {
+        let mut rate = RateSampler::new();
+        let sb = Scoreboard::new();
+        rate.on_send(seq(0), seq(1000), ms(0), false);
+        rate.on_ack(ms(150), seq(1000), &sb).unwrap();
+
+        // A retransmission acknowledged quickly gives neither an RTT nor a rate.
+        rate.on_send(seq(1000), seq(2000), ms(200), false);
+        rate.on_send(seq(1000), seq(2000), ms(290), true);
+        let sample = rate.on_ack(ms(300), seq(2000), &sb).unwrap();
+        assert_eq!(sample.rtt(), None);
+        assert_eq!(sample.delivery_rate(), None);
+    }
+
+    #[test]
+    fn test_rate_sample_app_limited() {
+        let mut rate = RateSampler::new();
+        let sb = Scoreboard::new();
+        rate.on_send(seq(0), seq(1000), ms(0), false);
+        rate.on_app_limited(1000);
+        rate.on_send(seq(1000), seq(2000), ms(10), false);
+
+        // Only what was sent after the application ran out of data is limited.
+        let sample = rate.on_ack(ms(100), seq(1000), &sb).unwrap();
+        assert!(!sample.is_app_limited());
+        let sample = rate.on_ack(ms(110), seq(2000), &sb).unwrap();
+        assert!(sample.is_app_limited());
+
+        // Once the data in flight then is delivered, samples are no longer limited.
+        rate.on_send(seq(2000), seq(3000), ms(120), false);
+        let sample = rate.on_ack(ms(220), seq(3000), &sb).unwrap();
+        assert!(!sample.is_app_limited());
+    }
+
+    #[test]
+    fn test_full_merges_into_last() {
+        let mut rate = RateSampler::new();
+        for i in 0..TCP_RATE_SEGMENT_COUNT as i32 + 2 {
+            rate.on_send(seq(i * 10), seq(i * 10 + 10), ms(i as i64), false);
+        }
+        let last = rate.sent[TCP_RATE_SEGMENT_COUNT - 1];
+        assert_eq!(last.start, seq((TCP_RATE_SEGMENT_COUNT as i32 - 1) * 10));
+        assert_eq!(last.end, seq((TCP_RATE_SEGMENT_COUNT as i32 + 2) * 10));
+        assert_eq!(last.xmit_ts, ms(TCP_RATE_SEGMENT_COUNT as i64 - 1));
+    }
+}
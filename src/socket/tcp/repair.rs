@@ -0,0 +1,450 @@
+// Connection repair, in the spirit of Linux's TCP_REPAIR.
+//
+// A checkpoint holds the protocol state of a synchronized connection, followed by
+// the data in its receive buffer and in its transmit buffer, so that another
+// socket, possibly in another process, can take the connection over without a
+// handshake. The octets sent but not acknowledged are kept in the transmit
+// buffer anyway, and the restored socket retransmits them. Out-of-order data
+// isn't saved: it was not acknowledged, and the remote retransmits it.
+//
+// What the socket was configured with, such as timeouts, the ACK policy or the
+// timestamp generator, isn't part of the checkpoint, and is set again on the
+// restored socket.
+
+use core::fmt;
+use core::ops::Range;
+
+use super::{ActiveCongestionControl, CongestionControl, State};
+use crate::time::Duration;
+use crate::wire::{IpAddress, IpEndpoint, TcpSeqNumber};
+
+/// The version of the checkpoint format.
+const FORMAT_VERSION: u8 = 1;
+
+mod field {
+    use core::ops::Range;
+
+    pub const VERSION: usize = 0;
+    pub const STATE: usize = 1;
+    pub const FLAGS: usize = 2;
+    pub const WIN_SHIFT: usize = 3;
+    pub const WIN_SCALE: usize = 4;
+    pub const CONGESTION_CONTROL: usize = 5;
+    pub const MSS: Range<usize> = 6..8;
+    pub const LAST_WIN: Range<usize> = 8..10;
+    pub const LOCAL_SEQ: Range<usize> = 10..14;
+    pub const REMOTE_SEQ: Range<usize> = 14..18;
+    pub const LAST_ACK: Range<usize> = 18..22;
+    pub const WIN_LEN: Range<usize> = 22..26;
+    pub const TSVAL: Range<usize> = 26..30;
+    pub const SRTT: Range<usize> = 30..34;
+    pub const RTTVAR: Range<usize> = 34..38;
+    pub const RTO: Range<usize> = 38..42;
+    pub const CWND: Range<usize> = 42..46;
+    pub const SSTHRESH: Range<usize> = 46..50;
+    pub const RX_LEN: Range<usize> = 50..54;
+    pub const TX_LEN: Range<usize> = 54..58;
+    pub const LOCAL_TSVAL: Range<usize> = 58..62;
+    pub const LOCAL: Range<usize> = 62..81;
+    pub const REMOTE: Range<usize> = 81..100;
+}
+
+const HEADER_LEN: usize = field::REMOTE.end;
+
+mod flags {
+    pub const SACK: u8 = 0x01;
+    pub const ECN: u8 = 0x02;
+    pub const TIMESTAMPS: u8 = 0x04;
+    pub const RX_FIN: u8 = 0x08;
+    pub const WIN_SCALE: u8 = 0x10;
+    pub const RTT: u8 = 0x20;
+    pub const SSTHRESH: u8 = 0x40;
+}
+
+/// Error returned by [`Socket::checkpoint`](super::Socket::checkpoint) and
+/// [`Socket::restore`](super::Socket::restore).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RepairError {
+    /// The connection is not synchronized, or is in TIME-WAIT.
+    InvalidState,
+    /// The socket holds state that can't be saved, or the checkpoint holds an
+    /// address of a protocol this build doesn't support.
+    Unsupported,
+    /// The buffer is too small for the checkpoint, or a socket buffer for its data.
+    BufferTooSmall,
+    /// The checkpoint is truncated, malformed, or of an unknown format.
+    Malformed,
+}
+
+impl fmt::Display for RepairError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RepairError::InvalidState => write!(f, "invalid state"),
+            RepairError::Unsupported => write!(f, "unsupported"),
+            RepairError::BufferTooSmall => write!(f, "buffer too small"),
+            RepairError::Malformed => write!(f, "malformed checkpoint"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RepairError {}
+
+/// The protocol state of a connection, as saved in a checkpoint, see
+/// [Socket::checkpoint](super::Socket::checkpoint).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RepairState {
+    pub state: State,
+    pub local: IpEndpoint,
+    pub remote: IpEndpoint,
+    /// The sequence number of the first octet in the transmit buffer, the first
+    /// one not acknowledged.
+    pub local_seq_no: TcpSeqNumber,
+    /// The sequence number of the first octet in the receive buffer.
+    pub remote_seq_no: TcpSeqNumber,
+    /// The last acknowledgement number sent.
+    pub remote_last_ack: TcpSeqNumber,
+    /// The last window sent, scaled.
+    pub remote_last_win: u16,
+    /// The window of the remote, from `local_seq_no`.
+    pub remote_win_len: usize,
+    /// The scaling factor of the windows sent.
+    pub remote_win_shift: u8,
+    /// The scaling factor of the windows received, or `None` if the remote doesn't
+    /// support window scaling.
+    pub remote_win_scale: Option<u8>,
+    /// The maximum segment size of the remote.
+    pub remote_mss: usize,
+    /// Whether the remote supports selective acknowledgements.
+    pub remote_has_sack: bool,
+    /// Whether ECN was negotiated with the remote.
+    pub remote_has_ecn: bool,
+    /// Whether TCP timestamps were negotiated with the remote. The timestamp
+    /// generator can't be saved, and must be set again on the restored socket.
+    pub timestamps: bool,
+    /// The last timestamp value received.
+    pub last_remote_tsval: u32,
+    /// The last timestamp value sent. The timestamps sent by the restored socket
+    /// must not be older.
+    pub last_local_tsval: u32,
+    /// Whether the remote sent a FIN.
+    pub rx_fin_received: bool,
+    /// The smoothed round-trip time and its variation, if measured.
+    pub rtt: Option<(Duration, Duration)>,
+    /// The retransmission timeout.
+    pub retransmission_timeout: Duration,
+    /// The congestion control algorithm. It is `Custom` for an algorithm not
+    /// built into this build, whose state isn't restored.
+    pub congestion_control: ActiveCongestionControl,
+    /// The congestion window.
+    pub congestion_window: usize,
+    /// The slow start threshold, if set.
+    pub slow_start_threshold: Option<usize>,
+    /// How many octets the receive buffer holds.
+    pub rx_len: usize,
+    /// How many octets the transmit buffer holds.
+    pub tx_len: usize,
+}
+
+impl RepairState {
+    /// Return the length of a checkpoint of this state, with its data.
+    pub fn buffer_len(&self) -> usize {
+        HEADER_LEN + self.rx_len + self.tx_len
+    }
+
+    /// Return the range of the receive buffer data in a checkpoint.
+    pub(super) fn rx_range(&self) -> Range<usize> {
+        HEADER_LEN..HEADER_LEN + self.rx_len
+    }
+
+    /// Return the range of the transmit buffer data in a checkpoint.
+    pub(super) fn tx_range(&self) -> Range<usize> {
+        HEADER_LEN + self.rx_len..self.buffer_len()
+    }
+
+    /// Parse the state saved in `checkpoint`, which must hold its data as well.
+    pub fn parse(checkpoint: &[u8]) -> Result<RepairState, RepairError> {
+        if checkpoint.len() < HEADER_LEN || checkpoint[field::VERSION] != FORMAT_VERSION {
+            return Err(RepairError::Malformed);
+        }
+
+        let state = match checkpoint[field::STATE] {
+            4 => State::Established,
+            5 => State::FinWait1,
+            6 => State::FinWait2,
+            7 => State::CloseWait,
+            8 => State::Closing,
+            9 => State::LastAck,
+            _ => return Err(RepairError::Malformed),
+        };
+        let flags = checkpoint[field::FLAGS];
+        let congestion_control = match checkpoint[field::CONGESTION_CONTROL] {
+            0 => ActiveCongestionControl::BuiltIn(CongestionControl::None),
+            #[cfg(feature = "socket-tcp-reno")]
+            1 => ActiveCongestionControl::BuiltIn(CongestionControl::Reno),
+            #[cfg(feature = "socket-tcp-cubic")]
+            2 => ActiveCongestionControl::BuiltIn(CongestionControl::Cubic),
+            #[cfg(feature = "socket-tcp-bbr")]
+            3 => ActiveCongestionControl::BuiltIn(CongestionControl::Bbr),
+            _ => ActiveCongestionControl::Custom,
+        };
+
+        let repair = RepairState {
+            state,
+            local: read_endpoint(&checkpoint[field::LOCAL])?,
+            remote: read_endpoint(&checkpoint[field::REMOTE])?,
+            local_seq_no: TcpSeqNumber(read_u32(checkpoint, field::LOCAL_SEQ) as i32),
+            remote_seq_no: TcpSeqNumber(read_u32(checkpoint, field::REMOTE_SEQ) as i32),
+            remote_last_ack: TcpSeqNumber(read_u32(checkpoint, field::LAST_ACK) as i32),
+            remote_last_win: read_u16(checkpoint, field::LAST_WIN),
+            remote_win_len: read_u32(checkpoint, field::WIN_LEN) as usize,
+            remote_win_shift: checkpoint[field::WIN_SHIFT],
+            remote_win_scale: (flags & flags::WIN_SCALE != 0)
+                .then_some(checkpoint[field::WIN_SCALE]),
+            remote_mss: read_u16(checkpoint, field::MSS) as usize,
+            remote_has_sack: flags & flags::SACK != 0,
+            remote_has_ecn: flags & flags::ECN != 0,
+            timestamps: flags & flags::TIMESTAMPS != 0,
+            last_remote_tsval: read_u32(checkpoint, field::TSVAL),
+            last_local_tsval: read_u32(checkpoint, field::LOCAL_TSVAL),
+            rx_fin_received: flags & flags::RX_FIN != 0,
+            rtt: (flags & flags::RTT != 0).then(|| {
+                (
+                    Duration::from_millis(read_u32(checkpoint, field::SRTT) as u64),
+                    Duration::from_millis(read_u32(checkpoint, field::RTTVAR) as u64),
+                )
+            }),
+            retransmission_timeout: Duration::from_millis(read_u32(checkpoint, field::RTO) as u64),
+            congestion_control,
+            congestion_window: unsaturate(read_u32(checkpoint, field::CWND)),
+            slow_start_threshold: (flags & flags::SSTHRESH != 0)
+                .then(|| unsaturate(read_u32(checkpoint, field::SSTHRESH))),
+            rx_len: read_u32(checkpoint, field::RX_LEN) as usize,
+            tx_len: read_u32(checkpoint, field::TX_LEN) as usize,
+        };
+        if repair.remote_mss == 0 || repair.remote_win_shift > 14 {
+            return Err(RepairError::Malformed);
+        }
+        if checkpoint.len() < repair.buffer_len() {
+            return Err(RepairError::Malformed);
+        }
+        Ok(repair)
+    }
+
+    /// Emit the state into the start of `checkpoint`, which must be at least
+    /// `buffer_len()` octets long. The data is copied by the caller.
+    pub(super) fn emit(&self, checkpoint: &mut [u8]) {
+        let state = match self.state {
+            State::Established => 4,
+            State::FinWait1 => 5,
+            State::FinWait2 => 6,
+            State::CloseWait => 7,
+            State::Closing => 8,
+            State::LastAck => 9,
+            _ => unreachable!(),
+        };
+        let congestion_control = match self.congestion_control {
+            ActiveCongestionControl::BuiltIn(CongestionControl::None) => 0,
+            #[cfg(feature = "socket-tcp-reno")]
+            ActiveCongestionControl::BuiltIn(CongestionControl::Reno) => 1,
+            #[cfg(feature = "socket-tcp-cubic")]
+            ActiveCongestionControl::BuiltIn(CongestionControl::Cubic) => 2,
+            #[cfg(feature = "socket-tcp-bbr")]
+            ActiveCongestionControl::BuiltIn(CongestionControl::Bbr) => 3,
+            ActiveCongestionControl::Custom => 4,
+        };
+        let mut flags = 0;
+        for (flag, set) in [
+            (flags::SACK, self.remote_has_sack),
+            (flags::ECN, self.remote_has_ecn),
+            (flags::TIMESTAMPS, self.timestamps),
+            (flags::RX_FIN, self.rx_fin_received),
+            (flags::WIN_SCALE, self.remote_win_scale.is_some()),
+            (flags::RTT, self.rtt.is_some()),
+            (flags::SSTHRESH, self.slow_start_threshold.is_some()),
+        ] {
+            if set {
+                flags |= flag;
+            }
+        }
+        let (srtt, rttvar) = self.rtt.unwrap_or_default();
+
+        checkpoint[field::VERSION] = FORMAT_VERSION;
+        checkpoint[field::STATE] = state;
+        checkpoint[field::FLAGS] = flags;
+        checkpoint[field::WIN_SHIFT] = self.remote_win_shift;
+        checkpoint[field::WIN_SCALE] = self.remote_win_scale.unwrap_or(0);
+        checkpoint[field::CONGESTION_CONTROL] = congestion_control;
+        write_u16(checkpoint, field::MSS, self.remote_mss as u16);
+        write_u16(checkpoint, field::LAST_WIN, self.remote_last_win);
+        write_u32(checkpoint, field::LOCAL_SEQ, self.local_seq_no.0 as u32);
+        write_u32(checkpoint, field::REMOTE_SEQ, self.remote_seq_no.0 as u32);
+        write_u32(checkpoint, field::LAST_ACK, self.remote_last_ack.0 as u32);
+        write_u32(checkpoint, field::WIN_LEN, saturate(self.remote_win_len));
+        write_u32(checkpoint, field::TSVAL, self.last_remote_tsval);
+        write_u32(checkpoint, field::SRTT, srtt.total_millis() as u32);
+        write_u32(checkpoint, field::RTTVAR, rttvar.total_millis() as u32);
+        write_u32(
+            checkpoint,
+            field::RTO,
+            self.retransmission_timeout.total_millis() as u32,
+        );
+        write_u32(checkpoint, field::CWND, saturate(self.congestion_window));
+        write_u32(
+            checkpoint,
+            field::SSTHRESH,
+            saturate(self.slow_start_threshold.unwrap_or(0)),
+        );
+        write_u32(checkpoint, field::RX_LEN, self.rx_len as u32);
+        write_u32(checkpoint, field::TX_LEN, self.tx_len as u32);
+        write_u32(checkpoint, field::LOCAL_TSVAL, self.last_local_tsval);
+        write_endpoint(&mut checkpoint[field::LOCAL], self.local);
+        write_endpoint(&mut checkpoint[field::REMOTE], self.remote);
+    }
+}
+
+/// Windows too large for 32 bits are saved as `u32::MAX`, and read back as
+/// `usize::MAX`, which stands for no limit.
+fn saturate(value: usize) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+fn unsaturate(value: u32) -> usize {
+    match value {
+        u32::MAX => usize::MAX,
+        value => value as usize,
+    }
+}
+
+fn read_u16(data: &[u8], field: Range<usize>) -> u16 {
+    u16::from_be_bytes(data[field].try_into().unwrap())
+}
+
+fn read_u32(data: &[u8], field: Range<usize>) -> u32 {
+    u32::from_be_bytes(data[field].try_into().unwrap())
+}
+
+fn write_u16(data: &mut [u8], field: Range<usize>, value: u16) {
+    data[field].copy_from_slice(&value.to_be_bytes());
+}
+
+fn write_u32(data: &mut [u8], field: Range<usize>, value: u32) {
+    data[field].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Read an endpoint saved as an IP version, 16 octets of address and a port.
+fn read_endpoint(data: &[u8]) -> Result<IpEndpoint, RepairError> {
+    let addr: IpAddress = match data[0] {
+        #[cfg(feature = "proto-ipv4")]
+        4 => IpAddress::Ipv4(<[u8; 4]>::try_from(&data[1..5]).unwrap().into()),
+        #[cfg(feature = "proto-ipv6")]
+        6 => IpAddress::Ipv6(<[u8; 16]>::try_from(&data[1..17]).unwrap().into()),
+        // A protocol this build doesn't support.
+        #[allow(unreachable_patterns)]
+        4 | 6 => return Err(RepairError::Unsupported),
+        _ => return Err(RepairError::Malformed),
+    };
+    let port = u16::from_be_bytes([data[17], data[18]]);
+    Ok(IpEndpoint::new(addr, port))
+}
+
+fn write_endpoint(data: &mut [u8], endpoint: IpEndpoint) {
+    data.fill(0);
+    match endpoint.addr {
+        #[cfg(feature = "proto-ipv4")]
+        IpAddress::Ipv4(addr) => {
+            data[0] = 4;
+            data[1..5].copy_from_slice(&addr.octets());
+        }
+        #[cfg(feature = "proto-ipv6")]
+        IpAddress::Ipv6(addr) => {
+            data[0] = 6;
+            data[1..17].copy_from_slice(&addr.octets());
+        }
+    }
+    data[17..19].copy_from_slice(&endpoint.port.to_be_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "proto-ipv4")]
+    const LOCAL: IpEndpoint = IpEndpoint::new(IpAddress::v4(192, 168, 1, 1), 80);
+    #[cfg(feature = "proto-ipv4")]
+    const REMOTE: IpEndpoint = IpEndpoint::new(IpAddress::v4(192, 168, 1, 2), 49500);
+    #[cfg(not(feature = "proto-ipv4"))]
+    const LOCAL: IpEndpoint = IpEndpoint::new(IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1), 80);
+    #[cfg(not(feature = "proto-ipv4"))]
+    const REMOTE: IpEndpoint = IpEndpoint::new(IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 2), 49500);
+
+    fn repair_state() -> RepairState {
+        RepairState {
+            state: State::FinWait1,
+            local: LOCAL,
+            remote: REMOTE,
+            local_seq_no: TcpSeqNumber(-10),
+            remote_seq_no: TcpSeqNumber(1000),
+            remote_last_ack: TcpSeqNumber(1006),
+            remote_last_win: 256,
+            remote_win_len: 1 << 20,
+            remote_win_shift: 2,
+            remote_win_scale: Some(7),
+            remote_mss: 1460,
+            remote_has_sack: true,
+            remote_has_ecn: false,
+            timestamps: true,
+            last_remote_tsval: 0xdeadbeef,
+            last_local_tsval: 0x1234_5678,
+            rx_fin_received: false,
+            rtt: Some((Duration::from_millis(40), Duration::from_millis(10))),
+            retransmission_timeout: Duration::from_millis(1000),
+            congestion_control: ActiveCongestionControl::BuiltIn(CongestionControl::None),
+            congestion_window: usize::MAX,
+            slow_start_threshold: Some(20000),
+            rx_len: 6,
+            tx_len: 3,
+        }
+    }
+
+    #[test]
+    fn test_emit_parse() {
+        let repair = repair_state();
+        let mut checkpoint = [0xff; HEADER_LEN + 9];
+        assert_eq!(repair.buffer_len(), checkpoint.len());
+        repair.emit(&mut checkpoint);
+        checkpoint[repair.rx_range()].copy_from_slice(b"abcdef");
+        checkpoint[repair.tx_range()].copy_from_slice(b"xyz");
+
+        let parsed = RepairState::parse(&checkpoint).unwrap();
+        assert_eq!(parsed, repair);
+        assert_eq!(&checkpoint[parsed.rx_range()], b"abcdef");
+        assert_eq!(&checkpoint[parsed.tx_range()], b"xyz");
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let repair = repair_state();
+        let mut checkpoint = [0; HEADER_LEN + 9];
+        repair.emit(&mut checkpoint);
+
+        // The data is truncated.
+        assert_eq!(
+            RepairState::parse(&checkpoint[..HEADER_LEN + 8]),
+            Err(RepairError::Malformed)
+        );
+
+        let mut unknown_version = checkpoint;
+        unknown_version[field::VERSION] = FORMAT_VERSION + 1;
+        assert_eq!(
+            RepairState::parse(&unknown_version),
+            Err(RepairError::Malformed)
+        );
+
+        let mut listening = checkpoint;
+        listening[field::STATE] = 1;
+        assert_eq!(RepairState::parse(&listening), Err(RepairError::Malformed));
+    }
+}
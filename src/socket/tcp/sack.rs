@@ -0,0 +1,486 @@
+// SACK-based loss recovery for the sending side, as described in RFC 6675.
+//
+// The scoreboard remembers which octets above the cumulative acknowledgement
+// the remote has selectively acknowledged. From it the socket derives which
+// holes are considered lost, which hole to retransmit next, and the "pipe":
+// an estimate of the octets still in flight, which is compared against the
+// congestion controller's window.
+
+use crate::config::TCP_SACK_SCOREBOARD_COUNT;
+use crate::wire::TcpSeqNumber;
+
+/// The number of duplicate ACKs (or discontiguous SACKed segments) that make
+/// a hole count as lost. RFC 6675 calls this `DupThresh`.
+pub(super) const DUP_THRESH: usize = 3;
+
+/// A contiguous range of SACKed octets, `[left, right)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Block {
+    left: TcpSeqNumber,
+    right: TcpSeqNumber,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(super) struct Scoreboard {
+    /// SACKed ranges, sorted by sequence number and never overlapping or touching.
+    /// Only the first `len` entries are valid.
+    blocks: [Block; TCP_SACK_SCOREBOARD_COUNT],
+    len: usize,
+    /// Whether we are in loss recovery.
+    in_recovery: bool,
+    /// The highest sequence number sent when loss recovery was last entered
+    /// (`RecoveryPoint`). Recovery is not entered again until it is acknowledged.
+    recovery_point: Option<TcpSeqNumber>,
+    /// The highest sequence number retransmitted during this recovery (`HighRxt`).
+    high_rxt: TcpSeqNumber,
+    /// The highest cumulative acknowledgement seen during this recovery (`HighACK`).
+    high_ack: TcpSeqNumber,
+    /// Octets below this have been found lost by RACK, unless they have been SACKed.
+    lost_end: Option<TcpSeqNumber>,
+    /// Whether the first hole must be retransmitted regardless of `IsLost()`.
+    /// This is the case when entering recovery, as required by RFC 6675 section 5
+    /// step (4.3), and after a partial acknowledgement, like in RFC 6582.
+    rxt_first_hole: bool,
+}
+
+impl Scoreboard {
+    pub(super) fn new() -> Self {
+        let empty = Block {
+            left: TcpSeqNumber::default(),
+            right: TcpSeqNumber::default(),
+        };
+        Scoreboard {
+            blocks: [empty; TCP_SACK_SCOREBOARD_COUNT],
+            len: 0,
+            in_recovery: false,
+            recovery_point: None,
+            high_rxt: TcpSeqNumber::default(),
+            high_ack: TcpSeqNumber::default(),
+            lost_end: None,
+            rxt_first_hole: false,
+        }
+    }
+
+    fn blocks(&self) -> &[Block] {
+        &self.blocks[..self.len]
+    }
+
+    /// Return whether we are in SACK loss recovery.
+    pub(super) fn in_recovery(&self) -> bool {
+        self.in_recovery
+    }
+
+    /// Return whether the first hole has yet to be retransmitted after entering
+    /// loss recovery. That retransmission is not subject to the congestion window.
+    pub(super) fn must_retransmit(&self) -> bool {
+        self.in_recovery && self.rxt_first_hole
+    }
+
+    /// Process the cumulative acknowledgement and SACK blocks of an incoming segment.
+    ///
+    /// `high_data` is the highest sequence number that could possibly have been sent;
+    /// blocks outside of `snd_una..=high_data` are ignored as invalid (RFC 2883).
+    pub(super) fn on_ack(
+        &mut self,
+        snd_una: TcpSeqNumber,
+        high_data: TcpSeqNumber,
+        sack_ranges: &[Option<(u32, u32)>; 3],
+    ) {
+        if self.in_recovery && snd_una > self.high_ack {
+            self.high_ack = snd_una;
+            self.rxt_first_hole = true;
+        }
+        self.prune(snd_una);
+
+        for &(left, right) in sack_ranges.iter().flatten() {
+            let left = TcpSeqNumber(left as i32);
+            let right = TcpSeqNumber(right as i32);
+            if left >= right || left < snd_una || right > high_data {
+                continue;
+            }
+            self.insert(Block { left, right });
+        }
+
+        if let Some(recovery_point) = self.recovery_point {
+            if snd_una >= recovery_point {
+                if self.in_recovery {
+                    net_trace!("sack: leaving loss recovery at {}", snd_una);
+                }
+                self.in_recovery = false;
+                self.recovery_point = None;
+            }
+        }
+    }
+
+    /// Forget everything below the cumulative acknowledgement.
+    fn prune(&mut self, snd_una: TcpSeqNumber) {
+        if self.lost_end.is_some_and(|lost_end| lost_end <= snd_una) {
+            self.lost_end = None;
+        }
+
+        let mut kept = 0;
+        for i in 0..self.len {
+            let mut block = self.blocks[i];
+            if block.right <= snd_una {
+                continue;
+            }
+            if block.left < snd_una {
+                block.left = snd_una;
+            }
+            self.blocks[kept] = block;
+            kept += 1;
+        }
+        self.len = kept;
+    }
+
+    fn insert(&mut self, mut block: Block) {
+        // Merge every block overlapping or adjacent to the new one into it.
+        let mut kept = 0;
+        for i in 0..self.len {
+            let other = self.blocks[i];
+            if other.right < block.left || other.left > block.right {
+                self.blocks[kept] = other;
+                kept += 1;
+            } else {
+                block.left = block.left.min(other.left);
+                block.right = block.right.max(other.right);
+            }
+        }
+        self.len = kept;
+
+        let pos = self
+            .blocks()
+            .iter()
+            .position(|other| other.left > block.left)
+            .unwrap_or(self.len);
+
+        if self.len == TCP_SACK_SCOREBOARD_COUNT {
+            // Out of space, forget the highest range. The lower ones matter
+            // more, as they are the ones that make the holes below them lost.
+            if pos == self.len {
+                return;
+            }
+            self.len -= 1;
+        }
+
+        self.blocks.copy_within(pos..self.len, pos + 1);
+        self.blocks[pos] = block;
+        self.len += 1;
+    }
+
+    /// Return whether `seq` has been SACKed.
+    fn is_sacked(&self, seq: TcpSeqNumber) -> bool {
+        self.blocks()
+            .iter()
+            .any(|block| block.left <= seq && seq < block.right)
+    }
+
+    /// Return whether all of `[start, end)` has been SACKed.
+    pub(super) fn is_range_sacked(&self, start: TcpSeqNumber, end: TcpSeqNumber) -> bool {
+        self.blocks()
+            .iter()
+            .any(|block| block.left <= start && end <= block.right)
+    }
+
+    /// If `seq` falls into a SACKed range, return the end of that range,
+    /// otherwise return `seq`.
+    pub(super) fn skip_sacked(&self, seq: TcpSeqNumber) -> TcpSeqNumber {
+        self.blocks()
+            .iter()
+            .find(|block| block.left <= seq && seq < block.right)
+            .map_or(seq, |block| block.right)
+    }
+
+    /// Return the start of the first SACKed range above `seq`, if any.
+    pub(super) fn next_sacked(&self, seq: TcpSeqNumber) -> Option<TcpSeqNumber> {
+        self.blocks()
+            .iter()
+            .find(|block| block.left > seq)
+            .map(|block| block.left)
+    }
+
+    /// RFC 6675 `IsLost(seq)`: a sequence number is considered lost if at least
+    /// `DupThresh` discontiguous SACKed ranges, or more than `(DupThresh - 1) * SMSS`
+    /// octets, have been SACKed above it.
+    ///
+    /// Octets found lost by RACK are considered lost as well.
+    pub(super) fn is_lost(&self, seq: TcpSeqNumber, mss: usize) -> bool {
+        if self.lost_end.is_some_and(|lost_end| seq < lost_end) {
+            return true;
+        }
+
+        let mut count = 0;
+        let mut octets = 0;
+        for block in self.blocks().iter().filter(|block| block.right > seq) {
+            let left = block.left.max(seq + 1);
+            count += 1;
+            octets += block.right - left;
+        }
+        count >= DUP_THRESH || octets > (DUP_THRESH - 1) * mss
+    }
+
+    /// Return the number of octets SACKed above the cumulative acknowledgement.
+    pub(super) fn sacked_len(&self) -> usize {
+        self.blocks()
+            .iter()
+            .map(|block| block.right - block.left)
+            .sum()
+    }
+
+    /// Return whether any hole at or above `seq` is considered lost, see `is_lost()`.
+    pub(super) fn is_lost_above(&self, seq: TcpSeqNumber, mss: usize) -> bool {
+        let mut seq = seq;
+        for block in self.blocks() {
+            if block.right <= seq {
+                continue;
+            }
+            if block.left > seq && self.is_lost(seq, mss) {
+                return true;
+            }
+            seq = block.right;
+        }
+        self.lost_end.is_some_and(|lost_end| seq < lost_end)
+    }
+
+    /// Record that RACK found the transmission of `[start, end)` lost.
+    ///
+    /// If that was a retransmission made during this recovery, the range is
+    /// made eligible for being retransmitted again.
+    pub(super) fn mark_lost(
+        &mut self,
+        start: TcpSeqNumber,
+        end: TcpSeqNumber,
+        retransmitted: bool,
+    ) {
+        if retransmitted && self.in_recovery {
+            if start < self.high_rxt {
+                self.high_rxt = start;
+            }
+        } else if self.lost_end.is_none_or(|lost_end| lost_end < end) {
+            self.lost_end = Some(end);
+        }
+    }
+
+    /// Enter loss recovery, unless the previous recovery episode hasn't been
+    /// completed yet. Returns whether recovery was entered.
+    pub(super) fn enter_recovery(
+        &mut self,
+        snd_una: TcpSeqNumber,
+        high_data: TcpSeqNumber,
+    ) -> bool {
+        if self.in_recovery || self.recovery_point.is_some() {
+            return false;
+        }
+        net_trace!(
+            "sack: entering loss recovery at {}, recovery point {}",
+            snd_una,
+            high_data
+        );
+        self.in_recovery = true;
+        self.recovery_point = Some(high_data);
+        self.high_rxt = snd_una;
+        self.high_ack = snd_una;
+        self.rxt_first_hole = true;
+        true
+    }
+
+    /// Leave loss recovery because the retransmission timer expired.
+    ///
+    /// The SACKed ranges are kept, so that the go-back-N retransmission that
+    /// follows can skip them; recovery is not re-entered until everything
+    /// sent so far is acknowledged.
+    pub(super) fn on_rto(&mut self, high_data: TcpSeqNumber) {
+        self.in_recovery = false;
+        self.rxt_first_hole = false;
+        self.lost_end = None;
+        self.recovery_point = Some(high_data);
+    }
+
+    /// RFC 6675 `NextSeg()`, rule (1): return the sequence number and length of
+    /// the next hole that should be retransmitted, if any.
+    pub(super) fn next_seg(
+        &self,
+        snd_una: TcpSeqNumber,
+        mss: usize,
+    ) -> Option<(TcpSeqNumber, usize)> {
+        if !self.in_recovery {
+            return None;
+        }
+
+        let mut seq = self.high_rxt.max(snd_una);
+        for block in self.blocks() {
+            if block.right <= seq {
+                continue;
+            }
+            if block.left > seq {
+                let is_first = seq == snd_una && self.rxt_first_hole;
+                if is_first || self.is_lost(seq, mss) {
+                    return Some((seq, (block.left - seq).min(mss)));
+                }
+                // Holes further up have fewer SACKed octets above them.
+                return None;
+            }
+            seq = block.right;
+        }
+
+        None
+    }
+
+    /// Record that `[seq, end)` has been retransmitted.
+    pub(super) fn on_retransmit(&mut self, end: TcpSeqNumber) {
+        self.rxt_first_hole = false;
+        if end > self.high_rxt {
+            self.high_rxt = end;
+        }
+    }
+
+    /// RFC 6675 `SetPipe()`: estimate the number of octets in flight between
+    /// `snd_una` and `high_data`.
+    ///
+    /// Octets count as in flight unless they have been SACKed or are considered
+    /// lost; lost octets that have been retransmitted count once more.
+    pub(super) fn pipe(&self, snd_una: TcpSeqNumber, high_data: TcpSeqNumber, mss: usize) -> usize {
+        let mut pipe = 0;
+        let mut seq = snd_una;
+        let mut blocks = self.blocks().iter().filter(|block| block.right > snd_una);
+        while seq < high_data {
+            let (hole_end, next) = match blocks.next() {
+                Some(block) => (block.left.max(seq).min(high_data), Some(block.right)),
+                None => (high_data, None),
+            };
+
+            if hole_end > seq {
+                if !self.is_lost(seq, mss) {
+                    pipe += hole_end - seq;
+                }
+                if self.in_recovery && self.high_rxt > seq {
+                    pipe += hole_end.min(self.high_rxt) - seq;
+                }
+            }
+
+            match next {
+                Some(right) => seq = right,
+                None => break,
+            }
+        }
+        debug_assert!(!self.is_sacked(snd_una));
+        pipe
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seq(n: i32) -> TcpSeqNumber {
+        TcpSeqNumber(n)
+    }
+
+    fn ranges(ranges: &[(u32, u32)]) -> [Option<(u32, u32)>; 3] {
+        let mut out = [None; 3];
+        for (slot, &range) in out.iter_mut().zip(ranges) {
+            *slot = Some(range);
+        }
+        out
+    }
+
+    #[test]
+    fn test_merge_and_prune() {
+        let mut sb = Scoreboard::new();
+        sb.on_ack(seq(0), seq(100), &ranges(&[(20, 30), (40, 50)]));
+        sb.on_ack(seq(0), seq(100), &ranges(&[(30, 40)]));
+        assert_eq!(
+            sb.blocks(),
+            &[Block {
+                left: seq(20),
+                right: seq(50)
+            }]
+        );
+
+        sb.on_ack(seq(25), seq(100), &ranges(&[]));
+        assert_eq!(
+            sb.blocks(),
+            &[Block {
+                left: seq(25),
+                right: seq(50)
+            }]
+        );
+
+        sb.on_ack(seq(50), seq(100), &ranges(&[]));
+        assert!(sb.blocks().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_blocks_ignored() {
+        let mut sb = Scoreboard::new();
+        sb.on_ack(seq(10), seq(100), &ranges(&[(0, 5), (90, 110), (60, 50)]));
+        assert!(sb.blocks().is_empty());
+    }
+
+    #[test]
+    fn test_full_drops_highest() {
+        let mut sb = Scoreboard::new();
+        for i in 0..TCP_SACK_SCOREBOARD_COUNT as u32 {
+            sb.on_ack(seq(0), seq(1000), &ranges(&[(10 + i * 20, 20 + i * 20)]));
+        }
+        sb.on_ack(seq(0), seq(1000), &ranges(&[(900, 910), (1, 2)]));
+        assert_eq!(
+            sb.blocks()[0],
+            Block {
+                left: seq(1),
+                right: seq(2)
+            }
+        );
+        assert!(!sb.is_sacked(seq(900)));
+        assert_eq!(sb.blocks().len(), TCP_SACK_SCOREBOARD_COUNT);
+    }
+
+    #[test]
+    fn test_is_lost() {
+        let mut sb = Scoreboard::new();
+        sb.on_ack(seq(0), seq(100), &ranges(&[(10, 20)]));
+        assert!(!sb.is_lost(seq(0), 6));
+        sb.on_ack(seq(0), seq(100), &ranges(&[(30, 33)]));
+        // 13 octets SACKed above, more than 2 * 6.
+        assert!(sb.is_lost(seq(0), 6));
+        assert!(!sb.is_lost(seq(20), 6));
+    }
+
+    #[test]
+    fn test_next_seg_and_pipe() {
+        let mut sb = Scoreboard::new();
+        sb.on_ack(seq(0), seq(60), &ranges(&[(10, 20), (30, 40)]));
+        assert_eq!(sb.next_seg(seq(0), 10), None);
+        assert_eq!(sb.pipe(seq(0), seq(60), 10), 40);
+
+        assert!(sb.enter_recovery(seq(0), seq(60)));
+        assert!(!sb.enter_recovery(seq(0), seq(60)));
+        // The first hole is retransmitted even though it isn't considered lost.
+        assert_eq!(sb.next_seg(seq(0), 10), Some((seq(0), 10)));
+        sb.on_retransmit(seq(10));
+        assert_eq!(sb.next_seg(seq(0), 10), None);
+        // Retransmitted octets that are not considered lost count twice.
+        assert_eq!(sb.pipe(seq(0), seq(60), 10), 10 + 10 + 10 + 20);
+
+        sb.on_ack(seq(0), seq(60), &ranges(&[(42, 44), (50, 55)]));
+        // Octets 0..10 are now lost and retransmitted, 20..30 lost.
+        assert_eq!(sb.next_seg(seq(0), 10), Some((seq(20), 10)));
+        assert_eq!(sb.pipe(seq(0), seq(60), 10), 10 + 2 + 6 + 5);
+
+        sb.on_ack(seq(60), seq(60), &ranges(&[]));
+        assert!(!sb.in_recovery());
+        assert!(sb.blocks().is_empty());
+    }
+
+    #[test]
+    fn test_skip_sacked() {
+        let mut sb = Scoreboard::new();
+        sb.on_ack(seq(0), seq(60), &ranges(&[(10, 20)]));
+        assert_eq!(sb.skip_sacked(seq(5)), seq(5));
+        assert_eq!(sb.skip_sacked(seq(10)), seq(20));
+        assert_eq!(sb.next_sacked(seq(5)), Some(seq(10)));
+        assert_eq!(sb.next_sacked(seq(10)), None);
+    }
+}
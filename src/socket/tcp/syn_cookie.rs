@@ -0,0 +1,242 @@
+// SYN cookies, as described in RFC 4987 section 3.6.
+//
+// SYNs that find no socket listening are answered by the interface on behalf of
+// a socket with SYN cookies, without any state. What a listening socket would have
+// remembered about the SYN (the MSS, window scale and SACK options) is encoded in
+// the initial sequence number of the SYN|ACK, along
+// with a keyed hash of the connection and a coarse timestamp, in the same layout
+// as Linux:
+//
+//     isn = hash(conn, 0) + remote_isn + (count << 24)
+//         + ((hash(conn, count) + data) mod 2^24)
+//
+// where `count` advances every 64 seconds. The ACK completing the handshake
+// carries the sequence number back, and a socket listening on the endpoint
+// rebuilds the SYN-RECEIVED state from it.
+
+use super::fast_open::siphash;
+use crate::rand::Rand;
+use crate::time::Instant;
+use crate::wire::{IpAddress, IpEndpoint, TcpSeqNumber};
+
+/// The MSS values a cookie can encode, in increasing order.
+const MSS_TABLE: [u16; 8] = [536, 1220, 1280, 1300, 1440, 1460, 4312, 8960];
+
+/// The window scale encoding no window scaling.
+const NO_WINDOW_SCALE: u32 = 0xf;
+
+const COOKIE_BITS: u32 = 24;
+const COOKIE_MASK: u32 = (1 << COOKIE_BITS) - 1;
+
+/// How many counter periods a cookie stays valid for.
+const MAX_AGE: u32 = 2;
+
+/// The options of a SYN, as remembered by a cookie.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct SynCookie {
+    pub(crate) mss: u16,
+    pub(crate) window_scale: Option<u8>,
+    pub(crate) sack_permitted: bool,
+}
+
+impl SynCookie {
+    /// Return the cookie remembering the given SYN options, rounding the MSS down to
+    /// a value that can be encoded.
+    pub(crate) fn new(mss: u16, window_scale: Option<u8>, sack_permitted: bool) -> SynCookie {
+        let index = MSS_TABLE.iter().rposition(|&m| m <= mss).unwrap_or(0);
+        SynCookie {
+            mss: MSS_TABLE[index],
+            window_scale: window_scale.map(|shift| shift.min(14)),
+            sack_permitted,
+        }
+    }
+
+    fn encode(&self) -> u32 {
+        // NOTE(unwrap): the MSS was rounded to a value of the table.
+        let mss = MSS_TABLE.iter().position(|&m| m == self.mss).unwrap() as u32;
+        let window_scale = self
+            .window_scale
+            .map(|shift| shift as u32)
+            .unwrap_or(NO_WINDOW_SCALE);
+        (mss << 5) | (window_scale << 1) | self.sack_permitted as u32
+    }
+
+    fn decode(data: u32) -> Option<SynCookie> {
+        if data >> 8 != 0 {
+            return None;
+        }
+        let window_scale = match (data >> 1) & 0xf {
+            NO_WINDOW_SCALE => None,
+            shift @ 0..=14 => Some(shift as u8),
+            _ => return None,
+        };
+        Some(SynCookie {
+            mss: MSS_TABLE[(data >> 5) as usize],
+            window_scale,
+            sack_permitted: data & 1 != 0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct SynCookies {
+    /// Key for the cookies handed out.
+    key: Option<[u64; 2]>,
+}
+
+impl SynCookies {
+    pub(crate) const fn new() -> Self {
+        SynCookies { key: None }
+    }
+
+    /// Return the initial sequence number encoding `cookie`, for a SYN with the
+    /// initial sequence number `remote_isn` sent from `remote` to `local`.
+    pub(crate) fn generate(
+        &mut self,
+        rand: &mut Rand,
+        now: Instant,
+        local: IpEndpoint,
+        remote: IpEndpoint,
+        remote_isn: TcpSeqNumber,
+        cookie: SynCookie,
+    ) -> TcpSeqNumber {
+        let key = *self.key.get_or_insert_with(|| {
+            let mut key = [0; 2];
+            for half in key.iter_mut() {
+                *half = (rand.rand_u32() as u64) << 32 | rand.rand_u32() as u64;
+            }
+            key
+        });
+        let count = counter(now);
+        let isn = hash(key, local, remote, 0)
+            .wrapping_add(remote_isn.0 as u32)
+            .wrapping_add(count << COOKIE_BITS)
+            .wrapping_add(
+                hash(key, local, remote, count).wrapping_add(cookie.encode()) & COOKIE_MASK,
+            );
+        TcpSeqNumber(isn as i32)
+    }
+
+    /// Return the cookie encoded in `isn`, if it is one we handed out recently for
+    /// the SYN with the initial sequence number `remote_isn` sent from `remote` to
+    /// `local`.
+    pub(crate) fn check(
+        &self,
+        now: Instant,
+        local: IpEndpoint,
+        remote: IpEndpoint,
+        remote_isn: TcpSeqNumber,
+        isn: TcpSeqNumber,
+    ) -> Option<SynCookie> {
+        let key = self.key?;
+        let diff = (isn.0 as u32)
+            .wrapping_sub(hash(key, local, remote, 0))
+            .wrapping_sub(remote_isn.0 as u32);
+        let count = diff >> COOKIE_BITS;
+        if counter(now).wrapping_sub(count) & (u32::MAX >> COOKIE_BITS) > MAX_AGE {
+            return None;
+        }
+        SynCookie::decode(diff.wrapping_sub(hash(key, local, remote, count)) & COOKIE_MASK)
+    }
+}
+
+/// Return the counter period of `now`, modulo the bits left for it.
+fn counter(now: Instant) -> u32 {
+    (now.secs() / 64) as u32 & (u32::MAX >> COOKIE_BITS)
+}
+
+/// Hash the connection from `remote` to `local` for the counter period `count`.
+fn hash(key: [u64; 2], local: IpEndpoint, remote: IpEndpoint, count: u32) -> u32 {
+    let mut data = [0; 2 * 16 + 2 * 2 + 4];
+    let mut len = 0;
+    for endpoint in [local, remote] {
+        match endpoint.addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(addr) => {
+                data[len..len + 4].copy_from_slice(&addr.octets());
+                len += 4;
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(addr) => {
+                data[len..len + 16].copy_from_slice(&addr.octets());
+                len += 16;
+            }
+        }
+        data[len..len + 2].copy_from_slice(&endpoint.port.to_be_bytes());
+        len += 2;
+    }
+    data[len..len + 4].copy_from_slice(&count.to_be_bytes());
+    len += 4;
+    siphash(key, &data[..len]) as u32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::time::Duration;
+
+    #[cfg(feature = "proto-ipv4")]
+    const LOCAL: IpEndpoint = IpEndpoint::new(IpAddress::v4(192, 168, 1, 1), 80);
+    #[cfg(feature = "proto-ipv4")]
+    const REMOTE: IpEndpoint = IpEndpoint::new(IpAddress::v4(192, 168, 1, 2), 49500);
+    #[cfg(not(feature = "proto-ipv4"))]
+    const LOCAL: IpEndpoint = IpEndpoint::new(IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1), 80);
+    #[cfg(not(feature = "proto-ipv4"))]
+    const REMOTE: IpEndpoint = IpEndpoint::new(IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 2), 49500);
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(SynCookie::new(1460, Some(7), true).mss, 1460);
+        assert_eq!(SynCookie::new(1400, None, false).mss, 1300);
+        assert_eq!(SynCookie::new(100, None, false).mss, 536);
+        assert_eq!(SynCookie::new(9000, Some(20), false).window_scale, Some(14));
+
+        for cookie in [
+            SynCookie::new(536, None, false),
+            SynCookie::new(1460, Some(0), true),
+            SynCookie::new(8960, Some(14), true),
+        ] {
+            assert_eq!(SynCookie::decode(cookie.encode()), Some(cookie));
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let mut cookies = SynCookies::new();
+        let mut rand = Rand::new(1234);
+        let now = Instant::from_secs(1000);
+        let remote_isn = TcpSeqNumber(-1);
+        let cookie = SynCookie::new(1460, Some(7), true);
+
+        // No cookie was handed out yet.
+        assert_eq!(
+            cookies.check(now, LOCAL, REMOTE, remote_isn, TcpSeqNumber(0)),
+            None
+        );
+
+        let isn = cookies.generate(&mut rand, now, LOCAL, REMOTE, remote_isn, cookie);
+        assert_eq!(
+            cookies.check(now, LOCAL, REMOTE, remote_isn, isn),
+            Some(cookie)
+        );
+        let later = now + Duration::from_secs(2 * 64);
+        assert_eq!(
+            cookies.check(later, LOCAL, REMOTE, remote_isn, isn),
+            Some(cookie)
+        );
+
+        // Expired.
+        let expired = now + Duration::from_secs(4 * 64);
+        assert_eq!(cookies.check(expired, LOCAL, REMOTE, remote_isn, isn), None);
+        // For another connection.
+        assert_eq!(cookies.check(now, REMOTE, LOCAL, remote_isn, isn), None);
+        assert_eq!(
+            cookies.check(now, LOCAL, REMOTE, remote_isn + 0x10000, isn),
+            None
+        );
+        assert_eq!(
+            cookies.check(now, LOCAL, REMOTE, remote_isn, isn + 0x10000),
+            None
+        );
+    }
+}
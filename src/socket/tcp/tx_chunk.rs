@@ -0,0 +1,323 @@
+// Zero-copy transmission.
+//
+// Besides copying data into its transmit buffer, a socket can queue chunks of data
+// owned by the caller, and build segments straight from them. The stream sent
+// interleaves both in the order they were queued: each chunk remembers how many
+// octets of the transmit buffer come before it. Once all of a chunk is
+// acknowledged, it is handed back to the caller.
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+use core::ops::Deref;
+use managed::ManagedSlice;
+
+use crate::storage::{Full, RingBuffer, SocketBufferT};
+
+/// A chunk of data sent without copying, see [Socket::send_chunk](super::Socket::send_chunk).
+#[derive(Debug, Clone)]
+pub enum TxChunk<'a> {
+    /// Data borrowed for the lifetime of the socket.
+    Borrowed(&'a [u8]),
+    /// Reference-counted data, which may be shared between sockets.
+    #[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+    Shared(Arc<[u8]>),
+}
+
+impl Deref for TxChunk<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            TxChunk::Borrowed(data) => data,
+            #[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+            TxChunk::Shared(data) => data,
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for TxChunk<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        TxChunk::Borrowed(data)
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl From<Arc<[u8]>> for TxChunk<'_> {
+    fn from(data: Arc<[u8]>) -> Self {
+        TxChunk::Shared(data)
+    }
+}
+
+/// Storage for a chunk queued in a socket, see
+/// [Socket::set_tx_chunk_storage](super::Socket::set_tx_chunk_storage).
+#[derive(Debug)]
+pub struct TxChunkSlot<'a> {
+    chunk: Option<TxChunk<'a>>,
+    /// How many octets were copied into the transmit buffer before the chunk was
+    /// queued, counting from the start of the connection.
+    buffer_mark: usize,
+}
+
+impl<'a> TxChunkSlot<'a> {
+    /// An empty slot.
+    pub const EMPTY: TxChunkSlot<'a> = TxChunkSlot {
+        chunk: None,
+        buffer_mark: 0,
+    };
+}
+
+/// The chunks queued in a socket, in front of which completed chunks wait to be
+/// taken back.
+#[derive(Debug)]
+pub(super) struct TxChunkQueue<'a> {
+    slots: RingBuffer<'a, TxChunkSlot<'a>>,
+    /// How many chunks at the front of the queue are completed.
+    completed: usize,
+    /// How many octets of the first chunk not completed are acknowledged.
+    front_acked: usize,
+    /// How many octets were dequeued from the transmit buffer, the origin of
+    /// `buffer_mark`.
+    buffer_dequeued: usize,
+    /// How many octets of the chunks are not acknowledged.
+    len: usize,
+}
+
+impl<'a> TxChunkQueue<'a> {
+    pub(super) fn new() -> Self {
+        TxChunkQueue {
+            slots: RingBuffer::new(ManagedSlice::Borrowed(&mut [])),
+            completed: 0,
+            front_acked: 0,
+            buffer_dequeued: 0,
+            len: 0,
+        }
+    }
+
+    /// Replace the storage of the queue, dropping all the chunks queued.
+    pub(super) fn set_storage(&mut self, storage: ManagedSlice<'a, TxChunkSlot<'a>>) {
+        self.clear();
+        self.slots = RingBuffer::new(storage);
+    }
+
+    /// Query whether a chunk is queued, completed or not.
+    pub(super) fn has_chunks(&self) -> bool {
+        !self.slots.is_empty()
+    }
+
+    /// Return the amount of octets queued in chunks that are not acknowledged.
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Queue `chunk` after the `buffer_len` octets of the transmit buffer.
+    pub(super) fn push(&mut self, buffer_len: usize, chunk: TxChunk<'a>) -> Result<(), Full> {
+        let slot = self.slots.enqueue_one()?;
+        self.len += chunk.len();
+        slot.chunk = Some(chunk);
+        slot.buffer_mark = self.buffer_dequeued.wrapping_add(buffer_len);
+        Ok(())
+    }
+
+    /// Return the first pending chunk with the given index, and the amount of
+    /// octets of the transmit buffer that come before it.
+    fn pending(&self, index: usize) -> Option<(usize, &[u8])> {
+        if self.completed + index >= self.slots.len() {
+            return None;
+        }
+        let slot = &self.slots.get_allocated(self.completed + index, 1)[0];
+        // NOTE(unwrap): all the chunks queued are in a slot.
+        let mut data = slot.chunk.as_deref().unwrap();
+        if index == 0 {
+            data = &data[self.front_acked..];
+        }
+        Some((slot.buffer_mark.wrapping_sub(self.buffer_dequeued), data))
+    }
+
+    /// Return up to `size` unacknowledged octets starting at `offset`, from either
+    /// `buffer` or a chunk.
+    ///
+    /// Like `SocketBufferT::get_allocated`, this returns a contiguous slice, which may
+    /// be shorter than `size` if it ends at the edge of a chunk.
+    pub(super) fn get<'b, B: SocketBufferT<'a>>(
+        &'b self,
+        buffer: &'b B,
+        mut offset: usize,
+        size: usize,
+    ) -> &'b [u8] {
+        let mut buffer_offset = 0;
+        let mut index = 0;
+        while let Some((buffered, data)) = self.pending(index) {
+            let run = buffered - buffer_offset;
+            if offset < run {
+                return buffer.get_allocated(buffer_offset + offset, size.min(run - offset));
+            }
+            offset -= run;
+            buffer_offset = buffered;
+            if offset < data.len() {
+                return &data[offset..data.len().min(offset + size)];
+            }
+            offset -= data.len();
+            index += 1;
+        }
+        buffer.get_allocated(buffer_offset + offset, size)
+    }
+
+    /// Dequeue `count` acknowledged octets from `buffer` and the chunks, completing
+    /// the chunks that are acknowledged in full.
+    pub(super) fn dequeue<B: SocketBufferT<'a>>(&mut self, buffer: &mut B, mut count: usize) {
+        while let Some((buffered, data)) = self.pending(0) {
+            if buffered > 0 {
+                if count == 0 {
+                    return;
+                }
+                let size = buffered.min(count);
+                buffer.dequeue_allocated(size);
+                self.buffer_dequeued = self.buffer_dequeued.wrapping_add(size);
+                count -= size;
+                continue;
+            }
+
+            let size = data.len().min(count);
+            let done = size == data.len();
+            self.front_acked += size;
+            self.len -= size;
+            count -= size;
+            if !done {
+                return;
+            }
+            self.front_acked = 0;
+            self.completed += 1;
+        }
+        buffer.dequeue_allocated(count);
+        self.buffer_dequeued = self.buffer_dequeued.wrapping_add(count);
+    }
+
+    /// Take back the first completed chunk.
+    pub(super) fn pop_completed(&mut self) -> Option<TxChunk<'a>> {
+        if self.completed == 0 {
+            return None;
+        }
+        self.completed -= 1;
+        self.slots
+            .dequeue_one()
+            .ok()
+            .and_then(|slot| slot.chunk.take())
+    }
+
+    /// Drop all the chunks, completed or not.
+    pub(super) fn clear(&mut self) {
+        while let Ok(slot) = self.slots.dequeue_one() {
+            slot.chunk = None;
+        }
+        self.slots.clear();
+        self.completed = 0;
+        self.front_acked = 0;
+        self.buffer_dequeued = 0;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::RingBuffer;
+
+    const DATA: &[u8] = b"0123456789";
+
+    fn setup<'a>(slots: &'a mut [TxChunkSlot<'a>]) -> (TxChunkQueue<'a>, RingBuffer<'a, u8>) {
+        let mut queue = TxChunkQueue::new();
+        queue.set_storage(ManagedSlice::Borrowed(slots));
+        (queue, RingBuffer::new(vec![0; 16]))
+    }
+
+    #[test]
+    fn test_interleave() {
+        let mut slots = [TxChunkSlot::EMPTY, TxChunkSlot::EMPTY];
+        let (mut queue, mut buffer) = setup(&mut slots);
+
+        // "ab", DATA[..4], "cd", DATA[4..], "ef"
+        assert_eq!(buffer.enqueue_slice(b"ab"), 2);
+        queue.push(buffer.len(), DATA[..4].into()).unwrap();
+        assert_eq!(buffer.enqueue_slice(b"cd"), 2);
+        queue.push(buffer.len(), DATA[4..].into()).unwrap();
+        assert_eq!(queue.push(buffer.len(), DATA.into()), Err(Full));
+        assert_eq!(buffer.enqueue_slice(b"ef"), 2);
+        assert_eq!(queue.len(), 10);
+
+        assert_eq!(queue.get(&buffer, 0, 16), b"ab");
+        assert_eq!(queue.get(&buffer, 1, 16), b"b");
+        assert_eq!(queue.get(&buffer, 2, 16), b"0123");
+        assert_eq!(queue.get(&buffer, 3, 2), b"12");
+        assert_eq!(queue.get(&buffer, 6, 16), b"cd");
+        assert_eq!(queue.get(&buffer, 8, 16), b"456789");
+        assert_eq!(queue.get(&buffer, 14, 16), b"ef");
+        assert_eq!(queue.get(&buffer, 16, 16), b"");
+    }
+
+    #[test]
+    fn test_dequeue() {
+        let mut slots = [TxChunkSlot::EMPTY, TxChunkSlot::EMPTY];
+        let (mut queue, mut buffer) = setup(&mut slots);
+
+        assert_eq!(buffer.enqueue_slice(b"ab"), 2);
+        queue.push(buffer.len(), DATA[..4].into()).unwrap();
+        queue.push(buffer.len(), DATA[4..].into()).unwrap();
+        assert_eq!(buffer.enqueue_slice(b"cd"), 2);
+
+        queue.dequeue(&mut buffer, 3);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(queue.len(), 9);
+        assert_eq!(queue.get(&buffer, 0, 16), b"123");
+        assert!(queue.pop_completed().is_none());
+
+        queue.dequeue(&mut buffer, 5);
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.get(&buffer, 0, 16), b"6789");
+        assert_eq!(queue.pop_completed().as_deref(), Some(&DATA[..4]));
+        assert!(queue.pop_completed().is_none());
+
+        queue.dequeue(&mut buffer, 5);
+        assert_eq!(queue.len(), 0);
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(queue.get(&buffer, 0, 16), b"d");
+        assert_eq!(queue.pop_completed().as_deref(), Some(&DATA[4..]));
+
+        // Chunks queued later are positioned after what is left in the buffer.
+        queue.push(buffer.len(), DATA[..2].into()).unwrap();
+        assert_eq!(queue.get(&buffer, 0, 16), b"d");
+        assert_eq!(queue.get(&buffer, 1, 16), b"01");
+        queue.dequeue(&mut buffer, 3);
+        assert!(buffer.is_empty());
+        assert_eq!(queue.pop_completed().as_deref(), Some(&DATA[..2]));
+    }
+
+    #[test]
+    fn test_empty_chunk() {
+        let mut slots = [TxChunkSlot::EMPTY, TxChunkSlot::EMPTY];
+        let (mut queue, mut buffer) = setup(&mut slots);
+
+        assert_eq!(buffer.enqueue_slice(b"ab"), 2);
+        queue.push(buffer.len(), DATA[..0].into()).unwrap();
+        queue.dequeue(&mut buffer, 1);
+        assert!(queue.pop_completed().is_none());
+        queue.dequeue(&mut buffer, 1);
+        assert_eq!(queue.pop_completed().as_deref(), Some(&b""[..]));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut slots = [TxChunkSlot::EMPTY, TxChunkSlot::EMPTY];
+        let (mut queue, mut buffer) = setup(&mut slots);
+
+        assert_eq!(buffer.enqueue_slice(b"ab"), 2);
+        queue.push(buffer.len(), DATA.into()).unwrap();
+        queue.clear();
+        buffer.clear();
+        assert_eq!(queue.len(), 0);
+        assert!(queue.pop_completed().is_none());
+
+        assert_eq!(buffer.enqueue_slice(b"cd"), 2);
+        queue.push(buffer.len(), DATA[..2].into()).unwrap();
+        assert_eq!(queue.get(&buffer, 2, 16), b"01");
+    }
+}
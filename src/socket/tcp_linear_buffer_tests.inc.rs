@@ -3699,7 +3699,7 @@
         let mut s = socket_established();
         s.rx_buffer = BufferType::new(vec![0; 6]);
         s.assembler = Assembler::new();
-        s.ack_delay = None;
+        s.set_ack_delay(None);
 
         send!(
             s,
@@ -4814,6 +4814,7 @@
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         });
         assert!(s.socket.accepts(&mut s.cx, &ip_repr, &tcp_repr));
 
@@ -4823,6 +4824,7 @@
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         });
         assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_src, &tcp_repr));
 
@@ -4832,6 +4834,7 @@
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            ecn: IpEcn::NotEct,
         });
         assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_dst, &tcp_repr));
     }
@@ -4889,6 +4892,12 @@
             assert_eq!(s.congestion_control(), CongestionControl::Cubic);
         }
 
+        #[cfg(feature = "socket-tcp-bbr")]
+        {
+            s.set_congestion_control(CongestionControl::Bbr);
+            assert_eq!(s.congestion_control(), CongestionControl::Bbr);
+        }
+
         s.set_congestion_control(CongestionControl::None);
         assert_eq!(s.congestion_control(), CongestionControl::None);
     }
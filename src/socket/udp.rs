@@ -2,13 +2,19 @@ use core::cmp::min;
 #[cfg(feature = "async")]
 use core::task::Waker;
 
-use crate::iface::Context;
+use crate::iface::{Context, Flow};
 use crate::phy::PacketMeta;
 use crate::socket::PollAt;
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
 use crate::storage::Empty;
-use crate::wire::{IpAddress, IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, UdpRepr};
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::IPV4_HEADER_LEN;
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::IPV6_HEADER_LEN;
+use crate::wire::{
+    IpAddress, IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, UDP_HEADER_LEN, UdpRepr,
+};
 
 /// Metadata for a sent or received UDP packet.
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -123,6 +129,9 @@ pub struct Socket<'a> {
     tx_buffer: PacketBuffer<'a>,
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The mark of outgoing packets, selecting their routing table.
+    #[cfg(feature = "packetmeta-mark")]
+    mark: u32,
     #[cfg(feature = "async")]
     rx_waker: WakerRegistration,
     #[cfg(feature = "async")]
@@ -137,6 +146,8 @@ impl<'a> Socket<'a> {
             rx_buffer,
             tx_buffer,
             hop_limit: None,
+            #[cfg(feature = "packetmeta-mark")]
+            mark: 0,
             #[cfg(feature = "async")]
             rx_waker: WakerRegistration::new(),
             #[cfg(feature = "async")]
@@ -212,6 +223,25 @@ impl<'a> Socket<'a> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the mark of outgoing packets.
+    ///
+    /// See also the [set_mark](#method.set_mark) method
+    #[cfg(feature = "packetmeta-mark")]
+    pub fn mark(&self) -> u32 {
+        self.mark
+    }
+
+    /// Set the mark of outgoing packets (ie. `SO_MARK`), used by the
+    /// [route rules](crate::iface::RouteRule) of the interface to select the
+    /// routing table. Datagrams sent with a mark in their
+    /// [metadata](crate::phy::PacketMeta::mark) keep it.
+    ///
+    /// A socket without a mark uses zero.
+    #[cfg(feature = "packetmeta-mark")]
+    pub fn set_mark(&mut self, mark: u32) {
+        self.mark = mark
+    }
+
     /// Bind the socket to the given endpoint.
     ///
     /// This function returns `Err(Error::Illegal)` if the socket was open
@@ -296,6 +326,18 @@ impl<'a> Socket<'a> {
         self.tx_buffer.payload_capacity()
     }
 
+    /// Return the largest payload that can be sent to `addr` without fragmentation,
+    /// according to the path MTU known to the interface.
+    pub fn max_payload_size(&self, cx: &Context, addr: IpAddress) -> usize {
+        let ip_header_len = match addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(_) => IPV4_HEADER_LEN,
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(_) => IPV6_HEADER_LEN,
+        };
+        cx.path_mtu(&addr) - ip_header_len - UDP_HEADER_LEN
+    }
+
     /// Enqueue a packet to be sent to a given remote endpoint, and return a pointer
     /// to its payload.
     ///
@@ -541,14 +583,31 @@ impl<'a> Socket<'a> {
     {
         let endpoint = self.endpoint;
         let hop_limit = self.hop_limit.unwrap_or(64);
+        #[cfg(feature = "packetmeta-mark")]
+        let mark = self.mark;
 
         let res = self.tx_buffer.dequeue_with(|packet_meta, payload_buf| {
+            #[allow(unused_mut)]
+            let mut meta = packet_meta.meta;
+            #[cfg(feature = "packetmeta-mark")]
+            if meta.mark == 0 {
+                meta.mark = mark;
+            }
+
+            let flow = Flow {
+                src_addr: None,
+                protocol: Some(IpProtocol::Udp),
+                #[cfg(feature = "packetmeta-mark")]
+                mark: meta.mark,
+                #[cfg(not(feature = "packetmeta-mark"))]
+                mark: 0,
+            };
             let src_addr = if let Some(s) = packet_meta.local_address {
                 s
             } else {
                 match endpoint.addr {
                     Some(addr) => addr,
-                    None => match cx.get_source_address(&packet_meta.endpoint.addr) {
+                    None => match cx.get_source_address_flow(&packet_meta.endpoint.addr, &flow) {
                         Some(addr) => addr,
                         None => {
                             net_trace!(
@@ -581,7 +640,7 @@ impl<'a> Socket<'a> {
                 hop_limit,
             );
 
-            emit(cx, packet_meta.meta, (ip_repr, repr, payload_buf))
+            emit(cx, meta, (ip_repr, repr, payload_buf))
         });
         match res {
             Err(Empty) => Ok(()),
@@ -606,7 +665,7 @@ impl<'a> Socket<'a> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::wire::{IpRepr, UdpRepr};
+    use crate::wire::{IpEcn, IpRepr, UdpRepr};
 
     use crate::phy::Medium;
     use crate::tests::setup;
@@ -683,6 +742,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 8 + 6,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     });
 
     pub const REMOTE_IP_REPR: IpRepr = IpReprIpvX(IpvXRepr {
@@ -691,6 +751,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 8 + 6,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     });
 
     pub const BAD_IP_REPR: IpRepr = IpReprIpvX(IpvXRepr {
@@ -699,6 +760,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 8 + 6,
         hop_limit: 64,
+        ecn: IpEcn::NotEct,
     });
 
     const LOCAL_UDP_REPR: UdpRepr = UdpRepr {
@@ -776,6 +838,26 @@ mod test {
         );
     }
 
+    #[test]
+    #[cfg(feature = "medium-ip")]
+    fn test_max_payload_size() {
+        let (mut iface, _, _) = setup(Medium::Ip);
+        let mtu = iface.path_mtu(&REMOTE_ADDR.into());
+        let socket = socket(buffer(0), buffer(1));
+        let header_len = IpRepr::new(
+            LOCAL_ADDR.into(),
+            REMOTE_ADDR.into(),
+            IpProtocol::Udp,
+            0,
+            64,
+        )
+        .header_len();
+        assert_eq!(
+            socket.max_payload_size(iface.context(), REMOTE_ADDR.into()),
+            mtu - header_len - UDP_HEADER_LEN
+        );
+    }
+
     #[rstest]
     #[case::ip(Medium::Ip)]
     #[cfg(feature = "medium-ip")]
@@ -989,6 +1071,7 @@ mod test {
                         next_header: IpProtocol::Udp,
                         payload_len: 8 + 6,
                         hop_limit: 0x2a,
+                        ecn: IpEcn::NotEct,
                     })
                 );
                 Ok::<_, ()>(())
@@ -997,6 +1080,49 @@ mod test {
         );
     }
 
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    #[case::ieee802154(Medium::Ieee802154)]
+    #[cfg(feature = "medium-ieee802154")]
+    #[cfg(feature = "packetmeta-mark")]
+    fn test_set_mark(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut s = socket(buffer(0), buffer(2));
+
+        assert_eq!(s.bind(LOCAL_END), Ok(()));
+
+        s.set_mark(7);
+        let meta = PacketMeta {
+            mark: 9,
+            ..PacketMeta::default()
+        };
+        assert_eq!(s.send_slice(b"abc", REMOTE_END), Ok(()));
+        assert_eq!(
+            s.send_slice(
+                b"def",
+                UdpMetadata {
+                    meta,
+                    ..REMOTE_END.into()
+                }
+            ),
+            Ok(())
+        );
+        for mark in [7, 9] {
+            assert_eq!(
+                s.dispatch(cx, |_, meta, _| {
+                    assert_eq!(meta.mark, mark);
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+    }
+
     #[rstest]
     #[case::ip(Medium::Ip)]
     #[cfg(feature = "medium-ip")]
@@ -223,6 +223,35 @@ fn test_window_contiguous<'a, B: TestBuffer<'a>>() {
     assert!(buf.contiguous_window() <= buf.window());
 }
 
+fn test_grow<'a, B: TestBuffer<'a>>() {
+    let mut buf = B::test_new(8);
+    assert!(buf.can_grow());
+
+    // Move the data to the end of the storage, with out-of-order data past it.
+    assert_eq!(buf.enqueue_slice(b"abcdef"), 6);
+    assert_eq!(buf.dequeue_slice(&mut [0; 5]), 5);
+    assert_eq!(buf.enqueue_slice(b"gh"), 2);
+    assert_eq!(buf.write_unallocated(1, b"jk"), 2);
+
+    assert!(buf.grow(16));
+    assert!(!buf.grow(16));
+    assert_eq!(buf.capacity(), 16);
+    assert_eq!(buf.len(), 3);
+    assert_eq!(buf.window(), 13);
+
+    assert_eq!(buf.write_unallocated(0, b"i"), 1);
+    buf.enqueue_unallocated(3);
+    let mut data = [0; 6];
+    assert_eq!(buf.dequeue_slice(&mut data), 6);
+    assert_eq!(&data, b"fghijk");
+
+    // Borrowed storage can't grow.
+    let mut buf = B::new(&mut [][..]);
+    assert!(!buf.can_grow());
+    assert!(!buf.grow(16));
+    assert_eq!(buf.capacity(), 0);
+}
+
 // =============================================================================
 // Test instantiation macros
 // =============================================================================
@@ -291,6 +320,11 @@ macro_rules! buffer_generic_tests {
             fn window_contiguous() {
                 test_window_contiguous::<$buffer_type>();
             }
+
+            #[test]
+            fn grow() {
+                test_grow::<$buffer_type>();
+            }
         }
     };
 }
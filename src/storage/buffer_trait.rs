@@ -144,4 +144,24 @@ pub trait SocketBufferT<'a>: Sized + core::fmt::Debug {
     /// This returns a contiguous slice; for ring buffers the returned size may
     /// be less than `size` if the buffer wraps.
     fn dequeue_many(&mut self, size: usize) -> &mut [u8];
+
+    // === Growing ===
+
+    /// Query whether the buffer can grow, i.e. whether its storage is owned.
+    ///
+    /// The default implementation returns `false`.
+    fn can_grow(&self) -> bool {
+        false
+    }
+
+    /// Grow the buffer to hold `capacity` elements, keeping both the allocated
+    /// elements and the unallocated ones written past them in place.
+    ///
+    /// Returns whether the buffer was grown. A buffer with borrowed storage, or
+    /// already holding `capacity` elements, is left unchanged.
+    ///
+    /// The default implementation never grows the buffer and returns `false`.
+    fn grow(&mut self, _capacity: usize) -> bool {
+        false
+    }
 }
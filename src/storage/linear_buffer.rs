@@ -330,6 +330,25 @@ impl<'a> SocketBufferT<'a> for LinearBuffer<'a> {
         }
         &mut self.storage[read_at..read_at + size]
     }
+
+    fn can_grow(&self) -> bool {
+        #[cfg(feature = "alloc")]
+        return self.has_owned_storage();
+        #[cfg(not(feature = "alloc"))]
+        return false;
+    }
+
+    fn grow(&mut self, capacity: usize) -> bool {
+        #[cfg(feature = "alloc")]
+        if let ManagedSlice::Owned(storage) = &mut self.storage
+            && capacity > storage.len()
+        {
+            storage.resize(capacity, 0);
+            return true;
+        }
+        let _ = capacity;
+        false
+    }
 }
 
 // === From implementations for ergonomic construction ===
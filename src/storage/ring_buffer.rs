@@ -497,6 +497,29 @@ impl<'a> SocketBufferT<'a> for RingBuffer<'a, u8> {
     fn dequeue_many(&mut self, size: usize) -> &mut [u8] {
         RingBuffer::dequeue_many(self, size)
     }
+
+    fn can_grow(&self) -> bool {
+        #[cfg(feature = "alloc")]
+        return matches!(self.storage, ManagedSlice::Owned(_));
+        #[cfg(not(feature = "alloc"))]
+        return false;
+    }
+
+    fn grow(&mut self, capacity: usize) -> bool {
+        #[cfg(feature = "alloc")]
+        if let ManagedSlice::Owned(storage) = &mut self.storage
+            && capacity > storage.len()
+        {
+            // Unwrap the buffer, so that the octets past the allocated ones stay
+            // contiguous with them in the larger storage.
+            storage.rotate_left(self.read_at);
+            storage.resize(capacity, 0);
+            self.read_at = 0;
+            return true;
+        }
+        let _ = capacity;
+        false
+    }
 }
 
 #[cfg(test)]
@@ -89,6 +89,10 @@ impl TestingDevice {
     pub(crate) fn set_tx_available(&mut self, available: bool) {
         self.tx_available = available;
     }
+
+    pub(crate) fn set_max_transmission_unit(&mut self, mtu: usize) {
+        self.max_transmission_unit = mtu;
+    }
 }
 
 impl Device for TestingDevice {
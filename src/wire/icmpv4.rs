@@ -4,7 +4,7 @@ use core::{cmp, fmt};
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::checksum;
-use crate::wire::{Ipv4Packet, Ipv4Repr};
+use crate::wire::{IpEcn, Ipv4Packet, Ipv4Repr};
 
 enum_with_unknown! {
     /// Internet protocol control message type.
@@ -179,6 +179,8 @@ mod field {
     pub const ECHO_IDENT: Field = 4..6;
     pub const ECHO_SEQNO: Field = 6..8;
 
+    pub const NEXT_HOP_MTU: Field = 6..8;
+
     pub const HEADER_END: usize = 8;
 }
 
@@ -259,6 +261,17 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u16(&data[field::ECHO_SEQNO])
     }
 
+    /// Return the next-hop MTU field (for fragmentation required messages, see
+    /// RFC 1191 section 4).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable packet.
+    #[inline]
+    pub fn next_hop_mtu(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::NEXT_HOP_MTU])
+    }
+
     /// Return the header length.
     /// The result depends on the value of the message type field.
     pub fn header_len(&self) -> usize {
@@ -335,6 +348,16 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(&mut data[field::ECHO_SEQNO], value)
     }
 
+    /// Set the next-hop MTU field (for fragmentation required messages).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable packet.
+    #[inline]
+    pub fn set_next_hop_mtu(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::NEXT_HOP_MTU], value)
+    }
+
     /// Compute and fill in the header checksum.
     pub fn fill_checksum(&mut self) {
         self.set_checksum(0);
@@ -437,6 +460,7 @@ impl<'a> Repr<'a> {
                         next_header: ip_packet.next_header(),
                         payload_len: payload.len(),
                         hop_limit: ip_packet.hop_limit(),
+                        ecn: IpEcn::from(ip_packet.ecn()),
                     },
                     data: payload,
                 })
@@ -460,6 +484,7 @@ impl<'a> Repr<'a> {
                         next_header: ip_packet.next_header(),
                         payload_len: payload.len(),
                         hop_limit: ip_packet.hop_limit(),
+                        ecn: IpEcn::from(ip_packet.ecn()),
                     },
                     data: payload,
                 })
@@ -670,6 +695,19 @@ mod test {
         assert_eq!(&packet.into_inner()[..], &ECHO_PACKET_BYTES[..]);
     }
 
+    #[test]
+    fn test_next_hop_mtu() {
+        let mut bytes = vec![0; 8];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_msg_type(Message::DstUnreachable);
+        packet.set_msg_code(DstUnreachable::FragRequired.into());
+        packet.set_next_hop_mtu(1400);
+        assert_eq!(
+            &packet.into_inner()[..],
+            &[0x03, 0x04, 0x00, 0x00, 0x00, 0x00, 0x05, 0x78]
+        );
+    }
+
     fn echo_packet_repr() -> Repr<'static> {
         Repr::EchoRequest {
             ident: 0x1234,
@@ -10,7 +10,7 @@ use crate::wire::NdiscRepr;
 use crate::wire::RplRepr;
 use crate::wire::ip::checksum;
 use crate::wire::{IPV6_HEADER_LEN, IPV6_MIN_MTU};
-use crate::wire::{IpProtocol, Ipv6Address, Ipv6Packet, Ipv6Repr};
+use crate::wire::{IpEcn, IpProtocol, Ipv6Address, Ipv6Packet, Ipv6Repr};
 
 /// Error packets must not exceed min MTU
 const MAX_ERROR_PACKET_LEN: usize = IPV6_MIN_MTU - IPV6_HEADER_LEN;
@@ -644,6 +644,7 @@ impl<'a> Repr<'a> {
                 next_header: ip_packet.next_header(),
                 payload_len: ip_packet.payload_len().into(),
                 hop_limit: ip_packet.hop_limit(),
+                ecn: IpEcn::from(ip_packet.traffic_class() & 0x03),
             };
             Ok((payload, repr))
         }
@@ -891,6 +892,7 @@ mod test {
                 next_header: IpProtocol::Udp,
                 payload_len: 12,
                 hop_limit: 0x40,
+                ecn: IpEcn::NotEct,
             },
             data: &PKT_TOO_BIG_UDP_PAYLOAD,
         }
@@ -1014,6 +1016,7 @@ mod test {
                 next_header: IpProtocol::Tcp,
                 hop_limit: 64,
                 payload_len: 1280,
+                ecn: IpEcn::NotEct,
             },
             data: &vec![0; 9999],
         };
@@ -1028,6 +1031,7 @@ mod test {
             next_header: IpProtocol::Tcp,
             hop_limit: 64,
             payload_len: IPV6_MIN_MTU - IPV6_HEADER_LEN,
+            ecn: IpEcn::NotEct,
         };
         let mut ip_packet = Ipv6Packet::new_unchecked(vec![0; IPV6_MIN_MTU]);
         ip_packet_repr.emit(&mut ip_packet);
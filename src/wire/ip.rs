@@ -83,6 +83,32 @@ impl fmt::Display for Protocol {
     }
 }
 
+enum_with_unknown! {
+    /// Explicit Congestion Notification codepoint, as described in RFC 3168.
+    pub enum Ecn(u8) {
+        /// Not ECN-Capable Transport.
+        NotEct = 0b00,
+        /// ECN-Capable Transport, ECT(1).
+        Ect1   = 0b01,
+        /// ECN-Capable Transport, ECT(0).
+        Ect0   = 0b10,
+        /// Congestion Experienced.
+        Ce     = 0b11
+    }
+}
+
+impl fmt::Display for Ecn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Ecn::NotEct => write!(f, "Not-ECT"),
+            Ecn::Ect1 => write!(f, "ECT(1)"),
+            Ecn::Ect0 => write!(f, "ECT(0)"),
+            Ecn::Ce => write!(f, "CE"),
+            Ecn::Unknown(id) => write!(f, "0x{id:02x}"),
+        }
+    }
+}
+
 /// An internetworking address.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Address {
@@ -608,6 +634,7 @@ impl Repr {
                 next_header,
                 payload_len,
                 hop_limit,
+                ecn: Ecn::NotEct,
             }),
             #[cfg(feature = "proto-ipv6")]
             (Address::Ipv6(src_addr), Address::Ipv6(dst_addr)) => Self::Ipv6(Ipv6Repr {
@@ -616,6 +643,7 @@ impl Repr {
                 next_header,
                 payload_len,
                 hop_limit,
+                ecn: Ecn::NotEct,
             }),
             #[allow(unreachable_patterns)]
             _ => panic!("IP version mismatch: src={src_addr:?} dst={dst_addr:?}"),
@@ -720,6 +748,26 @@ impl Repr {
         }
     }
 
+    /// Return the Explicit Congestion Notification codepoint.
+    pub const fn ecn(&self) -> Ecn {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(Ipv4Repr { ecn, .. }) => ecn,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { ecn, .. }) => ecn,
+        }
+    }
+
+    /// Set the Explicit Congestion Notification codepoint.
+    pub fn set_ecn(&mut self, value: Ecn) {
+        match self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(Ipv4Repr { ecn, .. }) => *ecn = value,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { ecn, .. }) => *ecn = value,
+        }
+    }
+
     /// Return the length of a header that will be emitted from this high-level representation.
     pub const fn header_len(&self) -> usize {
         match *self {
@@ -1063,6 +1111,7 @@ pub(crate) mod test {
             next_header: Protocol::Icmp,
             payload_len: 0,
             hop_limit: 64,
+            ecn: Ecn::NotEct,
         };
 
         let packet = Packet::new_unchecked(&ipv4_packet_bytes[..]);
@@ -1105,6 +1154,7 @@ pub(crate) mod test {
             next_header: Protocol::Udp,
             payload_len: 12,
             hop_limit: 64,
+            ecn: Ecn::NotEct,
         };
 
         let packet = Packet::new_unchecked(&ipv6_packet_bytes[..]);
@@ -5,6 +5,7 @@ use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::{checksum, pretty_print_ip_payload};
 
+pub use super::IpEcn as Ecn;
 pub use super::IpProtocol as Protocol;
 
 /// Minimum MTU required of all links supporting IPv4. See [RFC 791 § 3.1].
@@ -546,6 +547,7 @@ pub struct Repr {
     pub next_header: Protocol,
     pub payload_len: usize,
     pub hop_limit: u8,
+    pub ecn: Ecn,
 }
 
 impl Repr {
@@ -573,7 +575,8 @@ impl Repr {
         let payload_len = packet.total_len() as usize - packet.header_len() as usize;
 
         // All DSCP values are acceptable, since they are of no concern to receiving endpoint.
-        // All ECN values are acceptable, since ECN requires opt-in from both endpoints.
+        // All ECN values are acceptable, since ECN requires opt-in from both endpoints;
+        // the transport layer decides what to make of them.
         // All TTL values are acceptable, since we do not perform routing.
         Ok(Repr {
             src_addr: packet.src_addr(),
@@ -581,6 +584,7 @@ impl Repr {
             next_header: packet.next_header(),
             payload_len,
             hop_limit: packet.hop_limit(),
+            ecn: Ecn::from(packet.ecn()),
         })
     }
 
@@ -599,7 +603,7 @@ impl Repr {
         packet.set_version(4);
         packet.set_header_len(field::DST_ADDR.end as u8);
         packet.set_dscp(0);
-        packet.set_ecn(0);
+        packet.set_ecn(self.ecn.into());
         let total_len = packet.header_len() as u16 + self.payload_len as u16;
         packet.set_total_len(total_len);
         packet.set_ident(0);
@@ -821,6 +825,7 @@ pub(crate) mod test {
             next_header: Protocol::Icmp,
             payload_len: 4,
             hop_limit: 64,
+            ecn: Ecn::NotEct,
         }
     }
 
@@ -862,6 +867,23 @@ pub(crate) mod test {
         assert_eq!(&*packet.into_inner(), &REPR_PACKET_BYTES[..]);
     }
 
+    #[test]
+    fn test_ecn() {
+        let mut repr = packet_repr();
+        repr.ecn = Ecn::Ect0;
+        let mut bytes = vec![0xa5; repr.buffer_len() + REPR_PAYLOAD_BYTES.len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        assert_eq!(packet.ecn(), 0b10);
+        assert_eq!(packet.dscp(), 0);
+
+        packet.set_ecn(0b11);
+        packet.fill_checksum();
+        let packet = Packet::new_unchecked(&*packet.into_inner());
+        let repr = Repr::parse(&packet, &ChecksumCapabilities::default()).unwrap();
+        assert_eq!(repr.ecn, Ecn::Ce);
+    }
+
     #[test]
     fn test_unspecified() {
         assert!(Address::UNSPECIFIED.is_unspecified());
@@ -6,6 +6,7 @@ use core::fmt;
 use super::{Error, Result};
 use crate::wire::ip::pretty_print_ip_payload;
 
+pub use super::IpEcn as Ecn;
 pub use super::IpProtocol as Protocol;
 
 /// Minimum MTU required of all links supporting IPv6. See [RFC 8200 § 5].
@@ -584,6 +585,8 @@ pub struct Repr {
     pub payload_len: usize,
     /// The 8-bit hop limit field.
     pub hop_limit: u8,
+    /// The Explicit Congestion Notification bits of the traffic class field.
+    pub ecn: Ecn,
 }
 
 impl Repr {
@@ -600,6 +603,7 @@ impl Repr {
             next_header: packet.next_header(),
             payload_len: packet.payload_len() as usize,
             hop_limit: packet.hop_limit(),
+            ecn: Ecn::from(packet.traffic_class() & 0x03),
         })
     }
 
@@ -614,7 +618,7 @@ impl Repr {
         // Make no assumptions about the original state of the packet buffer.
         // Make sure to set every byte.
         packet.set_version(6);
-        packet.set_traffic_class(0);
+        packet.set_traffic_class(self.ecn.into());
         packet.set_flow_label(0);
         packet.set_payload_len(self.payload_len as u16);
         packet.set_hop_limit(self.hop_limit);
@@ -1010,6 +1014,7 @@ pub(crate) mod test {
             next_header: Protocol::Udp,
             payload_len: 12,
             hop_limit: 64,
+            ecn: Ecn::NotEct,
         }
     }
 
@@ -53,6 +53,7 @@ let repr = Ipv4Repr {
     next_header: IpProtocol::Tcp,
     payload_len: 10,
     hop_limit:   64,
+    ecn:         IpEcn::NotEct,
 };
 let mut buffer = vec![0; repr.buffer_len() + repr.payload_len];
 { // emission
@@ -179,7 +180,7 @@ pub use self::ieee802154::{
 };
 
 pub use self::ip::{
-    Address as IpAddress, Cidr as IpCidr, Endpoint as IpEndpoint,
+    Address as IpAddress, Cidr as IpCidr, Ecn as IpEcn, Endpoint as IpEndpoint,
     ListenEndpoint as IpListenEndpoint, Protocol as IpProtocol, Repr as IpRepr,
     Version as IpVersion,
 };
@@ -272,8 +273,10 @@ pub use self::mld::{
 pub use self::udp::{HEADER_LEN as UDP_HEADER_LEN, Packet as UdpPacket, Repr as UdpRepr};
 
 pub use self::tcp::{
-    Control as TcpControl, HEADER_LEN as TCP_HEADER_LEN, Packet as TcpPacket, Repr as TcpRepr,
-    SeqNumber as TcpSeqNumber, TcpOption, TcpTimestampGenerator, TcpTimestampRepr,
+    AoAlgorithm as TcpAoAlgorithm, AoTrafficKey as TcpAoTrafficKey, Auth as TcpAuth,
+    AuthKey as TcpAuthKey, AuthSecret as TcpAuthSecret, Control as TcpControl,
+    FastOpenCookie as TcpFastOpenCookie, HEADER_LEN as TCP_HEADER_LEN, Packet as TcpPacket,
+    Repr as TcpRepr, SeqNumber as TcpSeqNumber, TcpOption, TcpTimestampGenerator, TcpTimestampRepr,
 };
 
 #[cfg(feature = "proto-dhcpv4")]
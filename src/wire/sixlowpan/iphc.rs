@@ -854,8 +854,18 @@ impl Repr {
 
         packet.set_dispatch_field();
 
-        // FIXME(thvdveld): we don't set anything from the traffic flow.
-        packet.set_tf_field(0b11);
+        // FIXME(thvdveld): we don't set the flow label.
+        let idx = match (self.ecn, self.dscp) {
+            (Some(ecn), Some(dscp)) if self.flow_label.is_none() => {
+                packet.set_tf_field(0b10);
+                packet.set_field(idx, &[ecn | dscp]);
+                idx + 1
+            }
+            _ => {
+                packet.set_tf_field(0b11);
+                idx
+            }
+        };
 
         let idx = packet.set_next_header(self.next_header, idx);
         let idx = packet.set_hop_limit(self.hop_limit, idx);
@@ -945,4 +955,30 @@ mod test {
             )))
         );
     }
+
+    #[test]
+    fn iphc_emit_ecn() {
+        let repr = Repr {
+            src_addr: ipv6::Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1),
+            ll_src_addr: None,
+            dst_addr: ipv6::Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2),
+            ll_dst_addr: None,
+            next_header: NextHeader::Compressed,
+            hop_limit: 64,
+            ecn: Some(0b10 << 6),
+            dscp: Some(0),
+            flow_label: None,
+        };
+        let mut bytes = [0; 64];
+        let mut packet = Packet::new_unchecked(&mut bytes[..repr.buffer_len()]);
+        repr.emit(&mut packet);
+
+        let packet = Packet::new_unchecked(&bytes[..repr.buffer_len()]);
+        assert_eq!(packet.tf_field(), 0b10);
+        assert_eq!(packet.ecn_field(), Some(0b10 << 6));
+        assert_eq!(packet.dscp_field(), Some(0));
+        assert_eq!(packet.flow_label_field(), None);
+        assert_eq!(packet.next_header(), NextHeader::Compressed);
+        assert_eq!(packet.hop_limit(), 64);
+    }
 }
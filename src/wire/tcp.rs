@@ -2,9 +2,10 @@ use byteorder::{ByteOrder, NetworkEndian};
 use core::{cmp, fmt, ops};
 
 use super::{Error, Result};
+use crate::crypto::{AesCmac, Digest, HmacSha1, Md5};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::checksum;
-use crate::wire::{IpAddress, IpProtocol};
+use crate::wire::{IpAddress, IpEndpoint, IpProtocol};
 
 /// A TCP sequence number.
 ///
@@ -124,6 +125,9 @@ mod field {
     pub const OPT_SACKPERM: u8 = 0x04;
     pub const OPT_SACKRNG: u8 = 0x05;
     pub const OPT_TSTAMP: u8 = 0x08;
+    pub const OPT_MD5: u8 = 0x13;
+    pub const OPT_AO: u8 = 0x1d;
+    pub const OPT_TFO: u8 = 0x22;
 }
 
 pub const HEADER_LEN: usize = field::URGENT.end;
@@ -636,8 +640,24 @@ pub enum TcpOption<'a> {
     WindowScale(u8),
     SackPermitted,
     SackRange([Option<(u32, u32)>; 3]),
-    TimeStamp { tsval: u32, tsecr: u32 },
-    Unknown { kind: u8, data: &'a [u8] },
+    TimeStamp {
+        tsval: u32,
+        tsecr: u32,
+    },
+    /// A TCP Fast Open cookie, or a cookie request if empty.
+    FastOpenCookie(&'a [u8]),
+    /// A TCP-MD5 signature, as described in RFC 2385.
+    Md5Signature(&'a [u8]),
+    /// A TCP Authentication Option, as described in RFC 5925.
+    Authentication {
+        key_id: u8,
+        rnext_key_id: u8,
+        mac: &'a [u8],
+    },
+    Unknown {
+        kind: u8,
+        data: &'a [u8],
+    },
 }
 
 impl<'a> TcpOption<'a> {
@@ -705,6 +725,17 @@ impl<'a> TcpOption<'a> {
                         let tsecr = NetworkEndian::read_u32(&data[4..8]);
                         option = TcpOption::TimeStamp { tsval, tsecr };
                     }
+                    (field::OPT_TFO, _) => option = TcpOption::FastOpenCookie(data),
+                    (field::OPT_MD5, 18) => option = TcpOption::Md5Signature(data),
+                    (field::OPT_MD5, _) => return Err(Error),
+                    (field::OPT_AO, n) if n >= 4 => {
+                        option = TcpOption::Authentication {
+                            key_id: data[0],
+                            rnext_key_id: data[1],
+                            mac: &data[2..],
+                        }
+                    }
+                    (field::OPT_AO, _) => return Err(Error),
                     (_, _) => option = TcpOption::Unknown { kind, data },
                 }
             }
@@ -721,6 +752,9 @@ impl<'a> TcpOption<'a> {
             TcpOption::SackPermitted => 2,
             TcpOption::SackRange(s) => s.iter().filter(|s| s.is_some()).count() * 8 + 2,
             TcpOption::TimeStamp { tsval: _, tsecr: _ } => 10,
+            TcpOption::FastOpenCookie(cookie) => 2 + cookie.len(),
+            TcpOption::Md5Signature(_) => 18,
+            TcpOption::Authentication { mac, .. } => 4 + mac.len(),
             TcpOption::Unknown { data, .. } => 2 + data.len(),
         }
     }
@@ -773,6 +807,24 @@ impl<'a> TcpOption<'a> {
                         NetworkEndian::write_u32(&mut buffer[2..], tsval);
                         NetworkEndian::write_u32(&mut buffer[6..], tsecr);
                     }
+                    &TcpOption::FastOpenCookie(cookie) => {
+                        buffer[0] = field::OPT_TFO;
+                        buffer[2..length].copy_from_slice(cookie);
+                    }
+                    &TcpOption::Md5Signature(digest) => {
+                        buffer[0] = field::OPT_MD5;
+                        buffer[2..length].copy_from_slice(digest);
+                    }
+                    &TcpOption::Authentication {
+                        key_id,
+                        rnext_key_id,
+                        mac,
+                    } => {
+                        buffer[0] = field::OPT_AO;
+                        buffer[2] = key_id;
+                        buffer[3] = rnext_key_id;
+                        buffer[4..length].copy_from_slice(mac);
+                    }
                     &TcpOption::Unknown {
                         kind,
                         data: provided,
@@ -823,6 +875,10 @@ pub struct Repr<'a> {
     pub src_port: u16,
     pub dst_port: u16,
     pub control: Control,
+    /// The ECN-Echo flag, as described in RFC 3168.
+    pub ece: bool,
+    /// The Congestion Window Reduced flag, as described in RFC 3168.
+    pub cwr: bool,
     pub seq_number: SeqNumber,
     pub ack_number: Option<SeqNumber>,
     pub window_len: u16,
@@ -831,9 +887,375 @@ pub struct Repr<'a> {
     pub sack_permitted: bool,
     pub sack_ranges: [Option<(u32, u32)>; 3],
     pub timestamp: Option<TcpTimestampRepr>,
+    /// The TCP Fast Open option, as described in RFC 7413.
+    pub fast_open: Option<FastOpenCookie>,
+    /// Segment authentication, as described in RFC 2385 (TCP-MD5) and RFC 5925 (TCP-AO).
+    pub auth: Option<Auth<'a>>,
     pub payload: &'a [u8],
 }
 
+/// A TCP Fast Open cookie, as described in RFC 7413.
+///
+/// An empty cookie is a cookie request.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FastOpenCookie {
+    len: u8,
+    bytes: [u8; FastOpenCookie::MAX_LEN],
+}
+
+impl FastOpenCookie {
+    /// The minimum length of a cookie.
+    pub const MIN_LEN: usize = 4;
+    /// The maximum length of a cookie.
+    pub const MAX_LEN: usize = 16;
+
+    /// Return a cookie request.
+    pub const fn request() -> FastOpenCookie {
+        FastOpenCookie {
+            len: 0,
+            bytes: [0; FastOpenCookie::MAX_LEN],
+        }
+    }
+
+    /// Create a cookie from its octets, or a cookie request if `data` is empty.
+    ///
+    /// Returns `Err(Error)` if the length is not valid for a cookie, that is an even
+    /// number of octets from 4 to 16 (RFC 7413 section 4.1.1).
+    pub fn new(data: &[u8]) -> Result<FastOpenCookie> {
+        if !data.is_empty()
+            && (!(Self::MIN_LEN..=Self::MAX_LEN).contains(&data.len()) || data.len() % 2 != 0)
+        {
+            return Err(Error);
+        }
+        let mut cookie = FastOpenCookie::request();
+        cookie.len = data.len() as u8;
+        cookie.bytes[..data.len()].copy_from_slice(data);
+        Ok(cookie)
+    }
+
+    /// Return whether this is a cookie request rather than a cookie.
+    pub const fn is_request(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the octets of the cookie.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+/// A secret shared with the peer: a TCP-MD5 key, or a TCP-AO master key.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct AuthSecret {
+    len: u8,
+    bytes: [u8; AuthSecret::MAX_LEN],
+}
+
+impl AuthSecret {
+    /// The maximum length of a secret, as for TCP-MD5 keys in RFC 2385.
+    pub const MAX_LEN: usize = 80;
+
+    /// Create a secret from its octets.
+    ///
+    /// Returns `Err(Error)` if `data` is longer than `MAX_LEN`.
+    pub fn new(data: &[u8]) -> Result<AuthSecret> {
+        if data.len() > Self::MAX_LEN {
+            return Err(Error);
+        }
+        let mut secret = AuthSecret {
+            len: data.len() as u8,
+            bytes: [0; Self::MAX_LEN],
+        };
+        secret.bytes[..data.len()].copy_from_slice(data);
+        Ok(secret)
+    }
+
+    /// Return the octets of the secret.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+impl fmt::Debug for AuthSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keep secrets out of logs.
+        write!(f, "AuthSecret(len={})", self.len)
+    }
+}
+
+/// A TCP-AO algorithm, as described in RFC 5926.
+///
+/// Both use a 96-bit MAC.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AoAlgorithm {
+    /// HMAC-SHA-1-96, with KDF_HMAC_SHA1.
+    HmacSha1,
+    /// AES-128-CMAC-96, with KDF_AES_128_CMAC.
+    AesCmac,
+}
+
+impl AoAlgorithm {
+    /// The length of the MAC carried in the option.
+    pub const MAC_LEN: usize = 12;
+}
+
+/// A TCP-AO traffic key, used for the segments of one direction of a connection.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct AoTrafficKey {
+    algorithm: AoAlgorithm,
+    bytes: [u8; 20],
+}
+
+impl AoTrafficKey {
+    /// Derive the traffic key for segments sent from `src` to `dst` from the master key,
+    /// as described in RFC 5926 section 3.1.
+    ///
+    /// `src_isn` and `dst_isn` are the initial sequence numbers of the sender and of the
+    /// receiver of those segments. RFC 5925 section 5.2 uses zero for `dst_isn` when
+    /// signing a SYN segment.
+    pub fn derive(
+        algorithm: AoAlgorithm,
+        master_key: &AuthSecret,
+        src: IpEndpoint,
+        dst: IpEndpoint,
+        src_isn: SeqNumber,
+        dst_isn: SeqNumber,
+    ) -> AoTrafficKey {
+        let feed = |digest: &mut dyn Digest, output_len: u16| {
+            // i || Label || Context || Output_Length
+            digest.update(&[1]);
+            digest.update(b"TCP-AO");
+            for addr in [src.addr, dst.addr] {
+                match addr {
+                    #[cfg(feature = "proto-ipv4")]
+                    IpAddress::Ipv4(addr) => digest.update(&addr.octets()),
+                    #[cfg(feature = "proto-ipv6")]
+                    IpAddress::Ipv6(addr) => digest.update(&addr.octets()),
+                }
+            }
+            digest.update(&src.port.to_be_bytes());
+            digest.update(&dst.port.to_be_bytes());
+            digest.update(&src_isn.0.to_be_bytes());
+            digest.update(&dst_isn.0.to_be_bytes());
+            digest.update(&output_len.to_be_bytes());
+        };
+
+        let mut bytes = [0; 20];
+        match algorithm {
+            AoAlgorithm::HmacSha1 => {
+                let mut kdf = HmacSha1::new(master_key.as_bytes());
+                feed(&mut kdf, 160);
+                bytes = kdf.finish();
+            }
+            AoAlgorithm::AesCmac => {
+                // Master keys of another length are first hashed down to 128 bits.
+                let key = match master_key.as_bytes().try_into() {
+                    Ok(key) => key,
+                    Err(_) => {
+                        let mut cmac = AesCmac::new(&[0; 16]);
+                        cmac.update(master_key.as_bytes());
+                        cmac.finish()
+                    }
+                };
+                let mut kdf = AesCmac::new(&key);
+                feed(&mut kdf, 128);
+                bytes[..16].copy_from_slice(&kdf.finish());
+            }
+        }
+        AoTrafficKey { algorithm, bytes }
+    }
+
+    /// Return the algorithm this key is for.
+    pub const fn algorithm(&self) -> AoAlgorithm {
+        self.algorithm
+    }
+}
+
+impl fmt::Debug for AoTrafficKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keep secrets out of logs.
+        write!(f, "AoTrafficKey({:?})", self.algorithm)
+    }
+}
+
+/// A key to sign or verify segments with.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AuthKey {
+    /// TCP-MD5, as described in RFC 2385.
+    Md5(AuthSecret),
+    /// TCP-AO, as described in RFC 5925.
+    ///
+    /// When verifying, `key_id` is the expected `KeyID` and `rnext_key_id` is ignored.
+    Ao {
+        key_id: u8,
+        rnext_key_id: u8,
+        traffic_key: AoTrafficKey,
+        /// The sequence number extension of the segment, as described in RFC 5925
+        /// section 6.2.
+        sne: u32,
+    },
+}
+
+impl AuthKey {
+    /// Return the length of the option carrying the MAC.
+    const fn option_len(&self) -> usize {
+        match self {
+            AuthKey::Md5(_) => 18,
+            AuthKey::Ao { .. } => 4 + AoAlgorithm::MAC_LEN,
+        }
+    }
+
+    /// Compute the MAC of `segment`, whose MAC field is at `mac` and whose header is
+    /// `header_len` octets long. Only the first `mac.len()` octets are meaningful.
+    fn compute(
+        &self,
+        segment: &[u8],
+        header_len: usize,
+        mac: ops::Range<usize>,
+        src_addr: &IpAddress,
+        dst_addr: &IpAddress,
+    ) -> [u8; 20] {
+        let feed_pseudo_header = |digest: &mut dyn Digest| {
+            let len = segment.len() as u16;
+            match (src_addr, dst_addr) {
+                #[cfg(feature = "proto-ipv4")]
+                (IpAddress::Ipv4(src_addr), IpAddress::Ipv4(dst_addr)) => {
+                    digest.update(&src_addr.octets());
+                    digest.update(&dst_addr.octets());
+                    digest.update(&[0, IpProtocol::Tcp.into()]);
+                    digest.update(&len.to_be_bytes());
+                }
+                #[cfg(feature = "proto-ipv6")]
+                (IpAddress::Ipv6(src_addr), IpAddress::Ipv6(dst_addr)) => {
+                    digest.update(&src_addr.octets());
+                    digest.update(&dst_addr.octets());
+                    digest.update(&(len as u32).to_be_bytes());
+                    digest.update(&[0, 0, 0, IpProtocol::Tcp.into()]);
+                }
+                #[allow(unreachable_patterns)]
+                _ => unreachable!(),
+            }
+        };
+        // The checksum is always taken as zero.
+        let feed_header = |digest: &mut dyn Digest, end: usize| {
+            digest.update(&segment[..field::CHECKSUM.start]);
+            digest.update(&[0, 0]);
+            digest.update(&segment[field::CHECKSUM.end..end]);
+        };
+
+        let mut out = [0; 20];
+        match self {
+            AuthKey::Md5(secret) => {
+                // RFC 2385: the pseudo-header, the header without options, the data, and
+                // the key.
+                let mut digest = Md5::new();
+                feed_pseudo_header(&mut digest);
+                feed_header(&mut digest, field::URGENT.end);
+                digest.update(&segment[header_len..]);
+                digest.update(secret.as_bytes());
+                out[..16].copy_from_slice(&digest.finish());
+            }
+            AuthKey::Ao {
+                traffic_key, sne, ..
+            } => {
+                // RFC 5925: the SNE, the pseudo-header, the header with options and the
+                // MAC taken as zero, and the data.
+                let feed = |digest: &mut dyn Digest| {
+                    digest.update(&sne.to_be_bytes());
+                    feed_pseudo_header(digest);
+                    feed_header(digest, mac.start);
+                    digest.update(&[0; AoAlgorithm::MAC_LEN][..mac.len()]);
+                    digest.update(&segment[mac.end..]);
+                };
+                match traffic_key.algorithm {
+                    AoAlgorithm::HmacSha1 => {
+                        let mut digest = HmacSha1::new(&traffic_key.bytes);
+                        feed(&mut digest);
+                        out = digest.finish();
+                    }
+                    AoAlgorithm::AesCmac => {
+                        // NOTE(unwrap): the slice is 16 octets.
+                        let mut digest = AesCmac::new(traffic_key.bytes[..16].try_into().unwrap());
+                        feed(&mut digest);
+                        out[..16].copy_from_slice(&digest.finish());
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Find the TCP-MD5 or TCP-AO option of `segment`, returning its `KeyID` (zero for
+/// TCP-MD5) and where its MAC is.
+fn find_auth_option(segment: &[u8]) -> Option<(u8, bool, ops::Range<usize>)> {
+    let header_len = Packet::new_checked(segment).ok()?.header_len() as usize;
+    let mut options = &segment[field::URGENT.end..header_len];
+    while !options.is_empty() {
+        let offset = header_len - options.len();
+        let (next_options, option) = TcpOption::parse(options).ok()?;
+        match option {
+            TcpOption::EndOfList => break,
+            TcpOption::Md5Signature(digest) => {
+                return Some((0, false, offset + 2..offset + 2 + digest.len()));
+            }
+            TcpOption::Authentication { key_id, mac, .. } => {
+                return Some((key_id, true, offset + 4..offset + 4 + mac.len()));
+            }
+            _ => (),
+        }
+        options = next_options;
+    }
+    None
+}
+
+/// Segment authentication, as described in RFC 2385 (TCP-MD5) and RFC 5925 (TCP-AO).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Auth<'a> {
+    /// Sign the segment with this key when emitting it.
+    Sign(AuthKey),
+    /// The segment as received, which carried a TCP-MD5 or TCP-AO option.
+    /// Check it with [`Auth::verify`].
+    Received(&'a [u8]),
+}
+
+impl<'a> Auth<'a> {
+    /// Return whether a received segment was signed with `key`.
+    ///
+    /// # Panics
+    /// This function panics unless `src_addr` and `dst_addr` belong to the same family,
+    /// and that family is IPv4 or IPv6.
+    pub fn verify(&self, key: &AuthKey, src_addr: &IpAddress, dst_addr: &IpAddress) -> bool {
+        let Auth::Received(segment) = *self else {
+            return false;
+        };
+        let Some((key_id, is_ao, mac)) = find_auth_option(segment) else {
+            return false;
+        };
+        match *key {
+            AuthKey::Md5(_) if is_ao || mac.len() != 16 => return false,
+            AuthKey::Ao {
+                key_id: expected, ..
+            } if !is_ao || key_id != expected || mac.len() != AoAlgorithm::MAC_LEN => {
+                return false;
+            }
+            _ => (),
+        }
+        // NOTE(unwrap): find_auth_option checked the header.
+        let header_len = Packet::new_checked(segment).unwrap().header_len() as usize;
+        let expected = key.compute(segment, header_len, mac.clone(), src_addr, dst_addr);
+
+        // Compare in constant time.
+        segment[mac.clone()]
+            .iter()
+            .zip(&expected[..mac.len()])
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+}
+
 pub type TcpTimestampGenerator = fn() -> u32;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -885,6 +1307,7 @@ impl<'a> Repr<'a> {
         let ack_number = packet.ack().then(|| packet.ack_number());
 
         let mut timestamp = None;
+        let mut auth = None;
         let mut options = packet.options();
         if options.len() >= 12
             && options[0] == field::OPT_NOP
@@ -896,24 +1319,30 @@ impl<'a> Repr<'a> {
                 NetworkEndian::read_u32(&options[4..8]),
                 NetworkEndian::read_u32(&options[8..12]),
             ));
-        } else {
-            while !options.is_empty() {
-                let (next_options, option) = TcpOption::parse(options)?;
-                match option {
-                    TcpOption::EndOfList => break,
-                    TcpOption::TimeStamp { tsval, tsecr } => {
-                        timestamp = Some(TcpTimestampRepr::new(tsval, tsecr));
-                    }
-                    _ => {}
+            // A signature may still follow.
+            options = &options[12..];
+        }
+        while !options.is_empty() {
+            let (next_options, option) = TcpOption::parse(options)?;
+            match option {
+                TcpOption::EndOfList => break,
+                TcpOption::TimeStamp { tsval, tsecr } => {
+                    timestamp = Some(TcpTimestampRepr::new(tsval, tsecr));
                 }
-                options = next_options;
+                TcpOption::Md5Signature(_) | TcpOption::Authentication { .. } => {
+                    auth = Some(Auth::Received(packet.buffer.as_ref()));
+                }
+                _ => {}
             }
+            options = next_options;
         }
 
         Ok(Repr {
             src_port: packet.src_port(),
             dst_port: packet.dst_port(),
             control,
+            ece: packet.ece(),
+            cwr: packet.cwr(),
             seq_number: packet.seq_number(),
             ack_number,
             window_len: packet.window_len(),
@@ -922,6 +1351,8 @@ impl<'a> Repr<'a> {
             sack_permitted: false,
             sack_ranges: [None, None, None],
             timestamp,
+            fast_open: None,
+            auth,
             payload: packet.payload(),
         })
     }
@@ -973,6 +1404,8 @@ impl<'a> Repr<'a> {
         let mut sack_permitted = false;
         let mut sack_ranges = [None, None, None];
         let mut timestamp = None;
+        let mut fast_open = None;
+        let mut auth = None;
         while !options.is_empty() {
             let (next_options, option) = TcpOption::parse(options)?;
             match option {
@@ -1002,6 +1435,11 @@ impl<'a> Repr<'a> {
                 TcpOption::TimeStamp { tsval, tsecr } => {
                     timestamp = Some(TcpTimestampRepr::new(tsval, tsecr));
                 }
+                // RFC 7413: options with invalid Length values MUST be ignored.
+                TcpOption::FastOpenCookie(cookie) => fast_open = FastOpenCookie::new(cookie).ok(),
+                TcpOption::Md5Signature(_) | TcpOption::Authentication { .. } => {
+                    auth = Some(Auth::Received(packet.buffer.as_ref()));
+                }
                 _ => (),
             }
             options = next_options;
@@ -1011,6 +1449,8 @@ impl<'a> Repr<'a> {
             src_port: packet.src_port(),
             dst_port: packet.dst_port(),
             control: control,
+            ece: packet.ece(),
+            cwr: packet.cwr(),
             seq_number: packet.seq_number(),
             ack_number: ack_number,
             window_len: packet.window_len(),
@@ -1019,6 +1459,8 @@ impl<'a> Repr<'a> {
             sack_permitted: sack_permitted,
             sack_ranges: sack_ranges,
             timestamp: timestamp,
+            fast_open: fast_open,
+            auth: auth,
             payload: packet.payload(),
         })
     }
@@ -1041,6 +1483,12 @@ impl<'a> Repr<'a> {
         if self.timestamp.is_some() {
             length += 10;
         }
+        if let Some(cookie) = self.fast_open {
+            length += 2 + cookie.as_bytes().len();
+        }
+        if let Some(Auth::Sign(key)) = self.auth {
+            length += key.option_len();
+        }
         let sack_range_len: usize = self
             .sack_ranges
             .iter()
@@ -1084,6 +1532,8 @@ impl<'a> Repr<'a> {
             Control::Fin => packet.set_fin(true),
             Control::Rst => packet.set_rst(true),
         }
+        packet.set_ece(self.ece);
+        packet.set_cwr(self.cwr);
         packet.set_ack(self.ack_number.is_some());
         {
             let mut options = packet.options_mut();
@@ -1110,6 +1560,31 @@ impl<'a> Repr<'a> {
                 }
                 .emit(tmp);
             }
+            if let Some(cookie) = self.fast_open {
+                let tmp = options;
+                options = TcpOption::FastOpenCookie(cookie.as_bytes()).emit(tmp);
+            }
+            // The MAC is filled in once the rest of the segment is.
+            match self.auth {
+                Some(Auth::Sign(AuthKey::Md5(_))) => {
+                    let tmp = options;
+                    options = TcpOption::Md5Signature(&[0; 16]).emit(tmp);
+                }
+                Some(Auth::Sign(AuthKey::Ao {
+                    key_id,
+                    rnext_key_id,
+                    ..
+                })) => {
+                    let tmp = options;
+                    options = TcpOption::Authentication {
+                        key_id,
+                        rnext_key_id,
+                        mac: &[0; AoAlgorithm::MAC_LEN],
+                    }
+                    .emit(tmp);
+                }
+                _ => (),
+            }
 
             if !options.is_empty() {
                 TcpOption::EndOfList.emit(options);
@@ -1118,6 +1593,20 @@ impl<'a> Repr<'a> {
         packet.set_urgent_at(0);
         packet.payload_mut()[..self.payload.len()].copy_from_slice(self.payload);
 
+        if let Some(Auth::Sign(key)) = self.auth {
+            let header_len = packet.header_len() as usize;
+            // NOTE(unwrap): the option was emitted above.
+            let (_, _, mac) = find_auth_option(packet.buffer.as_ref()).unwrap();
+            let value = key.compute(
+                packet.buffer.as_ref(),
+                header_len,
+                mac.clone(),
+                src_addr,
+                dst_addr,
+            );
+            packet.buffer.as_mut()[mac.clone()].copy_from_slice(&value[..mac.len()]);
+        }
+
         if checksum_caps.tcp.tx() {
             packet.fill_checksum(src_addr, dst_addr)
         } else {
@@ -1193,6 +1682,9 @@ impl<T: AsRef<[u8]> + ?Sized> fmt::Display for Packet<&T> {
                 TcpOption::TimeStamp { tsval, tsecr } => {
                     write!(f, " tsval {tsval:08x} tsecr {tsecr:08x}")?
                 }
+                TcpOption::FastOpenCookie(cookie) => write!(f, " tfo={}", cookie.len())?,
+                TcpOption::Md5Signature(_) => write!(f, " md5")?,
+                TcpOption::Authentication { key_id, .. } => write!(f, " ao={key_id}")?,
                 TcpOption::Unknown { kind, .. } => write!(f, " opt({kind})")?,
             }
             options = next_options;
@@ -1211,6 +1703,12 @@ impl<'a> fmt::Display for Repr<'a> {
             Control::Psh => write!(f, " psh")?,
             Control::None => (),
         }
+        if self.ece {
+            write!(f, " ece")?;
+        }
+        if self.cwr {
+            write!(f, " cwr")?;
+        }
         write!(f, " seq={}", self.seq_number)?;
         if let Some(ack_number) = self.ack_number {
             write!(f, " ack={ack_number}")?;
@@ -1235,6 +1733,12 @@ impl<'a> defmt::Format for Repr<'a> {
             Control::Psh => defmt::write!(fmt, " psh"),
             Control::None => (),
         }
+        if self.ece {
+            defmt::write!(fmt, " ece");
+        }
+        if self.cwr {
+            defmt::write!(fmt, " cwr");
+        }
         defmt::write!(fmt, " seq={}", self.seq_number);
         if let Some(ack_number) = self.ack_number {
             defmt::write!(fmt, " ack={}", ack_number);
@@ -1411,10 +1915,14 @@ mod test {
             window_len: 0x0123,
             window_scale: None,
             control: Control::Syn,
+            ece: false,
+            cwr: false,
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None, None, None],
             timestamp: None,
+            fast_open: None,
+            auth: None,
             payload: &PAYLOAD_BYTES,
         }
     }
@@ -1448,6 +1956,159 @@ mod test {
         assert_eq!(&*packet.into_inner(), &SYN_PACKET_BYTES[..]);
     }
 
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_ecn_flags() {
+        let mut repr = packet_repr();
+        repr.ece = true;
+        repr.cwr = true;
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(
+            &mut packet,
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        );
+        assert!(packet.syn() && packet.ece() && packet.cwr());
+
+        let packet = Packet::new_unchecked(&bytes[..]);
+        let parsed = Repr::parse(
+            &packet,
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        )
+        .unwrap();
+        assert_eq!(parsed, repr);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_fast_open() {
+        let mut repr = packet_repr();
+        repr.fast_open = Some(FastOpenCookie::new(&[1, 2, 3, 4, 5, 6]).unwrap());
+        assert_eq!(repr.header_len(), 28);
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(
+            &mut packet,
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        );
+        let packet = Packet::new_unchecked(&bytes[..]);
+        let parsed = Repr::parse(
+            &packet,
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        )
+        .unwrap();
+        assert_eq!(parsed, repr);
+
+        assert!(FastOpenCookie::request().is_request());
+        assert_eq!(FastOpenCookie::new(&[1, 2, 3]), Err(Error));
+        assert_eq!(FastOpenCookie::new(&[1, 2, 3, 4, 5]), Err(Error));
+        assert_eq!(FastOpenCookie::new(&[0; 17]), Err(Error));
+    }
+
+    #[cfg(feature = "proto-ipv4")]
+    fn emit_signed(repr: &Repr) -> Vec<u8> {
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(
+            &mut packet,
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        );
+        bytes
+    }
+
+    #[cfg(feature = "proto-ipv4")]
+    fn parse_auth(bytes: &[u8]) -> Auth<'_> {
+        let packet = Packet::new_unchecked(bytes);
+        let repr = Repr::parse(
+            &packet,
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        )
+        .unwrap();
+        assert_eq!(repr.payload, &PAYLOAD_BYTES);
+        repr.auth.unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_md5_signature() {
+        let key = AuthKey::Md5(AuthSecret::new(b"secret").unwrap());
+        let mut repr = packet_repr();
+        repr.auth = Some(Auth::Sign(key));
+        assert_eq!(repr.header_len(), 40);
+        let mut bytes = emit_signed(&repr);
+        let auth = parse_auth(&bytes);
+        assert!(auth.verify(&key, &SRC_ADDR.into(), &DST_ADDR.into()));
+
+        let other = AuthKey::Md5(AuthSecret::new(b"other").unwrap());
+        assert!(!auth.verify(&other, &SRC_ADDR.into(), &DST_ADDR.into()));
+        assert!(!auth.verify(&key, &DST_ADDR.into(), &SRC_ADDR.into()));
+
+        // Changing the payload invalidates the signature.
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let auth = Auth::Received(&bytes);
+        assert!(!auth.verify(&key, &SRC_ADDR.into(), &DST_ADDR.into()));
+
+        assert_eq!(AuthSecret::new(&[0; 81]), Err(Error));
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_ao_signature() {
+        let master_key = AuthSecret::new(b"master key").unwrap();
+        let src = IpEndpoint::new(SRC_ADDR.into(), 48896);
+        let dst = IpEndpoint::new(DST_ADDR.into(), 80);
+        for algorithm in [AoAlgorithm::HmacSha1, AoAlgorithm::AesCmac] {
+            let derive = |src, dst, src_isn, dst_isn| {
+                AoTrafficKey::derive(
+                    algorithm,
+                    &master_key,
+                    src,
+                    dst,
+                    SeqNumber(src_isn),
+                    SeqNumber(dst_isn),
+                )
+            };
+            let traffic_key = derive(src, dst, 1000, 2000);
+            // The traffic key depends on the connection.
+            assert_ne!(derive(dst, src, 2000, 1000), traffic_key);
+            assert_ne!(derive(src, dst, 1000, 0), traffic_key);
+
+            let ao_key = |key_id, sne| AuthKey::Ao {
+                key_id,
+                rnext_key_id: 4,
+                traffic_key,
+                sne,
+            };
+            let mut repr = packet_repr();
+            repr.auth = Some(Auth::Sign(ao_key(3, 0)));
+            assert_eq!(repr.header_len(), 36);
+            let bytes = emit_signed(&repr);
+            let auth = parse_auth(&bytes);
+            assert!(auth.verify(&ao_key(3, 0), &SRC_ADDR.into(), &DST_ADDR.into()));
+
+            // The key ID and the sequence number extension must match.
+            assert!(!auth.verify(&ao_key(4, 0), &SRC_ADDR.into(), &DST_ADDR.into()));
+            assert!(!auth.verify(&ao_key(3, 1), &SRC_ADDR.into(), &DST_ADDR.into()));
+
+            // A TCP-MD5 key doesn't verify a TCP-AO signature.
+            let md5 = AuthKey::Md5(master_key);
+            assert!(!auth.verify(&md5, &SRC_ADDR.into(), &DST_ADDR.into()));
+        }
+    }
+
     #[test]
     #[cfg(feature = "proto-ipv4")]
     fn test_header_len_multiple_of_4() {
@@ -1506,6 +2167,29 @@ mod test {
                 0x00, 0x6a, 0xcf, 0xc0 //tsecr
             ]
         );
+        assert_option_parses!(TcpOption::FastOpenCookie(&[][..]), &[0x22, 0x02]);
+        assert_option_parses!(
+            TcpOption::FastOpenCookie(&[1, 2, 3, 4, 5, 6, 7, 8][..]),
+            &[0x22, 0x0a, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]
+        );
+        assert_option_parses!(
+            TcpOption::Md5Signature(&[0xaa; 16][..]),
+            &[
+                0x13, 0x12, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa,
+                0xaa, 0xaa, 0xaa, 0xaa
+            ]
+        );
+        assert_option_parses!(
+            TcpOption::Authentication {
+                key_id: 1,
+                rnext_key_id: 2,
+                mac: &[0xbb; 12][..]
+            },
+            &[
+                0x1d, 0x10, 0x01, 0x02, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb,
+                0xbb, 0xbb
+            ]
+        );
         assert_option_parses!(
             TcpOption::Unknown {
                 kind: 12,
@@ -1523,5 +2207,7 @@ mod test {
         assert_eq!(TcpOption::parse(&[0xc, 0x01]), Err(Error));
         assert_eq!(TcpOption::parse(&[0x2, 0x02]), Err(Error));
         assert_eq!(TcpOption::parse(&[0x3, 0x02]), Err(Error));
+        assert_eq!(TcpOption::parse(&[0x13, 0x04, 0x01, 0x02]), Err(Error));
+        assert_eq!(TcpOption::parse(&[0x1d, 0x03, 0x01]), Err(Error));
     }
 }